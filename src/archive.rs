@@ -0,0 +1,158 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use chrono::{Months, NaiveDate};
+use tempfile::NamedTempFile;
+
+use crate::{
+    data::{archived_path, data_files, verify_file},
+    gzip,
+};
+
+/// Returns the data files in `storage_dir` that only hold records from
+/// before the given date
+pub fn plan(storage_dir: &Path, before: NaiveDate) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for file in data_files(storage_dir)? {
+        let Some(stem) = file.file_stem().map(|s| s.to_string_lossy()) else {
+            continue;
+        };
+        // day files end before the next day, month files before the next
+        // month
+        let end = NaiveDate::parse_from_str(&stem, "%Y-%m-%d")
+            .ok()
+            .and_then(|day| day.succ_opt())
+            .or_else(|| {
+                NaiveDate::parse_from_str(&format!("{stem}-01"), "%Y-%m-%d")
+                    .ok()
+                    .and_then(|month| month.checked_add_months(Months::new(1)))
+            });
+        if end.is_some_and(|end| end <= before) {
+            files.push(file);
+        }
+    }
+    Ok(files)
+}
+
+/// Moves a data file into the archive, compressing it with gzip if
+/// `compress` is set, and returns its new path. Records in the archive can
+/// still be read, but aren't migrated or checked by `doctor`.
+pub fn archive_file(
+    storage_dir: &Path,
+    file: &Path,
+    compress: bool,
+) -> Result<PathBuf> {
+    // so that a corrupted file isn't hidden away
+    verify_file(file)?;
+    let file_name = file
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut target = archived_path(storage_dir, &file_name);
+    let mut content = fs::read(file)?;
+    if compress {
+        target = target.with_file_name(format!("{file_name}.gz"));
+        content = gzip::compress(&content);
+    }
+    let target_dir = target.parent().unwrap_or(storage_dir);
+    fs::create_dir_all(target_dir)?;
+
+    let mut temp_file = NamedTempFile::new_in(target_dir)?;
+    temp_file.write_all(&content)?;
+    temp_file
+        .persist(&target)
+        .with_context(|| format!("Could not write {}", target.display()))?;
+    fs::remove_file(file)?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::data::{
+        check_clock, create_event, delete_event, read_events, DayFiles, Event,
+        Format, MonthFiles, Storage,
+    };
+
+    #[test]
+    fn archived_files_are_still_readable() {
+        let d = tempdir().unwrap();
+        let storage = DayFiles::new(d.path(), Format::Csv);
+        let dt = Utc.with_ymd_and_hms(2022, 12, 31, 8, 0, 0).unwrap();
+        for event in [
+            Event::clock_in(&dt),
+            Event::clock_in(&(dt + Duration::days(1))),
+        ] {
+            create_event(&storage, &event, Duration::zero()).unwrap();
+        }
+        let before = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+
+        let files = plan(d.path(), before).unwrap();
+        assert_eq!(files, [d.path().join("2022-12-31.csv")]);
+        let target = archive_file(d.path(), &files[0], true).unwrap();
+        assert_eq!(target, d.path().join("archive/2022/2022-12-31.csv.gz"));
+        assert!(!files[0].exists());
+        assert_eq!(
+            read_events(&storage, dt.date_naive()).unwrap(),
+            [Event::clock_in(&dt)]
+        );
+    }
+
+    #[test]
+    fn archived_days_are_only_left_out_of_days() {
+        let dt = Utc.with_ymd_and_hms(2022, 12, 31, 8, 0, 0).unwrap();
+        let next_day = dt + Duration::days(31);
+        let before = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+        for month_files in [false, true] {
+            let d = tempdir().unwrap();
+            let storage: Box<dyn Storage> = if month_files {
+                Box::new(MonthFiles::new(d.path(), Format::Jsonl))
+            } else {
+                Box::new(DayFiles::new(d.path(), Format::Csv))
+            };
+            let event = Event::clock_in(&dt);
+            create_event(storage.as_ref(), &event, Duration::zero()).unwrap();
+            for file in plan(d.path(), before).unwrap() {
+                archive_file(d.path(), &file, month_files).unwrap();
+            }
+            assert_eq!(storage.days().unwrap(), []);
+            assert_eq!(storage.archived_days().unwrap(), [dt.date_naive()]);
+            // a clock that was set back is noticed from archived days, too
+            let now = dt - Duration::hours(1);
+            let clock_in = Event::clock_in(&now);
+            assert!(check_clock(storage.as_ref(), &clock_in, now).is_err());
+
+            let later = Event::clock_in(&next_day);
+            create_event(storage.as_ref(), &later, Duration::zero()).unwrap();
+            assert_eq!(storage.days().unwrap(), [next_day.date_naive()]);
+            assert_eq!(
+                storage.all_days().unwrap(),
+                [dt.date_naive(), next_day.date_naive()]
+            );
+        }
+    }
+
+    #[test]
+    fn deleted_events_of_archived_months_stay_deleted() {
+        let d = tempdir().unwrap();
+        let storage = MonthFiles::new(d.path(), Format::Csv);
+        let dt = Utc.with_ymd_and_hms(2022, 12, 30, 8, 0, 0).unwrap();
+        create_event(&storage, &Event::clock_in(&dt), Duration::zero())
+            .unwrap();
+        let before = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+        for file in plan(d.path(), before).unwrap() {
+            archive_file(d.path(), &file, true).unwrap();
+        }
+
+        delete_event(&storage, dt.date_naive(), 0).unwrap();
+        assert_eq!(read_events(&storage, dt.date_naive()).unwrap(), []);
+        assert_eq!(storage.all_days().unwrap(), []);
+    }
+}
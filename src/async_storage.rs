@@ -0,0 +1,200 @@
+//! Async access to the storage, for servers and daemons that must not block
+//! their runtime on disk IO. Like `tokio::fs`, each operation runs on a
+//! separate thread and the returned future completes when it's done, which
+//! works with any executor.
+
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+
+use crate::data::{self, Event, Format, Layout, Storage};
+
+/// The async counterpart of [`data::open`]
+#[derive(Debug, Clone)]
+pub struct AsyncStorage {
+    dir: PathBuf,
+    format: Format,
+    layout: Layout,
+}
+
+impl AsyncStorage {
+    pub fn open(dir: &Path, format: Format, layout: Layout) -> AsyncStorage {
+        AsyncStorage {
+            dir: dir.to_path_buf(),
+            format,
+            layout,
+        }
+    }
+
+    /// See [`Storage::read_day`]
+    pub fn read_day(&self, date: NaiveDate) -> Blocking<Result<Vec<Event>>> {
+        self.run(move |storage| storage.read_day(date))
+    }
+
+    /// See [`Storage::write_day`]
+    pub fn write_day(
+        &self,
+        date: NaiveDate,
+        events: Vec<Event>,
+    ) -> Blocking<Result<()>> {
+        self.run(move |storage| storage.write_day(date, &events))
+    }
+
+    /// See [`Storage::remove_day`]
+    pub fn remove_day(&self, date: NaiveDate) -> Blocking<Result<()>> {
+        self.run(move |storage| storage.remove_day(date))
+    }
+
+    /// See [`Storage::days`]
+    pub fn days(&self) -> Blocking<Result<Vec<NaiveDate>>> {
+        self.run(|storage| storage.days())
+    }
+
+    /// See [`data::read_range`]
+    pub fn read_range(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Blocking<Result<Vec<Event>>> {
+        self.run(move |storage| data::read_range(storage, from, to))
+    }
+
+    fn run<T, F>(&self, f: F) -> Blocking<Result<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn Storage) -> Result<T> + Send + 'static,
+    {
+        let storage = self.clone();
+        Blocking::spawn(move || {
+            f(&*data::open(&storage.dir, storage.format, storage.layout))
+        })
+    }
+}
+
+/// A future that completes once a blocking operation that runs on its own
+/// thread has finished
+pub struct Blocking<T> {
+    state: Arc<Mutex<State<T>>>,
+}
+
+struct State<T> {
+    /// `None` until the operation has finished or panicked
+    result: Option<T>,
+    panicked: bool,
+    waker: Option<Waker>,
+}
+
+impl<T: Send + 'static> Blocking<Result<T>> {
+    fn spawn<F>(f: F) -> Blocking<Result<T>>
+    where
+        F: FnOnce() -> Result<T> + Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(State {
+            result: None,
+            panicked: false,
+            waker: None,
+        }));
+        let thread_state = Arc::clone(&state);
+        thread::spawn(move || {
+            // marks the state as panicked if `f` unwinds, so that the
+            // future doesn't wait forever
+            let guard = PanicGuard(&thread_state);
+            let result = f();
+            std::mem::forget(guard);
+            let mut state = thread_state.lock().unwrap();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        Blocking { state }
+    }
+}
+
+impl<T> Future for Blocking<Result<T>> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(result) = state.result.take() {
+            return Poll::Ready(result);
+        }
+        if state.panicked {
+            return Poll::Ready(Err(anyhow!("Storage operation panicked")));
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+struct PanicGuard<'a, T>(&'a Mutex<State<T>>);
+
+impl<T> Drop for PanicGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut state = match self.0.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        state.panicked = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Wake;
+
+    use chrono::{TimeZone, Utc};
+    use tempfile::tempdir;
+
+    use super::*;
+
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    #[test]
+    fn written_days_can_be_read() {
+        let d = tempdir().unwrap();
+        let storage = AsyncStorage::open(d.path(), Format::Csv, Layout::Day);
+        let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
+        let event = Event::clock_in(
+            &Utc.with_ymd_and_hms(2020, 1, 31, 8, 0, 0).unwrap(),
+        );
+        block_on(storage.write_day(date, vec![event.clone()])).unwrap();
+        assert_eq!(block_on(storage.read_day(date)).unwrap(), vec![event]);
+        assert_eq!(block_on(storage.days()).unwrap(), vec![date]);
+    }
+
+    #[test]
+    fn panics_are_reported_as_errors() {
+        let future = Blocking::<Result<()>>::spawn(|| panic!("boom"));
+        assert!(block_on(future).is_err());
+    }
+}
@@ -1,21 +1,119 @@
-use anyhow::{anyhow, Result};
+use std::{
+    collections::hash_map::RandomState,
+    ffi::OsString,
+    fmt::Write as _,
+    hash::BuildHasher,
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
 use busy_bee::{
-    cli::{Cli, Commands},
-    data::{create_event, delete_event, read_events, Event},
-    view::{daily_report, monthly_report},
+    archive,
+    burndown::{self, Burndown},
+    cli::{
+        self, parse_profile, BackfillArgs, Cli, ClockInArgs, Commands,
+        ConfigAction, DeleteArgs, ExpenseAction, ExportArgs, MergeArgs,
+        OutputArgs, Prefer, ReportArgs, StandbyAction, StatusArgs,
+        TrashAction,
+    },
+    compliance::Compliance,
+    config::{self, Config},
+    data::{
+        self, create_event, create_events, delete_event, events_to_string,
+        parse_events, read_events, read_range, read_stored_events, Batch,
+        DayNote, DryRun, Event, EventKind, FileChange, Format, Layout,
+        PersistenceError, Snapshot, Source, Storage, StoredEvent,
+    },
+    diff, doctor,
+    earnings::Earnings,
+    edit,
+    expense::{format_amount, Expense},
+    export::{self, ExportFormat},
+    hooks::{self, Hook},
+    i18n::{self, tr, Language},
+    ics,
+    import::{self, Outcome},
+    interval::{
+        auto_clock_out, implausible_days, intervals, merge_gaps, with_tags,
+        Interval,
+    },
+    man, merge,
+    output::Destination,
+    platform,
+    query::Query,
+    report::{self, ReportOptions},
+    rpc,
+    sections::{Registry, Scope, ScriptSection},
+    signals,
+    status::{OutputFormat, Status},
+    suggest,
+    view::{
+        client_report, daily_report, daily_report_with_ids, expense_report,
+        location_report, monthly_report, project_report, range_report,
+        standby_report, tag_report, travel_report, week_timeline, DailyReport,
+        Grouping, ReportFormat, RowFilter, RowOptions,
+    },
 };
 use chrono::{
-    DateTime, Datelike, Days, Local, NaiveDate, NaiveTime, TimeZone, Timelike,
-    Utc,
+    DateTime, Datelike, Days, Duration, Local, Months, NaiveDate,
+    NaiveDateTime, NaiveTime, Utc, Weekday,
 };
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 use directories::ProjectDirs;
 
+/// What commands need to know about their environment
+#[allow(clippy::struct_excessive_bools)]
+struct App<'a> {
+    config: Config,
+    /// `None` if there is no config directory on this OS
+    config_path: Option<PathBuf>,
+    storage_dir: PathBuf,
+    hooks_dir: Option<PathBuf>,
+    storage: &'a dyn Storage,
+    /// The batch that `storage` writes to, when running one
+    batch: Option<&'a Batch<'a>>,
+    dry_run: bool,
+    /// Whether to record events that seem too far off
+    force: bool,
+    /// Which instant to take for local times that happen twice
+    prefer: Option<Prefer>,
+    /// Whether to show long output in a pager
+    pager: bool,
+    /// Whether to print lines for scripts instead, see [`Cli::porcelain`]
+    porcelain: bool,
+    /// Appended to the output of `view` and `report`
+    sections: Registry,
+}
+
 fn main() {
-    let args = Cli::parse();
+    let project_dirs = ProjectDirs::from("", "", "busy-bee");
+    let config_dir = project_dirs.as_ref().map(ProjectDirs::config_dir);
+    let config_path = config_dir.map(|dir| dir.join("config.toml"));
+    let mut config = config_path
+        .as_deref()
+        .map_or_else(
+            || Ok(Config::default()),
+            |path| Config::load_layered(path, std::env::vars()),
+        )
+        .unwrap();
+    let args = Cli::parse_from(cli::expand_alias(
+        std::env::args_os().collect(),
+        &config.aliases,
+    ));
+    i18n::set_language(config.language.unwrap_or_else(locale_language));
+    i18n::set_duration_format(
+        args.duration_format.unwrap_or(config.duration_format),
+    );
+    let hooks_dir = config
+        .hooks_dir
+        .clone()
+        .or_else(|| config_dir.map(|dir| dir.join("hooks")));
 
-    let storage_dir = args.storage_dir.unwrap_or_else(|| {
-        let default_dir = ProjectDirs::from("", "", "busy-bee")
+    let storage_dir = args.storage_dir.or_else(|| config.storage_dir.clone());
+    let storage_dir = storage_dir.unwrap_or_else(|| {
+        let default_dir = project_dirs
+            .as_ref()
             .map(|pd| pd.data_local_dir().to_path_buf());
         default_dir.expect(
             "Could not determine the local data directory for your OS. Please \
@@ -23,88 +121,1880 @@ fn main() {
             should be saved.",
         )
     });
-    if !storage_dir.exists() {
-        std::fs::create_dir(&storage_dir).unwrap();
-    }
-
-    match args.command {
-        Commands::ClockIn { date, time } => {
-            let dt = get_date_time(date, time).unwrap();
-            let event = Event::clock_in(&dt);
-            let events = create_event(&storage_dir, &event).unwrap();
-            let report = daily_report(&dt.date_naive(), &events).unwrap();
-            println!("{report}");
-        }
-        Commands::ClockOut { date, time } => {
-            let dt = get_date_time(date, time).unwrap();
-            let event = Event::clock_out(&dt);
-            let events = create_event(&storage_dir, &event).unwrap();
-            let report = daily_report(&dt.date_naive(), &events).unwrap();
-            println!("{report}");
-        }
-        Commands::Delete { date, id } => {
-            let date = match date {
-                Some(d) => d,
-                None => Local::now().date_naive(),
+    let profile = args.profile.or_else(env_profile);
+    let storage_dir = platform::normalize(&storage_dir);
+    let storage_dir = match profile {
+        Some(profile) => storage_dir.join("profiles").join(profile),
+        None => storage_dir,
+    };
+    config.storage_read_only |= args.read_only;
+    if config.storage_read_only && args.command.writes() {
+        eprintln!(
+            "The storage directory {} is read-only, so this command can't \
+            change it; drop --read-only or storage.read_only from the config \
+            file to write",
+            storage_dir.display()
+        );
+        std::process::exit(1);
+    }
+    // held until the command, e.g., a whole batch, is done
+    let _lock = (args.command.writes() && !args.dry_run).then(|| {
+        data::create_dir(&storage_dir).unwrap();
+        data::lock(&storage_dir).unwrap()
+    });
+    let opened =
+        data::open(&storage_dir, config.storage_format, config.storage_layout);
+    let dry_run = DryRun::new(opened.as_ref());
+    let storage = if args.dry_run {
+        &dry_run
+    } else {
+        opened.as_ref()
+    };
+    // holds back the changes of a batch's commands until all succeeded
+    let batch = Batch::new(storage);
+    let batching = matches!(args.command, Commands::Batch { .. });
+    let mut sections = Registry::new();
+    for (title, command) in &config.report_sections {
+        sections.register(Box::new(ScriptSection {
+            title: title.clone(),
+            command: command.clone(),
+        }));
+    }
+    let app = App {
+        config,
+        config_path,
+        storage_dir,
+        hooks_dir: hooks_dir.filter(|_| !batching),
+        storage: if batching { &batch } else { storage },
+        batch: batching.then_some(&batch),
+        dry_run: args.dry_run,
+        prefer: args.prefer,
+        force: args.force,
+        pager: !args.no_pager,
+        porcelain: args.porcelain,
+        sections,
+    };
+
+    run(&app, args.command);
+    if let Some(batch) = app.batch {
+        commit_batch(&app, batch).unwrap();
+    }
+
+    if args.dry_run {
+        print_changes(&dry_run.changes());
+        println!("Dry run, nothing was written");
+    }
+}
+
+/// Writes the changes of a batch's commands together, or shows them for
+/// a dry run
+fn commit_batch(app: &App, batch: &Batch) -> Result<()> {
+    let changes = batch.plan_commit(&app.storage_dir)?;
+    if app.dry_run {
+        print_changes(&changes);
+        return Ok(());
+    }
+    data::apply(&changes)
+}
+
+/// The profile that `BUSY_BEE_PROFILE` names, if it's set
+fn env_profile() -> Option<String> {
+    let name = std::env::var("BUSY_BEE_PROFILE").ok()?;
+    match parse_profile(&name) {
+        Ok(profile) => Some(profile),
+        Err(err) => {
+            eprintln!("Invalid BUSY_BEE_PROFILE: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The language of the locale, as set by the usual environment variables
+fn locale_language() -> Language {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|locale| !locale.is_empty())
+        .map_or(Language::English, |locale| Language::from_locale(&locale))
+}
+
+fn run(app: &App, command: Commands) {
+    let storage = app.storage;
+    match command {
+        command if command.batches() => run_batchable(app, command).unwrap(),
+        Commands::Batch { file } => {
+            if let Err(err) = batch(app, &file) {
+                eprintln!("{err:#}");
+                eprintln!("Nothing of the batch was written");
+                std::process::exit(1);
+            }
+        }
+        Commands::Note { text, date } => note(app, text, date).unwrap(),
+        Commands::Search { text, source } => {
+            search(app, text.as_deref(), source).unwrap();
+        }
+        Commands::Expense { action } => expense(app, action).unwrap(),
+        Commands::Trash { action } => trash_command(app, &action).unwrap(),
+        Commands::View {
+            period,
+            from,
+            to,
+            week,
+            raw,
+            verbose,
+            format,
+            output,
+        } => {
+            let (first, last) = view_range(period, from, to);
+            if app.porcelain {
+                porcelain_view(app, first, last).unwrap();
+            } else if week {
+                timeline(app, first, &output).unwrap();
+            } else {
+                view(app, (first, last), format, &output, raw, verbose)
+                    .unwrap();
+            }
+        }
+        Commands::EditRaw { date } => edit_raw(app, date),
+        Commands::Report(args) => report_command(app, &args),
+        Commands::Query { query } => print_query(app, &query).unwrap(),
+        Commands::Earnings { month } => earnings(app, month),
+        Commands::Compliance { month } => compliance(app, month),
+        Commands::Migrate { format, layout } => {
+            migrate_storage(app, format, layout);
+        }
+        Commands::Import { file, diff } => import_file(storage, &file, diff),
+        Commands::Merge(args) => merge_dir(app, &args).unwrap(),
+        Commands::Status(args) => print_status(app, &args),
+        Commands::Prompt { max_latency } => prompt(app, max_latency),
+        Commands::Watch { interval } => watch(app, interval),
+        Commands::Export(args) => {
+            // tells, e.g., how to repair a corrupted file
+            if let Err(err) = export_period(app, &args) {
+                eprintln!("{err:#}");
+                std::process::exit(1);
+            }
+        }
+        Commands::Suggest { date } => {
+            let yesterday = Local::now().date_naive().pred_opt().unwrap();
+            suggest_events(app, date.unwrap_or(yesterday));
+        }
+        Commands::Archive { before, compress } => {
+            archive_files(app, before, compress);
+        }
+        Commands::Dedupe { window, yes } => dedupe(app, window, yes).unwrap(),
+        Commands::Doctor { fix } => doctor(app, fix),
+        Commands::Init { storage_dir } => {
+            init(app, storage_dir.as_deref()).unwrap();
+        }
+        Commands::Rpc => serve(app).unwrap(),
+        Commands::Config { action } => configure(app, action).unwrap(),
+        Commands::Info => info(app).unwrap(),
+        Commands::Man { out_dir } => man_pages(out_dir.as_deref()).unwrap(),
+        #[cfg(feature = "self-update")]
+        Commands::SelfUpdate { check } => self_update(check).unwrap(),
+        Commands::ClockIn(_)
+        | Commands::ClockOut { .. }
+        | Commands::AddDay { .. }
+        | Commands::Backfill(_)
+        | Commands::Interrupt { .. }
+        | Commands::Standby { .. }
+        | Commands::Delete(_) => unreachable!("batches, see above"),
+    }
+}
+
+/// Runs one of the commands that can run in a batch, which fail with an
+/// error instead of a panic, so that a batch can stop without writing
+fn run_batchable(app: &App, command: Commands) -> Result<()> {
+    match command {
+        Commands::ClockIn(args) => clock_in(app, args),
+        Commands::ClockOut {
+            date,
+            time,
+            at,
+            duration,
+        } => {
+            let dt = clock_out_time(app, date, time, at, duration)?;
+            record(app, &Event::clock_out(&dt))
+        }
+        Commands::AddDay {
+            date,
+            project,
+            tags,
+        } => add_day(app, date, project, tags),
+        Commands::Backfill(args) => backfill(app, &args),
+        Commands::Interrupt { note, minutes } => interrupt(app, note, minutes),
+        Commands::Standby { action } => standby(app, &action),
+        Commands::Delete(args) => delete_command(app, &args),
+        _ => unreachable!("only commands that batch run here"),
+    }
+}
+
+fn clock_in(app: &App, args: ClockInArgs) -> Result<()> {
+    if let (Some(client), Some(project)) = (&args.client, &args.project) {
+        check_client(&app.config, client, project)?;
+    }
+    let time = args.time.or(args.date.map(|_| app.config.default_start));
+    let dt = resolve_date_time(app, args.at, args.date, time)?;
+    let event = Event::clock_in(&dt)
+        .with_project(args.project)
+        .with_tags(args.tags)
+        .with_location(args.location);
+    record(app, &event)
+}
+
+fn standby(app: &App, action: &StandbyAction) -> Result<()> {
+    let event = match action {
+        StandbyAction::Start { date, time } => {
+            Event::standby_start(&get_date_time(app, *date, *time)?)
+        }
+        StandbyAction::End { date, time } => {
+            Event::standby_end(&get_date_time(app, *date, *time)?)
+        }
+    };
+    record(app, &event)
+}
+
+fn note(app: &App, text: String, date: Option<NaiveDate>) -> Result<()> {
+    let note = DayNote {
+        date: date.unwrap_or_else(|| Local::now().date_naive()),
+        text,
+    };
+    let change = data::plan_add_day_note(&app.storage_dir, &note)?;
+    if app.dry_run {
+        print_changes(&[change]);
+        return Ok(());
+    }
+    data::apply(&[change])?;
+    println!("Added a note to {}", note.date);
+    Ok(())
+}
+
+/// Finds the notes that contain `text`, and with a `source`, only events
+/// recorded that way, whose notes contain `text` if there is one
+fn search(
+    app: &App,
+    text: Option<&str>,
+    source: Option<Source>,
+) -> Result<()> {
+    let text = text.map(str::to_lowercase);
+    let matches = |note: &str| {
+        text.as_ref()
+            .is_some_and(|text| note.to_lowercase().contains(text))
+    };
+    let mut found: Vec<(NaiveDate, String)> = Vec::new();
+    // day notes aren't events, so they have no source
+    if source.is_none() {
+        for note in data::read_all_day_notes(&app.storage_dir)? {
+            if matches(&note.text) {
+                found.push((
+                    note.date,
+                    format!("{} | {}", note.date, note.text),
+                ));
+            }
+        }
+    }
+    for date in app.storage.all_days()? {
+        for event in read_events(app.storage, date)? {
+            if source.is_some_and(|source| event.source != Some(source)) {
+                continue;
+            }
+            let time = event.dt.with_timezone(&Local).format("%H:%M");
+            match event.note.as_deref() {
+                Some(note) if matches(note) => {
+                    found.push((date, format!("{date} {time} | {note}")));
+                }
+                _ if text.is_none() => {
+                    found.push((
+                        date,
+                        format!("{date}{}", import::describe(&event)),
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+    // stable, so that notes on a day come before its interruptions
+    found.sort_by_key(|(date, _)| *date);
+    if found.is_empty() {
+        println!("Found nothing");
+    }
+    for (_, line) in found {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+fn expense(app: &App, action: ExpenseAction) -> Result<()> {
+    match action {
+        ExpenseAction::Add {
+            amount,
+            description,
+            date,
+        } => {
+            let expense = Expense {
+                date: date.unwrap_or_else(|| Local::now().date_naive()),
+                cents: amount,
+                description,
             };
-            let events = delete_event(&storage_dir, date, id).unwrap();
-            let report = daily_report(&date, &events).unwrap();
-            println!("{report}");
+            let change = data::plan_add_expense(&app.storage_dir, &expense)?;
+            if app.dry_run {
+                print_changes(&[change]);
+                return Ok(());
+            }
+            data::apply(&[change])?;
+            let currency = app
+                .config
+                .currency
+                .as_ref()
+                .map_or_else(String::new, |currency| format!(" {currency}"));
+            println!(
+                "Added {}{currency} for {} on {}",
+                format_amount(expense.cents),
+                expense.description,
+                expense.date
+            );
         }
-        Commands::View { date } => {
-            let events = read_events(&storage_dir, date).unwrap();
-            let report = daily_report(&date, &events).unwrap();
-            println!("{report}");
+    }
+    Ok(())
+}
+
+fn archive_files(app: &App, before: NaiveDate, compress: bool) {
+    for file in archive::plan(&app.storage_dir, before).unwrap() {
+        if app.dry_run {
+            println!("Would archive {}", file.display());
+            continue;
         }
-        Commands::Report { date } => {
-            let first_of_month = date.unwrap_or_else(|| {
-                Local::now().date_naive().with_day(1).unwrap()
-            });
-            let mut events = Vec::new();
-            // iterator over all days in the month
-            let days = std::iter::successors(Some(first_of_month), |day| {
-                day.checked_add_days(Days::new(1))
-                    .filter(|d| d.month0() == first_of_month.month0())
-            });
-            days.for_each(|date| {
-                events.extend(read_events(&storage_dir, date).unwrap());
-            });
+        let target =
+            archive::archive_file(&app.storage_dir, &file, compress).unwrap();
+        println!("Archived {} to {}", file.display(), target.display());
+    }
+}
 
-            let report = monthly_report(&first_of_month, &events).unwrap();
-            println!("{report}");
+fn delete_command(app: &App, args: &DeleteArgs) -> Result<()> {
+    let date = args.date.unwrap_or_else(|| Local::now().date_naive());
+    match (args.id, args.between.as_deref()) {
+        (_, Some(&[start, end])) => {
+            delete_between(app, date, (start, end), args.yes)
         }
+        (Some(id), _) => delete(app, date, id),
+        _ => unreachable!("clap requires an ID or --between"),
+    }
+}
+
+fn delete(app: &App, date: NaiveDate, id: u32) -> Result<()> {
+    let stored = read_stored_events(app.storage, date)?;
+    if let Some(deleted) = stored.iter().find(|stored| stored.id == id) {
+        trash(app, date, std::slice::from_ref(&deleted.event))?;
+    }
+    let events = match delete_event(app.storage, date, id) {
+        Ok(events) => events,
+        Err(err)
+            if matches!(
+                err.downcast_ref(),
+                Some(PersistenceError::EventNotFoundError { .. })
+            ) =>
+        {
+            // Shows the IDs there are to pick from instead
+            eprintln!("{err} on {date}");
+            let report = daily_report_with_ids(
+                &date,
+                &stored,
+                &[],
+                &[],
+                ReportFormat::Text,
+            )
+            .unwrap();
+            eprintln!("{report}");
+            std::process::exit(1);
+        }
+        Err(err) => return Err(err),
     };
+    if let Some(deleted) = stored.into_iter().find(|stored| stored.id == id) {
+        run_hook(app, Hook::PostDelete, &deleted.event);
+    }
+    print_day(app, date, &events);
+    Ok(())
 }
 
-fn get_date_time(
+/// Deletes the events of `date` within the `window` of local times, once
+/// they've been shown and the user agreed, unless `yes`
+fn delete_between(
+    app: &App,
+    date: NaiveDate,
+    window: (NaiveTime, NaiveTime),
+    yes: bool,
+) -> Result<()> {
+    let (start, end) = window;
+    let selected = data::read_events_between(app.storage, date, start, end)?;
+    if selected.is_empty() {
+        println!(
+            "There are no events from {} to {} on {date}",
+            start.format("%H:%M"),
+            end.format("%H:%M")
+        );
+        return Ok(());
+    }
+    let preview =
+        daily_report_with_ids(&date, &selected, &[], &[], ReportFormat::Text)
+            .unwrap();
+    println!("{preview}");
+    if !yes && !app.dry_run && !confirm("Delete the events above?") {
+        println!("Nothing was deleted");
+        return Ok(());
+    }
+    let deleted: Vec<Event> =
+        selected.iter().map(|stored| stored.event.clone()).collect();
+    trash(app, date, &deleted)?;
+    let ids: Vec<u32> = selected.iter().map(|stored| stored.id).collect();
+    let events = data::delete_events(app.storage, date, &ids)?;
+    for stored in &selected {
+        run_hook(app, Hook::PostDelete, &stored.event);
+    }
+    print_day(app, date, &events);
+    Ok(())
+}
+
+/// Removes the duplicates across all days in one transaction, after
+/// listing them and asking unless `yes`
+fn dedupe(app: &App, window: Duration, yes: bool) -> Result<()> {
+    let mut days = Vec::new();
+    let mut count = 0;
+    for date in app.storage.days()? {
+        let events = app.storage.read_day(date)?;
+        let (kept, duplicates) = data::find_duplicates(&events, window);
+        for event in &duplicates {
+            println!("{date}{}", import::describe(event));
+        }
+        if !duplicates.is_empty() {
+            count += duplicates.len();
+            days.push((date, kept));
+        }
+    }
+    if days.is_empty() {
+        println!("Found no duplicates");
+        return Ok(());
+    }
+    if !yes && !app.dry_run && !confirm("Remove the duplicates above?") {
+        println!("Nothing was removed");
+        return Ok(());
+    }
+    app.storage.write_days(&days)?;
+    println!("Removed {count} duplicates");
+    Ok(())
+}
+
+/// Keeps the `events` of `date` that are about to be deleted in the trash,
+/// before they are, so that a failure can't lose them. A batch writes them
+/// along with its other changes instead.
+fn trash(app: &App, date: NaiveDate, events: &[Event]) -> Result<()> {
+    if let Some(batch) = app.batch {
+        batch.trash(date, events);
+        return Ok(());
+    }
+    let change = data::plan_trash(&app.storage_dir, date, events)?;
+    if app.dry_run {
+        print_changes(&[change]);
+        return Ok(());
+    }
+    data::apply(&[change])
+}
+
+fn trash_command(app: &App, action: &TrashAction) -> Result<()> {
+    match action {
+        TrashAction::List => {
+            let trash = data::read_trash(&app.storage_dir)?;
+            if trash.is_empty() {
+                println!("The trash is empty");
+            }
+            for trashed in trash {
+                println!(
+                    "{} | {}{}",
+                    trashed.id,
+                    trashed.date,
+                    import::describe(&trashed.event)
+                );
+            }
+        }
+        TrashAction::Restore { id } => {
+            let (restored, change) =
+                match data::plan_untrash(&app.storage_dir, *id) {
+                    Ok(untrash) => untrash,
+                    Err(err) => {
+                        eprintln!(
+                            "{err} in the trash, see busy-bee trash list"
+                        );
+                        std::process::exit(1);
+                    }
+                };
+            // recorded again before it leaves the trash, like deleting
+            let created = create_event(
+                app.storage,
+                &restored.event,
+                app.config.dedupe_window,
+            )?;
+            if app.dry_run {
+                print_changes(&[change]);
+            } else {
+                data::apply(&[change])?;
+            }
+            print_day(app, restored.date, &created.events);
+        }
+    }
+    Ok(())
+}
+
+/// Asks `question` on the terminal, taking only "y" or "yes" for a yes
+fn confirm(question: &str) -> bool {
+    eprint!("{question} [y/N] ");
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).is_ok()
+        && matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
+fn print_status(app: &App, args: &StatusArgs) {
+    let Some(events) = read_today(app, args.max_latency) else {
+        eprintln!(
+            "Reading today's records took longer than {}ms",
+            args.max_latency.unwrap_or_default().num_milliseconds()
+        );
+        std::process::exit(1);
+    };
+    let events = events.unwrap();
+    let status = Status::compute(&events, Utc::now(), app.config.daily_target);
+    if app.porcelain {
+        println!("{}", status.to_porcelain());
+        return;
+    }
+    match args.format {
+        OutputFormat::Text => {
+            println!("{status}");
+            let mut events = events;
+            if status.clocked_in {
+                events.push(Event::clock_out(&Utc::now()));
+            }
+            warn_implausible(app, &intervals(&events));
+        }
+        OutputFormat::Json => println!("{}", status.to_json()),
+        OutputFormat::Waybar => println!("{}", status.to_waybar()),
+        OutputFormat::Prometheus => print!("{}", status.to_prometheus()),
+    }
+}
+
+/// Prints the status for a shell prompt, or nothing if it fails, so that
+/// the prompt stays usable
+fn prompt(app: &App, max_latency: Option<Duration>) {
+    if let Some(Ok(events)) = read_today(app, max_latency) {
+        let status =
+            Status::compute(&events, Utc::now(), app.config.daily_target);
+        println!("{}", status.to_prompt());
+    }
+}
+
+/// Reads today's records, or with a `max_latency` only the end of a month
+/// file and within that time, or else returns `None`
+fn read_today(
+    app: &App,
+    max_latency: Option<Duration>,
+) -> Option<Result<Vec<Event>>> {
+    let today = Local::now().date_naive();
+    let Some(max_latency) = max_latency else {
+        return Some(read_events(app.storage, today));
+    };
+    let dir = app.storage_dir.clone();
+    let (format, layout) =
+        (app.config.storage_format, app.config.storage_layout);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    // a read that hangs, e.g., on a network share, is left behind
+    std::thread::spawn(move || {
+        let _ =
+            sender.send(data::read_latest_day(&dir, format, layout, today));
+    });
+    receiver
+        .recv_timeout(max_latency.to_std().unwrap_or_default())
+        .ok()
+}
+
+fn doctor(app: &App, fix: bool) {
+    let mut findings =
+        doctor::check(&app.storage_dir, fix && !app.dry_run).unwrap();
+    findings.extend(
+        doctor::check_days(
+            app.storage,
+            app.config.auto_clockout,
+            Utc::now(),
+            fix,
+        )
+        .unwrap(),
+    );
+    findings.extend(
+        doctor::check_constraints(
+            app.storage,
+            &app.config.hours,
+            Local::now().date_naive(),
+        )
+        .unwrap(),
+    );
+    if findings.is_empty() {
+        println!("No problems found");
+    }
+    for finding in findings {
+        println!("{finding}");
+    }
+}
+
+fn check_client(config: &Config, client: &str, project: &str) -> Result<()> {
+    let projects = config.clients.get(client).ok_or_else(|| {
+        anyhow!("Unknown client {client}, add it to [clients] in your config")
+    })?;
+    if !projects.iter().any(|p| p == project) {
+        return Err(anyhow!(
+            "{project} is not a project of {client}; its projects are: {}",
+            projects.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Records an interruption that ends now, which has to fall within the
+/// current working block
+fn interrupt(app: &App, note: String, minutes: u32) -> Result<()> {
+    let start = Utc::now() - Duration::minutes(i64::from(minutes));
+    let events = read_events(app.storage, start.date_naive())?;
+    let block = events
+        .iter()
+        .filter(|event| event.kind.is_clock())
+        .max_by_key(|event| event.dt);
+    match block {
+        Some(clock_in)
+            if clock_in.kind == EventKind::ClockIn && clock_in.dt <= start => {
+        }
+        _ => bail!(
+            "You haven't been clocked in since {}, so there is no working \
+            block to interrupt",
+            start.with_timezone(&Local).format("%H:%M")
+        ),
+    }
+    record(app, &Event::interruption(&start, minutes, note))
+}
+
+/// Stores a clock event and prints the day's records
+fn record(app: &App, event: &Event) -> Result<()> {
+    let event = &event.clone().with_source(Source::Manual);
+    check_plausible(app, event)?;
+    let created = create_event(app.storage, event, app.config.dedupe_window)?;
+    let events = created.events;
+    let is_new = created.duplicate_of.is_none();
+    if let Some(existing) = created.duplicate_of {
+        let time = existing.dt.with_timezone(&Local).format("%H:%M");
+        let kind = match existing.kind {
+            EventKind::ClockIn => "Clock in",
+            EventKind::ClockOut => "Clock out",
+            EventKind::StandbyStart => "Standby start",
+            EventKind::StandbyEnd => "Standby end",
+            EventKind::Interruption { .. } => "Interruption",
+        };
+        let message =
+            format!("{kind} already recorded at {time}, nothing to do");
+        if app.porcelain {
+            eprintln!("{message}");
+        } else {
+            println!("{message}");
+        }
+    } else {
+        run_hook(app, Hook::after(event), event);
+        for warning in app.config.hours.warnings(event, &events) {
+            eprintln!("Warning: {warning}");
+        }
+    }
+    // a configured auto clock-out closes the day anyway
+    if let (Some(dangling), None) =
+        (created.dangling, app.config.auto_clockout)
+    {
+        warn_dangling(app, &dangling);
+    }
+    print_day(app, event.dt.date_naive(), &events);
+    if is_new && event.kind == EventKind::ClockOut {
+        print_day_summary(app, event, &events);
+    }
+    Ok(())
+}
+
+/// Tells how the day compares to the daily target, if `clock_out` closed
+/// its last working block and the summary isn't turned off
+fn print_day_summary(app: &App, clock_out: &Event, events: &[Event]) {
+    let is_last = !events
+        .iter()
+        .any(|event| event.kind.is_clock() && event.dt > clock_out.dt);
+    let date = clock_out.dt.date_naive();
+    let report =
+        DailyReport::compute(date, &StoredEvent::number(events), &[], &[]);
+    if app.config.day_summary && !app.porcelain && is_last && report.complete {
+        let target = burndown::due(
+            date,
+            date,
+            app.config.daily_target,
+            &app.config.holidays,
+        );
+        println!("{}", report.summary(target));
+    }
+}
+
+/// Prints the `events` of `date` after a command changed them, as a report
+/// or as a line for scripts
+fn print_day(app: &App, date: NaiveDate, events: &[Event]) {
+    if app.porcelain {
+        let stored = StoredEvent::number(events);
+        let report = DailyReport::compute(date, &stored, &[], &[]);
+        println!("{}", report.to_porcelain());
+    } else {
+        println!(
+            "{}",
+            daily_report(&date, events, ReportFormat::Text).unwrap()
+        );
+    }
+}
+
+/// Points out that the day of the `dangling` clock-in has no clock-out
+/// after it, with the command that adds one
+fn warn_dangling(app: &App, dangling: &Event) {
+    let local = dangling.dt.with_timezone(&Local);
+    let date = local.date_naive();
+    let yesterday = Local::now().date_naive().pred_opt();
+    let date_arg = if Some(date) == yesterday {
+        "yesterday".to_string()
+    } else {
+        date.to_string()
+    };
+    eprintln!(
+        "Warning: {date} ends with a clock-in at {} and no clock-out. To \
+        add one, run:\n  busy-bee clock-out --date {date_arg} {}",
+        local.format("%H:%M"),
+        app.config.end_of_day().format("%H%M")
+    );
+}
+
+/// Fails if `event` is further in the past or future than configured, or
+/// if the system clock seems off, unless forced
+fn check_plausible(app: &App, event: &Event) -> Result<()> {
+    if app.force {
+        return Ok(());
+    }
+    let now = Utc::now();
+    app.config.check_plausible(event.dt, now)?;
+    data::check_clock(app.storage, event, now)
+}
+
+/// Records a day from the default start to the default end time
+fn add_day(
+    app: &App,
+    date: NaiveDate,
+    project: Option<String>,
+    tags: Vec<String>,
+) -> Result<()> {
+    if !read_events(app.storage, date)?.is_empty() {
+        bail!("{date} already has records, use clock-in and clock-out");
+    }
+    let start =
+        get_date_time(app, Some(date), Some(app.config.default_start))?;
+    let end = get_date_time(app, Some(date), Some(app.config.end_of_day()))?;
+    if end <= start {
+        bail!("The default end time must be after the default start time");
+    }
+    let clock_in = Event::clock_in(&start)
+        .with_project(project)
+        .with_tags(tags)
+        .with_source(Source::Manual);
+    // Only the start, so that the rest of today can be recorded in advance
+    check_plausible(app, &clock_in)?;
+    let events =
+        [clock_in, Event::clock_out(&end).with_source(Source::Manual)];
+    create_events(app.storage, &events)?;
+    for event in &events {
+        run_hook(app, Hook::after(event), event);
+    }
+    print_day(app, date, &events);
+    Ok(())
+}
+
+/// Runs the commands in `file`, or stdin for `-`, after checking them all.
+/// Their changes are written once all of them ran, see `main`.
+fn batch(app: &App, file: &Path) -> Result<()> {
+    let input = if file == Path::new("-") {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(file)
+            .with_context(|| format!("Could not read {}", file.display()))?
+    };
+    let mut commands = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let number = i + 1;
+        let mut args = vec![OsString::from("busy-bee")];
+        args.extend(
+            cli::split_words(line)
+                .map_err(|err| anyhow!("Line {number}: {err}"))?
+                .into_iter()
+                .map(OsString::from),
+        );
+        let args = cli::expand_alias(args, &app.config.aliases);
+        let command = Cli::try_parse_from(args)
+            .map_err(|err| anyhow!("Line {number}: {err}"))?
+            .command;
+        if !command.batches() {
+            bail!(
+                "Line {number}: {line} can't run in a batch, only commands \
+                that record or delete events can"
+            );
+        }
+        commands.push((number, command));
+    }
+    for (number, command) in commands {
+        run_batchable(app, command)
+            .with_context(|| format!("Line {number} failed"))?;
+    }
+    Ok(())
+}
+
+/// Records a working day for each day of a range that has no records yet
+fn backfill(app: &App, args: &BackfillArgs) -> Result<()> {
+    if args.from > args.to {
+        bail!(
+            "The range ends on {}, before it starts on {}",
+            args.to,
+            args.from
+        );
+    }
+    let start = args.start.unwrap_or(app.config.default_start);
+    let end = args.end.unwrap_or_else(|| app.config.end_of_day());
+    if end <= start {
+        bail!("The end time {end} must be after the start time {start}");
+    }
+    let mut events = Vec::new();
+    let days = args.from.iter_days().take_while(|date| *date <= args.to);
+    for date in days {
+        let weekend = matches!(date.weekday(), Weekday::Sat | Weekday::Sun);
+        let skipped = if args.skip_weekends && weekend {
+            Some("weekend")
+        } else if args.skip_holidays && app.config.holidays.contains(&date) {
+            Some("holiday")
+        } else if !read_events(app.storage, date)?.is_empty() {
+            Some("already has records")
+        } else {
+            None
+        };
+        if let Some(reason) = skipped {
+            println!("{date}: skipped, {reason}");
+            continue;
+        }
+        let clock_in =
+            Event::clock_in(&get_date_time(app, Some(date), Some(start))?)
+                .with_project(args.project.clone())
+                .with_tags(args.tags.clone())
+                .with_source(Source::Manual);
+        events.push(clock_in);
+        events.push(
+            Event::clock_out(&get_date_time(app, Some(date), Some(end))?)
+                .with_source(Source::Manual),
+        );
+        println!(
+            "{date}: {} to {}",
+            start.format("%H:%M"),
+            end.format("%H:%M")
+        );
+    }
+    let clock_ins = events.iter().filter(|e| e.kind == EventKind::ClockIn);
+    for clock_in in clock_ins {
+        check_plausible(app, clock_in)?;
+    }
+    create_events(app.storage, &events)?;
+    for event in &events {
+        run_hook(app, Hook::after(event), event);
+    }
+    Ok(())
+}
+
+/// Reads a day's events, closing the day at the `auto_clockout` time if it
+/// ends with a clock-in
+fn read_closed(app: &App, date: NaiveDate) -> Result<Vec<Event>> {
+    let mut events = read_events(app.storage, date)?;
+    events.extend(closing_clock_out(app, date, &events));
+    Ok(events)
+}
+
+/// The clock-out at the `auto_clockout` time that closes a day ending with
+/// a clock-in
+fn closing_clock_out(
+    app: &App,
+    date: NaiveDate,
+    events: &[Event],
+) -> Option<Event> {
+    let cutoff = app.config.auto_clockout?;
+    auto_clock_out(date, events, cutoff, Utc::now())
+}
+
+/// The events of a month, or of any range of days, and the days whose
+/// records could not be read
+struct Month {
+    first_day: NaiveDate,
+    events: Vec<Event>,
+    unreadable: Vec<(NaiveDate, anyhow::Error)>,
+}
+
+/// Reads the events of a month, default is the current month
+fn read_month(app: &App, date: Option<NaiveDate>) -> Month {
+    let (first_day, last_day) = month_range(date);
+    read_days(app, first_day, last_day)
+}
+
+/// Reads the days from `first_day` to `last_day` like [`read_month`]
+fn read_days(app: &App, first_day: NaiveDate, last_day: NaiveDate) -> Month {
+    let mut month = Month {
+        first_day,
+        events: Vec::new(),
+        unreadable: Vec::new(),
+    };
+    for date in first_day.iter_days().take_while(|date| *date <= last_day) {
+        match read_closed(app, date) {
+            Ok(events) => month.events.extend(events),
+            Err(err) => month.unreadable.push((date, err)),
+        }
+    }
+    month
+}
+
+/// Warns about the days of `intervals` that add up to more than the
+/// plausible hours, highlighted on a terminal
+fn warn_implausible(app: &App, intervals: &[Interval]) {
+    if app.config.max_plausible_hours <= 0 {
+        return;
+    }
+    let max = Duration::hours(app.config.max_plausible_hours);
+    let (start, end) = if std::io::stderr().is_terminal() {
+        ("\x1b[1;31m", "\x1b[0m")
+    } else {
+        ("", "")
+    };
+    for day in implausible_days(intervals, max) {
+        eprintln!("{start}Warning: {day}{end}");
+    }
+}
+
+fn print_unreadable(unreadable: &[(NaiveDate, anyhow::Error)]) {
+    for (date, err) in unreadable {
+        println!("Could not read the records of {date}: {err:#}");
+    }
+}
+
+/// The first and last day to view, default is today
+fn view_range(
+    period: Option<(NaiveDate, NaiveDate)>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> (NaiveDate, NaiveDate) {
+    let today = Local::now().date_naive();
+    match (period, from) {
+        (Some(period), _) => period,
+        (None, Some(from)) => (from, to.unwrap_or(today)),
+        (None, None) => (today, today),
+    }
+}
+
+/// Draws the week from Monday to Sunday that `date` is in
+fn timeline(app: &App, date: NaiveDate, output: &OutputArgs) -> Result<()> {
+    let monday =
+        date - Days::new(date.weekday().num_days_from_monday().into());
+    let mut days = Vec::new();
+    for date in monday.iter_days().take(7) {
+        days.push((date, read_events(app.storage, date)?));
+    }
+    let content = week_timeline(&days, Utc::now()).unwrap();
+    send(app, output, &content)
+}
+
+/// Prints a line for scripts for each day from `first` to `last`, also
+/// for days without records
+fn porcelain_view(app: &App, first: NaiveDate, last: NaiveDate) -> Result<()> {
+    if first > last {
+        bail!("The range ends on {last}, before it starts on {first}");
+    }
+    for date in first.iter_days().take_while(|date| *date <= last) {
+        let stored = read_stored_events(app.storage, date)?;
+        let events: Vec<Event> =
+            stored.iter().map(|stored| stored.event.clone()).collect();
+        let closing = closing_clock_out(app, date, &events);
+        let report =
+            DailyReport::compute(date, &stored, closing.as_slice(), &[]);
+        println!("{}", report.to_porcelain());
+    }
+    Ok(())
+}
+
+/// Prints the daily reports of the days from `first` to `last`; days
+/// without records are left out of ranges
+fn view(
+    app: &App,
+    (first, last): (NaiveDate, NaiveDate),
+    format: ReportFormat,
+    output: &OutputArgs,
+    raw: bool,
+    verbose: bool,
+) -> Result<()> {
+    if first > last {
+        bail!("The range ends on {last}, before it starts on {first}");
+    }
+    let mut content = String::new();
+    let mut worked = Vec::new();
+    for date in first.iter_days().take_while(|date| *date <= last) {
+        let mut stored = read_stored_events(app.storage, date)?;
+        if !raw {
+            // Keeps the IDs of the records shown, for delete
+            let events: Vec<Event> =
+                stored.iter().map(|stored| stored.event.clone()).collect();
+            let kept = merge_gaps(&events, app.config.merge_gap);
+            stored.retain(|stored| kept.contains(&stored.event));
+        }
+        let notes: Vec<DayNote> =
+            data::read_day_notes(&app.storage_dir, date)?
+                .into_iter()
+                .filter(|note| note.date == date)
+                .collect();
+        if stored.is_empty() && notes.is_empty() && first != last {
+            continue;
+        }
+        let mut events: Vec<Event> =
+            stored.iter().map(|stored| stored.event.clone()).collect();
+        let closing = closing_clock_out(app, date, &events);
+        events.extend(closing.clone());
+        worked.extend(intervals(&events));
+        let report =
+            DailyReport::compute(date, &stored, closing.as_slice(), &notes)
+                .with_sources(verbose)
+                .with_width(output.width())
+                .render(format)
+                .unwrap();
+        if let ReportFormat::Json | ReportFormat::Html = format {
+            // one report per day, without the parts that are only text
+            content.push_str(&report);
+            continue;
+        }
+        let standby = standby_report(&events).unwrap();
+        let sections = app.sections.render(Scope::Day(date), &events);
+        let _ = writeln!(content, "{report}{standby}{sections}");
+    }
+    let structured = matches!(format, ReportFormat::Json | ReportFormat::Html);
+    if first != last && !structured {
+        let total: Duration = worked.iter().map(Interval::duration).sum();
+        let _ = writeln!(
+            content,
+            "{} {first} {} {last}: {} {}",
+            tr("Total working time from"),
+            tr("to"),
+            i18n::duration(&total),
+            tr("hours")
+        );
+    }
+    send(app, output, &content)?;
+    warn_implausible(app, &worked);
+    Ok(())
+}
+
+/// Sends the output of a command to where the user asked for
+fn send(app: &App, output: &OutputArgs, content: &str) -> Result<()> {
+    let destination = match output.destination() {
+        Destination::Stdout if app.pager => Destination::Pager,
+        destination => destination,
+    };
+    destination.send(content)?;
+    match destination {
+        Destination::Stdout | Destination::Pager => {}
+        Destination::File(path) => {
+            eprintln!("Output written to {}", path.display());
+        }
+        Destination::Clipboard => eprintln!("Output copied to the clipboard"),
+    }
+    Ok(())
+}
+
+fn report_command(app: &App, args: &ReportArgs) {
+    let scope = match (args.from, args.period) {
+        (Some(from), _) => Scope::Range(
+            from,
+            args.to.unwrap_or_else(|| Local::now().date_naive()),
+        ),
+        (None, Some((first, last)))
+            if (first, last) != month_range(Some(first)) =>
+        {
+            Scope::Range(first, last)
+        }
+        (None, period) => {
+            Scope::Month(month_range(period.map(|(first, _)| first)).0)
+        }
+    };
+    if matches!(scope, Scope::Range(..)) && (args.burndown || args.per_project)
+    {
+        eprintln!("--burndown and --per-project only report on whole months");
+        std::process::exit(1);
+    }
+    if args.per_project
+        && matches!(args.format, ReportFormat::Json | ReportFormat::Html)
+    {
+        eprintln!("--per-project only reports as text or markdown");
+        std::process::exit(1);
+    }
+    if let Some(year) = args.year {
+        let year = year.unwrap_or_else(|| Local::now().year());
+        year_report(app, year, &args.tags, &args.output).unwrap();
+    } else if let (true, Scope::Month(month)) = (args.burndown, scope) {
+        burndown_chart(app, Some(month), &args.tags, &args.output).unwrap();
+    } else {
+        report(app, scope, args).unwrap();
+    }
+    if let Some(path) = &args.export_per_day_csv {
+        export_per_day(app, scope, &args.tags, path).unwrap();
+    }
+}
+
+/// The first and last day of a report's month or range
+fn scope_range(scope: Scope) -> (NaiveDate, NaiveDate) {
+    match scope {
+        Scope::Day(date) => (date, date),
+        Scope::Month(date) => month_range(Some(date)),
+        Scope::Range(first, last) => (first, last),
+    }
+}
+
+fn report(app: &App, scope: Scope, args: &ReportArgs) -> Result<()> {
+    let ReportArgs {
+        tags, raw, format, ..
+    } = args;
+    let format = *format;
+    let grouping = (!args.per_project).then_some(args.group_by);
+    let structured = matches!(format, ReportFormat::Json | ReportFormat::Html);
+    let (first_day, last_day) = scope_range(scope);
+    if first_day > last_day {
+        bail!("The range ends on {last_day}, before it starts on {first_day}");
+    }
+    let Month {
+        mut events,
+        unreadable,
+        ..
+    } = read_days(app, first_day, last_day);
+    if !raw {
+        events = merge_gaps(&events, app.config.merge_gap);
+    }
+    let mut options = RowOptions::default();
+    options.daily_target = app.config.daily_target;
+    options.holidays.clone_from(&app.config.holidays);
+    options.only = if args.only_incomplete {
+        Some(RowFilter::Incomplete)
+    } else if args.only_overtime {
+        Some(RowFilter::Overtime)
+    } else {
+        None
+    };
+    // before filtering, as days without tagged time aren't missing
+    options.missing = burndown::missing_days(
+        first_day,
+        last_day,
+        &events,
+        &app.config.holidays,
+        Local::now().date_naive(),
+    );
+    let worked = intervals(&events);
+    if !tags.is_empty() {
+        events = with_tags(&events, tags);
+    }
+    let days = grouping.map(|grouping| {
+        days_report(app, scope, &events, grouping, &options, format)
+    });
+    if structured {
+        // the other parts of the report are only text
+        send(app, &args.output, &days.unwrap_or_default())?;
+        print_unreadable(&unreadable);
+        warn_implausible(app, &worked);
+        return Ok(());
+    }
+    let sections = app.sections.render(scope, &events);
+    let standby = standby_report(&events).unwrap();
+    let expenses = expense_report(
+        &read_expenses(app, first_day, last_day)?,
+        app.config.currency.as_deref(),
+        format,
+    )
+    .unwrap();
+    let mut content = if let Some(report) = days {
+        let per_tag = tag_report(&events, format).unwrap();
+        let per_client =
+            client_report(&events, &app.config.clients, format).unwrap();
+        let per_location = location_report(&events, format).unwrap();
+        let travel =
+            travel_report(&events, app.config.travel_share, format).unwrap();
+        format!(
+            "{report}{per_tag}{per_client}{per_location}{travel}{standby}\
+            {expenses}{sections}\n"
+        )
+    } else {
+        let report =
+            project_report(&first_day, &events, format, args.output.width())
+                .unwrap();
+        format!("{report}{standby}{expenses}{sections}\n")
+    };
+    content += &app.config.hours.report(&events, Local::now().date_naive());
+    send(app, &args.output, &content)?;
+    print_unreadable(&unreadable);
+    warn_implausible(app, &worked);
+    Ok(())
+}
+
+/// The days of `scope` one by one, or week by week with `grouping`
+fn days_report(
+    app: &App,
+    scope: Scope,
+    events: &[Event],
+    grouping: Grouping,
+    options: &RowOptions,
+    format: ReportFormat,
+) -> String {
+    let (first_day, last_day) = scope_range(scope);
+    if let Scope::Range(..) = scope {
+        let due = burndown::due(
+            first_day,
+            last_day,
+            app.config.daily_target,
+            &app.config.holidays,
+        );
+        range_report(
+            &first_day, &last_day, events, grouping, due, options, format,
+        )
+    } else {
+        monthly_report(&first_day, events, grouping, options, format)
+    }
+    .unwrap()
+}
+
+fn year_report(
+    app: &App,
+    year: i32,
+    tags: &[String],
+    output: &OutputArgs,
+) -> Result<()> {
+    let mut options = ReportOptions::from_config(&app.config);
+    options.tags = tags.to_vec();
+    // a dry run doesn't write, not even to the cache
+    let cache_dir = (!app.dry_run && !app.config.storage_read_only)
+        .then_some(app.storage_dir.as_path());
+    let report = report::yearly(
+        app.storage,
+        cache_dir,
+        year,
+        &options,
+        Local::now().date_naive(),
+    )?;
+    send(app, output, &report.to_string())
+}
+
+fn burndown_chart(
+    app: &App,
+    date: Option<NaiveDate>,
+    tags: &[String],
+    output: &OutputArgs,
+) -> Result<()> {
+    let Month {
+        first_day,
+        mut events,
+        unreadable,
+    } = read_month(app, date);
+    if !tags.is_empty() {
+        events = with_tags(&events, tags);
+    }
+    let burndown = Burndown::compute(
+        first_day,
+        &intervals(&events),
+        app.config.daily_target,
+        &app.config.holidays,
+        Local::now().date_naive(),
+    );
+    let content = format!(
+        "{} {}:\n{}{}\n",
+        tr("Summary for"),
+        i18n::month(&first_day),
+        burndown.chart(output.width().unwrap_or(80)),
+        burndown.balance_line()
+    );
+    send(app, output, &content)?;
+    print_unreadable(&unreadable);
+    Ok(())
+}
+
+/// The expenses of the days from `first_day` to `last_day`
+fn read_expenses(
+    app: &App,
+    first_day: NaiveDate,
+    last_day: NaiveDate,
+) -> Result<Vec<Expense>> {
+    let mut expenses = Vec::new();
+    let mut month = first_day.with_day(1).unwrap();
+    while month <= last_day {
+        expenses.extend(data::read_expenses(&app.storage_dir, month)?);
+        month = month + Months::new(1);
+    }
+    expenses.retain(|e| first_day <= e.date && e.date <= last_day);
+    Ok(expenses)
+}
+
+fn export_per_day(
+    app: &App,
+    scope: Scope,
+    tags: &[String],
+    path: &Path,
+) -> Result<()> {
+    let (first_day, last_day) = scope_range(scope);
+    let mut events = read_days(app, first_day, last_day).events;
+    if !tags.is_empty() {
+        events = with_tags(&events, tags);
+    }
+    let csv =
+        export::per_day_csv(&intervals(&events), app.config.daily_target);
+    std::fs::write(path, csv)?;
+    println!("Wrote the summary of each day to {}", path.display());
+    Ok(())
+}
+
+/// Prints the answer to `query`, reading only the days it may cover
+fn print_query(app: &App, query: &str) -> Result<()> {
+    let query = Query::parse(query)?;
+    let mut events = Vec::new();
+    for date in app.storage.all_days()? {
+        if query.covers(date) {
+            events.extend(read_closed(app, date).with_context(|| {
+                format!("Could not read the records of {date}")
+            })?);
+        }
+    }
+    events = merge_gaps(&events, app.config.merge_gap);
+    print!("{}", query.run(&events));
+    Ok(())
+}
+
+fn earnings(app: &App, month: Option<NaiveDate>) {
+    let month = read_month(app, month);
+    let earnings = Earnings::compute(
+        month.first_day,
+        &intervals(&month.events),
+        &app.config.rates,
+        app.config.currency.clone(),
+    );
+    println!("{earnings}");
+    print_unreadable(&month.unreadable);
+}
+
+fn init(app: &App, storage_dir: Option<&Path>) -> Result<()> {
+    let dir = storage_dir.unwrap_or(&app.storage_dir);
+    data::create_dir(dir)?;
+    println!("Storage directory: {}", dir.display());
+    let path = app
+        .config_path
+        .as_deref()
+        .ok_or(anyhow!("Could not determine the config directory"))?;
+    if path.exists() {
+        println!("Config file: {} exists, left it as is", path.display());
+    } else {
+        config::init(path, storage_dir)?;
+        println!("Config file: {} created", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "self-update")]
+fn self_update(check: bool) -> Result<()> {
+    let release = busy_bee::update::Release::latest()?;
+    let current = env!("CARGO_PKG_VERSION");
+    if !release.is_newer() {
+        println!("busy-bee {current} is the latest release");
+    } else if check {
+        println!(
+            "busy-bee {} is available, you have {current}",
+            release.version
+        );
+    } else {
+        let path = release.install()?;
+        println!("Updated {} to busy-bee {}", path.display(), release.version);
+    }
+    Ok(())
+}
+
+fn man_pages(out_dir: Option<&Path>) -> Result<()> {
+    let pages = man::pages(&Cli::command());
+    let Some(dir) = out_dir else {
+        print!("{}", pages[0].1);
+        return Ok(());
+    };
+    std::fs::create_dir_all(dir)?;
+    for (name, page) in &pages {
+        std::fs::write(dir.join(name), page)?;
+    }
+    println!("Wrote {} man pages to {}", pages.len(), dir.display());
+    Ok(())
+}
+
+fn configure(app: &App, action: ConfigAction) -> Result<()> {
+    let path = app
+        .config_path
+        .as_deref()
+        .ok_or(anyhow!("Could not determine the config directory"))?;
+    match action {
+        ConfigAction::Get { key } => {
+            config::check_key(&key)?;
+            let mut table = config::read_table(path)?;
+            table.extend(config::env_table(std::env::vars()));
+            match table.get(&key) {
+                Some(value) => println!("{value}"),
+                None => println!("{key} is not set, the default applies"),
+            }
+        }
+        ConfigAction::Set { key, value } => {
+            config::set(path, &key, &value)?;
+            println!("Set {key} in {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Answers JSON requests read from stdin on stdout, see [`rpc`]
+fn serve(app: &App) -> Result<()> {
+    let server = rpc::Server {
+        storage: app.storage,
+        storage_dir: &app.storage_dir,
+        config: &app.config,
+        force: app.force,
+    };
+    server.serve(std::io::stdin().lock(), std::io::stdout())?;
+    Ok(())
+}
+
+fn info(app: &App) -> Result<()> {
+    println!("busy-bee {}", env!("CARGO_PKG_VERSION"));
+    match &app.config_path {
+        Some(path) if path.exists() => {
+            println!("Config file: {}", path.display());
+        }
+        Some(path) => {
+            println!("Config file: {} (not found)", path.display());
+        }
+        None => println!("Config file: none"),
+    }
+    println!("Storage directory: {}", app.storage_dir.display());
+    println!(
+        "Storage: {}, {} layout{}",
+        app.config.storage_format,
+        app.config.storage_layout,
+        if app.config.storage_read_only {
+            ", read-only"
+        } else {
+            ""
+        }
+    );
+    if let Some(dir) = &app.hooks_dir {
+        println!("Hooks directory: {}", dir.display());
+    }
+    let days = app.storage.all_days()?;
+    match (days.first(), days.last()) {
+        (Some(first), Some(last)) => {
+            println!("Tracked days: {} from {first} to {last}", days.len());
+        }
+        _ => println!("Tracked days: 0"),
+    }
+    println!("Language: {:?}", i18n::language());
+    println!("Settings: {:#?}", app.config);
+    Ok(())
+}
+
+fn compliance(app: &App, month: Option<NaiveDate>) {
+    let month = read_month(app, month);
+    let compliance =
+        Compliance::check(month.first_day, &intervals(&month.events));
+    print!("{compliance}");
+    print_unreadable(&month.unreadable);
+}
+
+fn export_period(app: &App, args: &ExportArgs) -> Result<()> {
+    let (first_day, last_day) =
+        args.period.unwrap_or_else(|| month_range(None));
+    let mut content = String::new();
+    let events = read_range(app.storage, first_day, last_day)?;
+    let format = args.format.unwrap_or(ExportFormat::Records);
+    match format {
+        ExportFormat::Jira if args.anonymize => {
+            bail!(
+                "Jira worklogs need the projects, which --anonymize \
+                replaces; try --format records"
+            );
+        }
+        ExportFormat::Jira => {
+            let (worklogs, unmapped) = export::jira_worklogs(
+                &intervals(&events),
+                &app.config.jira_issues,
+                args.issue_from_project,
+            );
+            for worklog in worklogs {
+                let _ = writeln!(content, "{}", worklog.to_json());
+            }
+            for interval in unmapped {
+                eprintln!(
+                    "Warning: no Jira issue for {} on {}, skipped",
+                    interval
+                        .project
+                        .as_deref()
+                        .unwrap_or("time without project"),
+                    interval
+                        .start
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M"),
+                );
+            }
+        }
+        ExportFormat::Records if args.anonymize => {
+            // up to about ten years back, random enough to hide the dates
+            let weeks = RandomState::new().hash_one(Utc::now()) % 520 + 1;
+            let events =
+                export::anonymize(&events, u32::try_from(weeks).unwrap());
+            content = events_to_string(&events, Format::Csv);
+        }
+        ExportFormat::Records => {
+            content = events_to_string(&events, Format::Csv);
+        }
+    }
+    send(app, &args.output, &content)
+}
+
+/// The first and last day of the given month, default is the current month
+fn month_range(date: Option<NaiveDate>) -> (NaiveDate, NaiveDate) {
+    let first_of_month =
+        date.unwrap_or_else(|| Local::now().date_naive().with_day(1).unwrap());
+    let last_of_month = first_of_month
+        .checked_add_months(Months::new(1))
+        .and_then(|next| next.pred_opt())
+        .unwrap();
+    (first_of_month, last_of_month)
+}
+
+/// Rewrites the records as `format` in `layout`, default is the configured
+/// ones
+fn migrate_storage(app: &App, format: Option<Format>, layout: Option<Layout>) {
+    let config = &app.config;
+    let format = format.unwrap_or(config.storage_format);
+    let layout = layout.unwrap_or(config.storage_layout);
+    if format == config.storage_format && layout == config.storage_layout {
+        println!(
+            "Records are already stored as {format} in the {layout} layout, \
+            nothing to do"
+        );
+        return;
+    }
+    let target = data::open(&app.storage_dir, format, layout);
+    let (migrated, changes) =
+        data::plan_migrate(app.storage, target.as_ref()).unwrap();
+    if app.dry_run {
+        print_changes(&changes);
+    } else {
+        data::apply(&changes).unwrap();
+    }
+    println!(
+        "Migrated {migrated} days to {format} in the {layout} layout. Set \
+        storage.format = \"{format}\" and storage.layout = \"{layout}\" in \
+        your config file to keep using them."
+    );
+}
+
+fn suggest_events(app: &App, date: NaiveDate) {
+    if app.config.git_repos.is_empty() {
+        println!(
+            "No git repositories configured, add them as git_repos = \
+            [\"~/path/to/repo\"] to your config file"
+        );
+        return;
+    }
+    let mut commits = Vec::new();
+    for repo in &app.config.git_repos {
+        match suggest::commit_times(repo, date) {
+            Ok(times) => commits.extend(times),
+            Err(err) => {
+                eprintln!("Warning: skipping {}: {err:#}", repo.display());
+            }
+        }
+    }
+    let events = read_events(app.storage, date).unwrap();
+    let suggestions = suggest::suggest(&events, &commits);
+    if suggestions.is_empty() {
+        println!("Nothing to suggest for {date}");
+    }
+    for event in suggestions {
+        let kind = match event.kind {
+            EventKind::ClockIn => "clock in",
+            EventKind::ClockOut => "clock out",
+            EventKind::StandbyStart => "standby start",
+            EventKind::StandbyEnd => "standby end",
+            EventKind::Interruption { .. } => "interruption",
+        };
+        let time = event.dt.with_timezone(&Local).format("%H:%M");
+        print!("Record {kind} at {time} on {date}? [y/N] ");
+        std::io::stdout().flush().unwrap();
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer).unwrap();
+        if answer.trim().eq_ignore_ascii_case("y") {
+            record(app, &event).unwrap();
+        }
+    }
+}
+
+fn edit_raw(app: &App, date: NaiveDate) {
+    let events = read_events(app.storage, date).unwrap();
+    let format = app.config.storage_format;
+    let mut file = tempfile::Builder::new()
+        .prefix(&format!("busy-bee-{date}-"))
+        .suffix(&format!(".{format}"))
+        .tempfile()
+        .unwrap();
+    file.write_all(data::events_to_string(&events, format).as_bytes())
+        .unwrap();
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    loop {
+        let mut words = editor.split_whitespace();
+        let status = std::process::Command::new(words.next().unwrap_or("vi"))
+            .args(words)
+            .arg(file.path())
+            .status()
+            .unwrap();
+        if !status.success() {
+            println!("The editor exited with {status}, nothing was saved");
+            return;
+        }
+        let content = std::fs::read_to_string(file.path()).unwrap();
+        match edit::normalize(date, &content) {
+            Ok(edited) => {
+                app.storage.write_day(date, &edited).unwrap();
+                println!(
+                    "{}",
+                    daily_report(&date, &edited, ReportFormat::Text).unwrap()
+                );
+                return;
+            }
+            Err(err) => {
+                print!("{err:#}\nEdit again? [Y/n] ");
+                std::io::stdout().flush().unwrap();
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer).unwrap();
+                if answer.trim().eq_ignore_ascii_case("n") {
+                    println!("Nothing was saved");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn run_hook(app: &App, hook: Hook, event: &Event) {
+    let Some(hooks_dir) = &app.hooks_dir else {
+        return;
+    };
+    if app.dry_run {
+        return;
+    }
+    // The records have been written at this point, so a failing hook
+    // doesn't fail the command
+    if let Err(err) = hooks::run(hooks_dir, hook, event, &app.storage_dir) {
+        eprintln!("Warning: {hook} hook failed: {err:#}");
+    }
+}
+
+/// Shows the status every `interval`, and as soon as the records change,
+/// e.g., because another program wrote to them, until interrupted
+fn watch(app: &App, interval: Duration) {
+    signals::catch_interrupts();
+    let interval = interval
+        .to_std()
+        .unwrap_or(std::time::Duration::from_mins(1))
+        .max(std::time::Duration::from_secs(1));
+    // interrupts are checked for more often than the files, which may be
+    // slow to list
+    let tick = std::time::Duration::from_millis(100);
+    let ticks_per_poll = 10;
+    loop {
+        // the date is determined anew each time, so that watching continues
+        // past midnight
+        let today = Local::now().date_naive();
+        let events = read_events(app.storage, today).unwrap();
+        let status =
+            Status::compute(&events, Utc::now(), app.config.daily_target);
+        // rewrite the current line, clearing what's left of the previous
+        // output
+        print!("\r{status}\x1b[K");
+        std::io::stdout().flush().unwrap();
+        let shown = Snapshot::take(&app.storage_dir).unwrap_or_default();
+        let refresh_at = std::time::Instant::now() + interval;
+        for tick_count in 1.. {
+            if std::time::Instant::now() >= refresh_at {
+                break;
+            }
+            std::thread::sleep(tick);
+            if signals::interrupted() {
+                // leave the shell prompt a line of its own
+                println!();
+                return;
+            }
+            if tick_count % ticks_per_poll == 0
+                && Snapshot::take(&app.storage_dir).unwrap_or_default()
+                    != shown
+            {
+                break;
+            }
+        }
+    }
+}
+
+fn import_file(storage: &dyn Storage, file: &Path, diff: bool) {
+    let content = std::fs::read_to_string(file).unwrap();
+    let events = if ics::is_calendar(&content) {
+        let calendar = ics::parse(&content).unwrap();
+        let (events, overlapping) =
+            import::meetings(storage, &calendar).unwrap();
+        for meeting in overlapping {
+            println!(
+                "Skipped meeting '{}' at {}, it overlaps recorded time",
+                meeting.summary,
+                meeting.start.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+            );
+        }
+        events
+    } else {
+        parse_events(&content).unwrap()
+    };
+    let plan = import::plan(storage, &events).unwrap();
+    if diff {
+        print!("{}", import::render_diff(&plan));
+        return;
+    }
+
+    let added = import::apply(storage, &plan).unwrap();
+    let conflicts: Vec<_> = plan
+        .iter()
+        .filter(|c| matches!(c.outcome, Outcome::Conflict { .. }))
+        .cloned()
+        .collect();
+    let skipped = plan.len() - added - conflicts.len();
+    println!(
+        "Imported {added} events, skipped {skipped} duplicates and {} \
+        conflicting events",
+        conflicts.len()
+    );
+    if !conflicts.is_empty() {
+        print!("{}", import::render_diff(&conflicts));
+    }
+}
+
+fn merge_dir(app: &App, args: &MergeArgs) -> Result<()> {
+    let layout = args.layout.unwrap_or(app.config.storage_layout);
+    // the format only matters for writing, both are read
+    let theirs = data::open(&args.dir, app.config.storage_format, layout);
+    let plan = merge::plan(app.storage, theirs.as_ref(), args.window)?;
+    app.storage.write_days(&plan.days)?;
+    println!("Merged {} events into {} days", plan.added, plan.days.len());
+    if plan.conflicts.is_empty() {
+        return Ok(());
+    }
+    let report = merge::render_conflicts(&plan.conflicts);
+    println!("{} days couldn't be merged", plan.conflicts.len());
+    if app.dry_run {
+        print!("{report}");
+    } else {
+        std::fs::write(&args.report, report).with_context(|| {
+            format!("Could not write {}", args.report.display())
+        })?;
+        println!("See {} for what to resolve", args.report.display());
+    }
+    Ok(())
+}
+
+fn print_changes(changes: &[FileChange]) {
+    for change in changes {
+        print!("{}", diff::render(change));
+    }
+}
+
+/// Like [`get_date_time`], with a combined date and time taking precedence
+fn clock_out_time(
+    app: &App,
+    date: Option<NaiveDate>,
+    time: Option<NaiveTime>,
+    at: Option<NaiveDateTime>,
+    duration: Option<Duration>,
+) -> Result<DateTime<Utc>> {
+    if let Some(duration) = duration {
+        return clock_out_after(app, date, duration);
+    }
+    let time = time.or(date.map(|_| app.config.end_of_day()));
+    resolve_date_time(app, at, date, time)
+}
+
+/// When `duration` has passed since the last clock-in of `date`, default
+/// is today
+fn clock_out_after(
+    app: &App,
+    date: Option<NaiveDate>,
+    duration: Duration,
+) -> Result<DateTime<Utc>> {
+    let date = date.unwrap_or_else(|| Local::now().date_naive());
+    let events = read_events(app.storage, date)?;
+    let last_clock = events.iter().rev().find(|event| event.kind.is_clock());
+    let clock_in = match last_clock {
+        Some(event) if event.kind == EventKind::ClockIn => event,
+        Some(event) => bail!(
+            "Already clocked out at {} on {date}",
+            event.dt.with_timezone(&Local).format("%H:%M")
+        ),
+        None => bail!("There is no clock-in on {date} to count from"),
+    };
+    let dt = clock_in.dt + duration;
+    if dt > Utc::now() {
+        bail!(
+            "Clocking out at {} would be in the future",
+            dt.with_timezone(&Local).format("%H:%M")
+        );
+    }
+    Ok(dt)
+}
+
+fn resolve_date_time(
+    app: &App,
+    at: Option<NaiveDateTime>,
     maybe_date: Option<NaiveDate>,
     maybe_time: Option<NaiveTime>,
 ) -> Result<DateTime<Utc>> {
-    match (maybe_date, maybe_time) {
-        (Some(date), Some(time)) => {
-            let naive_dt = date.and_time(time);
-            Local
-                .from_local_datetime(&naive_dt)
-                .single()
-                .ok_or_else(|| {
-                    anyhow!(
-                    "{} cannot be converted to an unambiguous point in time",
-                    naive_dt
-                )
-                })
-                .map(|dt| dt.to_utc())
-        }
-        (None, Some(time)) => Ok(Local::now())
-            .and_then(|t| {
-                t.with_hour(time.hour())
-                    .ok_or(anyhow!("Cannot use {} as hour", time.hour()))
-            })
-            .and_then(|t| {
-                t.with_minute(time.minute())
-                    .ok_or(anyhow!("Cannot use {} as minute", time.minute()))
-            })
-            .map(|t| t.with_timezone(&Utc)),
-        (Some(_), None) => Err(anyhow!("Date specified, but no time")),
-        (None, None) => Ok(Local::now().to_utc()),
+    match at {
+        Some(at) => get_date_time(app, Some(at.date()), Some(at.time())),
+        None => get_date_time(app, maybe_date, maybe_time),
     }
 }
+
+/// The instant of `maybe_time` on `maybe_date`, default is today, or now
+/// if neither is given
+fn get_date_time(
+    app: &App,
+    maybe_date: Option<NaiveDate>,
+    maybe_time: Option<NaiveTime>,
+) -> Result<DateTime<Utc>> {
+    let time = match (maybe_date, maybe_time) {
+        (_, Some(time)) => time,
+        (Some(_), None) => bail!("Date specified, but no time"),
+        (None, None) => return Ok(Local::now().to_utc()),
+    };
+    let date = maybe_date.unwrap_or_else(|| Local::now().date_naive());
+    cli::resolve_local(&Local, date.and_time(time), app.prefer)
+        .map_err(|err| anyhow!(err))
+}
@@ -0,0 +1,262 @@
+//! The hours worked in a month so far against the hours to work by then
+
+use std::fmt::Write;
+
+use chrono::{
+    DateTime, Datelike, Duration, Local, Months, NaiveDate, Utc, Weekday,
+};
+
+use crate::{
+    i18n::{self, tr},
+    interval::Interval,
+};
+
+/// The height of the chart in lines, without the axis and legend
+pub const HEIGHT: usize = 12;
+
+/// How many hours were worked and due by the end of each day of a month
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Burndown {
+    pub month: NaiveDate,
+    /// The hours due from the start of the month to each day, one entry per
+    /// day of the month
+    pub target: Vec<Duration>,
+    /// The hours worked from the start of the month to each day, up to
+    /// today
+    pub worked: Vec<Duration>,
+}
+
+impl Burndown {
+    /// Adds up the `intervals` of the month of `month` day by day, and the
+    /// `daily_target` of each weekday that isn't one of the `holidays`
+    pub fn compute(
+        month: NaiveDate,
+        intervals: &[Interval],
+        daily_target: Duration,
+        holidays: &[NaiveDate],
+        today: NaiveDate,
+    ) -> Burndown {
+        let first_day = month.with_day(1).unwrap();
+        let next_month = first_day + Months::new(1);
+        let mut target = Vec::new();
+        let mut worked = Vec::new();
+        let mut due = Duration::zero();
+        let mut done = Duration::zero();
+        for date in first_day.iter_days().take_while(|d| *d < next_month) {
+            if is_workday(date, holidays) {
+                due += daily_target;
+            }
+            target.push(due);
+            if date > today {
+                continue;
+            }
+            done += intervals
+                .iter()
+                .filter(|interval| local(interval.start).date_naive() == date)
+                .map(Interval::duration)
+                .sum();
+            worked.push(done);
+        }
+        Burndown {
+            month: first_day,
+            target,
+            worked,
+        }
+    }
+
+    /// How much more was worked than was due by the last day worked, which
+    /// is negative when behind
+    pub fn balance(&self) -> Duration {
+        match (self.worked.last(), self.worked.len().checked_sub(1)) {
+            (Some(worked), Some(i)) => *worked - self.target[i],
+            _ => Duration::zero(),
+        }
+    }
+
+    /// Tells the hours worked and due so far, and by how much you're ahead
+    /// or behind
+    pub fn balance_line(&self) -> String {
+        let worked = self.worked.last().copied().unwrap_or_default();
+        let balance = self.balance();
+        format!(
+            "{}: {} / {} {}, {} {}",
+            tr("Worked"),
+            i18n::duration(&worked),
+            i18n::duration(&(worked - balance)),
+            tr("hours"),
+            i18n::duration(&balance.abs()),
+            if balance < Duration::zero() {
+                tr("behind")
+            } else {
+                tr("ahead")
+            }
+        )
+    }
+
+    /// Draws the worked hours as bars and the target as a line, fitting
+    /// the chart into `width` columns where the month has enough days,
+    /// e.g., for a terminal as wide
+    pub fn chart(&self, width: usize) -> String {
+        let max = self
+            .target
+            .iter()
+            .chain(&self.worked)
+            .max()
+            .copied()
+            .unwrap_or_default()
+            .num_minutes()
+            .max(1);
+        let top = format!("{}h", (max + 59) / 60);
+        let label_width = top.len();
+        let days = self.target.len().max(1);
+        let day_width =
+            (width.saturating_sub(label_width + 2) / days).clamp(1, 3);
+        // The line that each amount of hours is drawn up to, 0 is nothing
+        let level = |duration: &Duration| {
+            let minutes = usize::try_from(duration.num_minutes()).unwrap_or(0);
+            let max = usize::try_from(max).unwrap_or(1);
+            (minutes * HEIGHT + max / 2) / max
+        };
+
+        let mut result = String::new();
+        for line in (1..=HEIGHT).rev() {
+            let label = if line == HEIGHT { top.as_str() } else { "" };
+            let mut row = format!("{label:>label_width$} |");
+            for (i, target) in self.target.iter().enumerate() {
+                let bar = self.worked.get(i).map_or(0, &level);
+                let cell = match (bar >= line, level(target) == line) {
+                    (true, true) => '+',
+                    (true, false) => '#',
+                    (false, true) => '-',
+                    (false, false) => ' ',
+                };
+                row.extend(std::iter::repeat_n(cell, day_width));
+            }
+            writeln!(result, "{}", row.trim_end()).unwrap();
+        }
+        let axis = "-".repeat(days * day_width);
+        writeln!(result, "{:>label_width$} +{axis}", "0h").unwrap();
+        let mut numbers = String::new();
+        for day in 1..=days {
+            let column = (day - 1) * day_width;
+            if (day == 1 || day % 5 == 0) && numbers.len() <= column {
+                let padding = column - numbers.len();
+                write!(numbers, "{:padding$}{day}", "").unwrap();
+            }
+        }
+        writeln!(result, "{:label_width$}  {numbers}", "").unwrap();
+        writeln!(
+            result,
+            "# {}, - {}, + {}",
+            tr("worked"),
+            tr("target"),
+            tr("both")
+        )
+        .unwrap();
+        result
+    }
+}
+
+/// The hours due from `from` to `to`, both inclusive: the `daily_target`
+/// for each weekday that isn't one of the `holidays`, e.g., for a range
+/// that covers part of a month
+pub fn due(
+    from: NaiveDate,
+    to: NaiveDate,
+    daily_target: Duration,
+    holidays: &[NaiveDate],
+) -> Duration {
+    from.iter_days()
+        .take_while(|date| *date <= to)
+        .filter(|date| is_workday(*date, holidays))
+        .map(|_| daily_target)
+        .sum()
+}
+
+/// The weekdays from `from` to `to`, both inclusive, before `today` that
+/// aren't one of the `holidays` and have none of the `events`, which
+/// suggests the records of those days were forgotten
+pub fn missing_days(
+    from: NaiveDate,
+    to: NaiveDate,
+    events: &[crate::record::Event],
+    holidays: &[NaiveDate],
+    today: NaiveDate,
+) -> Vec<NaiveDate> {
+    from.iter_days()
+        .take_while(|date| *date <= to && *date < today)
+        .filter(|date| is_workday(*date, holidays))
+        .filter(|date| !events.iter().any(|e| e.dt.date_naive() == *date))
+        .collect()
+}
+
+fn is_workday(date: NaiveDate, holidays: &[NaiveDate]) -> bool {
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+        && !holidays.contains(&date)
+}
+
+fn local(dt: DateTime<Utc>) -> DateTime<Local> {
+    DateTime::from(dt)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::record::Event;
+
+    fn day(date: NaiveDate, hours: u32) -> Interval {
+        let start = Local
+            .from_local_datetime(&date.and_hms_opt(9, 0, 0).unwrap())
+            .unwrap()
+            .to_utc();
+        Interval {
+            start,
+            end: start + Duration::hours(hours.into()),
+            project: None,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn worked_hours_are_compared_with_the_hours_due() {
+        // June 2024 starts on a Saturday
+        let date = |d| NaiveDate::from_ymd_opt(2024, 6, d).unwrap();
+        let intervals = [day(date(3), 9), day(date(4), 6), day(date(5), 10)];
+        let burndown = Burndown::compute(
+            date(1),
+            &intervals,
+            Duration::hours(8),
+            &[date(4)],
+            date(5),
+        );
+        assert_eq!(burndown.target.len(), 30);
+        assert_eq!(burndown.target[4], Duration::hours(16));
+        assert_eq!(burndown.worked.len(), 5);
+        assert_eq!(burndown.worked[4], Duration::hours(25));
+        assert_eq!(burndown.balance(), Duration::hours(9));
+        assert_eq!(
+            burndown.balance_line(),
+            "Worked: 25:00 / 16:00 hours, 09:00 ahead"
+        );
+
+        let chart = burndown.chart(80);
+        let lines: Vec<_> = chart.lines().collect();
+        assert_eq!(lines.len(), HEIGHT + 3);
+        assert!(lines[0].starts_with("152h |"));
+        assert!(lines[HEIGHT].starts_with("  0h +"));
+        assert!(lines[HEIGHT + 1].trim_start().starts_with("1 "));
+
+        // Monday to the next Tuesday, without the holiday
+        assert_eq!(
+            due(date(3), date(11), Duration::hours(8), &[date(4)]),
+            Duration::hours(48)
+        );
+        let events = [Event::clock_in(&intervals[0].start)];
+        assert_eq!(
+            missing_days(date(1), date(30), &events, &[date(4)], date(7)),
+            [date(5), date(6)]
+        );
+    }
+}
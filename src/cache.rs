@@ -0,0 +1,185 @@
+//! Totals of past months, kept in the storage directory so that reports
+//! over a year don't read every data file again, e.g., on a network share.
+//! A cached month is used only while its data files have the same sizes
+//! and modification times as when it was cached.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDate};
+
+use crate::{
+    data::{archived_path, data_files, Storage},
+    json,
+    report::{self, MonthTotals, ReportOptions},
+};
+
+/// The subdirectory of the storage directory that months are cached in,
+/// one JSON file per month
+pub const CACHE_DIR: &str = "cache";
+
+/// The totals of the month of `month`, from the cache if it's still up to
+/// date, and otherwise read from `storage` and cached. Failing to cache
+/// only costs time, so it isn't an error.
+pub fn month_totals(
+    storage: &dyn Storage,
+    storage_dir: &Path,
+    month: NaiveDate,
+    options: &ReportOptions,
+) -> Result<MonthTotals> {
+    let stamp = stamp(storage_dir, month, options)?;
+    let path = cache_path(storage_dir, month);
+    if let Some(totals) = read(&path, month, &stamp) {
+        return Ok(totals);
+    }
+    let totals = MonthTotals::from(&report::monthly(storage, month, options)?);
+    if fs::create_dir_all(parent(&path)).is_ok() {
+        let _ = fs::write(&path, to_json(&totals, &stamp).to_string());
+    }
+    Ok(totals)
+}
+
+fn cache_path(storage_dir: &Path, month: NaiveDate) -> PathBuf {
+    storage_dir
+        .join(CACHE_DIR)
+        .join(format!("{}.json", month.format("%Y-%m")))
+}
+
+fn parent(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new("."))
+}
+
+/// Describes the data files of the month, as they are and as they're
+/// archived, and the `options` that the totals depend on, so that a change
+/// to either makes for a different stamp
+fn stamp(
+    storage_dir: &Path,
+    month: NaiveDate,
+    options: &ReportOptions,
+) -> Result<String> {
+    let prefix = month.format("%Y-%m").to_string();
+    let archived = parent(&archived_path(storage_dir, &prefix)).to_owned();
+    let mut files = data_files(storage_dir)?;
+    // archived files may be compressed, which data files aren't
+    if archived.is_dir() {
+        for entry in fs::read_dir(&archived)? {
+            files.push(entry?.path());
+        }
+        files.sort();
+    }
+    let mut stamp = format!(
+        "{:?} {} {:?}",
+        options.tags,
+        options.merge_gap.num_seconds(),
+        options.auto_clockout
+    );
+    for file in files {
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        if !name.starts_with(&prefix) {
+            continue;
+        }
+        let metadata = fs::metadata(&file)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let _ = write!(stamp, ";{name} {} {modified}", metadata.len());
+    }
+    Ok(stamp)
+}
+
+fn to_json(totals: &MonthTotals, stamp: &str) -> json::Value {
+    let projects = totals
+        .per_project
+        .iter()
+        .map(|(project, worked)| {
+            json::Value::Object(vec![
+                ("project".to_string(), project.clone().into()),
+                ("seconds".to_string(), worked.num_seconds().into()),
+            ])
+        })
+        .collect();
+    json::Value::Object(vec![
+        ("stamp".to_string(), stamp.into()),
+        ("seconds".to_string(), totals.worked.num_seconds().into()),
+        ("projects".to_string(), json::Value::Array(projects)),
+    ])
+}
+
+/// The cached totals at `path`, unless they are missing, unreadable or
+/// were cached with a different `stamp`
+fn read(path: &Path, month: NaiveDate, stamp: &str) -> Option<MonthTotals> {
+    let value = json::parse(&fs::read_to_string(path).ok()?).ok()?;
+    if value.get("stamp")?.as_str()? != stamp {
+        return None;
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    let seconds = |value: &json::Value| {
+        Some(Duration::seconds(value.get("seconds")?.as_f64()? as i64))
+    };
+    let mut per_project = BTreeMap::new();
+    for project in value.get("projects")?.as_array()? {
+        let name = project.get("project")?.as_str().map(String::from);
+        per_project.insert(name, seconds(project)?);
+    }
+    Some(MonthTotals {
+        month,
+        worked: seconds(&value)?,
+        per_project,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::data::{self, create_events, Layout};
+    use crate::record::{Event, Format};
+
+    #[test]
+    fn months_are_cached_until_their_files_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = data::open(dir.path(), Format::Csv, Layout::Day);
+        let at = |d, h| Utc.with_ymd_and_hms(2024, 6, d, h, 0, 0).unwrap();
+        let month = at(1, 0).date_naive();
+        let options = ReportOptions::default();
+        create_events(
+            storage.as_ref(),
+            &[Event::clock_in(&at(3, 9)), Event::clock_out(&at(3, 17))],
+        )
+        .unwrap();
+
+        let totals =
+            month_totals(storage.as_ref(), dir.path(), month, &options)
+                .unwrap();
+        assert_eq!(totals.worked, Duration::hours(8));
+        let cached = cache_path(dir.path(), month);
+        assert!(cached.is_file());
+        assert_eq!(
+            month_totals(storage.as_ref(), dir.path(), month, &options)
+                .unwrap(),
+            totals
+        );
+
+        // a stale cache is recognized by the stamp of the files
+        let stale = fs::read_to_string(&cached).unwrap();
+        create_events(
+            storage.as_ref(),
+            &[Event::clock_in(&at(4, 9)), Event::clock_out(&at(4, 13))],
+        )
+        .unwrap();
+        let totals =
+            month_totals(storage.as_ref(), dir.path(), month, &options)
+                .unwrap();
+        assert_eq!(totals.worked, Duration::hours(12));
+        assert_ne!(fs::read_to_string(&cached).unwrap(), stale);
+    }
+}
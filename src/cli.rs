@@ -1,13 +1,29 @@
-use std::path::PathBuf;
+use std::{collections::BTreeMap, ffi::OsString, path::PathBuf, str::FromStr};
 
-use chrono::{Datelike, Days};
-use chrono::{Local, NaiveDate, NaiveTime};
-use clap::{Parser, Subcommand};
+use chrono::{
+    DateTime, Datelike, Days, Duration, LocalResult, Months, TimeZone, Utc,
+    Weekday,
+};
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime};
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use regex::Regex;
 
+use crate::{
+    data::{Format, Layout, Source},
+    expense::parse_amount,
+    export::ExportFormat,
+    i18n::DurationFormat,
+    output::{self, Destination},
+    platform,
+    record::check_note,
+    status::OutputFormat,
+    view::{Grouping, ReportFormat},
+};
+
 /// A small tool to maintain a log of working times
 #[derive(Parser)]
 #[command(version, about)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
@@ -16,63 +32,672 @@ pub struct Cli {
     /// system specific convention.
     #[arg(long, short)]
     pub storage_dir: Option<PathBuf>,
+
+    /// Keep separate records under this name, in a subdirectory of the
+    /// storage directory. Defaults to `BUSY_BEE_PROFILE`.
+    #[arg(value_parser=parse_profile, long, short = 'P', global = true)]
+    pub profile: Option<String>,
+
+    /// Print the changes a command would make to the stored records instead
+    /// of writing them
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Refuse to run commands that change the stored records, e.g., for a
+    /// storage directory that another machine syncs to. Also set by
+    /// `storage.read_only` in the config file.
+    #[arg(long, global = true)]
+    pub read_only: bool,
+
+    /// Record events even if they're further in the past or future than
+    /// the configured limits, or now while a later event is recorded
+    #[arg(long, global = true)]
+    pub force: bool,
+
+    /// Print long output of view, report and export directly instead of
+    /// showing it in `PAGER`
+    #[arg(long, global = true)]
+    pub no_pager: bool,
+
+    /// Print status, view and the commands that record as tab-separated
+    /// lines for scripts, e.g., launchers and shell prompts, whose fields
+    /// don't change between versions
+    #[arg(long, global = true)]
+    pub porcelain: bool,
+
+    /// How reports show durations: hms, like 07:45, or decimal, like 7.75.
+    /// Defaults to the configured format.
+    #[arg(value_parser=parse_duration_format, long, global = true)]
+    pub duration_format: Option<DurationFormat>,
+
+    /// Which time to record where a local time happens twice because the
+    /// clocks go back: earliest or latest. Without it, such times are
+    /// refused.
+    #[arg(value_parser=parse_prefer, long, global = true)]
+    pub prefer: Option<Prefer>,
+}
+
+/// Which of the two instants to take for a local time that happens twice,
+/// as when daylight saving time ends
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Prefer {
+    Earliest,
+    Latest,
+}
+
+impl FromStr for Prefer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "earliest" => Ok(Prefer::Earliest),
+            "latest" => Ok(Prefer::Latest),
+            _ => Err(format!("Unknown choice {s}, try earliest or latest")),
+        }
+    }
+}
+
+/// The instant that the `local` date and time is in `tz`. Fails with a
+/// hint where the clocks skip the time, or where they go back over it
+/// unless `prefer` picks one of its two instants.
+pub fn resolve_local<Tz: TimeZone>(
+    tz: &Tz,
+    local: NaiveDateTime,
+    prefer: Option<Prefer>,
+) -> Result<DateTime<Utc>, String> {
+    let shown = local.format("%Y-%m-%d %H:%M");
+    let (earliest, latest) = match tz.from_local_datetime(&local) {
+        LocalResult::Single(dt) => return Ok(dt.to_utc()),
+        // sorted, as not every time zone returns them in order
+        LocalResult::Ambiguous(a, b) => {
+            let (a, b) = (a.to_utc(), b.to_utc());
+            (a.min(b), a.max(b))
+        }
+        LocalResult::None => {
+            return Err(format!(
+                "{shown} doesn't exist as the clocks go forward past it; \
+                use the time before or after the change"
+            ))
+        }
+    };
+    match prefer {
+        Some(Prefer::Earliest) => Ok(earliest),
+        Some(Prefer::Latest) => Ok(latest),
+        None => Err(format!(
+            "{shown} happens twice as the clocks go back, at {} and at {} \
+            UTC; choose with --prefer earliest or --prefer latest",
+            earliest.format("%H:%M"),
+            latest.format("%H:%M")
+        )),
+    }
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     /// Record when you started working or came back from a break
-    ClockIn {
+    #[command(visible_alias = "in")]
+    #[command(after_help = "Examples:
+  busy-bee clock-in
+  busy-bee clock-in 0830 --project web --tag billable
+  busy-bee clock-in --location home
+  busy-bee clock-in --at \"yesterday 1730\"")]
+    ClockIn(ClockInArgs),
+    /// Record when you took a break or stopped working
+    #[command(visible_alias = "out")]
+    #[command(after_help = "Examples:
+  busy-bee clock-out
+  busy-bee clock-out 5pm
+  busy-bee clock-out --duration 7h30m")]
+    ClockOut {
         /// Specify the date, default is today
         #[arg(value_parser=parse_date, long, short)]
         date: Option<NaiveDate>,
-        /// Specify the time, default is now
+        /// Specify the time, default is now, or the configured default end
+        /// time if a date is given
         #[arg(value_parser=parse_time)]
         time: Option<NaiveTime>,
+        /// Specify date and time at once, e.g., "yesterday 1730"
+        #[arg(value_parser=parse_date_time, long, conflicts_with_all = ["date", "time"])]
+        at: Option<NaiveDateTime>,
+        /// Clock out this long after the day's last clock-in, e.g., 7h30m
+        #[arg(value_parser=parse_duration, long, conflicts_with_all = ["time", "at"])]
+        duration: Option<Duration>,
     },
-    /// Record when you took a break or stopped working
-    ClockOut {
+    /// Record a normal working day retroactively, from the configured
+    /// default start time to the default end time
+    #[command(after_help = "Examples:
+  busy-bee add-day yesterday
+  busy-bee add-day 2024-03-01 --project web")]
+    AddDay {
+        /// The day to record
+        #[arg(value_parser=parse_date)]
+        date: NaiveDate,
+        /// What you worked on
+        #[arg(value_parser=parse_project, long, short)]
+        project: Option<String>,
+        /// Labels for the day's time; can be given multiple times
+        #[arg(value_parser=parse_tag, long = "tag", short)]
+        tags: Vec<String>,
+    },
+    /// Record normal working days for a whole range of days at once,
+    /// leaving out days that already have records
+    #[command(after_help = "Examples:
+  busy-bee backfill --from 2024-03-01 --to 2024-03-15 --skip-weekends")]
+    Backfill(BackfillArgs),
+    /// Run several commands that record or delete events, one per line of
+    /// a file, and write all their changes at once, or none if one fails.
+    /// Hooks don't run, and options before the command are taken from
+    /// the batch.
+    #[command(after_help = "Examples:
+  busy-bee batch corrections.txt
+  printf 'clock-in 0900 --date 2024-03-01\\nclock-out 1700 --date 2024-03-01\\n' | busy-bee batch -
+
+Lines are split into words like a shell would, and empty lines and lines
+starting with # are skipped.")]
+    Batch {
+        /// The file to read the commands from, or - for stdin
+        file: PathBuf,
+    },
+    /// Record an interruption of the current working block that just
+    /// ended, to account for context switches
+    #[command(after_help = "Examples:
+  busy-bee interrupt \"prod alert\" --minutes 20")]
+    Interrupt {
+        /// What the interruption was about, e.g., "prod alert"
+        #[arg(value_parser=parse_note)]
+        note: String,
+        /// How long the interruption took
+        #[arg(long, short)]
+        minutes: u32,
+    },
+    /// Attach a note to a day as a whole, which is shown when viewing it
+    #[command(after_help = "Examples:
+  busy-bee note \"deployed release 3.2\"
+  busy-bee note \"sick in the afternoon\" --date yesterday")]
+    Note {
+        /// What to note, e.g., "deployed release 3.2"
+        #[arg(value_parser=parse_note)]
+        text: String,
         /// Specify the date, default is today
         #[arg(value_parser=parse_date, long, short)]
         date: Option<NaiveDate>,
-        /// Specify the time, default is now
-        #[arg(value_parser=parse_time)]
-        time: Option<NaiveTime>,
     },
-    /// View log entries for a specific day
+    /// Find the notes on days and interruptions that contain a text,
+    /// ignoring case, or the events recorded a certain way
+    #[command(after_help = "Examples:
+  busy-bee search release
+  busy-bee search --source daemon")]
+    Search {
+        /// The text to look for
+        #[arg(required_unless_present = "source")]
+        text: Option<String>,
+        /// Find only events recorded this way: manual, daemon, import or
+        /// api
+        #[arg(value_parser=parse_source, long, short)]
+        source: Option<Source>,
+    },
+    /// Record when on-call standby starts or ends; it is reported
+    /// separately and only time you clock in for counts as working time
+    #[command(after_help = "Examples:
+  busy-bee standby start
+  busy-bee standby end 0700 --date 2024-03-02")]
+    Standby {
+        #[command(subcommand)]
+        action: StandbyAction,
+    },
+    /// Record expenses, e.g., for parking, which are listed in the monthly
+    /// report
+    #[command(after_help = "Examples:
+  busy-bee expense add 12.50 parking
+  busy-bee expense add 4,80 \"train ticket\" --date yesterday")]
+    Expense {
+        #[command(subcommand)]
+        action: ExpenseAction,
+    },
+    /// View log entries for a day or a range of days
+    #[command(visible_alias = "v")]
+    #[command(after_help = "Examples:
+  busy-bee view
+  busy-bee view yesterday
+  busy-bee view lastweek --format markdown
+  busy-bee view 2024-05
+  busy-bee view 2024-W19
+  busy-bee view --from 2024-03-01 --to 2024-03-15
+  busy-bee view lastweek --week
+  busy-bee view yesterday --verbose")]
     View {
+        /// Day, week or month to view, e.g., yesterday, lastweek, 2024-W19
+        /// or 2024-05; default is today
+        #[arg(value_parser=parse_period, conflicts_with = "from")]
+        period: Option<(NaiveDate, NaiveDate)>,
+        /// First day of a range to view
+        #[arg(value_parser=parse_date, long)]
+        from: Option<NaiveDate>,
+        /// Last day of the range, default is today
+        #[arg(value_parser=parse_date, long, requires = "from")]
+        to: Option<NaiveDate>,
+        /// Draw the week of the day as a timeline, one character per 15
+        /// minutes, to spot missing clock-ins and clock-outs
+        #[arg(long, short, conflicts_with_all = ["from", "format"])]
+        week: bool,
+        /// Show each record, also where the configured merge gap counts
+        /// intervals with a tiny gap between them as one
+        #[arg(long)]
+        raw: bool,
+        /// Tell how each event was recorded: manual, daemon, import or api
+        #[arg(long, short)]
+        verbose: bool,
+        /// Output format: text, markdown, json or html
+        #[arg(value_parser=parse_report_format, long, short, default_value = "text")]
+        format: ReportFormat,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Delete a previously recorded log entry, which is kept in the trash
+    /// until it's restored
+    #[command(visible_alias = "rm")]
+    #[command(after_help = "Examples:
+  busy-bee view
+  busy-bee delete 2
+  busy-bee delete --date yesterday 0
+  busy-bee delete --date yesterday --between 1200 1300")]
+    Delete(DeleteArgs),
+    /// List deleted log entries or restore one
+    #[command(after_help = "Examples:
+  busy-bee trash list
+  busy-bee trash restore 0")]
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+    /// Edit the records of a day in your $EDITOR, checking them before they
+    /// are saved
+    #[command(after_help = "Examples:
+  busy-bee edit-raw yesterday")]
+    EditRaw {
+        /// Date to edit the records of
         #[arg(value_parser=parse_date)]
         date: NaiveDate,
     },
-    /// Delete a previously recorded log entry
-    Delete {
-        /// Date of the event to delete, default is today
+    /// View a monthly summary of recorded times
+    #[command(visible_alias = "r")]
+    #[command(after_help = "Examples:
+  busy-bee report
+  busy-bee report mar --per-project
+  busy-bee report 3/2024 --group-by week --format markdown
+  busy-bee report W19
+  busy-bee report --burndown
+  busy-bee report --from 2024-03-11 --to 2024-04-05
+  busy-bee report --year 2024
+  busy-bee report --tag billable --out march.md")]
+    Report(ReportArgs),
+    /// Add up the recorded time with a query, e.g., for scripts. A query
+    /// takes sum, avg or count of hours, minutes or *, conditions on
+    /// project, tag, kind (work, standby or interruption), date, month or
+    /// year after where, joined by and, and a key to group by after by,
+    /// which prints CSV instead of a single value.
+    #[command(after_help = "Examples:
+  busy-bee query \"sum(hours) where project = 'acme' and month = 2024-03\"
+  busy-bee query \"count(*) where kind = interruption and date >= 2024-03-01\"
+  busy-bee query \"sum(hours) where year = 2024 by month\"")]
+    Query {
+        /// The query, quoted to keep it together
+        query: String,
+    },
+    /// Show what the time of a month is worth, based on the hourly rates
+    /// configured per project
+    #[command(after_help = "Examples:
+  busy-bee earnings
+  busy-bee earnings --month feb")]
+    Earnings {
+        /// Month to compute the earnings of, default is the current month
+        #[arg(value_parser=parse_month, long, short)]
+        month: Option<NaiveDate>,
+    },
+    /// Check a month for breaches of working time rules: days of more than
+    /// 10 hours, less than 11 hours of rest between days and more than 6
+    /// hours without a break
+    #[command(after_help = "Examples:
+  busy-bee compliance --month feb")]
+    Compliance {
+        /// Month to check, default is the current month
+        #[arg(value_parser=parse_month, long, short)]
+        month: Option<NaiveDate>,
+    },
+    /// Rewrite all stored records, e.g., to change the storage format
+    #[command(after_help = "Examples:
+  busy-bee migrate --format jsonl --layout month
+  busy-bee --dry-run migrate --format csv")]
+    Migrate {
+        /// Storage format to convert to: csv or jsonl, default is the
+        /// configured format
+        #[arg(value_parser=parse_format, long, short)]
+        format: Option<Format>,
+        /// Storage layout to convert to: day or month, default is the
+        /// configured layout
+        #[arg(value_parser=parse_layout, long, short)]
+        layout: Option<Layout>,
+    },
+    /// Add the events from a file, skipping those that are already
+    /// recorded
+    #[command(after_help = "Examples:
+  busy-bee import --diff old-laptop/2024-03-01.csv
+  busy-bee import meetings.ics")]
+    Import {
+        /// A file in any format this application stores records in, or an
+        /// iCalendar (.ics) file whose meetings are recorded as worked time
+        file: PathBuf,
+        /// Only show which events would be added, skipped as duplicates, or
+        /// conflict with existing records
+        #[arg(long)]
+        diff: bool,
+    },
+    /// Add the events of another storage directory, e.g., of a second
+    /// machine, day by day, skipping duplicates and reporting the days
+    /// whose events contradict each other
+    #[command(after_help = "Examples:
+  busy-bee merge ~/laptop-records
+  busy-bee merge --layout month --report conflicts.txt /mnt/old")]
+    Merge(MergeArgs),
+    /// Show whether you're clocked in and how long you worked today
+    #[command(after_help = "Examples:
+  busy-bee status
+  busy-bee status --format waybar
+  busy-bee status --format prometheus > /var/lib/node_exporter/busybee.prom
+  busy-bee status --porcelain --max-latency 50ms")]
+    Status(StatusArgs),
+    /// Print a short status for a shell prompt, e.g., "⏱ 3:42 ▶" while
+    /// clocked in; prints nothing if the records can't be read
+    #[command(after_help = "Examples:
+  PS1='$(busy-bee prompt) \\$ '
+
+As a custom module in starship.toml:
+  [custom.busybee]
+  command = \"busy-bee prompt --max-latency 50ms\"
+  when = true")]
+    Prompt {
+        /// Print nothing if reading today's records takes longer, e.g.,
+        /// 50ms; reads only the end of a month file
+        #[arg(value_parser=parse_latency, long)]
+        max_latency: Option<Duration>,
+    },
+    /// Keep showing the current status, refreshing it periodically and as
+    /// soon as the records change, e.g., through a sync client
+    #[command(after_help = "Examples:
+  busy-bee watch --interval 30s")]
+    Watch {
+        /// How often to refresh, e.g., 30s or 1m
+        #[arg(value_parser=parse_duration, long, short, default_value = "1m")]
+        interval: Duration,
+    },
+    /// Print the recorded intervals of a month or another period for
+    /// another system
+    #[command(after_help = "Examples:
+  busy-bee export --format jira
+  busy-bee export feb --format jira --issue-from-project --clipboard
+  busy-bee export W19 --format jira
+  busy-bee export --anonymize --out records.csv")]
+    Export(ExportArgs),
+    /// Suggest clock events you may have forgotten, based on the commits
+    /// you made in the configured git repositories
+    #[command(after_help = "Examples:
+  busy-bee suggest
+  busy-bee suggest --date 2024-03-01")]
+    Suggest {
+        /// The day to look at, default is yesterday
         #[arg(value_parser=parse_date, long, short)]
         date: Option<NaiveDate>,
-        /// Event ID to delete
-        id: u32,
     },
-    /// View a monthly summary of recorded times
-    Report {
-        /// Month to view recorded times for
-        #[arg(value_parser=parse_month)]
-        date: Option<NaiveDate>,
+    /// Move the files of old records into the archive directory, where they
+    /// can still be read
+    #[command(after_help = "Examples:
+  busy-bee archive --before 2024-01-01 --compress")]
+    Archive {
+        /// Archive the records of the days before this date
+        #[arg(value_parser=parse_date, long, short)]
+        before: NaiveDate,
+        /// Compress the archived files with gzip
+        #[arg(long, short)]
+        compress: bool,
+    },
+    /// Remove events recorded twice, e.g., after merging the records of
+    /// two machines, after showing them and asking whether to
+    #[command(after_help = "Examples:
+  busy-bee dedupe
+  busy-bee dedupe --window 5m
+  busy-bee --dry-run dedupe")]
+    Dedupe {
+        /// How far apart otherwise identical events may be to count as the
+        /// same, e.g., 30s or 5m
+        #[arg(value_parser=parse_duration, long, short, default_value = "1m")]
+        window: Duration,
+        /// Don't ask before removing the duplicates
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Check stored records for problems
+    #[command(after_help = "Examples:
+  busy-bee doctor
+  busy-bee doctor --fix")]
+    Doctor {
+        /// Repair the problems found where possible
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Set up a new machine: create the storage directory and a config
+    /// file to start from
+    #[command(after_help = "Examples:
+  busy-bee init
+  busy-bee init --storage-dir ~/Nextcloud/busy-bee")]
+    Init {
+        /// Where to store the records, written to the config file. Default
+        /// is the storage directory in use.
+        #[arg(long, short)]
+        storage_dir: Option<PathBuf>,
+    },
+    /// Answer requests in JSON, one per line of input, with a line of JSON
+    /// each, e.g., for editor plugins; the methods are `clock_in`,
+    /// `clock_out`, `status`, `day` and `month`
+    #[command(after_help = "Examples:
+  echo '{\"id\": 1, \"method\": \"status\"}' | busy-bee rpc
+  echo '{\"id\": 2, \"method\": \"clock_in\", \"params\": {\"project\": \"web\"}}' | busy-bee rpc")]
+    Rpc,
+    /// Read or change settings of the config file
+    #[command(after_help = "Examples:
+  busy-bee config get storage.format
+  busy-bee config set daily_target 7h30m
+  busy-bee config set rates.web 95")]
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Print the version, the resolved paths and settings, and what is
+    /// stored, e.g., to include in bug reports
+    #[command(after_help = "Examples:
+  busy-bee info")]
+    Info,
+    /// Print the man page, or write the man pages of all commands to a
+    /// directory, e.g., to package this program
+    #[command(after_help = "Examples:
+  busy-bee man | man -l -
+  busy-bee man --out-dir /usr/local/share/man/man1")]
+    Man {
+        /// Where to write a page per command, named like busy-bee-view.1
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+    /// Replace this program with the latest release from GitHub
+    #[cfg(feature = "self-update")]
+    #[command(after_help = "Examples:
+  busy-bee self-update --check
+  busy-bee self-update")]
+    SelfUpdate {
+        /// Only tell whether there is a newer release
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+impl Commands {
+    /// Whether the command changes the storage directory, which a
+    /// read-only one doesn't allow
+    pub fn writes(&self) -> bool {
+        match self {
+            Commands::ClockIn { .. }
+            | Commands::ClockOut { .. }
+            | Commands::AddDay { .. }
+            | Commands::Backfill(_)
+            | Commands::Batch { .. }
+            | Commands::Interrupt { .. }
+            | Commands::Note { .. }
+            | Commands::Standby { .. }
+            | Commands::Expense { .. }
+            | Commands::Delete(_)
+            | Commands::EditRaw { .. }
+            | Commands::Migrate { .. }
+            | Commands::Archive { .. }
+            | Commands::Dedupe { .. }
+            | Commands::Merge(_)
+            // records the suggestions that are accepted
+            | Commands::Suggest { .. }
+            | Commands::Init { .. } => true,
+            Commands::Import { diff, .. } => !diff,
+            Commands::Doctor { fix } => *fix,
+            Commands::Trash { action } => {
+                matches!(action, TrashAction::Restore { .. })
+            }
+            Commands::Search { .. }
+            | Commands::View { .. }
+            | Commands::Report(_)
+            | Commands::Query { .. }
+            | Commands::Earnings { .. }
+            | Commands::Compliance { .. }
+            | Commands::Status(_)
+            | Commands::Prompt { .. }
+            | Commands::Watch { .. }
+            | Commands::Export(_)
+            | Commands::Config { .. }
+            // refuses the methods that write by itself
+            | Commands::Rpc
+            | Commands::Info
+            | Commands::Man { .. } => false,
+            #[cfg(feature = "self-update")]
+            Commands::SelfUpdate { .. } => false,
+        }
+    }
+
+    /// Whether the command can run in a batch, which only holds back the
+    /// changes to events
+    pub fn batches(&self) -> bool {
+        matches!(
+            self,
+            Commands::ClockIn { .. }
+                | Commands::ClockOut { .. }
+                | Commands::AddDay { .. }
+                | Commands::Backfill(_)
+                | Commands::Interrupt { .. }
+                | Commands::Standby { .. }
+                | Commands::Delete(_)
+        )
+    }
+}
+
+/// Splits a line into words like a shell, where quotes keep words with
+/// spaces together and a backslash keeps the next character as it is,
+/// e.g., for the commands of a batch
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None | Some('"'), '\\') => {
+                let escaped = chars
+                    .next()
+                    .ok_or(format!("Line ends with a backslash: {line}"))?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (_, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(format!("Unclosed quote in: {line}"));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Print the value of a setting, as set in the config file or by a
+    /// `BUSY_BEE_*` environment variable
+    Get {
+        /// Name of the setting, e.g., storage.format or rates.web
+        key: String,
+    },
+    /// Change a setting in the config file
+    Set {
+        /// Name of the setting, e.g., storage.format or rates.web
+        key: String,
+        /// New value; values that aren't numbers, booleans, arrays or
+        /// quoted strings are taken as strings
+        value: String,
     },
 }
 
 fn parse_time(user_input: &str) -> Result<NaiveTime, String> {
-    if user_input == "now" {
-        return Ok(Local::now().naive_local().time());
+    match user_input.to_ascii_lowercase().as_str() {
+        "now" => return Ok(Local::now().naive_local().time()),
+        "noon" => return Ok(NaiveTime::from_hms_opt(12, 0, 0).unwrap()),
+        "midnight" => return Ok(NaiveTime::MIN),
+        _ => {}
     }
 
-    let re = Regex::new(r"^(\d{1,2}):?(\d{2})$").unwrap();
+    let re =
+        Regex::new(r"(?i)^([0-9]{1,2})(?::?([0-9]{2}))?\s*([ap]m)?$").unwrap();
     let captures = re.captures(user_input).ok_or(format!(
-        "Unknown time format: '{user_input}'; try e.g. 730, 0730, 07:30"
+        "Unknown time format: '{user_input}'; try e.g. 730, 07:30, 9, 5pm"
     ))?;
-    let (hour, minute) = (&captures[1], &captures[2]);
     // Can just unwrap() the parse results, because the regex ensures that
     // we're dealing with numeric characters only
-    NaiveTime::from_hms_opt(hour.parse().unwrap(), minute.parse().unwrap(), 0)
-        .ok_or(format!("{hour}:{minute} is not a valid time"))
+    let mut hour: u32 = captures[1].parse().unwrap();
+    let minute: u32 =
+        captures.get(2).map_or(0, |m| m.as_str().parse().unwrap());
+    if let Some(suffix) = captures.get(3) {
+        if !(1..=12).contains(&hour) {
+            return Err(format!("{user_input} is not a valid time"));
+        }
+        let pm = suffix.as_str().eq_ignore_ascii_case("pm");
+        hour = hour % 12 + if pm { 12 } else { 0 };
+    }
+    NaiveTime::from_hms_opt(hour, minute, 0)
+        .ok_or(format!("{user_input} is not a valid time"))
+}
+
+/// Parses a date and a time separated by whitespace, e.g.,
+/// `yesterday 1730` or `2024-05-02 09:00`; a time alone is today's
+fn parse_date_time(user_input: &str) -> Result<NaiveDateTime, String> {
+    let user_input = user_input.trim();
+    match user_input.split_once(char::is_whitespace) {
+        Some((date, time)) => {
+            Ok(parse_date(date)?.and_time(parse_time(time.trim())?))
+        }
+        None => parse_time(user_input)
+            .map(|time| Local::now().date_naive().and_time(time))
+            .map_err(|_| {
+                format!(
+                    "Unknown date and time: '{user_input}'; try e.g. \
+                    \"yesterday 1730\" or \"2024-05-02 09:00\""
+                )
+            }),
+    }
 }
 
 fn parse_date(user_input: &str) -> Result<NaiveDate, String> {
@@ -83,7 +708,7 @@ fn parse_date(user_input: &str) -> Result<NaiveDate, String> {
         return Ok((Local::now().naive_local() - Days::new(1)).date());
     }
 
-    let re = Regex::new(r"^(\d{2,4})-?(\d{2})-?(\d{2})$").unwrap();
+    let re = Regex::new(r"^([0-9]{2,4})-?([0-9]{2})-?([0-9]{2})$").unwrap();
     let captures = re.captures(user_input).ok_or(format!(
         "Unknown date format: '{user_input}'; \
         try e.g. 2024-01-31, 20240131, 240131"
@@ -102,9 +727,350 @@ fn parse_date(user_input: &str) -> Result<NaiveDate, String> {
         .ok_or(format!("{year}-{month}-{day} is not a valid date"))
 }
 
+/// Where to send the output of a command instead of printing it
+#[derive(Args)]
+pub struct OutputArgs {
+    /// Write the output to this file instead of printing it
+    #[arg(long, value_name = "FILE", conflicts_with = "clipboard")]
+    pub out: Option<PathBuf>,
+    /// Copy the output to the clipboard instead of printing it
+    #[arg(long)]
+    pub clipboard: bool,
+    /// Fit text output into this many columns, default is the width of the
+    /// terminal
+    #[arg(long, value_name = "COLUMNS")]
+    pub width: Option<usize>,
+}
+
+impl OutputArgs {
+    pub fn destination(&self) -> Destination {
+        match &self.out {
+            Some(path) => Destination::File(path.clone()),
+            None if self.clipboard => Destination::Clipboard,
+            None => Destination::Stdout,
+        }
+    }
+
+    /// How many columns text output fits into, unless it isn't limited,
+    /// e.g., in a file
+    pub fn width(&self) -> Option<usize> {
+        self.width.or_else(|| match self.destination() {
+            Destination::Stdout => output::terminal_width(),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Args)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ReportArgs {
+    /// Month to view recorded times for, e.g., feb or 2024-05, or another
+    /// period, e.g., lastweek or 2024-W19, along with the hours due in it;
+    /// default is the current month
+    #[arg(value_parser=parse_period)]
+    pub period: Option<(NaiveDate, NaiveDate)>,
+    /// First day of a range to report on instead of a month, along with
+    /// the hours due in that range
+    #[arg(value_parser=parse_date, long, conflicts_with_all = ["period", "per_project", "burndown"])]
+    pub from: Option<NaiveDate>,
+    /// Last day of the range, default is today
+    #[arg(value_parser=parse_date, long, requires = "from")]
+    pub to: Option<NaiveDate>,
+    /// Only count time with this tag; can be given multiple times to
+    /// count time that has all of the tags
+    #[arg(value_parser=parse_tag, long = "tag", short)]
+    pub tags: Vec<String>,
+    /// Break the time down by project and tag instead of by day
+    #[arg(long)]
+    pub per_project: bool,
+    /// List the days one by one, or by ISO week with the time worked in
+    /// each week: day or week
+    #[arg(value_parser=parse_grouping, long, default_value = "day", conflicts_with = "per_project")]
+    pub group_by: Grouping,
+    /// Add up each month of a year instead, default is the current year;
+    /// past months are cached until their records change
+    #[arg(
+        long,
+        num_args = 0..=1,
+        value_name = "YEAR",
+        conflicts_with_all = [
+            "period", "from", "per_project", "burndown", "format",
+            "only_incomplete", "only_overtime", "export_per_day_csv",
+        ]
+    )]
+    pub year: Option<Option<i32>>,
+    /// Chart the hours worked so far against the hours due by each day
+    /// instead, to see whether you're ahead or behind
+    #[arg(long, conflicts_with_all = ["per_project", "group_by"])]
+    pub burndown: bool,
+    /// Count the gaps that the configured merge gap would merge as breaks
+    #[arg(long)]
+    pub raw: bool,
+    /// Only list the days whose records are incomplete or missing
+    #[arg(long, conflicts_with_all = ["per_project", "burndown"])]
+    pub only_incomplete: bool,
+    /// Only list the days with more time worked than due
+    #[arg(long, conflicts_with_all = ["per_project", "burndown", "only_incomplete"])]
+    pub only_overtime: bool,
+    /// Output format: text, markdown, json or html
+    #[arg(value_parser=parse_report_format, long, short, default_value = "text")]
+    pub format: ReportFormat,
+    /// Also write a CSV file with one row per day: its start, end,
+    /// breaks, net hours, overtime and hours per project
+    #[arg(long, value_name = "FILE")]
+    pub export_per_day_csv: Option<PathBuf>,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+#[derive(Args)]
+pub struct ClockInArgs {
+    /// Specify the date, default is today
+    #[arg(value_parser=parse_date, long, short)]
+    pub date: Option<NaiveDate>,
+    /// Specify the time, default is now, or the configured default
+    /// start time if a date is given
+    #[arg(value_parser=parse_time)]
+    pub time: Option<NaiveTime>,
+    /// Specify date and time at once, e.g., "yesterday 1730"
+    #[arg(value_parser=parse_date_time, long, conflicts_with_all = ["date", "time"])]
+    pub at: Option<NaiveDateTime>,
+    /// What you're going to work on
+    #[arg(value_parser=parse_project, long, short)]
+    pub project: Option<String>,
+    /// Who the project is for; checked against the projects configured
+    /// for the client
+    #[arg(long, short, requires = "project")]
+    pub client: Option<String>,
+    /// Labels for the time until the next clock-out, e.g., billable;
+    /// can be given multiple times
+    #[arg(value_parser=parse_tag, long = "tag", short)]
+    pub tags: Vec<String>,
+    /// Where you're working, e.g., office or home, for the days per
+    /// location in the monthly report
+    #[arg(value_parser=parse_location, long, short)]
+    pub location: Option<String>,
+}
+
+#[derive(Args)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ExportArgs {
+    /// Month to export, e.g., feb or 2024-05, or another period, e.g.,
+    /// lastweek or 2024-W19; default is the current month
+    #[arg(value_parser=parse_period)]
+    pub period: Option<(NaiveDate, NaiveDate)>,
+    /// Output format: jira, or records, which is the default with
+    /// --anonymize
+    #[arg(value_parser=parse_export_format, long, short, required_unless_present = "anonymize")]
+    pub format: Option<ExportFormat>,
+    /// Use project names that look like Jira issue keys, e.g., WEB-42,
+    /// as issue keys if no issue is configured for them
+    #[arg(long)]
+    pub issue_from_project: bool,
+    /// Replace projects, tags, locations and notes with placeholders
+    /// and move the records back by a random number of weeks, e.g., to
+    /// attach them to a bug report
+    #[arg(long)]
+    pub anonymize: bool,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+#[derive(Args)]
+pub struct StatusArgs {
+    /// Output format: text, json, waybar, or prometheus
+    #[arg(value_parser=parse_output_format, long, short, default_value = "text")]
+    pub format: OutputFormat,
+    /// Give up with an error if reading today's records takes longer, e.g.,
+    /// 50ms; reads only the end of a month file
+    #[arg(value_parser=parse_latency, long)]
+    pub max_latency: Option<Duration>,
+}
+
+#[derive(Args)]
+pub struct MergeArgs {
+    /// The storage directory to take the events from, which isn't changed
+    pub dir: PathBuf,
+    /// Storage layout of that directory: day or month, default is the
+    /// configured layout
+    #[arg(value_parser=parse_layout, long, short)]
+    pub layout: Option<Layout>,
+    /// How far apart otherwise identical events may be to count as the
+    /// same, e.g., 30s or 5m
+    #[arg(value_parser=parse_duration, long, short, default_value = "1m")]
+    pub window: Duration,
+    /// Where to write the report of the days that couldn't be merged
+    #[arg(long, short, default_value = "merge-conflicts.txt")]
+    pub report: PathBuf,
+}
+
+#[derive(Args)]
+pub struct DeleteArgs {
+    /// Date of the event to delete, default is today
+    #[arg(value_parser=parse_date, long, short)]
+    pub date: Option<NaiveDate>,
+    /// Event ID to delete
+    #[arg(required_unless_present = "between")]
+    pub id: Option<u32>,
+    /// Delete all events from START to END, both inclusive, after
+    /// showing them and asking whether to
+    #[arg(
+        long,
+        short,
+        num_args = 2,
+        value_names = ["START", "END"],
+        value_parser = parse_time,
+        conflicts_with = "id"
+    )]
+    pub between: Option<Vec<NaiveTime>>,
+    /// Don't ask before deleting the events of --between
+    #[arg(long, short, requires = "between")]
+    pub yes: bool,
+}
+
+#[derive(Args)]
+pub struct BackfillArgs {
+    /// First day to record
+    #[arg(value_parser=parse_date, long)]
+    pub from: NaiveDate,
+    /// Last day to record
+    #[arg(value_parser=parse_date, long)]
+    pub to: NaiveDate,
+    /// When to clock in, default is the configured default start time
+    #[arg(value_parser=parse_time, long)]
+    pub start: Option<NaiveTime>,
+    /// When to clock out, default is the configured default end time
+    #[arg(value_parser=parse_time, long)]
+    pub end: Option<NaiveTime>,
+    /// Leave out Saturdays and Sundays
+    #[arg(long)]
+    pub skip_weekends: bool,
+    /// Leave out the days configured as holidays
+    #[arg(long)]
+    pub skip_holidays: bool,
+    /// What you worked on
+    #[arg(value_parser=parse_project, long, short)]
+    pub project: Option<String>,
+    /// Labels for the time; can be given multiple times
+    #[arg(value_parser=parse_tag, long = "tag", short)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Subcommand)]
+pub enum StandbyAction {
+    /// Record that you're on call from now on
+    Start {
+        /// Specify the date, default is today
+        #[arg(value_parser=parse_date, long, short)]
+        date: Option<NaiveDate>,
+        /// Specify the time, default is now
+        #[arg(value_parser=parse_time)]
+        time: Option<NaiveTime>,
+    },
+    /// Record that you're no longer on call
+    End {
+        /// Specify the date, default is today
+        #[arg(value_parser=parse_date, long, short)]
+        date: Option<NaiveDate>,
+        /// Specify the time, default is now
+        #[arg(value_parser=parse_time)]
+        time: Option<NaiveTime>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TrashAction {
+    /// Show the deleted log entries with the IDs that restoring takes
+    List,
+    /// Record a deleted log entry again
+    Restore {
+        /// ID of the entry, as shown by trash list
+        id: u32,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ExpenseAction {
+    /// Record an expense
+    Add {
+        /// How much was spent, e.g., 12.50
+        #[arg(value_parser=parse_amount, allow_negative_numbers = true)]
+        amount: i64,
+        /// What was paid for, e.g., parking
+        #[arg(value_parser=parse_note)]
+        description: String,
+        /// Specify the date, default is today
+        #[arg(value_parser=parse_date, long, short)]
+        date: Option<NaiveDate>,
+    },
+}
+
+/// Parses a period of days into its first and last day: a single day like
+/// `parse_date` takes, `thisweek` or `lastweek`, an ISO week like
+/// `2024-W19`, or `W19` of the current year, or a month like
+/// [`parse_month`] takes
+pub fn parse_period(
+    user_input: &str,
+) -> Result<(NaiveDate, NaiveDate), String> {
+    let today = Local::now().date_naive();
+    let monday =
+        today - Days::new(u64::from(today.weekday().num_days_from_monday()));
+    match user_input {
+        "thisweek" => return Ok((monday, today)),
+        "lastweek" => {
+            return Ok((monday - Days::new(7), monday - Days::new(1)));
+        }
+        _ => {}
+    }
+    if let Some(week) = parse_week(user_input, today.iso_week().year()) {
+        return week;
+    }
+    // months first, as the dashes of dates are optional, which would take
+    // 2012-05 for December 5, 2020
+    if let Ok(first) = parse_month(user_input) {
+        let last = (first + Months::new(1)).pred_opt().unwrap();
+        return Ok((first, last));
+    }
+    let date = parse_date(user_input).map_err(|_| {
+        format!(
+            "Unknown period '{user_input}'; try e.g. yesterday, 2024-01-31, \
+            lastweek, 2024-W19, 2024-05 or 'May 2024'"
+        )
+    })?;
+    Ok((date, date))
+}
+
+/// The Monday and Sunday of an ISO week like `2024-W19`, or `W19` in
+/// `year`, unless `user_input` isn't one
+fn parse_week(
+    user_input: &str,
+    year: i32,
+) -> Option<Result<(NaiveDate, NaiveDate), String>> {
+    let re = Regex::new(r"^(?:([0-9]{4})-?)?[Ww]([0-9]{1,2})$").unwrap();
+    let captures = re.captures(user_input)?;
+    // the regex ensures that these are numbers
+    let year = captures
+        .get(1)
+        .map_or(year, |y| y.as_str().parse().unwrap());
+    let week = captures[2].parse().unwrap();
+    let week = NaiveDate::from_isoywd_opt(year, week, Weekday::Mon)
+        .map(|monday| (monday, monday + Days::new(6)))
+        .ok_or(format!("{year} has no week {week}"));
+    Some(week)
+}
+
+/// Parses a month like `2024-05`, `May 2024`, `5/24` or `may`, which is in
+/// the current year, into its first day
 pub fn parse_month(user_input: &str) -> Result<NaiveDate, String> {
-    let parts: Vec<_> =
-        user_input.splitn(2, |c| c == '/' || c == ' ').collect();
+    let re = Regex::new(r"^([0-9]{4})-([0-9]{2})$").unwrap();
+    if let Some(captures) = re.captures(user_input) {
+        // the regex ensures that these are numbers
+        let year = captures[1].parse().unwrap();
+        let month = captures[2].parse().unwrap();
+        return NaiveDate::from_ymd_opt(year, month, 1)
+            .ok_or(format!("Invalid month: {month}"));
+    }
+    let parts: Vec<_> = user_input.splitn(2, ['/', ' ']).collect();
     let month = parts
         .first()
         .ok_or("Empty input for month".to_string())
@@ -120,6 +1086,145 @@ pub fn parse_month(user_input: &str) -> Result<NaiveDate, String> {
         .ok_or(format!("Invalid month: {month}"))
 }
 
+/// Parses durations like 7h30m, 2m, 90s or 7:30
+pub fn parse_duration(user_input: &str) -> Result<Duration, String> {
+    let error = || {
+        format!("Unknown duration format: '{user_input}'; try e.g. 7h30m, 2m, 7:30")
+    };
+    let re = Regex::new(r"^([0-9]{1,3}):([0-9]{2})$").unwrap();
+    if let Some(captures) = re.captures(user_input.trim()) {
+        // Can just unwrap() the parse results, because the regex ensures
+        // that we're dealing with a few numeric characters only
+        let hours: i64 = captures[1].parse().unwrap();
+        let minutes: i64 = captures[2].parse().unwrap();
+        return Ok(Duration::hours(hours) + Duration::minutes(minutes));
+    }
+
+    let re = Regex::new(
+        r"^(?:([0-9]{1,6})h)?\s*(?:([0-9]{1,6})m(?:in)?)?\s*(?:([0-9]{1,6})s)?$",
+    )
+    .unwrap();
+    let captures = re.captures(user_input.trim()).ok_or_else(error)?;
+    let part = |i| {
+        captures
+            .get(i)
+            .map_or(0, |m: regex::Match| m.as_str().parse::<i64>().unwrap())
+    };
+    if (1..=3).all(|i| captures.get(i).is_none()) {
+        return Err(error());
+    }
+    Ok(Duration::hours(part(1))
+        + Duration::minutes(part(2))
+        + Duration::seconds(part(3)))
+}
+
+fn parse_output_format(user_input: &str) -> Result<OutputFormat, String> {
+    user_input.parse()
+}
+
+fn parse_report_format(user_input: &str) -> Result<ReportFormat, String> {
+    user_input.parse()
+}
+
+/// Parses a short duration like 50ms, or any that [`parse_duration`] takes
+pub fn parse_latency(user_input: &str) -> Result<Duration, String> {
+    match user_input.trim().strip_suffix("ms") {
+        Some(millis) => millis
+            .parse()
+            .map(Duration::milliseconds)
+            .map_err(|_| format!("Unknown duration '{user_input}', try 50ms")),
+        None => parse_duration(user_input),
+    }
+}
+
+fn parse_duration_format(user_input: &str) -> Result<DurationFormat, String> {
+    user_input.parse()
+}
+
+fn parse_grouping(user_input: &str) -> Result<Grouping, String> {
+    user_input.parse()
+}
+
+fn parse_prefer(user_input: &str) -> Result<Prefer, String> {
+    user_input.parse()
+}
+
+fn parse_export_format(user_input: &str) -> Result<ExportFormat, String> {
+    user_input.parse()
+}
+
+fn parse_project(user_input: &str) -> Result<String, String> {
+    let project = user_input.trim();
+    if project.is_empty() || project.contains(['\n', '\r']) {
+        return Err(format!(
+            "Invalid project name '{user_input}'; it must not be empty or \
+            contain line breaks"
+        ));
+    }
+    Ok(project.to_string())
+}
+
+fn parse_location(user_input: &str) -> Result<String, String> {
+    let location = user_input.trim();
+    if location.is_empty() || location.contains(['\n', '\r']) {
+        return Err(format!(
+            "Invalid location '{user_input}'; it must not be empty or \
+            contain line breaks"
+        ));
+    }
+    Ok(location.to_string())
+}
+
+pub fn parse_profile(user_input: &str) -> Result<String, String> {
+    let profile = user_input.trim();
+    let valid = |c: char| c.is_alphanumeric() || "_-".contains(c);
+    if profile.is_empty() || !profile.chars().all(valid) {
+        return Err(format!(
+            "Invalid profile name '{user_input}'; it must consist of \
+            letters, digits, '-' and '_'"
+        ));
+    }
+    if platform::is_reserved(profile) {
+        return Err(format!(
+            "Invalid profile name '{user_input}'; Windows reserves it for a \
+            device"
+        ));
+    }
+    Ok(profile.to_string())
+}
+
+fn parse_note(user_input: &str) -> Result<String, String> {
+    check_note(user_input.trim()).map_err(|_| {
+        format!(
+            "Invalid note '{user_input}'; it must not be empty or contain \
+            line breaks"
+        )
+    })
+}
+
+fn parse_tag(user_input: &str) -> Result<String, String> {
+    let tag = user_input.trim();
+    if tag.is_empty() || tag.contains([';', '\n', '\r']) {
+        return Err(format!(
+            "Invalid tag '{user_input}'; it must not be empty or contain \
+            semicolons or line breaks"
+        ));
+    }
+    Ok(tag.to_string())
+}
+
+fn parse_format(user_input: &str) -> Result<Format, String> {
+    user_input.parse()
+}
+
+fn parse_layout(user_input: &str) -> Result<Layout, String> {
+    user_input.parse()
+}
+
+fn parse_source(user_input: &str) -> Result<Source, String> {
+    user_input.parse()
+}
+
 fn month_from_str(s: &str) -> Result<u32, String> {
     if s.chars().all(|c| c.is_ascii_digit()) {
         s.parse().map_err(|e| format!("{e}"))
@@ -144,12 +1249,122 @@ fn month_from_str(s: &str) -> Result<u32, String> {
     }
 }
 
+/// Replaces the name of a user-defined alias in `args`, as the application
+/// was started with, by the arguments it stands for, e.g., `standup` by
+/// `clock-in 0900 --project meetings`. Commands take precedence over aliases
+/// of the same name, and aliases can't refer to other aliases.
+pub fn expand_alias(
+    args: Vec<OsString>,
+    aliases: &BTreeMap<String, Vec<String>>,
+) -> Vec<OsString> {
+    let command = Cli::command();
+    // Skip the program name and the options before the command
+    let mut i = 1;
+    while let Some(arg) = args.get(i).and_then(|arg| arg.to_str()) {
+        if !arg.starts_with('-') || arg == "--" {
+            break;
+        }
+        i += if takes_separate_value(&command, arg) {
+            2
+        } else {
+            1
+        };
+    }
+    let Some(name) = args.get(i).and_then(|arg| arg.to_str()) else {
+        return args;
+    };
+    let Some(expansion) = aliases.get(name) else {
+        return args;
+    };
+    if command.find_subcommand(name).is_some() {
+        return args;
+    }
+    let mut expanded = args[..i].to_vec();
+    expanded.extend(expansion.iter().map(OsString::from));
+    expanded.extend_from_slice(&args[i + 1..]);
+    expanded
+}
+
+/// Whether `arg` is an option whose value is the next argument, like `-s`
+/// in `-s ~/records`
+fn takes_separate_value(command: &clap::Command, arg: &str) -> bool {
+    let option = if let Some(long) = arg.strip_prefix("--") {
+        command
+            .get_arguments()
+            .find(|option| option.get_long() == Some(long))
+    } else {
+        let mut shorts = arg.chars().skip(1);
+        let (Some(short), None) = (shorts.next(), shorts.next()) else {
+            return false;
+        };
+        command
+            .get_arguments()
+            .find(|option| option.get_short() == Some(short))
+    };
+    option.is_some_and(|option| option.get_action().takes_values())
+}
+
 #[cfg(test)]
 mod tests {
 
     use chrono::Datelike;
 
     use super::*;
+    use crate::testing::{CentralEurope, Rng};
+
+    #[test]
+    fn local_times_are_resolved_across_dst_changes() {
+        let local = |month, day, hour, minute| {
+            NaiveDate::from_ymd_opt(2024, month, day)
+                .unwrap()
+                .and_hms_opt(hour, minute, 0)
+                .unwrap()
+        };
+        let utc = |month, day, hour, minute| {
+            Ok(Utc.from_utc_datetime(&local(month, day, hour, minute)))
+        };
+        let resolve = |dt, prefer| resolve_local(&CentralEurope, dt, prefer);
+
+        // the clocks go forward from 02:00 to 03:00
+        assert_eq!(resolve(local(3, 31, 1, 59), None), utc(3, 31, 0, 59));
+        assert_eq!(resolve(local(3, 31, 3, 0), None), utc(3, 31, 1, 0));
+        let skipped = resolve(local(3, 31, 2, 30), Some(Prefer::Latest));
+        assert!(skipped.unwrap_err().contains("doesn't exist"));
+
+        // and back from 03:00 to 02:00
+        let twice = resolve(local(10, 27, 2, 30), None);
+        assert!(twice.unwrap_err().contains("--prefer"));
+        assert_eq!(
+            resolve(local(10, 27, 2, 30), Some(Prefer::Earliest)),
+            utc(10, 27, 0, 30)
+        );
+        assert_eq!(
+            resolve(local(10, 27, 2, 30), Some(Prefer::Latest)),
+            utc(10, 27, 1, 30)
+        );
+        assert_eq!(resolve(local(10, 27, 3, 0), None), utc(10, 27, 2, 0));
+    }
+
+    #[test]
+    fn parsers_do_not_panic_on_arbitrary_input() {
+        let mut rng = Rng::new(850);
+        let digits = ["0", "9", "٣", "１", "99999999999", "-", ":", "/", " "];
+        for _ in 0..1000 {
+            let mut input = rng.string(12);
+            // digit-heavy input gets past the regexes more often
+            for _ in 0..rng.below(6) {
+                #[allow(clippy::cast_possible_truncation)]
+                input
+                    .push_str(digits[rng.below(digits.len() as u64) as usize]);
+            }
+            let _ = parse_time(&input);
+            let _ = parse_date(&input);
+            let _ = parse_month(&input);
+            let _ = parse_period(&input);
+            let _ = parse_duration(&input);
+        }
+        assert!(parse_time("٣٣").is_err());
+    }
 
     #[test]
     fn test_parse_time_730() {
@@ -175,6 +1390,23 @@ mod tests {
         assert_eq!(parse_time("17:30"), Ok(expected));
     }
 
+    #[test]
+    fn test_parse_time_bare_hours_and_12_hour_clock() {
+        let at = |h, m| Ok(NaiveTime::from_hms_opt(h, m, 0).unwrap());
+        assert_eq!(parse_time("9"), at(9, 0));
+        assert_eq!(parse_time("17"), at(17, 0));
+        assert_eq!(parse_time("9am"), at(9, 0));
+        assert_eq!(parse_time("5pm"), at(17, 0));
+        assert_eq!(parse_time("5:30 PM"), at(17, 30));
+        assert_eq!(parse_time("12am"), at(0, 0));
+        assert_eq!(parse_time("12pm"), at(12, 0));
+        assert_eq!(parse_time("noon"), at(12, 0));
+        assert_eq!(parse_time("midnight"), at(0, 0));
+        assert!(parse_time("13pm").is_err());
+        assert!(parse_time("0am").is_err());
+        assert!(parse_time("25").is_err());
+    }
+
     #[test]
     fn test_parse_date_yesterday() {
         let yesterday = Local::now().naive_local() - Days::new(1);
@@ -206,6 +1438,104 @@ mod tests {
         assert_eq!(parse_date("24-01-13"), Ok(expected));
     }
 
+    #[test]
+    fn test_parse_period_lastweek() {
+        let (first, last) = parse_period("lastweek").unwrap();
+        assert_eq!(first.weekday(), chrono::Weekday::Mon);
+        assert_eq!(last - first, Duration::days(6));
+        assert!(last < Local::now().date_naive());
+    }
+
+    #[test]
+    fn test_parse_period_single_day() {
+        let expected = NaiveDate::from_ymd_opt(2024, 1, 13).unwrap();
+        assert_eq!(parse_period("2024-01-13"), Ok((expected, expected)));
+    }
+
+    #[test]
+    fn test_parse_period_week() {
+        let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        assert_eq!(parse_period("2024-W19"), Ok((date(5, 6), date(5, 12))));
+        // the ISO year of the first week starts in the year before
+        assert_eq!(
+            parse_period("2025w01"),
+            Ok((date(12, 30), date(12, 30) + Days::new(6)))
+        );
+        let (first, last) = parse_period("W19").unwrap();
+        assert_eq!(first.iso_week().week(), 19);
+        assert_eq!(last - first, Duration::days(6));
+        assert_eq!(
+            parse_period("2024-W53"),
+            Err("2024 has no week 53".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_period_month() {
+        let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        assert_eq!(parse_period("2024-02"), Ok((date(2, 1), date(2, 29))));
+        assert_eq!(parse_period("Dec 2024"), Ok((date(12, 1), date(12, 31))));
+        assert!(parse_period("2024-13").is_err());
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(
+            parse_period("2012-05"),
+            Ok((date(2012, 5, 1), date(2012, 5, 31)))
+        );
+        assert_eq!(
+            parse_period("201205"),
+            Ok((date(2020, 12, 5), date(2020, 12, 5)))
+        );
+    }
+
+    #[test]
+    fn test_parse_date_time() {
+        let expected = NaiveDate::from_ymd_opt(2024, 5, 2)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        assert_eq!(parse_date_time("2024-05-02 09:00"), Ok(expected));
+        assert_eq!(parse_date_time(" 240502   900 "), Ok(expected));
+        let yesterday = Local::now().date_naive() - Days::new(1);
+        assert_eq!(
+            parse_date_time("yesterday 1730").map(|dt| dt.date()),
+            Ok(yesterday)
+        );
+        assert!(parse_date_time("yesterday").is_err());
+        assert!(parse_date_time("2024-05-02 25:00").is_err());
+    }
+
+    #[test]
+    fn test_parse_profile_rejects_paths() {
+        assert_eq!(parse_profile(" work "), Ok("work".to_string()));
+        assert!(parse_profile("../work").is_err());
+        assert!(parse_profile("").is_err());
+        assert!(parse_profile("nul").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration_hm() {
+        let expected = Duration::minutes(450);
+        assert_eq!(parse_duration("7h30m"), Ok(expected));
+        assert_eq!(parse_duration("7:30"), Ok(expected));
+    }
+
+    #[test]
+    fn test_parse_duration_m() {
+        assert_eq!(parse_duration("2m"), Ok(Duration::minutes(2)));
+    }
+
+    #[test]
+    fn test_parse_duration_empty() {
+        assert!(parse_duration("").is_err());
+    }
+
+    #[test]
+    fn test_parse_latency() {
+        assert_eq!(parse_latency("50ms"), Ok(Duration::milliseconds(50)));
+        assert_eq!(parse_latency("1s"), Ok(Duration::seconds(1)));
+        assert!(parse_latency("fast").is_err());
+    }
+
     #[test]
     fn test_parse_month_mmm() {
         let current_year = Local::now().year();
@@ -250,4 +1580,63 @@ mod tests {
         let expected = NaiveDate::from_ymd_opt(2022, 2, 1).unwrap();
         assert_eq!(parse_month("02/2022"), Ok(expected));
     }
+
+    #[test]
+    fn aliases_are_expanded_before_parsing() {
+        let aliases = BTreeMap::from([
+            (
+                "standup".to_string(),
+                vec!["clock-in".into(), "0900".into()],
+            ),
+            ("view".to_string(), vec!["report".into()]),
+        ]);
+        let args = |args: &[&str]| -> Vec<OsString> {
+            args.iter().map(OsString::from).collect()
+        };
+        assert_eq!(
+            expand_alias(
+                args(&["bb", "-s", "standup", "standup", "-p", "x"]),
+                &aliases
+            ),
+            args(&["bb", "-s", "standup", "clock-in", "0900", "-p", "x"])
+        );
+        assert_eq!(
+            expand_alias(args(&["bb", "--dry-run", "view"]), &aliases),
+            args(&["bb", "--dry-run", "view"])
+        );
+        assert_eq!(
+            expand_alias(args(&["bb", "unknown"]), &aliases),
+            args(&["bb", "unknown"])
+        );
+    }
+
+    #[test]
+    fn commands_that_change_records_are_told_apart() {
+        let writes = |args: &[&str]| {
+            Cli::try_parse_from([&["bb"], args].concat())
+                .unwrap()
+                .command
+                .writes()
+        };
+        assert!(writes(&["clock-in"]));
+        assert!(writes(&["import", "old.csv"]));
+        assert!(!writes(&["import", "--diff", "old.csv"]));
+        assert!(!writes(&["doctor"]));
+        assert!(writes(&["doctor", "--fix"]));
+        assert!(writes(&["suggest"]));
+        assert!(!writes(&["--read-only", "report"]));
+    }
+
+    #[test]
+    fn words_are_split_like_a_shell_would() {
+        assert_eq!(
+            split_words(r#"interrupt "prod alert" -m 20"#).unwrap(),
+            ["interrupt", "prod alert", "-m", "20"]
+        );
+        assert_eq!(
+            split_words(r#"  note 'it''s' a\ b "" "#).unwrap(),
+            ["note", "its", "a b", ""]
+        );
+        assert!(split_words("note \"open").is_err());
+    }
 }
@@ -0,0 +1,182 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+
+use crate::{i18n, interval::Interval};
+
+/// How long one may work per day
+pub const MAX_DAILY: Duration = Duration::hours(10);
+/// How long the rest between two working days must be at least
+pub const MIN_REST: Duration = Duration::hours(11);
+/// How long one may work without a break
+pub const MAX_WITHOUT_BREAK: Duration = Duration::hours(6);
+/// Shorter pauses don't count as breaks
+pub const MIN_BREAK: Duration = Duration::minutes(15);
+
+/// A breach of the working time rules on a specific day
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Violation {
+    pub date: NaiveDate,
+    pub details: String,
+}
+
+/// The working time rules that were broken in a month
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Compliance {
+    pub month: NaiveDate,
+    pub violations: Vec<Violation>,
+}
+
+impl Compliance {
+    /// Checks the `intervals` of a month for days with too much work, too
+    /// little rest in between and too long stretches without a break
+    pub fn check(month: NaiveDate, intervals: &[Interval]) -> Compliance {
+        let mut days: BTreeMap<NaiveDate, Vec<&Interval>> = BTreeMap::new();
+        for interval in intervals {
+            days.entry(local(interval.start).date_naive())
+                .or_default()
+                .push(interval);
+        }
+        for day in days.values_mut() {
+            day.sort_by_key(|interval| interval.start);
+        }
+
+        let mut violations = Vec::new();
+        let mut previous_end: Option<(NaiveDate, DateTime<Local>)> = None;
+        for (date, day) in &days {
+            let mut violation = |details| {
+                violations.push(Violation {
+                    date: *date,
+                    details,
+                });
+            };
+            let worked = day.iter().fold(Duration::zero(), |sum, interval| {
+                sum + interval.duration()
+            });
+            if worked > MAX_DAILY {
+                violation(format!(
+                    "worked {}, more than {}",
+                    hours(worked),
+                    hours(MAX_DAILY)
+                ));
+            }
+            let start = local(day[0].start);
+            if let Some((previous, end)) = previous_end {
+                if start - end < MIN_REST {
+                    violation(format!(
+                        "rested only {} after {previous}, less than {}",
+                        hours(start - end),
+                        hours(MIN_REST)
+                    ));
+                }
+            }
+            for (start, end) in stretches(day) {
+                if end - start > MAX_WITHOUT_BREAK {
+                    violation(format!(
+                        "worked {} without a break from {} to {}",
+                        hours(end - start),
+                        start.format("%H:%M"),
+                        end.format("%H:%M")
+                    ));
+                }
+            }
+            let end = day.iter().map(|interval| interval.end).max();
+            previous_end = end.map(|end| (*date, local(end)));
+        }
+        Compliance { month, violations }
+    }
+}
+
+impl Display for Compliance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Working time rules in {}:", self.month.format("%B %Y"))?;
+        if self.violations.is_empty() {
+            return writeln!(f, "No violations");
+        }
+        for violation in &self.violations {
+            writeln!(f, "{}: {}", violation.date, violation.details)?;
+        }
+        Ok(())
+    }
+}
+
+/// Joins the sorted intervals of a day that are separated by less than a
+/// break
+fn stretches(day: &[&Interval]) -> Vec<(DateTime<Local>, DateTime<Local>)> {
+    let mut stretches: Vec<(DateTime<Local>, DateTime<Local>)> = Vec::new();
+    for interval in day {
+        let (start, end) = (local(interval.start), local(interval.end));
+        match stretches.last_mut() {
+            Some((_, last_end)) if start - *last_end < MIN_BREAK => {
+                *last_end = end.max(*last_end);
+            }
+            _ => stretches.push((start, end)),
+        }
+    }
+    stretches
+}
+
+fn local(dt: DateTime<Utc>) -> DateTime<Local> {
+    dt.with_timezone(&Local)
+}
+
+fn hours(duration: Duration) -> String {
+    format!("{} hours", i18n::duration(&duration))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn interval(day: u32, from: (u32, u32), to: (u32, u32)) -> Interval {
+        let at = |(h, m)| {
+            Local
+                .with_ymd_and_hms(2024, 3, day, h, m, 0)
+                .unwrap()
+                .to_utc()
+        };
+        Interval {
+            start: at(from),
+            end: at(to),
+            project: None,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn violations_are_listed_per_day() {
+        let month = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let intervals = [
+            interval(4, (8, 0), (12, 0)),
+            interval(4, (12, 10), (14, 30)),
+            interval(4, (15, 0), (22, 0)),
+            interval(5, (7, 0), (12, 0)),
+            interval(7, (9, 0), (12, 0)),
+        ];
+        assert_eq!(
+            Compliance::check(month, &intervals).to_string(),
+            "Working time rules in March 2024:\n\
+            2024-03-04: worked 13:20 hours, more than 10:00 hours\n\
+            2024-03-04: worked 06:30 hours without a break from 08:00 to \
+            14:30\n\
+            2024-03-04: worked 07:00 hours without a break from 15:00 to \
+            22:00\n\
+            2024-03-05: rested only 09:00 hours after 2024-03-04, less \
+            than 11:00 hours\n"
+        );
+    }
+
+    #[test]
+    fn short_days_comply() {
+        let month = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let compliance =
+            Compliance::check(month, &[interval(4, (8, 0), (14, 0))]);
+        assert!(compliance.violations.is_empty());
+        assert_eq!(
+            compliance.to_string(),
+            "Working time rules in March 2024:\nNo violations\n"
+        );
+    }
+}
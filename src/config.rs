@@ -0,0 +1,1000 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, Utc};
+use directories::BaseDirs;
+
+use crate::{
+    cli::parse_duration,
+    constraints::{Constraints, Window},
+    data::{Format, Layout},
+    i18n::{DurationFormat, Language},
+};
+
+/// Prefix of the environment variables that override settings of the
+/// config file, e.g., `BUSY_BEE_STORAGE_FORMAT` for `storage.format`
+pub const ENV_PREFIX: &str = "BUSY_BEE_";
+
+/// The settings that have a fixed name, as opposed to those named after
+/// projects or clients, e.g., `rates.web`
+pub const KEYS: [&str; 24] = [
+    "storage.dir",
+    "storage.format",
+    "storage.layout",
+    "storage.read_only",
+    "daily_target",
+    "travel_share",
+    "dedupe_window",
+    "merge_gap",
+    "auto_clockout",
+    "default_start",
+    "default_end",
+    "day_summary",
+    "max_backdate_days",
+    "max_future_minutes",
+    "max_plausible_hours",
+    "hours.core",
+    "hours.allowed",
+    "hours.max_daily",
+    "language",
+    "duration_format",
+    "currency",
+    "hooks_dir",
+    "git_repos",
+    "holidays",
+];
+
+/// The sections whose keys are named by the user
+const NAMED_SECTIONS: [&str; 5] = [
+    "jira.issues",
+    "report.sections",
+    "clients",
+    "rates",
+    "aliases",
+];
+
+/// A single value in the configuration file
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+/// Settings read from the user's `config.toml`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Config {
+    /// Where records are stored, unless given on the command line
+    pub storage_dir: Option<PathBuf>,
+    /// Which format new or rewritten day files use
+    pub storage_format: Format,
+    /// Whether records are kept in one file per day or per month
+    pub storage_layout: Layout,
+    /// Whether commands that change the records are refused, as with
+    /// `--read-only`
+    pub storage_read_only: bool,
+    /// How long you intend to work per day
+    pub daily_target: Duration,
+    /// The percentage of the time tagged `travel` that counts as working
+    /// time in reports
+    pub travel_share: u32,
+    /// Clocking in or out again within this time of an identical event does
+    /// nothing, e.g., when a shell alias fires twice. Zero turns this off.
+    pub dedupe_window: Duration,
+    /// Reports count a clock-out and a clock-in of the same project that
+    /// follows within this time as one stretch of work. Zero turns this
+    /// off.
+    pub merge_gap: Duration,
+    /// When to close a day that ends with a clock-in, so that a forgotten
+    /// clock-out doesn't count as working through the night
+    pub auto_clockout: Option<NaiveTime>,
+    /// When to clock in if only a date is given
+    pub default_start: NaiveTime,
+    /// When to clock out if only a date is given, default is the
+    /// `daily_target` after the `default_start`
+    pub default_end: Option<NaiveTime>,
+    /// Whether the clock-out that closes a day tells how long you worked
+    /// and how that compares to the `daily_target`
+    pub day_summary: bool,
+    /// Recording an event more than this many days ago takes `--force`, as
+    /// it's likely a typo
+    pub max_backdate_days: i64,
+    /// Recording an event more than this many minutes ahead takes `--force`
+    pub max_future_minutes: i64,
+    /// Days that add up to more hours than this get a warning in reports
+    /// and the status, as a clock-out was likely forgotten. Zero turns
+    /// this off.
+    pub max_plausible_hours: i64,
+    /// Where user scripts to run on clock events are located, defaults to
+    /// the `hooks` directory next to the config file
+    pub hooks_dir: Option<PathBuf>,
+    /// Jira issue keys to book the time of each project on, from the
+    /// `[jira.issues]` section
+    pub jira_issues: BTreeMap<String, String>,
+    /// Local repositories whose commits hint at when you worked
+    pub git_repos: Vec<PathBuf>,
+    /// Days off that `backfill --skip-holidays` leaves out
+    pub holidays: Vec<NaiveDate>,
+    /// Titles and commands of the programs that add sections to reports,
+    /// from the `[report.sections]` section
+    pub report_sections: Vec<(String, Vec<String>)>,
+    /// The projects of each client, from the `[clients]` section
+    pub clients: BTreeMap<String, Vec<String>>,
+    /// Hourly rates per project, from the `[rates]` section
+    pub rates: BTreeMap<String, f64>,
+    /// The arguments that stand for each user-defined command, from the
+    /// `[aliases]` section, e.g., `standup = "clock-in 0900 -p meetings"`
+    pub aliases: BTreeMap<String, Vec<String>>,
+    /// What to print after amounts of money, e.g., EUR
+    pub currency: Option<String>,
+    /// The language of reports, default is the one of the locale
+    pub language: Option<Language>,
+    /// Whether reports show durations like 07:45 or 7.75; the per-day CSV
+    /// always has decimal hours
+    pub duration_format: DurationFormat,
+    /// When and how long to work, from the `[hours]` section
+    pub hours: Constraints,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            storage_dir: None,
+            storage_format: Format::Csv,
+            storage_layout: Layout::Day,
+            storage_read_only: false,
+            daily_target: Duration::hours(8),
+            travel_share: 100,
+            dedupe_window: Duration::zero(),
+            merge_gap: Duration::zero(),
+            auto_clockout: None,
+            default_start: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            default_end: None,
+            day_summary: true,
+            max_backdate_days: 60,
+            max_future_minutes: 60,
+            max_plausible_hours: 16,
+            hooks_dir: None,
+            jira_issues: BTreeMap::new(),
+            git_repos: Vec::new(),
+            holidays: Vec::new(),
+            report_sections: Vec::new(),
+            clients: BTreeMap::new(),
+            rates: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            currency: None,
+            language: None,
+            duration_format: DurationFormat::Hms,
+            hours: Constraints::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration, falling back to the defaults if the file
+    /// does not exist
+    pub fn load(file_path: &Path) -> Result<Config> {
+        Config::from_table(&read_table(file_path)?)
+    }
+
+    /// Loads the configuration like [`Config::load`], with settings from
+    /// environment variables taking precedence over those of the file
+    pub fn load_layered(
+        file_path: &Path,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Config> {
+        let mut table = read_table(file_path)?;
+        table.extend(env_table(vars));
+        Config::from_table(&table)
+    }
+
+    /// When to clock out if only a date is given
+    pub fn end_of_day(&self) -> NaiveTime {
+        self.default_end
+            .unwrap_or(self.default_start + self.daily_target)
+    }
+
+    /// Fails if an event at `dt` is so far from `now` that the date is
+    /// likely a typo, e.g., 2034 instead of 2024
+    pub fn check_plausible(
+        &self,
+        dt: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let local = dt.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+        if dt < now - Duration::days(self.max_backdate_days) {
+            bail!(
+                "{local} is more than {} days ago, use --force if that's \
+                right",
+                self.max_backdate_days
+            );
+        }
+        if dt > now + Duration::minutes(self.max_future_minutes) {
+            bail!(
+                "{local} is more than {} minutes ahead, use --force if \
+                that's right",
+                self.max_future_minutes
+            );
+        }
+        Ok(())
+    }
+
+    fn from_table(table: &BTreeMap<String, Value>) -> Result<Config> {
+        let mut config = Config::default();
+        if let Some(value) = table.get("storage.dir") {
+            config.storage_dir =
+                Some(expand_home(as_str(value, "storage.dir")?));
+        }
+        if let Some(value) = table.get("storage.format") {
+            config.storage_format = as_str(value, "storage.format")?
+                .parse()
+                .map_err(|e: String| anyhow!(e))?;
+        }
+        if let Some(value) = table.get("storage.layout") {
+            config.storage_layout = as_str(value, "storage.layout")?
+                .parse()
+                .map_err(|e: String| anyhow!(e))?;
+        }
+        if let Some(value) = table.get("storage.read_only") {
+            config.storage_read_only = as_bool(value, "storage.read_only")?;
+        }
+        if let Some(value) = table.get("daily_target") {
+            config.daily_target = as_duration(value, "daily_target")?;
+        }
+        if let Some(value) = table.get("travel_share") {
+            config.travel_share = as_i64(value, "travel_share")?
+                .try_into()
+                .ok()
+                .filter(|percent| *percent <= 100)
+                .ok_or(anyhow!("'travel_share' must be a percentage"))?;
+        }
+        if let Some(value) = table.get("dedupe_window") {
+            config.dedupe_window = as_duration(value, "dedupe_window")?;
+        }
+        if let Some(value) = table.get("merge_gap") {
+            config.merge_gap = as_duration(value, "merge_gap")?;
+        }
+        if let Some(value) = table.get("auto_clockout") {
+            config.auto_clockout = Some(as_time(value, "auto_clockout")?);
+        }
+        if let Some(value) = table.get("default_start") {
+            config.default_start = as_time(value, "default_start")?;
+        }
+        if let Some(value) = table.get("default_end") {
+            config.default_end = Some(as_time(value, "default_end")?);
+        }
+        if let Some(value) = table.get("day_summary") {
+            config.day_summary = as_bool(value, "day_summary")?;
+        }
+        config.read_limits(table)?;
+        if let Some(value) = table.get("language") {
+            config.language = Some(
+                as_str(value, "language")?
+                    .parse()
+                    .map_err(|e: String| anyhow!(e))?,
+            );
+        }
+        if let Some(value) = table.get("duration_format") {
+            config.duration_format = as_str(value, "duration_format")?
+                .parse()
+                .map_err(|e: String| anyhow!(e))?;
+        }
+        if let Some(value) = table.get("currency") {
+            config.currency = Some(as_str(value, "currency")?.to_string());
+        }
+        if let Some(value) = table.get("hooks_dir") {
+            config.hooks_dir =
+                Some(PathBuf::from(as_str(value, "hooks_dir")?));
+        }
+        if let Some(value) = table.get("git_repos") {
+            config.git_repos = as_str_array(value, "git_repos")?
+                .into_iter()
+                .map(expand_home)
+                .collect();
+        }
+        if let Some(value) = table.get("holidays") {
+            config.holidays = as_str_array(value, "holidays")?
+                .into_iter()
+                .map(|date| {
+                    NaiveDate::parse_from_str(date, "%Y-%m-%d").with_context(
+                        || format!("Invalid date {date} in 'holidays'"),
+                    )
+                })
+                .collect::<Result<_>>()?;
+        }
+        config.read_named_sections(table)?;
+        Ok(config)
+    }
+
+    /// Reads the settings that limit what is recorded and how long to work
+    fn read_limits(&mut self, table: &BTreeMap<String, Value>) -> Result<()> {
+        if let Some(value) = table.get("max_backdate_days") {
+            self.max_backdate_days = as_i64(value, "max_backdate_days")?;
+        }
+        if let Some(value) = table.get("max_future_minutes") {
+            self.max_future_minutes = as_i64(value, "max_future_minutes")?;
+        }
+        if let Some(value) = table.get("max_plausible_hours") {
+            self.max_plausible_hours = as_i64(value, "max_plausible_hours")?;
+        }
+        if let Some(value) = table.get("hours.core") {
+            self.hours.core = Some(as_window(value, "hours.core")?);
+        }
+        if let Some(value) = table.get("hours.allowed") {
+            self.hours.allowed = Some(as_window(value, "hours.allowed")?);
+        }
+        if let Some(value) = table.get("hours.max_daily") {
+            self.hours.max_daily =
+                Some(as_duration(value, "hours.max_daily")?);
+        }
+        Ok(())
+    }
+
+    /// Reads the sections whose keys are named by the user, see
+    /// [`NAMED_SECTIONS`]
+    fn read_named_sections(
+        &mut self,
+        table: &BTreeMap<String, Value>,
+    ) -> Result<()> {
+        for (key, value) in table {
+            if let Some(project) = key.strip_prefix("jira.issues.") {
+                self.jira_issues
+                    .insert(project.to_string(), as_str(value, key)?.into());
+            }
+            if let Some(title) = key.strip_prefix("report.sections.") {
+                let mut command: Vec<String> = as_str(value, key)?
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect();
+                if let Some(program) = command.first_mut() {
+                    *program = expand_home(program).display().to_string();
+                }
+                self.report_sections.push((title.to_string(), command));
+            }
+            if let Some(client) = key.strip_prefix("clients.") {
+                let projects = as_str_array(value, key)?;
+                self.clients.insert(
+                    client.to_string(),
+                    projects.into_iter().map(String::from).collect(),
+                );
+            }
+            if let Some(project) = key.strip_prefix("rates.") {
+                self.rates.insert(project.to_string(), as_f64(value, key)?);
+            }
+            if let Some(name) = key.strip_prefix("aliases.") {
+                let args = split_args(as_str(value, key)?)
+                    .with_context(|| format!("Invalid alias {name}"))?;
+                self.aliases.insert(name.to_string(), args);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads the flattened settings of a config file, which are empty if the
+/// file does not exist
+pub fn read_table(file_path: &Path) -> Result<BTreeMap<String, Value>> {
+    let content = match fs::read_to_string(file_path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Ok(BTreeMap::new())
+        }
+        Err(err) => return Err(err.into()),
+    };
+    parse(&content).with_context(|| {
+        format!("Could not parse config file {}", file_path.display())
+    })
+}
+
+/// Picks the settings out of environment variables. Values that aren't
+/// valid TOML, e.g., `7h`, are taken as strings.
+pub fn env_table(
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> BTreeMap<String, Value> {
+    let mut table = BTreeMap::new();
+    for (name, value) in vars {
+        let Some(name) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let key = KEYS
+            .iter()
+            .find(|key| key.replace('.', "_").eq_ignore_ascii_case(name));
+        if let Some(key) = key {
+            let value =
+                parse_value(value.trim()).unwrap_or(Value::String(value));
+            table.insert((*key).to_string(), value);
+        }
+    }
+    table
+}
+
+/// The config file written by `init`, with most settings at their default
+const STARTER: &str = "\
+# Settings of busy-bee. Remove the # in front of a line to change a setting.
+
+# How long you intend to work per day
+# daily_target = \"8h\"
+# The percentage of time tagged travel that counts as working time
+# travel_share = 100
+# Count a clock-out and a clock-in that follows within this time as one
+# stretch of work in reports, e.g., \"5m\"; --raw shows them apart
+# merge_gap = \"0m\"
+# When to clock in and out if only a date is given
+# default_start = \"09:00\"
+# default_end = \"17:00\"
+# Days off, e.g., [\"2024-12-24\", \"2024-12-31\"]
+# holidays = []
+# The language of reports, en or de
+# language = \"en\"
+# Whether reports show durations like 07:45 (hms) or 7.75 (decimal)
+# duration_format = \"hms\"
+
+[storage]
+# The format of the data files, csv or jsonl
+# format = \"csv\"
+# Whether to keep one data file per day or per month
+# layout = \"day\"
+# Refuse to change the records, e.g., where another machine syncs them to
+# read_only = false
+";
+
+/// Writes a starter config file that explains the most common settings,
+/// with `storage_dir` as the storage directory if given. Fails if the file
+/// exists already.
+pub fn init(file_path: &Path, storage_dir: Option<&Path>) -> Result<()> {
+    if file_path.exists() {
+        bail!("{} exists already", file_path.display());
+    }
+    if let Some(dir) = file_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(file_path, STARTER)?;
+    if let Some(storage_dir) = storage_dir {
+        let storage_dir = std::path::absolute(storage_dir)?;
+        set(file_path, "storage.dir", &storage_dir.display().to_string())?;
+    }
+    Ok(())
+}
+
+/// Sets `key` to `value` in the config file, keeping its other lines as
+/// they are. Values that aren't valid TOML are written as strings.
+pub fn set(file_path: &Path, key: &str, value: &str) -> Result<()> {
+    let (section, name) = split_key(key)?;
+    let value = parse_value(value.trim())
+        .unwrap_or_else(|_| Value::String(value.to_string()));
+    let content = match fs::read_to_string(file_path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err.into()),
+    };
+    let updated = set_line(&content, section, name, &value);
+    Config::from_table(&parse(&updated)?)
+        .with_context(|| format!("Invalid value for '{key}'"))?;
+    if let Some(dir) = file_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(file_path, updated)?;
+    Ok(())
+}
+
+/// Splits a key into its section, which is empty for top-level keys, and
+/// its name within the section
+fn split_key(key: &str) -> Result<(&str, &str)> {
+    let named = NAMED_SECTIONS.iter().find_map(|section| {
+        let name = key.strip_prefix(section)?.strip_prefix('.')?;
+        (!name.is_empty()).then_some((*section, name))
+    });
+    if let Some(split) = named {
+        return Ok(split);
+    }
+    if !KEYS.contains(&key) {
+        bail!("Unknown setting '{key}'");
+    }
+    Ok(key.rsplit_once('.').unwrap_or(("", key)))
+}
+
+/// Fails if `key` names no setting
+pub fn check_key(key: &str) -> Result<()> {
+    split_key(key).map(|_| ())
+}
+
+fn set_line(
+    content: &str,
+    section: &str,
+    name: &str,
+    value: &Value,
+) -> String {
+    let bare = |c: char| c.is_ascii_alphanumeric() || "_-".contains(c);
+    let quoted = if name.chars().all(bare) {
+        name.to_string()
+    } else {
+        format!("\"{name}\"")
+    };
+    let new_line = format!("{quoted} = {value}");
+    let mut lines: Vec<String> = content.lines().map(String::from).collect();
+
+    let mut current = "";
+    // where a new key of the section goes, if it has no line yet
+    let mut insert_at = section.is_empty().then_some(0);
+    for (i, line) in content.lines().enumerate() {
+        let stripped = strip_comment(line).trim();
+        if let Some(header) = stripped.strip_prefix('[') {
+            current = header.trim_end_matches(']').trim();
+            if current == section {
+                insert_at = Some(i + 1);
+            }
+            continue;
+        }
+        if current != section {
+            continue;
+        }
+        match stripped.split_once('=') {
+            Some((key, _)) if key.trim().trim_matches('"') == name => {
+                lines[i] = new_line;
+                return join_lines(&lines);
+            }
+            Some(_) => insert_at = Some(i + 1),
+            None => {}
+        }
+    }
+    if let Some(i) = insert_at {
+        lines.insert(i, new_line);
+    } else {
+        if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+            lines.push(String::new());
+        }
+        lines.push(format!("[{section}]"));
+        lines.push(new_line);
+    }
+    join_lines(&lines)
+}
+
+fn join_lines(lines: &[String]) -> String {
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+fn as_duration(value: &Value, key: &str) -> Result<Duration> {
+    parse_duration(as_str(value, key)?)
+        .map_err(|e| anyhow!(e))
+        .with_context(|| format!("Invalid duration for '{key}'"))
+}
+
+fn as_time(value: &Value, key: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(as_str(value, key)?, "%H:%M")
+        .with_context(|| format!("Invalid time for '{key}', try e.g. 23:59"))
+}
+
+fn as_window(value: &Value, key: &str) -> Result<Window> {
+    as_str(value, key)?
+        .parse()
+        .map_err(|e: String| anyhow!(e))
+        .with_context(|| format!("Invalid time window for '{key}'"))
+}
+
+fn as_i64(value: &Value, key: &str) -> Result<i64> {
+    match value {
+        Value::Integer(i) if *i >= 0 => Ok(*i),
+        _ => bail!("Expected a whole number for '{key}'"),
+    }
+}
+
+fn as_bool(value: &Value, key: &str) -> Result<bool> {
+    match value {
+        Value::Boolean(b) => Ok(*b),
+        _ => bail!("Expected true or false for '{key}'"),
+    }
+}
+
+fn as_f64(value: &Value, key: &str) -> Result<f64> {
+    match value {
+        #[allow(clippy::cast_precision_loss)]
+        Value::Integer(i) => Ok(*i as f64),
+        Value::Float(f) => Ok(*f),
+        _ => bail!("Expected a number for '{key}'"),
+    }
+}
+
+fn as_str_array<'a>(value: &'a Value, key: &str) -> Result<Vec<&'a str>> {
+    match value {
+        Value::Array(values) => {
+            values.iter().map(|value| as_str(value, key)).collect()
+        }
+        _ => bail!("Expected an array of strings for '{key}'"),
+    }
+}
+
+/// Replaces a leading `~` with the user's home directory
+/// Splits `command` into arguments at whitespace that isn't quoted, like a
+/// shell
+fn split_args(command: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut quote = None;
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                arg.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(arg.take()),
+            (_, c) => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        bail!("Missing closing quote in '{command}'");
+    }
+    args.extend(arg);
+    if args.is_empty() {
+        bail!("Expected a command, but found nothing");
+    }
+    Ok(args)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    let home = BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+    match (path.strip_prefix("~/"), home) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn as_str<'a>(value: &'a Value, key: &str) -> Result<&'a str> {
+    match value {
+        Value::String(s) => Ok(s),
+        _ => bail!("Expected a string for '{key}'"),
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "\"{}\"", s.replace('"', "\\\"")),
+            Value::Integer(i) => write!(f, "{i}"),
+            Value::Float(x) => write!(f, "{x:?}"),
+            Value::Boolean(b) => write!(f, "{b}"),
+            Value::Array(values) => {
+                let items: Vec<String> =
+                    values.iter().map(ToString::to_string).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+        }
+    }
+}
+
+/// Parses the subset of TOML that this application uses: `[section]`
+/// headers, `key = value` pairs with strings, integers, booleans and arrays
+/// thereof, and `#` comments. Keys are returned flattened, e.g.,
+/// `storage.format`.
+pub fn parse(content: &str) -> Result<BTreeMap<String, Value>> {
+    let mut table = BTreeMap::new();
+    let mut section = String::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let line_no = i + 1;
+        if let Some(header) = line.strip_prefix('[') {
+            let name = header
+                .strip_suffix(']')
+                .ok_or(anyhow!("Unclosed section header in line {line_no}"))?;
+            section = name.trim().to_string();
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or(anyhow!("Expected 'key = value' in line {line_no}"))?;
+        let key = key.trim().trim_matches('"');
+        let value = parse_value(value.trim())
+            .with_context(|| format!("Invalid value in line {line_no}"))?;
+        let full_key = if section.is_empty() {
+            key.to_string()
+        } else {
+            format!("{section}.{key}")
+        };
+        table.insert(full_key, value);
+    }
+    Ok(table)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_value(s: &str) -> Result<Value> {
+    if let Some(inner) = s.strip_prefix('"') {
+        let inner = inner
+            .strip_suffix('"')
+            .ok_or(anyhow!("Unterminated string {s}"))?;
+        return Ok(Value::String(inner.replace("\\\"", "\"")));
+    }
+    if let Some(inner) = s.strip_prefix('[') {
+        let inner = inner
+            .strip_suffix(']')
+            .ok_or(anyhow!("Unterminated array {s}"))?;
+        return split_array(inner)
+            .into_iter()
+            .map(parse_value)
+            .collect::<Result<_>>()
+            .map(Value::Array);
+    }
+    match s {
+        "true" => Ok(Value::Boolean(true)),
+        "false" => Ok(Value::Boolean(false)),
+        _ => {
+            let number = s.replace('_', "");
+            number
+                .parse()
+                .map(Value::Integer)
+                .or_else(|_| number.parse().map(Value::Float))
+                .map_err(|_| {
+                    anyhow!("Unknown value {s}; strings must be quoted")
+                })
+        }
+    }
+}
+
+fn split_array(inner: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut in_string = false;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ',' if !in_string => {
+                items.push(inner[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(inner[start..].trim());
+    items.into_iter().filter(|item| !item.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn parse_flattens_sections() {
+        let content = "top = 1\n\
+            [storage]\n\
+            format = \"jsonl\" # a comment\n\
+            list = [\"a\", \"b,c\"]\n";
+        let table = parse(content).unwrap();
+        assert_eq!(table.get("top"), Some(&Value::Integer(1)));
+        assert_eq!(
+            table.get("storage.format"),
+            Some(&Value::String("jsonl".to_string()))
+        );
+        assert_eq!(
+            table.get("storage.list"),
+            Some(&Value::Array(vec![
+                Value::String("a".to_string()),
+                Value::String("b,c".to_string())
+            ]))
+        );
+    }
+
+    #[test]
+    fn parse_reads_numbers() {
+        let table = parse("a = 1_000\nb = 92.5\n").unwrap();
+        assert_eq!(table.get("a"), Some(&Value::Integer(1000)));
+        assert_eq!(table.get("b"), Some(&Value::Float(92.5)));
+    }
+
+    #[test]
+    fn parse_rejects_unquoted_strings() {
+        assert!(parse("format = jsonl").is_err());
+    }
+
+    #[test]
+    fn load_returns_defaults_if_file_does_not_exist() {
+        let d = tempdir().unwrap();
+        let config = Config::load(&d.path().join("config.toml")).unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn load_reads_storage_settings() {
+        let d = tempdir().unwrap();
+        let file_path = d.path().join("config.toml");
+        let content = "[storage]\nformat = \"jsonl\"\nlayout = \"month\"\n";
+        fs::write(&file_path, content).unwrap();
+        let config = Config::load(&file_path).unwrap();
+        assert_eq!(config.storage_format, Format::Jsonl);
+        assert_eq!(config.storage_layout, Layout::Month);
+    }
+
+    #[test]
+    fn load_reads_jira_issues() {
+        let d = tempdir().unwrap();
+        let file_path = d.path().join("config.toml");
+        let content = "[jira.issues]
+web = \"SHOP-7\"
+\"a.b\" = \"X-1\"
+";
+        fs::write(&file_path, content).unwrap();
+        let config = Config::load(&file_path).unwrap();
+        assert_eq!(
+            config.jira_issues,
+            BTreeMap::from([
+                ("a.b".to_string(), "X-1".to_string()),
+                ("web".to_string(), "SHOP-7".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn environment_overrides_config_file() {
+        let d = tempdir().unwrap();
+        let file_path = d.path().join("config.toml");
+        let content = "daily_target = \"8h\"\n[storage]\nformat = \"csv\"\n";
+        fs::write(&file_path, content).unwrap();
+        let vars = [
+            ("BUSY_BEE_STORAGE_FORMAT", "jsonl"),
+            ("BUSY_BEE_UNKNOWN", "x"),
+            ("HOME", "/home/me"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+        let config = Config::load_layered(&file_path, vars).unwrap();
+        assert_eq!(config.storage_format, Format::Jsonl);
+        assert_eq!(config.daily_target, Duration::hours(8));
+    }
+
+    #[test]
+    fn set_keeps_other_lines() {
+        let d = tempdir().unwrap();
+        let file_path = d.path().join("config.toml");
+        let content = "# my settings\n\
+            daily_target = \"8h\"\n\
+            [rates]\n\
+            web = 90 # per hour\n";
+        fs::write(&file_path, content).unwrap();
+        set(&file_path, "daily_target", "7h").unwrap();
+        set(&file_path, "rates.app", "80").unwrap();
+        set(&file_path, "storage.format", "jsonl").unwrap();
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "# my settings\n\
+            daily_target = \"7h\"\n\
+            [rates]\n\
+            web = 90 # per hour\n\
+            app = 80\n\
+            \n\
+            [storage]\n\
+            format = \"jsonl\"\n"
+        );
+        assert!(set(&file_path, "storage.format", "xml").is_err());
+        assert!(set(&file_path, "nonsense", "1").is_err());
+    }
+
+    #[test]
+    fn init_writes_a_config_with_the_defaults() {
+        let d = tempdir().unwrap();
+        let file_path = d.path().join("busy-bee").join("config.toml");
+        let storage_dir = d.path().join("records");
+        init(&file_path, Some(&storage_dir)).unwrap();
+        assert_eq!(
+            Config::load(&file_path).unwrap(),
+            Config {
+                storage_dir: Some(storage_dir),
+                ..Config::default()
+            }
+        );
+        assert!(init(&file_path, None).is_err());
+    }
+
+    #[test]
+    fn events_far_off_are_implausible() {
+        let config = Config::default();
+        let now = Utc.with_ymd_and_hms(2024, 5, 2, 12, 0, 0).unwrap();
+        let check = |dt| config.check_plausible(dt, now);
+        assert!(check(now - Duration::days(59)).is_ok());
+        assert!(check(now + Duration::minutes(59)).is_ok());
+        assert!(check(now - Duration::days(61)).is_err());
+        assert!(check(now + Duration::minutes(61)).is_err());
+        assert!(check(Utc.with_ymd_and_hms(2034, 5, 2, 12, 0, 0).unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn end_of_day_defaults_to_daily_target_after_start() {
+        let config = Config {
+            default_start: NaiveTime::from_hms_opt(8, 30, 0).unwrap(),
+            daily_target: Duration::minutes(450),
+            ..Config::default()
+        };
+        assert_eq!(
+            config.end_of_day(),
+            NaiveTime::from_hms_opt(16, 0, 0).unwrap()
+        );
+        let end = NaiveTime::from_hms_opt(15, 0, 0).unwrap();
+        let config = Config {
+            default_end: Some(end),
+            ..config
+        };
+        assert_eq!(config.end_of_day(), end);
+    }
+
+    #[test]
+    fn load_reads_travel_share() {
+        let d = tempdir().unwrap();
+        let file_path = d.path().join("config.toml");
+        fs::write(&file_path, "travel_share = 50\n").unwrap();
+        assert_eq!(Config::load(&file_path).unwrap().travel_share, 50);
+        fs::write(&file_path, "travel_share = 150\n").unwrap();
+        assert!(Config::load(&file_path).is_err());
+    }
+
+    #[test]
+    fn load_reads_language() {
+        let d = tempdir().unwrap();
+        let file_path = d.path().join("config.toml");
+        fs::write(&file_path, "language = \"de\"\n").unwrap();
+        let config = Config::load(&file_path).unwrap();
+        assert_eq!(config.language, Some(Language::German));
+    }
+
+    #[test]
+    fn load_reads_hours() {
+        let d = tempdir().unwrap();
+        let file_path = d.path().join("config.toml");
+        let content = "[hours]
+core = \"10:00-15:00\"
+max_daily = \"10h\"
+";
+        fs::write(&file_path, content).unwrap();
+        let config = Config::load(&file_path).unwrap();
+        assert_eq!(config.hours.core, Some("10:00-15:00".parse().unwrap()));
+        assert_eq!(config.hours.allowed, None);
+        assert_eq!(config.hours.max_daily, Some(Duration::hours(10)));
+
+        fs::write(&file_path, "[hours]\nallowed = \"20:00-06:00\"\n").unwrap();
+        assert!(Config::load(&file_path).is_err());
+    }
+
+    #[test]
+    fn load_reads_aliases() {
+        let d = tempdir().unwrap();
+        let file_path = d.path().join("config.toml");
+        let content = "[aliases]\n\
+            standup = \"clock-in 0900 -p meetings\"\n\
+            lunch = \"interrupt 'lunch with Ana' -m 45\"\n";
+        fs::write(&file_path, content).unwrap();
+        let config = Config::load(&file_path).unwrap();
+        assert_eq!(
+            config.aliases["standup"],
+            ["clock-in", "0900", "-p", "meetings"]
+        );
+        assert_eq!(
+            config.aliases["lunch"],
+            ["interrupt", "lunch with Ana", "-m", "45"]
+        );
+        fs::write(&file_path, "[aliases]\nx = \"view 'today\"\n").unwrap();
+        assert!(Config::load(&file_path).is_err());
+    }
+}
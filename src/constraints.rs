@@ -0,0 +1,245 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Write},
+    str::FromStr,
+};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, TimeZone};
+
+use crate::{
+    i18n,
+    interval::{intervals, Interval},
+    record::{Event, EventKind},
+};
+
+/// A span of local time that recurs every day, e.g., `10:00-15:00`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Window {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl Window {
+    fn contains(self, time: NaiveTime) -> bool {
+        self.start <= time && time <= self.end
+    }
+}
+
+impl Display for Window {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || format!("Invalid time window {s}, try e.g. 10:00-15:00");
+        let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|_| invalid())
+        };
+        let window = Window {
+            start: parse(start)?,
+            end: parse(end)?,
+        };
+        if window.start >= window.end {
+            return Err(invalid());
+        }
+        Ok(window)
+    }
+}
+
+/// Limits on when and how long to work, from the `[hours]` section of the
+/// config file
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Constraints {
+    /// When you must be clocked in on days you work
+    pub core: Option<Window>,
+    /// When you may clock in and out
+    pub allowed: Option<Window>,
+    /// How long you may work per day
+    pub max_daily: Option<Duration>,
+}
+
+impl Constraints {
+    /// Describes how the records of a past day violate the constraints.
+    /// Days without records are days off and never violate them.
+    pub fn violations(
+        &self,
+        date: NaiveDate,
+        events: &[Event],
+    ) -> Vec<String> {
+        let mut violations: Vec<String> = events
+            .iter()
+            .filter_map(|event| self.outside_allowed(event))
+            .collect();
+        let worked = intervals(events);
+        if worked.is_empty() {
+            return violations;
+        }
+        if let Some(core) = self.core {
+            for (start, end) in core_gaps(date, core, &worked) {
+                violations.push(format!(
+                    "not clocked in from {} to {} during core hours {core}",
+                    start.format("%H:%M"),
+                    end.format("%H:%M")
+                ));
+            }
+        }
+        violations.extend(self.over_max(&worked));
+        violations
+    }
+
+    /// Lists the violations of each day before `today`, for the monthly
+    /// report. Empty if there are none.
+    pub fn report(&self, events: &[Event], today: NaiveDate) -> String {
+        let mut days: BTreeMap<NaiveDate, Vec<Event>> = BTreeMap::new();
+        for event in events {
+            let date = event.dt.with_timezone(&Local).date_naive();
+            days.entry(date).or_default().push(event.clone());
+        }
+        let mut report = String::new();
+        for (date, events) in days.range(..today) {
+            for violation in self.violations(*date, events) {
+                let _ = writeln!(report, "  {date}: {violation}");
+            }
+        }
+        if report.is_empty() {
+            return report;
+        }
+        format!("Time constraints violated:\n{report}")
+    }
+
+    /// Warnings to print right after recording `event`, given all events of
+    /// its day
+    pub fn warnings(&self, event: &Event, events: &[Event]) -> Vec<String> {
+        let mut warnings: Vec<String> =
+            self.outside_allowed(event).into_iter().collect();
+        if event.kind == EventKind::ClockOut {
+            warnings.extend(self.over_max(&intervals(events)));
+        }
+        warnings
+    }
+
+    fn outside_allowed(&self, event: &Event) -> Option<String> {
+        let allowed = self.allowed?;
+        let kind = match event.kind {
+            EventKind::ClockIn => "clocked in",
+            EventKind::ClockOut => "clocked out",
+            _ => return None,
+        };
+        let time = event.dt.with_timezone(&Local).time();
+        (!allowed.contains(time)).then(|| {
+            format!(
+                "{kind} at {}, outside of the allowed hours {allowed}",
+                time.format("%H:%M")
+            )
+        })
+    }
+
+    fn over_max(&self, worked: &[Interval]) -> Option<String> {
+        let max = self.max_daily?;
+        let total = worked
+            .iter()
+            .fold(Duration::zero(), |sum, interval| sum + interval.duration());
+        (total > max).then(|| {
+            format!(
+                "worked {}, more than the maximum of {} per day",
+                hours(total),
+                hours(max)
+            )
+        })
+    }
+}
+
+/// The parts of the core hours of `date` that aren't covered by `worked`
+fn core_gaps(
+    date: NaiveDate,
+    core: Window,
+    worked: &[Interval],
+) -> Vec<(DateTime<Local>, DateTime<Local>)> {
+    let at = |time| Local.from_local_datetime(&date.and_time(time)).earliest();
+    let (Some(start), Some(end)) = (at(core.start), at(core.end)) else {
+        return Vec::new();
+    };
+    let mut sorted: Vec<&Interval> = worked.iter().collect();
+    sorted.sort_by_key(|interval| interval.start);
+
+    let mut gaps = Vec::new();
+    let mut covered_until = start;
+    for interval in sorted {
+        let interval_start = interval.start.with_timezone(&Local);
+        if interval_start > covered_until {
+            gaps.push((covered_until, interval_start.min(end)));
+        }
+        covered_until = covered_until.max(interval.end.with_timezone(&Local));
+        if covered_until >= end {
+            break;
+        }
+    }
+    if covered_until < end {
+        gaps.push((covered_until, end));
+    }
+    gaps.retain(|(gap_start, gap_end)| gap_start < gap_end);
+    gaps
+}
+
+fn hours(duration: Duration) -> String {
+    format!("{} hours", i18n::duration(&duration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: NaiveDate, h: u32, m: u32) -> DateTime<Local> {
+        Local
+            .from_local_datetime(&date.and_hms_opt(h, m, 0).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn violations_list_gaps_in_core_hours_and_overtime() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let constraints = Constraints {
+            core: Some("10:00-15:00".parse().unwrap()),
+            allowed: Some("07:00-20:00".parse().unwrap()),
+            max_daily: Some(Duration::hours(6)),
+        };
+        let events = [
+            Event::clock_in(&at(date, 6, 30)),
+            Event::clock_out(&at(date, 12, 0)),
+            Event::clock_in(&at(date, 12, 30)),
+            Event::clock_out(&at(date, 14, 0)),
+        ];
+        assert_eq!(
+            constraints.violations(date, &events),
+            vec![
+                "clocked in at 06:30, outside of the allowed hours \
+                07:00-20:00",
+                "not clocked in from 12:00 to 12:30 during core hours \
+                10:00-15:00",
+                "not clocked in from 14:00 to 15:00 during core hours \
+                10:00-15:00",
+                "worked 07:00 hours, more than the maximum of 06:00 hours \
+                per day",
+            ]
+        );
+        assert!(constraints.violations(date, &[]).is_empty());
+    }
+
+    #[test]
+    fn windows_must_not_be_empty() {
+        assert!("15:00-10:00".parse::<Window>().is_err());
+        assert!("10:00".parse::<Window>().is_err());
+    }
+}
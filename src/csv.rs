@@ -0,0 +1,106 @@
+//! Quoting and splitting comma separated values, just enough for the CSV
+//! data files and exports. Fields that contain commas, quotes or spaces at
+//! either end are quoted, and so is text that spreadsheets would run as a
+//! formula, which is prefixed with `'` in addition.
+
+use std::borrow::Cow;
+
+use anyhow::{bail, Result};
+
+/// The characters that make spreadsheets evaluate a cell, plus the prefix
+/// itself so that it can be told apart when reading
+const FORMULA_START: [char; 5] = ['=', '+', '-', '@', '\''];
+
+/// Quotes `field` if it can't be written as is
+pub fn escape(field: &str) -> Cow<'_, str> {
+    let is_formula = field.starts_with(FORMULA_START);
+    if !is_formula
+        && field.trim() == field
+        && !field.contains([',', '"', '\n', '\r'])
+    {
+        return Cow::Borrowed(field);
+    }
+    let prefix = if is_formula { "'" } else { "" };
+    Cow::Owned(format!("\"{prefix}{}\"", field.replace('"', "\"\"")))
+}
+
+/// Splits a line into its fields, undoing [`escape`]. Fields that aren't
+/// quoted are trimmed.
+pub fn split(line: &str) -> Result<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+        if chars.next_if_eq(&'"').is_none() {
+            let mut field = String::new();
+            let mut more = false;
+            for c in chars.by_ref() {
+                if c == ',' {
+                    more = true;
+                    break;
+                }
+                field.push(c);
+            }
+            fields.push(field.trim().to_string());
+            if !more {
+                return Ok(fields);
+            }
+            continue;
+        }
+        let mut field = String::new();
+        loop {
+            match chars.next() {
+                Some('"') if chars.next_if_eq(&'"').is_some() => {
+                    field.push('"');
+                }
+                Some('"') => break,
+                Some(c) => field.push(c),
+                None => bail!("Missing closing quote in line: {line}"),
+            }
+        }
+        while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+        if field.starts_with('\'') {
+            field.remove(0);
+        }
+        fields.push(field);
+        match chars.next() {
+            Some(',') => {}
+            None => return Ok(fields),
+            Some(_) => bail!("Unexpected text after a quote in line: {line}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Rng;
+
+    #[test]
+    fn fields_are_quoted_where_necessary() {
+        assert_eq!(escape("web"), "web");
+        assert_eq!(escape("a, b"), "\"a, b\"");
+        assert_eq!(escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape("=1+1"), "\"'=1+1\"");
+        assert_eq!(escape("'quoted'"), "\"''quoted'\"");
+        assert_eq!(
+            split("clock-in, web ,\"a, b\",\"'=1+1\",").unwrap(),
+            ["clock-in", "web", "a, b", "=1+1", ""]
+        );
+        assert!(split("\"open").is_err());
+        assert!(split("\"closed\" too").is_err());
+    }
+
+    #[test]
+    fn escaped_fields_split_into_what_they_were() {
+        let mut rng = Rng::new(883);
+        for _ in 0..500 {
+            let fields: Vec<String> = (0..=rng.below(4))
+                .map(|_| rng.string(8).replace(['\n', '\r'], ""))
+                .collect();
+            let line: Vec<_> = fields.iter().map(|f| escape(f)).collect();
+            let line = line.join(",");
+            assert_eq!(split(&line).unwrap(), fields, "{line}");
+        }
+    }
+}
@@ -1,225 +1,1601 @@
 use std::{
-    error::Error,
+    cell::RefCell,
+    collections::BTreeMap,
     fmt::Display,
-    fs::{self, File, OpenOptions},
-    io::{self, Read, Write},
-    path::Path,
+    fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
 };
 
 use anyhow::{bail, Context, Result};
-use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime, Utc};
 use tempfile::NamedTempFile;
 
-#[derive(Debug)]
-pub enum PersistenceError {
-    EventNotFoundError { id: u32 },
-    InvalidFormatError,
-    InvalidDataError { detail: String },
-    IoError { err: io::Error },
+pub use crate::record::{
+    events_to_string, parse_events, DayNote, Event, EventKind, Format,
+    PersistenceError, Source, StoredEvent,
+};
+use crate::{
+    expense::Expense,
+    gzip,
+    platform::{parent_dir, CaseVariants},
+    record::{parse_event, parse_row, row_to_str},
+};
+
+/// Marks the first line of a data file, which holds the checksum of the
+/// remaining content. It's a header rather than a trailer so that it
+/// survives the file being truncated.
+const CHECKSUM_PREFIX: &str = "# crc32 ";
+
+/// The subdirectory of the storage directory that old data files are
+/// moved into, grouped by year
+pub(crate) const ARCHIVE_DIR: &str = "archive";
+
+/// The subdirectory of the storage directory that expenses are kept in,
+/// one CSV file per month
+pub const EXPENSES_DIR: &str = "expenses";
+
+/// The subdirectory of the storage directory that notes on whole days are
+/// kept in, one CSV file per month
+pub const NOTES_DIR: &str = "notes";
+
+/// The subdirectory of the storage directory that deleted events are kept
+/// in until they're restored, one CSV file per month
+pub const TRASH_DIR: &str = "trash";
+
+/// How records are distributed over files
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum Layout {
+    /// One file per day
+    Day,
+    /// One file per month, with the date in each row
+    Month,
 }
 
-impl Display for PersistenceError {
+impl Display for Layout {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Persistence error") // TODO
+        match self {
+            Layout::Day => write!(f, "day"),
+            Layout::Month => write!(f, "month"),
+        }
     }
 }
 
-impl Error for PersistenceError {}
+impl FromStr for Layout {
+    type Err = String;
 
-impl From<io::Error> for PersistenceError {
-    fn from(err: std::io::Error) -> Self {
-        Self::IoError { err }
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "day" => Ok(Layout::Day),
+            "month" => Ok(Layout::Month),
+            _ => Err(format!("Unknown storage layout {s}, try day or month")),
+        }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub enum EventKind {
-    ClockIn,
-    ClockOut,
+/// Where and how events are persisted
+pub trait Storage {
+    /// Returns the events recorded for a day, sorted by time
+    fn read_day(&self, date: NaiveDate) -> Result<Vec<Event>>;
+
+    /// Returns the file changes that replacing the events recorded for a
+    /// day would cause, without writing anything
+    fn plan_write(
+        &self,
+        date: NaiveDate,
+        events: &[Event],
+    ) -> Result<Vec<FileChange>>;
+
+    /// Returns the file changes that removing all records of a day would
+    /// cause, without writing anything
+    fn plan_remove(&self, date: NaiveDate) -> Result<Vec<FileChange>>;
+
+    /// Replaces the events recorded for a day
+    fn write_day(&self, date: NaiveDate, events: &[Event]) -> Result<()> {
+        apply(&self.plan_write(date, events)?)
+    }
+
+    /// Removes all records of a day
+    fn remove_day(&self, date: NaiveDate) -> Result<()> {
+        apply(&self.plan_remove(date)?)
+    }
+
+    /// Returns the file changes that replacing the events recorded for
+    /// several days would cause, without writing anything
+    fn plan_write_days(
+        &self,
+        days: &[(NaiveDate, Vec<Event>)],
+    ) -> Result<Vec<FileChange>> {
+        let mut changes = Vec::new();
+        for (date, events) in days {
+            changes.extend(self.plan_write(*date, events)?);
+        }
+        Ok(changes)
+    }
+
+    /// Replaces the events recorded for several days in one transaction,
+    /// so that either all days are written or none
+    fn write_days(&self, days: &[(NaiveDate, Vec<Event>)]) -> Result<()> {
+        apply(&self.plan_write_days(days)?)
+    }
+
+    /// Returns all days for which records exist in the storage directory,
+    /// in ascending order. Days in the archive are left out, as commands
+    /// that rewrite every day, e.g., `migrate` or `dedupe`, would move them
+    /// back; see [`Storage::all_days`] for commands that only read.
+    fn days(&self) -> Result<Vec<NaiveDate>>;
+
+    /// Returns the days whose records were moved to the archive, in
+    /// ascending order
+    fn archived_days(&self) -> Result<Vec<NaiveDate>>;
+
+    /// Returns the days of [`Storage::days`] and [`Storage::archived_days`]
+    /// together, in ascending order
+    fn all_days(&self) -> Result<Vec<NaiveDate>> {
+        let mut days = self.days()?;
+        days.extend(self.archived_days()?);
+        days.sort();
+        days.dedup();
+        Ok(days)
+    }
+
+    /// Returns the file changes that removing every data file of this
+    /// storage's own format and layout would cause, without reading any
+    /// records, e.g., for files of the other format
+    fn plan_remove_all(&self) -> Result<Vec<FileChange>>;
 }
 
+/// A pending change of a single file
 #[derive(Debug, PartialEq, Eq, Clone)]
-pub struct Event {
-    pub kind: EventKind,
-    pub dt: DateTime<Utc>,
+pub struct FileChange {
+    pub path: PathBuf,
+    /// The current content, or `None` if the file doesn't exist
+    pub old: Option<String>,
+    /// The new content, or `None` if the file is to be removed
+    pub new: Option<String>,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub struct StoredEvent {
-    pub id: u32,
-    pub event: Event,
+impl FileChange {
+    fn new(path: PathBuf, new: Option<String>) -> Result<FileChange> {
+        let old = match fs::read_to_string(&path) {
+            Ok(content) => Some(content),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self { path, old, new })
+    }
+
+    fn is_noop(&self) -> bool {
+        self.old == self.new
+    }
+}
+
+/// Performs the given file changes as one transaction. The new contents
+/// are staged in temp files next to their targets before any file is
+/// touched, and if replacing or removing a file fails, the files changed
+/// before are restored to their old content.
+pub fn apply(changes: &[FileChange]) -> Result<()> {
+    let mut staged = Vec::new();
+    for change in changes {
+        let tmp_file = match &change.new {
+            Some(content) => {
+                Some(stage(&change.path, content).with_context(|| {
+                    let fd = change.path.display();
+                    format!("Could not write events to file {fd}")
+                })?)
+            }
+            None => None,
+        };
+        staged.push(tmp_file);
+    }
+    for (done, (change, tmp_file)) in changes.iter().zip(staged).enumerate() {
+        let result = match tmp_file {
+            Some(tmp_file) => tmp_file
+                .persist(&change.path)
+                .map_err(anyhow::Error::from)
+                .and_then(|_| sync_dir(&change.path)),
+            None => remove_if_exists(&change.path)
+                .and_then(|()| sync_dir(&change.path)),
+        };
+        if let Err(err) = result {
+            rollback(&changes[..done]);
+            let fd = change.path.display();
+            return Err(err.context(format!(
+                "Could not change file {fd}, no files were changed"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Restores the old content of files that were changed, in reverse order.
+/// This is best effort, as it runs after something already went wrong.
+fn rollback(changes: &[FileChange]) {
+    for change in changes.iter().rev() {
+        let _ = match &change.old {
+            Some(content) => write_to_file(&change.path, content),
+            None => remove_if_exists(&change.path),
+        };
+    }
+}
+
+/// Wraps a storage so that writes are only recorded, not performed
+pub struct DryRun<'a> {
+    inner: &'a dyn Storage,
+    changes: RefCell<Vec<FileChange>>,
+}
+
+impl<'a> DryRun<'a> {
+    pub fn new(inner: &'a dyn Storage) -> DryRun<'a> {
+        Self {
+            inner,
+            changes: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Returns the changes that would have been written so far
+    pub fn changes(&self) -> Vec<FileChange> {
+        self.changes.borrow().clone()
+    }
+}
+
+impl Storage for DryRun<'_> {
+    fn read_day(&self, date: NaiveDate) -> Result<Vec<Event>> {
+        self.inner.read_day(date)
+    }
+
+    fn plan_write(
+        &self,
+        date: NaiveDate,
+        events: &[Event],
+    ) -> Result<Vec<FileChange>> {
+        self.inner.plan_write(date, events)
+    }
+
+    fn plan_remove(&self, date: NaiveDate) -> Result<Vec<FileChange>> {
+        self.inner.plan_remove(date)
+    }
+
+    fn write_day(&self, date: NaiveDate, events: &[Event]) -> Result<()> {
+        let changes = self.plan_write(date, events)?;
+        self.changes.borrow_mut().extend(changes);
+        Ok(())
+    }
+
+    fn remove_day(&self, date: NaiveDate) -> Result<()> {
+        let changes = self.plan_remove(date)?;
+        self.changes.borrow_mut().extend(changes);
+        Ok(())
+    }
+
+    fn plan_write_days(
+        &self,
+        days: &[(NaiveDate, Vec<Event>)],
+    ) -> Result<Vec<FileChange>> {
+        self.inner.plan_write_days(days)
+    }
+
+    fn write_days(&self, days: &[(NaiveDate, Vec<Event>)]) -> Result<()> {
+        let changes = self.plan_write_days(days)?;
+        self.changes.borrow_mut().extend(changes);
+        Ok(())
+    }
+
+    fn days(&self) -> Result<Vec<NaiveDate>> {
+        self.inner.days()
+    }
+
+    fn archived_days(&self) -> Result<Vec<NaiveDate>> {
+        self.inner.archived_days()
+    }
+
+    fn plan_remove_all(&self) -> Result<Vec<FileChange>> {
+        self.inner.plan_remove_all()
+    }
+}
+
+/// Wraps a storage so that writes are kept in memory, where later reads
+/// see them, until they are committed as one transaction, e.g., to apply
+/// several commands at once or none of them
+pub struct Batch<'a> {
+    inner: &'a dyn Storage,
+    pending: RefCell<BTreeMap<NaiveDate, Vec<Event>>>,
+    /// The events deleted so far, for the trash
+    trashed: RefCell<Vec<(NaiveDate, Vec<Event>)>>,
+}
+
+impl<'a> Batch<'a> {
+    pub fn new(inner: &'a dyn Storage) -> Batch<'a> {
+        Self {
+            inner,
+            pending: RefCell::new(BTreeMap::new()),
+            trashed: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Keeps the deleted `events` of `date` for the trash until the batch
+    /// is committed, so that the trash doesn't get events whose deletion
+    /// never happened
+    pub fn trash(&self, date: NaiveDate, events: &[Event]) {
+        self.trashed.borrow_mut().push((date, events.to_vec()));
+    }
+
+    /// Returns the file changes that committing would cause, without
+    /// writing anything: the days written so far and the trash in the
+    /// storage directory `dir`
+    pub fn plan_commit(&self, dir: &Path) -> Result<Vec<FileChange>> {
+        let days: Vec<_> = self.pending.borrow().clone().into_iter().collect();
+        let mut changes = self.inner.plan_write_days(&days)?;
+        changes.extend(plan_trash_days(dir, &self.trashed.borrow())?);
+        Ok(changes)
+    }
+
+    /// Writes what the batch's commands changed to the wrapped storage and
+    /// the trash in `dir`, all or none
+    pub fn commit(&self, dir: &Path) -> Result<()> {
+        apply(&self.plan_commit(dir)?)
+    }
+}
+
+impl Storage for Batch<'_> {
+    fn read_day(&self, date: NaiveDate) -> Result<Vec<Event>> {
+        match self.pending.borrow().get(&date) {
+            Some(events) => Ok(events.clone()),
+            None => self.inner.read_day(date),
+        }
+    }
+
+    fn plan_write(
+        &self,
+        date: NaiveDate,
+        events: &[Event],
+    ) -> Result<Vec<FileChange>> {
+        self.inner.plan_write(date, events)
+    }
+
+    fn plan_remove(&self, date: NaiveDate) -> Result<Vec<FileChange>> {
+        self.inner.plan_remove(date)
+    }
+
+    fn write_day(&self, date: NaiveDate, events: &[Event]) -> Result<()> {
+        self.pending.borrow_mut().insert(date, events.to_vec());
+        Ok(())
+    }
+
+    fn remove_day(&self, date: NaiveDate) -> Result<()> {
+        self.write_day(date, &[])
+    }
+
+    fn write_days(&self, days: &[(NaiveDate, Vec<Event>)]) -> Result<()> {
+        self.pending.borrow_mut().extend(days.iter().cloned());
+        Ok(())
+    }
+
+    fn days(&self) -> Result<Vec<NaiveDate>> {
+        let pending = self.pending.borrow();
+        let mut days: Vec<_> = self
+            .inner
+            .days()?
+            .into_iter()
+            .chain(pending.keys().copied())
+            .filter(|date| pending.get(date).is_none_or(|e| !e.is_empty()))
+            .collect();
+        days.sort();
+        days.dedup();
+        Ok(days)
+    }
+
+    fn archived_days(&self) -> Result<Vec<NaiveDate>> {
+        self.inner.archived_days()
+    }
+
+    fn plan_remove_all(&self) -> Result<Vec<FileChange>> {
+        self.inner.plan_remove_all()
+    }
 }
 
-impl Event {
-    pub fn clock_in<Tz: TimeZone>(dt: &DateTime<Tz>) -> Event {
+/// The file in the storage directory that commands lock while they change
+/// records
+pub const LOCK_FILE: &str = ".lock";
+
+/// Waits until no other process is changing the records in `dir`, and
+/// keeps others waiting until the returned file is dropped, so that the
+/// reads and writes of a command, e.g., of a whole batch, don't interleave
+/// with another's
+pub fn lock(dir: &Path) -> Result<fs::File> {
+    let (path, file) = open_lock(dir)?;
+    file.lock()
+        .with_context(|| format!("Could not lock {}", path.display()))?;
+    Ok(file)
+}
+
+/// Like [`lock`], but fails instead of waiting while another process holds
+/// the lock, e.g., for a request that is better answered at once
+pub fn try_lock(dir: &Path) -> Result<fs::File> {
+    let (path, file) = open_lock(dir)?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(fs::TryLockError::WouldBlock) => bail!(
+            "Another command is changing the records in {}, try again",
+            dir.display()
+        ),
+        Err(fs::TryLockError::Error(err)) => Err(err)
+            .with_context(|| format!("Could not lock {}", path.display())),
+    }
+}
+
+fn open_lock(dir: &Path) -> Result<(PathBuf, fs::File)> {
+    let path = dir.join(LOCK_FILE);
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Could not open {}", path.display()))?;
+    Ok((path, file))
+}
+
+/// Opens the storage in `dir` with the given format and layout
+pub fn open(dir: &Path, format: Format, layout: Layout) -> Box<dyn Storage> {
+    match layout {
+        Layout::Day => Box::new(DayFiles::new(dir, format)),
+        Layout::Month => Box::new(MonthFiles::new(dir, format)),
+    }
+}
+
+/// Stores the events of each day in a separate file
+pub(crate) struct DayFiles {
+    dir: PathBuf,
+    format: Format,
+    variants: CaseVariants,
+}
+
+impl DayFiles {
+    pub fn new(dir: &Path, format: Format) -> DayFiles {
         Self {
-            kind: EventKind::ClockIn,
-            dt: dt.to_utc(),
+            dir: dir.to_path_buf(),
+            format,
+            variants: CaseVariants::new(dir),
         }
     }
+}
+
+impl Storage for DayFiles {
+    fn read_day(&self, date: NaiveDate) -> Result<Vec<Event>> {
+        let Some(file_content) = read_either_format(
+            &self.dir,
+            &self.variants,
+            |format| get_file_name(&date, format),
+            self.format,
+        )?
+        else {
+            return Ok(Vec::new());
+        };
+        file_content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(parse_event)
+            .collect()
+    }
 
-    pub fn clock_out<Tz: TimeZone>(dt: &DateTime<Tz>) -> Event {
+    fn plan_write(
+        &self,
+        date: NaiveDate,
+        events: &[Event],
+    ) -> Result<Vec<FileChange>> {
+        let file_path = self.dir.join(get_file_name(&date, self.format));
+        let other_path =
+            self.dir.join(get_file_name(&date, self.format.other()));
+        let content = seal(&write_events(events, self.format));
+        let mut changes =
+            plan_replace(&self.variants, file_path, Some(content))?;
+        changes.push(FileChange::new(other_path, None)?);
+        Ok(without_noops(changes))
+    }
+
+    fn plan_remove(&self, date: NaiveDate) -> Result<Vec<FileChange>> {
+        let file_path = self.dir.join(get_file_name(&date, self.format));
+        Ok(without_noops(plan_replace(
+            &self.variants,
+            file_path,
+            None,
+        )?))
+    }
+
+    fn days(&self) -> Result<Vec<NaiveDate>> {
+        let mut days: Vec<_> = list_stems(&self.dir)?
+            .iter()
+            .filter_map(|stem| {
+                NaiveDate::parse_from_str(stem, "%Y-%m-%d").ok()
+            })
+            .collect();
+        days.sort();
+        days.dedup();
+        Ok(days)
+    }
+
+    fn archived_days(&self) -> Result<Vec<NaiveDate>> {
+        Ok(list_archived_stems(&self.dir)?
+            .iter()
+            .filter_map(|stem| {
+                NaiveDate::parse_from_str(stem, "%Y-%m-%d").ok()
+            })
+            .collect())
+    }
+
+    fn plan_remove_all(&self) -> Result<Vec<FileChange>> {
+        let mut changes = Vec::new();
+        for date in self.days()? {
+            let file_path = self.dir.join(get_file_name(&date, self.format));
+            changes.push(FileChange::new(file_path, None)?);
+        }
+        Ok(without_noops(changes))
+    }
+}
+
+/// Stores the events of each month in a single file, which keeps the number
+/// of files small for backups and syncing
+pub(crate) struct MonthFiles {
+    dir: PathBuf,
+    format: Format,
+    variants: CaseVariants,
+}
+
+impl MonthFiles {
+    pub fn new(dir: &Path, format: Format) -> MonthFiles {
         Self {
-            kind: EventKind::ClockOut,
-            dt: dt.to_utc(),
+            dir: dir.to_path_buf(),
+            format,
+            variants: CaseVariants::new(dir),
         }
     }
+
+    fn read_month<T: Datelike>(
+        &self,
+        has_date: &T,
+    ) -> Result<Vec<(NaiveDate, Event)>> {
+        let Some(file_content) = read_either_format(
+            &self.dir,
+            &self.variants,
+            |format| get_month_file_name(has_date, format),
+            self.format,
+        )?
+        else {
+            return Ok(Vec::new());
+        };
+        file_content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(parse_row)
+            .collect()
+    }
+
+    fn plan_month<T: Datelike>(
+        &self,
+        has_date: &T,
+        rows: &[(NaiveDate, Event)],
+    ) -> Result<Vec<FileChange>> {
+        let file_path =
+            self.dir.join(get_month_file_name(has_date, self.format));
+        let other_path = self
+            .dir
+            .join(get_month_file_name(has_date, self.format.other()));
+        // an empty file hides an archived one, which would be read instead
+        let file_name = |format| get_month_file_name(has_date, format);
+        let content = if rows.is_empty() && !is_archived(&self.dir, file_name)
+        {
+            None
+        } else {
+            Some(seal(&write_rows(rows, self.format)))
+        };
+        let mut changes = plan_replace(&self.variants, file_path, content)?;
+        changes.push(FileChange::new(other_path, None)?);
+        Ok(without_noops(changes))
+    }
 }
 
-pub fn create_event(storage_dir: &Path, event: &Event) -> Result<Vec<Event>> {
-    let mut events = read_events(storage_dir, event.dt.date_naive())
-        .with_context(|| {
-            let sd = storage_dir.display();
-            format!("Could not read events from storage directory {sd}")
-        })?;
-    events.push(event.clone());
+impl Storage for MonthFiles {
+    fn read_day(&self, date: NaiveDate) -> Result<Vec<Event>> {
+        Ok(self
+            .read_month(&date)?
+            .into_iter()
+            .filter(|(row_date, _)| *row_date == date)
+            .map(|(_, event)| event)
+            .collect())
+    }
+
+    fn plan_write(
+        &self,
+        date: NaiveDate,
+        events: &[Event],
+    ) -> Result<Vec<FileChange>> {
+        let mut rows: Vec<_> = self
+            .read_month(&date)?
+            .into_iter()
+            .filter(|(row_date, _)| *row_date != date)
+            .collect();
+        rows.extend(events.iter().map(|event| (date, event.clone())));
+        self.plan_month(&date, &rows)
+    }
+
+    /// Plans each month file once, as planning the days of a month one by
+    /// one would let each plan overwrite the previous ones
+    fn plan_write_days(
+        &self,
+        days: &[(NaiveDate, Vec<Event>)],
+    ) -> Result<Vec<FileChange>> {
+        let mut months: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for day in days {
+            months
+                .entry((day.0.year(), day.0.month()))
+                .or_default()
+                .push(day);
+        }
+        let mut changes = Vec::new();
+        for month in months.values() {
+            let first = month[0].0;
+            let mut rows: Vec<_> = self
+                .read_month(&first)?
+                .into_iter()
+                .filter(|(row_date, _)| {
+                    month.iter().all(|(date, _)| date != row_date)
+                })
+                .collect();
+            for (date, events) in month {
+                rows.extend(events.iter().map(|event| (*date, event.clone())));
+            }
+            changes.extend(self.plan_month(&first, &rows)?);
+        }
+        Ok(changes)
+    }
+
+    fn plan_remove(&self, date: NaiveDate) -> Result<Vec<FileChange>> {
+        let rows: Vec<_> = self
+            .read_month(&date)?
+            .into_iter()
+            .filter(|(row_date, _)| *row_date != date)
+            .collect();
+        self.plan_month(&date, &rows)
+    }
+
+    fn days(&self) -> Result<Vec<NaiveDate>> {
+        let mut days = Vec::new();
+        for stem in list_stems(&self.dir)? {
+            let first_of_month = format!("{stem}-01");
+            let Ok(month) =
+                NaiveDate::parse_from_str(&first_of_month, "%Y-%m-%d")
+            else {
+                continue;
+            };
+            days.extend(self.read_month(&month)?.into_iter().map(|(d, _)| d));
+        }
+        days.sort();
+        days.dedup();
+        Ok(days)
+    }
+
+    fn archived_days(&self) -> Result<Vec<NaiveDate>> {
+        let mut days = Vec::new();
+        for stem in list_archived_stems(&self.dir)? {
+            let first_of_month = format!("{stem}-01");
+            let Ok(month) =
+                NaiveDate::parse_from_str(&first_of_month, "%Y-%m-%d")
+            else {
+                continue;
+            };
+            days.extend(self.read_month(&month)?.into_iter().map(|(d, _)| d));
+        }
+        days.sort();
+        days.dedup();
+        Ok(days)
+    }
+
+    fn plan_remove_all(&self) -> Result<Vec<FileChange>> {
+        let mut changes = Vec::new();
+        for stem in list_stems(&self.dir)? {
+            let first_of_month = format!("{stem}-01");
+            let Ok(month) =
+                NaiveDate::parse_from_str(&first_of_month, "%Y-%m-%d")
+            else {
+                continue;
+            };
+            let file_path =
+                self.dir.join(get_month_file_name(&month, self.format));
+            changes.push(FileChange::new(file_path, None)?);
+        }
+        Ok(without_noops(changes))
+    }
+}
+
+/// The content of a day file, the one way that all writes serialize events:
+/// sorted by time, one per line, each ending with a newline
+fn write_events(events: &[Event], format: Format) -> String {
+    let mut events = events.to_vec();
     events.sort_by_key(|event| event.dt);
+    events_to_string(&events, format)
+}
 
-    let events_as_str: String = events
+/// The content of a month file, like [`write_events`] with the rows sorted
+/// by date first
+fn write_rows(rows: &[(NaiveDate, Event)], format: Format) -> String {
+    let mut rows = rows.to_vec();
+    rows.sort_by_key(|(date, event)| (*date, event.dt));
+    rows.iter()
+        .map(|(date, event)| row_to_str(*date, event, format) + "\n")
+        .collect()
+}
+
+/// Reads the file named by `file_name` in the preferred format, or else in
+/// the other format, so that switching formats doesn't hide existing records
+fn read_either_format(
+    dir: &Path,
+    variants: &CaseVariants,
+    file_name: impl Fn(Format) -> String,
+    preferred: Format,
+) -> Result<Option<String>> {
+    for format in [preferred, preferred.other()] {
+        let mut file_path = dir.join(file_name(format));
+        let mut bytes = read_if_exists(&file_path)?;
+        if bytes.is_none() {
+            if let Some(variant) = variants.find(&file_path)? {
+                bytes = read_if_exists(&variant)?;
+                file_path = variant;
+            }
+        }
+        if let Some(bytes) = bytes {
+            return Ok(Some(unseal_bytes(&file_path, bytes)?));
+        }
+    }
+    read_archived(dir, file_name, preferred)
+}
+
+/// Like [`read_either_format`], but for files that were moved to the
+/// archive, where they may be compressed
+fn read_archived(
+    dir: &Path,
+    file_name: impl Fn(Format) -> String,
+    preferred: Format,
+) -> Result<Option<String>> {
+    for format in [preferred, preferred.other()] {
+        let file_path = archived_path(dir, &file_name(format));
+        if let Some(bytes) = read_if_exists(&file_path)? {
+            return Ok(Some(unseal_bytes(&file_path, bytes)?));
+        }
+        let compressed_path = file_path.with_extension(format!("{format}.gz"));
+        if let Some(bytes) = read_if_exists(&compressed_path)? {
+            let bytes = gzip::decompress(&bytes).with_context(|| {
+                format!("Could not read {}", compressed_path.display())
+            })?;
+            return Ok(Some(unseal_bytes(&file_path, bytes)?));
+        }
+    }
+    Ok(None)
+}
+
+/// Whether the file named by `file_name` was moved to the archive, in
+/// either format
+fn is_archived(dir: &Path, file_name: impl Fn(Format) -> String) -> bool {
+    [Format::Csv, Format::Jsonl].into_iter().any(|format| {
+        let file_path = archived_path(dir, &file_name(format));
+        let compressed_path = file_path.with_extension(format!("{format}.gz"));
+        file_path.exists() || compressed_path.exists()
+    })
+}
+
+/// Reads a file, telling a missing file, which just means that there are
+/// no records, apart from one that can't be read
+fn read_if_exists(
+    file_path: &Path,
+) -> Result<Option<Vec<u8>>, PersistenceError> {
+    match fs::read(file_path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+            Err(PersistenceError::PermissionDenied {
+                path: file_path.to_path_buf(),
+            })
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn unseal_bytes(file_path: &Path, bytes: Vec<u8>) -> Result<String> {
+    let file_content = String::from_utf8(bytes).with_context(|| {
+        format!("{} is not valid UTF-8", file_path.display())
+    })?;
+    Ok(unseal(file_path, &file_content)?.to_string())
+}
+
+/// Where a data file is kept once it's archived: in a directory of its
+/// year, which the file name starts with
+pub(crate) fn archived_path(dir: &Path, file_name: &str) -> PathBuf {
+    let year = file_name.get(..4).unwrap_or_default();
+    dir.join(ARCHIVE_DIR).join(year).join(file_name)
+}
+
+/// Returns all data files in `dir`, in any format or layout
+pub(crate) fn data_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        // only commands that write create the directory
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Ok(Vec::new());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let is_data_file = path.extension().is_some_and(|ext| {
+            ext.to_string_lossy().parse::<Format>().is_ok()
+        });
+        if is_data_file && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The sizes and modification times of the data files in a directory,
+/// which tell when another program changed them, e.g., a sync client or an
+/// editor
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Snapshot(Vec<(PathBuf, u64, SystemTime)>);
+
+impl Snapshot {
+    pub fn take(dir: &Path) -> Result<Snapshot> {
+        let mut files = Vec::new();
+        for path in data_files(dir)? {
+            let metadata = fs::metadata(&path)?;
+            files.push((path, metadata.len(), metadata.modified()?));
+        }
+        Ok(Snapshot(files))
+    }
+}
+
+/// Returns the names, without extension, of all data files in `dir`
+fn list_stems(dir: &Path) -> Result<Vec<String>> {
+    Ok(data_files(dir)?
         .iter()
-        .map(event_to_str)
-        .collect::<Vec<_>>()
-        .join("\n");
+        .filter_map(|path| path.file_stem())
+        .map(|stem| stem.to_string_lossy().to_string())
+        .collect())
+}
 
-    let file_name = get_file_name(&event.dt);
-    let file_path = storage_dir.join(file_name);
+/// Returns the names, without extensions, of all data files in the archive
+/// of `dir`, compressed or not
+fn list_archived_stems(dir: &Path) -> Result<Vec<String>> {
+    let archive_dir = dir.join(ARCHIVE_DIR);
+    if !archive_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut stems = Vec::new();
+    for entry in fs::read_dir(archive_dir)? {
+        let year_dir = entry?.path();
+        if !year_dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(year_dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            let name = name.strip_suffix(".gz").unwrap_or(&name);
+            if let Some((stem, extension)) = name.split_once('.') {
+                if extension.parse::<Format>().is_ok() {
+                    stems.push(stem.to_string());
+                }
+            }
+        }
+    }
+    stems.sort();
+    stems.dedup();
+    Ok(stems)
+}
 
-    write_to_file(&file_path, &events_as_str).with_context(|| {
-        let fd = file_path.display();
-        format!("Could not write events to file {fd}")
+/// Prefixes `content` with its checksum
+fn seal(content: &str) -> String {
+    format!(
+        "{CHECKSUM_PREFIX}{:08x}\n{content}",
+        gzip::crc32(content.as_bytes())
+    )
+}
+
+/// Verifies and strips the checksum line of a file's content. Files without
+/// a checksum, e.g., from earlier versions, are accepted as they are.
+fn unseal<'a>(
+    file_path: &Path,
+    file_content: &'a str,
+) -> Result<&'a str, PersistenceError> {
+    let Some(rest) = file_content.strip_prefix(CHECKSUM_PREFIX) else {
+        return Ok(file_content);
+    };
+    let (checksum, content) = rest.split_once('\n').unwrap_or((rest, ""));
+    if u32::from_str_radix(checksum.trim(), 16).ok()
+        != Some(gzip::crc32(content.as_bytes()))
+    {
+        return Err(PersistenceError::CorruptedFile {
+            path: file_path.to_path_buf(),
+        });
+    }
+    Ok(content)
+}
+
+/// Checks the checksum of a data file
+pub(crate) fn verify_file(file_path: &Path) -> Result<(), PersistenceError> {
+    let file_content = fs::read_to_string(file_path)?;
+    unseal(file_path, &file_content).map(|_| ())
+}
+
+/// Recomputes the checksum of a data file after validating that its content
+/// can be parsed, accepting it as it is
+pub(crate) fn reseal_file(file_path: &Path) -> Result<()> {
+    let file_content = fs::read_to_string(file_path)?;
+    let content = match file_content.strip_prefix(CHECKSUM_PREFIX) {
+        Some(rest) => rest.split_once('\n').map_or("", |(_, content)| content),
+        None => &file_content,
+    };
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        if parse_event(line).is_err() {
+            parse_row(line).with_context(|| {
+                format!("Cannot accept {}", file_path.display())
+            })?;
+        }
+    }
+    write_to_file(file_path, &seal(content))
+}
+
+/// Plans changing the file at `file_path`, after removing a file whose name
+/// only differs in case, so that on case-sensitive file systems a day's
+/// records don't end up in two files
+fn plan_replace(
+    variants: &CaseVariants,
+    file_path: PathBuf,
+    content: Option<String>,
+) -> Result<Vec<FileChange>> {
+    let mut changes = Vec::new();
+    if let Some(variant) = variants.find(&file_path)? {
+        changes.push(FileChange::new(variant, None)?);
+    }
+    changes.push(FileChange::new(file_path, content)?);
+    Ok(changes)
+}
+
+/// Drops changes that wouldn't change anything
+fn without_noops(changes: Vec<FileChange>) -> Vec<FileChange> {
+    changes.into_iter().filter(|c| !c.is_noop()).collect()
+}
+
+fn remove_if_exists(file_path: &Path) -> Result<()> {
+    match fs::remove_file(file_path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// The outcome of [`create_event`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct Created {
+    /// All events of the day
+    pub events: Vec<Event>,
+    /// An identical event recorded less than the dedupe window apart, in
+    /// which case the new event was not stored
+    pub duplicate_of: Option<Event>,
+    /// The clock-in that the day before ended with, if the new event is
+    /// the first clock-in of its day, as that clock-in may lack its
+    /// clock-out
+    pub dangling: Option<Event>,
+}
+
+/// Stores an event unless an identical one was recorded less than
+/// `dedupe_window` apart; a zero window stores every event
+pub fn create_event(
+    storage: &dyn Storage,
+    event: &Event,
+    dedupe_window: Duration,
+) -> Result<Created> {
+    let date = event.dt.date_naive();
+    let mut events = read_events(storage, date).with_context(|| {
+        format!("Could not read events for {date} from storage")
     })?;
+    let duplicate_of = events
+        .iter()
+        .find(|existing| is_duplicate(existing, event, dedupe_window))
+        .cloned();
+    if duplicate_of.is_none() {
+        events.push(event.clone());
+        events.sort_by_key(|event| event.dt);
+        storage.write_day(date, &events)?;
+    }
+    let first_clock_in = event.kind == EventKind::ClockIn
+        && !events
+            .iter()
+            .any(|other| other.kind.is_clock() && other.dt < event.dt);
+    let dangling = if first_clock_in {
+        last_clock_in(storage, date.pred_opt().unwrap_or(date))
+    } else {
+        None
+    };
+    Ok(Created {
+        events,
+        duplicate_of,
+        dangling,
+    })
+}
+
+/// Whether `a` and `b` are the same event, recorded less than `window`
+/// apart
+fn is_duplicate(a: &Event, b: &Event, window: Duration) -> bool {
+    a.kind == b.kind
+        && a.project == b.project
+        && a.tags == b.tags
+        && (a.dt - b.dt).abs() < window
+}
+
+/// Splits `events` into those to keep and duplicates of them, which are
+/// the same as an earlier event less than `window` apart, as when the
+/// records of two machines were merged
+pub fn find_duplicates(
+    events: &[Event],
+    window: Duration,
+) -> (Vec<Event>, Vec<Event>) {
+    let mut sorted = events.to_vec();
+    sorted.sort_by_key(|event| event.dt);
+    let mut kept: Vec<Event> = Vec::new();
+    let mut duplicates = Vec::new();
+    for event in sorted {
+        if kept.iter().any(|kept| is_duplicate(kept, &event, window)) {
+            duplicates.push(event);
+        } else {
+            kept.push(event);
+        }
+    }
+    (kept, duplicates)
+}
+
+/// The clock-in that `date` ends with, if any. A day that can't be read
+/// has none, so that it doesn't keep new events from being recorded.
+fn last_clock_in(storage: &dyn Storage, date: NaiveDate) -> Option<Event> {
+    read_events(storage, date)
+        .ok()?
+        .into_iter()
+        .filter(|event| event.kind.is_clock())
+        .max_by_key(|event| event.dt)
+        .filter(|event| event.kind == EventKind::ClockIn)
+}
+
+/// Stores several events at once, possibly of different days, in one
+/// transaction, so that either all events are stored or none
+pub fn create_events(storage: &dyn Storage, new: &[Event]) -> Result<()> {
+    let mut per_day: BTreeMap<NaiveDate, Vec<Event>> = BTreeMap::new();
+    for event in new {
+        per_day
+            .entry(event.dt.date_naive())
+            .or_default()
+            .push(event.clone());
+    }
+    let mut days = Vec::new();
+    for (date, new) in per_day {
+        let mut events = read_events(storage, date)?;
+        events.extend(new);
+        events.sort_by_key(|event| event.dt);
+        days.push((date, events));
+    }
+    storage
+        .write_days(&days)
+        .context("Could not write events, nothing was stored")
+}
+
+pub fn read_events(
+    storage: &dyn Storage,
+    date: NaiveDate,
+) -> Result<Vec<Event>> {
+    storage.read_day(date)
+}
+
+/// Fails if the system clock seems to have been set back, e.g., after a VM
+/// resumed: `event` is taken at the current time `now`, but a stored event
+/// is later. Events at other times are assumed to be given on purpose.
+pub fn check_clock(
+    storage: &dyn Storage,
+    event: &Event,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    if (event.dt - now).abs() > Duration::minutes(1) {
+        return Ok(());
+    }
+    // archived days are all older, so they only matter without others
+    let last_day = match storage.days()?.pop() {
+        Some(last_day) => Some(last_day),
+        None => storage.archived_days()?.pop(),
+    };
+    let Some(last_day) = last_day else {
+        return Ok(());
+    };
+    let latest = storage.read_day(last_day)?.into_iter().map(|e| e.dt).max();
+    if let Some(latest) = latest.filter(|latest| *latest > event.dt) {
+        let format = "%Y-%m-%d %H:%M";
+        bail!(
+            "An event is recorded at {}, after the current time {}; is the \
+            system clock off? Use --force if it's right",
+            latest.with_timezone(&Local).format(format),
+            now.with_timezone(&Local).format(format)
+        );
+    }
+    Ok(())
+}
+
+/// How much of the end of a month file [`read_latest_day`] reads
+const TAIL_BYTES: u64 = 16 * 1024;
+
+/// Reads the events of `date` like [`Storage::read_day`], but only the end
+/// of a month file, where the latest days are since rows are sorted, e.g.,
+/// for a status that must be quick on a network share. Where `date` may
+/// start before the end that was read, the whole file is read. The
+/// checksum isn't verified, as that takes the whole file.
+pub fn read_latest_day(
+    dir: &Path,
+    format: Format,
+    layout: Layout,
+    date: NaiveDate,
+) -> Result<Vec<Event>> {
+    let storage = open(dir, format, layout);
+    if layout == Layout::Day {
+        return storage.read_day(date);
+    }
+    let path = dir.join(get_month_file_name(&date, format));
+    let Ok(mut file) = fs::File::open(&path) else {
+        return storage.read_day(date);
+    };
+    let len = file.metadata()?.len();
+    if len <= TAIL_BYTES {
+        return storage.read_day(date);
+    }
+    file.seek(SeekFrom::Start(len - TAIL_BYTES))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    // the first line is likely cut off
+    let start = tail.iter().position(|b| *b == b'\n').map_or(0, |i| i + 1);
+    let Ok(tail) = std::str::from_utf8(&tail[start..]) else {
+        return storage.read_day(date);
+    };
+    let rows: Result<Vec<_>> = tail
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(parse_row)
+        .collect();
+    match rows {
+        Ok(rows) if rows.first().is_some_and(|(first, _)| *first < date) => {
+            Ok(rows
+                .into_iter()
+                .filter(|(row_date, _)| *row_date == date)
+                .map(|(_, event)| event)
+                .collect())
+        }
+        _ => storage.read_day(date),
+    }
+}
+
+/// Reads the events of a day along with the IDs that [`delete_event`]
+/// takes
+pub fn read_stored_events(
+    storage: &dyn Storage,
+    date: NaiveDate,
+) -> Result<Vec<StoredEvent>> {
+    Ok(StoredEvent::number(&storage.read_day(date)?))
+}
+
+/// The events of `date` from `start` to `end` local time, both inclusive,
+/// with their IDs, e.g., to remove a block that was imported by mistake
+pub fn read_events_between(
+    storage: &dyn Storage,
+    date: NaiveDate,
+    start: NaiveTime,
+    end: NaiveTime,
+) -> Result<Vec<StoredEvent>> {
+    if start > end {
+        bail!("The window from {start} to {end} ends before it starts");
+    }
+    Ok(read_stored_events(storage, date)?
+        .into_iter()
+        .filter(|stored| {
+            let time = stored.event.dt.with_timezone(&Local).time();
+            start <= time && time <= end
+        })
+        .collect())
+}
+
+/// Reads the events of all days from `from` to `to`, both inclusive
+pub fn read_range(
+    storage: &dyn Storage,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<Event>> {
+    let mut events = Vec::new();
+    for date in from.iter_days().take_while(|date| *date <= to) {
+        events.extend(storage.read_day(date)?);
+    }
     Ok(events)
 }
 
-pub fn read_events(storage_dir: &Path, date: NaiveDate) -> Result<Vec<Event>> {
-    let file_name = get_file_name(&date);
-    let file_path = storage_dir.join(file_name);
+/// Moves all records of `from` into `to`, e.g., to change the storage
+/// format or layout. `from` and `to` must not be configured identically,
+/// or the records would be removed right after writing them. Returns the
+/// number of days that were migrated.
+pub fn migrate(from: &dyn Storage, to: &dyn Storage) -> Result<usize> {
+    let (migrated, changes) = plan_migrate(from, to)?;
+    apply(&changes)?;
+    Ok(migrated)
+}
+
+/// Returns the file changes that [`migrate`] would cause, and the number of
+/// days it would move, without writing anything. All files of `to` are
+/// planned first, and only then the removal of the files in the format and
+/// layout of `from`, so that the files just planned for `to` can't be
+/// taken for those of `from`.
+pub fn plan_migrate(
+    from: &dyn Storage,
+    to: &dyn Storage,
+) -> Result<(usize, Vec<FileChange>)> {
+    let mut days = Vec::new();
+    for date in from.days()? {
+        days.push((date, from.read_day(date)?));
+    }
+    let mut changes = to
+        .plan_write_days(&days)
+        .context("Could not migrate the records")?;
+    for removal in from.plan_remove_all()? {
+        // where both storages use the same file, it's rewritten instead
+        if changes.iter().all(|change| change.path != removal.path) {
+            changes.push(removal);
+        }
+    }
+    Ok((days.len(), changes))
+}
+
+/// Removes the event with the given ID, as listed by
+/// [`crate::view::daily_report`], and returns the remaining events of the
+/// day. Fails with [`PersistenceError::EventNotFoundError`] if there is no
+/// such event, leaving the day as it is.
+pub fn delete_event(
+    storage: &dyn Storage,
+    date: NaiveDate,
+    id: u32,
+) -> Result<Vec<Event>> {
+    delete_events(storage, date, &[id])
+}
+
+/// Removes the events with the given IDs at once, like [`delete_event`].
+/// Fails without removing any if one of them doesn't exist.
+pub fn delete_events(
+    storage: &dyn Storage,
+    date: NaiveDate,
+    ids: &[u32],
+) -> Result<Vec<Event>> {
+    let stored = read_stored_events(storage, date)?;
+    if let Some(&id) =
+        ids.iter().find(|id| !stored.iter().any(|s| s.id == **id))
+    {
+        return Err(PersistenceError::EventNotFoundError { id }.into());
+    }
+    let events: Vec<Event> = stored
+        .into_iter()
+        .filter(|stored| !ids.contains(&stored.id))
+        .map(|stored| stored.event)
+        .collect();
+
+    storage.write_day(date, &events)?;
+    Ok(events)
+}
+
+/// Reads the expenses of the month of `month`, sorted by date
+pub fn read_expenses(dir: &Path, month: NaiveDate) -> Result<Vec<Expense>> {
+    read_sidecar(&sidecar_path(dir, EXPENSES_DIR, month))?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(Expense::parse)
+        .collect()
+}
+
+/// Returns the file change that adding `expense` to the expenses of its
+/// month would cause, without writing anything
+pub fn plan_add_expense(dir: &Path, expense: &Expense) -> Result<FileChange> {
+    let mut expenses = read_expenses(dir, expense.date)?;
+    // after the expenses of the same day that were added before
+    let index = expenses.partition_point(|e| e.date <= expense.date);
+    expenses.insert(index, expense.clone());
+    let content: String = expenses.iter().map(|e| e.to_csv() + "\n").collect();
+    let file_path = sidecar_path(dir, EXPENSES_DIR, expense.date);
+    FileChange::new(file_path, Some(seal(&content)))
+}
+
+/// Reads the notes on the days of the month of `month`, sorted by date
+pub fn read_day_notes(dir: &Path, month: NaiveDate) -> Result<Vec<DayNote>> {
+    read_sidecar(&sidecar_path(dir, NOTES_DIR, month))?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(DayNote::parse)
+        .collect()
+}
+
+/// Reads the notes on all days, sorted by date
+pub fn read_all_day_notes(dir: &Path) -> Result<Vec<DayNote>> {
+    let mut notes = Vec::new();
+    for file_path in sidecar_files(dir, NOTES_DIR)? {
+        for line in read_sidecar(&file_path)?.lines() {
+            if !line.trim().is_empty() {
+                notes.push(DayNote::parse(line)?);
+            }
+        }
+    }
+    Ok(notes)
+}
+
+/// Returns the file change that adding `note` to the notes of its month
+/// would cause, without writing anything
+pub fn plan_add_day_note(dir: &Path, note: &DayNote) -> Result<FileChange> {
+    let mut notes = read_day_notes(dir, note.date)?;
+    let index = notes.partition_point(|n| n.date <= note.date);
+    notes.insert(index, note.clone());
+    let content: String = notes.iter().map(|n| n.to_csv() + "\n").collect();
+    let file_path = sidecar_path(dir, NOTES_DIR, note.date);
+    FileChange::new(file_path, Some(seal(&content)))
+}
+
+/// A deleted event, with the ID that restoring it takes
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Trashed {
+    /// Kept with the event, so that it doesn't change as other events are
+    /// deleted or restored
+    pub id: u32,
+    /// The day the event was recorded for
+    pub date: NaiveDate,
+    pub event: Event,
+}
+
+/// Reads the deleted events of all days, by month and then in the order
+/// they were deleted
+pub fn read_trash(dir: &Path) -> Result<Vec<Trashed>> {
+    let mut trash = Vec::new();
+    for file_path in sidecar_files(dir, TRASH_DIR)? {
+        for line in read_sidecar(&file_path)?.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Some((Ok(id), row)) =
+                line.split_once(',').map(|(id, row)| (id.parse(), row))
+            else {
+                bail!("Invalid line in {}: {line}", file_path.display());
+            };
+            let (date, event) = parse_row(row)?;
+            trash.push(Trashed { id, date, event });
+        }
+    }
+    Ok(trash)
+}
+
+/// A line of a trash file: the ID, then the row of the event
+fn trash_line(trashed: &Trashed) -> String {
+    let row = row_to_str(trashed.date, &trashed.event, Format::Csv);
+    format!("{},{row}\n", trashed.id)
+}
 
-    let mut file_content = String::new();
+/// Returns the file change that keeping the deleted `events` of `date` in
+/// the trash would cause, without writing anything
+pub fn plan_trash(
+    dir: &Path,
+    date: NaiveDate,
+    events: &[Event],
+) -> Result<FileChange> {
+    let mut changes = plan_trash_days(dir, &[(date, events.to_vec())])?;
+    Ok(changes.remove(0))
+}
 
-    if !file_path.is_file() {
-        // This could also mean that the file is not readable by the current
-        // user
-        return Ok(Vec::new());
+/// Like [`plan_trash`] for the deleted events of several days, with one
+/// change per file of the trash
+pub fn plan_trash_days(
+    dir: &Path,
+    days: &[(NaiveDate, Vec<Event>)],
+) -> Result<Vec<FileChange>> {
+    // after the highest ID there is, so that IDs aren't shifted
+    let mut id = read_trash(dir)?.iter().map(|t| t.id + 1).max();
+    let mut files: BTreeMap<PathBuf, String> = BTreeMap::new();
+    for (date, events) in days {
+        let file_path = sidecar_path(dir, TRASH_DIR, *date);
+        if !files.contains_key(&file_path) {
+            let content = read_sidecar(&file_path)?;
+            files.insert(file_path.clone(), content);
+        }
+        let content = files.get_mut(&file_path).unwrap();
+        for event in events {
+            let trashed = Trashed {
+                id: id.unwrap_or(0),
+                date: *date,
+                event: event.clone(),
+            };
+            content.push_str(&trash_line(&trashed));
+            id = Some(trashed.id + 1);
+        }
     }
-
-    let _ = File::open(file_path)?.read_to_string(&mut file_content)?;
-    file_content
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .map(parse_event)
+    files
+        .into_iter()
+        .map(|(path, content)| FileChange::new(path, Some(seal(&content))))
         .collect()
 }
 
-fn parse_event(line: &str) -> Result<Event> {
-    let cols: Vec<_> = line.split(',').map(str::trim).collect();
-    if cols.len() != 2 {
-        bail!("Misformatted line: {line}")
-    }
-
-    // allowing [0] because we previously asserted that this element exists
-    #[allow(clippy::match_on_vec_items)]
-    let kind = match cols[0] {
-        "clock-in" => Ok(EventKind::ClockIn),
-        "clock-out" => Ok(EventKind::ClockOut),
-        other => Err(PersistenceError::InvalidDataError {
-            detail: format!("Unknown event kind {other}"),
-        }),
-    }?;
-
-    let date_str = cols[1];
-    let dt = DateTime::parse_from_rfc3339(date_str)
-        .map_err(|err| PersistenceError::InvalidDataError {
-            detail: format!("Could not parse {date_str} as datetime: {err}"),
-        })?
-        .with_timezone(&Utc);
-    Ok(Event { kind, dt })
+/// Returns the deleted event with the ID `id`, and the file change that
+/// taking it out of the trash would cause, without writing anything
+pub fn plan_untrash(dir: &Path, id: u32) -> Result<(Trashed, FileChange)> {
+    let trash = read_trash(dir)?;
+    let Some(restored) = trash.iter().find(|t| t.id == id).cloned() else {
+        return Err(PersistenceError::EventNotFoundError { id }.into());
+    };
+    let file_path = sidecar_path(dir, TRASH_DIR, restored.date);
+    let content: String = trash
+        .iter()
+        .filter(|t| t.id != id)
+        .filter(|t| sidecar_path(dir, TRASH_DIR, t.date) == file_path)
+        .map(trash_line)
+        .collect();
+    let new = (!content.is_empty()).then(|| seal(&content));
+    Ok((restored, FileChange::new(file_path, new)?))
 }
 
-fn event_to_str(event: &Event) -> String {
-    let kind_str = match event.kind {
-        EventKind::ClockIn => "clock-in",
-        EventKind::ClockOut => "clock-out",
+/// The files in the subdirectory `subdir` of the storage directory, by
+/// month
+fn sidecar_files(dir: &Path, subdir: &str) -> Result<Vec<PathBuf>> {
+    let mut files = match fs::read_dir(dir.join(subdir)) {
+        Ok(entries) => entries
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err.into()),
     };
-    let date_str = event.dt.to_rfc3339();
-
-    format!("{kind_str},{date_str}")
+    files.sort();
+    Ok(files)
 }
 
-pub fn delete_event(
-    storage_dir: &Path,
-    date: NaiveDate,
-    id: u32,
-) -> Result<Vec<Event>> {
-    let events = read_events(storage_dir, date)?;
-    #[allow(clippy::cast_possible_truncation)]
-    let events: Vec<Event> = events
-        .iter()
-        .enumerate()
-        .filter(|(event_id, _)| *event_id as u32 != id)
-        .map(|(_, event)| event.clone())
-        .collect();
-
-    let events_as_str: String = events
-        .iter()
-        .map(event_to_str)
-        .collect::<Vec<_>>()
-        .join("\n");
+/// Where the records of the month of `month` are kept that go alongside
+/// the events, e.g., expenses, in the subdirectory `subdir`
+fn sidecar_path(dir: &Path, subdir: &str, month: NaiveDate) -> PathBuf {
+    dir.join(subdir)
+        .join(get_month_file_name(&month, Format::Csv))
+}
 
-    let file_name = get_file_name(&date);
-    let file_path = storage_dir.join(file_name);
+/// Reads a file kept alongside the events, which is empty if it doesn't
+/// exist
+fn read_sidecar(file_path: &Path) -> Result<String> {
+    match read_if_exists(file_path)? {
+        Some(bytes) => unseal_bytes(file_path, bytes),
+        None => Ok(String::new()),
+    }
+}
 
-    write_to_file(&file_path, &events_as_str)?;
-    Ok(events)
+fn get_file_name<T: Datelike>(has_date: &T, format: Format) -> String {
+    format!(
+        "{}-{:0>2}-{:0>2}.{}",
+        has_date.year(),
+        has_date.month(),
+        has_date.day(),
+        format.extension(),
+    )
 }
 
-fn get_file_name<T: Datelike>(has_date: &T) -> String {
+fn get_month_file_name<T: Datelike>(has_date: &T, format: Format) -> String {
     format!(
-        "{}-{:0>2}-{:0>2}.csv",
+        "{}-{:0>2}.{}",
         has_date.year(),
         has_date.month(),
-        has_date.day()
+        format.extension(),
     )
 }
 
 fn write_to_file(file_path: &Path, content: &str) -> Result<()> {
     // atomic write, by writing to a temp file first then rename
-    let mut tmp_file = NamedTempFile::new()?;
-    tmp_file.write_all(content.as_bytes())?;
+    stage(file_path, content)?.persist(file_path)?;
+    sync_dir(file_path)
+}
 
-    fs::rename(tmp_file, file_path)?;
+/// Writes `content` to a temp file in the directory of `file_path`, so that
+/// it can be renamed to `file_path` without copying, even if the system's
+/// temp dir is on another file system. The temp file gets the permissions
+/// of the file it's going to replace.
+fn stage(file_path: &Path, content: &str) -> Result<NamedTempFile> {
+    let dir = parent_dir(file_path);
+    create_dir(dir)?;
+    let mut tmp_file = NamedTempFile::new_in(dir)?;
+    tmp_file.write_all(content.as_bytes())?;
+    match fs::metadata(file_path) {
+        Ok(metadata) => keep_owner(tmp_file.path(), &metadata)?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
 
     // Sync file in order to minimize the risk of data loss. There's an
     // interesting discussion here:
     // https://github.com/Stebalien/tempfile/issues/110
-    Ok(OpenOptions::new().write(true).open(file_path)?.sync_all()?)
+    tmp_file.as_file().sync_all()?;
+    Ok(tmp_file)
+}
+
+/// Gives the file at `path` the permissions and, if allowed, the owner from
+/// `metadata`
+fn keep_owner(path: &Path, metadata: &fs::Metadata) -> Result<()> {
+    fs::set_permissions(path, metadata.permissions())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // Only root may hand files to other users, so for everyone else
+        // this fails unless the owner stays the same anyway
+        let _ = std::os::unix::fs::chown(
+            path,
+            Some(metadata.uid()),
+            Some(metadata.gid()),
+        );
+    }
+    Ok(())
+}
+
+/// Syncs the directory of `file_path`, so that a file renamed into it or
+/// removed from it stays that way after a crash
+fn sync_dir(file_path: &Path) -> Result<()> {
+    // Directories can't be opened like this on Windows
+    if cfg!(unix) {
+        fs::File::open(parent_dir(file_path))?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Creates `dir` and any missing parents, accessible by the current user
+/// only, unless it exists already
+pub fn create_dir(dir: &Path) -> Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder
+        .create(dir)
+        .with_context(|| format!("Could not create {}", dir.display()))
 }
 
 #[cfg(test)]
 mod tests {
     use std::{fs::File, io::Write};
 
-    use chrono::Local;
+    use chrono::{Local, Months, TimeZone, Utc};
     use tempfile::tempdir;
 
     use super::*;
+    use crate::record::event_to_str;
+
+    #[test]
+    fn snapshots_tell_when_files_were_changed() {
+        let d = tempdir().unwrap();
+        let storage = DayFiles::new(d.path(), Format::Csv);
+        let before = Snapshot::take(d.path()).unwrap();
+        assert_eq!(before, Snapshot::default());
+        let event = Event::clock_in(&Utc::now());
+        create_event(&storage, &event, Duration::zero()).unwrap();
+        let after = Snapshot::take(d.path()).unwrap();
+        assert_ne!(after, before);
+        assert_eq!(Snapshot::take(d.path()).unwrap(), after);
+    }
 
     #[test]
     fn get_file_name_pads_month_and_day() {
         let date = NaiveDate::from_ymd_opt(2022, 1, 2).unwrap();
-        assert_eq!(get_file_name(&date), "2022-01-02.csv");
+        assert_eq!(get_file_name(&date, Format::Csv), "2022-01-02.csv");
     }
 
     #[test]
     fn create_read_delete_events() {
         // happy paths
         let d = tempdir().unwrap();
-        let dir = d.path();
-        let event1 = Event {
-            kind: EventKind::ClockIn,
-            dt: Local::now().to_utc(),
-        };
-        create_event(dir, &event1).unwrap();
+        let dir = &DayFiles::new(d.path(), Format::Csv);
+        let event1 = Event::clock_in(&Local::now().to_utc());
+        create_event(dir, &event1, Duration::zero()).unwrap();
 
         let expected_events = vec![event1.clone()];
         assert_eq!(
@@ -227,11 +1603,8 @@ mod tests {
             expected_events
         );
 
-        let event2 = Event {
-            kind: EventKind::ClockOut,
-            dt: Local::now().to_utc(),
-        };
-        create_event(dir, &event2).unwrap();
+        let event2 = Event::clock_out(&Local::now().to_utc());
+        create_event(dir, &event2, Duration::zero()).unwrap();
 
         let expected_events = vec![event1.clone(), event2.clone()];
         assert_eq!(
@@ -253,8 +1626,8 @@ mod tests {
         let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
 
         let d = tempdir().unwrap();
-        let dir = d.path();
-        let file_path = d.path().join(get_file_name(&date));
+        let dir = &DayFiles::new(d.path(), Format::Csv);
+        let file_path = d.path().join(get_file_name(&date, Format::Csv));
 
         let file_content = "clock-in,2020-01-31T08:15:00Z\n\
             clock-out,2020-01-31T16:15:00Z\n";
@@ -265,14 +1638,12 @@ mod tests {
 
         let actual = read_events(dir, date);
         let expected = vec![
-            Event {
-                kind: EventKind::ClockIn,
-                dt: Utc.with_ymd_and_hms(2020, 1, 31, 8, 15, 0).unwrap(),
-            },
-            Event {
-                kind: EventKind::ClockOut,
-                dt: Utc.with_ymd_and_hms(2020, 1, 31, 16, 15, 0).unwrap(),
-            },
+            Event::clock_in(
+                &Utc.with_ymd_and_hms(2020, 1, 31, 8, 15, 0).unwrap(),
+            ),
+            Event::clock_out(
+                &Utc.with_ymd_and_hms(2020, 1, 31, 16, 15, 0).unwrap(),
+            ),
         ];
         assert_eq!(actual.unwrap(), expected);
     }
@@ -282,7 +1653,7 @@ mod tests {
         let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
 
         let d = tempdir().unwrap();
-        let dir = d.path();
+        let dir = &DayFiles::new(d.path(), Format::Csv);
 
         let actual = read_events(dir, date).unwrap();
         assert!(actual.is_empty());
@@ -293,7 +1664,7 @@ mod tests {
         let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
 
         let d = tempdir().unwrap();
-        let dir = d.path();
+        let dir = &DayFiles::new(d.path(), Format::Csv);
         let file_path = d.path().join("2020-01-31.txt");
 
         let file_content = "\n";
@@ -305,4 +1676,663 @@ mod tests {
         let actual = read_events(dir, date).unwrap();
         assert!(actual.is_empty());
     }
+
+    #[test]
+    fn duplicates_are_found_within_the_window() {
+        let at = |m, s| Utc.with_ymd_and_hms(2024, 3, 1, 9, m, s).unwrap();
+        let events = [
+            Event::clock_in(&at(0, 0)),
+            Event::clock_out(&at(30, 0)),
+            Event::clock_in(&at(0, 20)),
+            Event::clock_in(&at(0, 0)).with_project(Some("web".to_string())),
+            Event::clock_in(&at(1, 10)),
+        ];
+        let (kept, duplicates) =
+            find_duplicates(&events, Duration::minutes(1));
+        assert_eq!(duplicates, [events[2].clone()]);
+        assert_eq!(
+            kept,
+            [
+                events[0].clone(),
+                events[3].clone(),
+                events[4].clone(),
+                events[1].clone()
+            ]
+        );
+    }
+
+    #[test]
+    fn create_event_skips_duplicates_within_window() {
+        let d = tempdir().unwrap();
+        let storage = DayFiles::new(d.path(), Format::Csv);
+        let dt = Utc.with_ymd_and_hms(2020, 1, 31, 8, 15, 0).unwrap();
+        let window = Duration::minutes(2);
+        let event = Event::clock_in(&dt);
+        create_event(&storage, &event, window).unwrap();
+
+        let again = Event::clock_in(&(dt + Duration::seconds(90)));
+        let created = create_event(&storage, &again, window).unwrap();
+        assert_eq!(created.duplicate_of, Some(event.clone()));
+        assert_eq!(created.events, vec![event.clone()]);
+
+        let later = Event::clock_in(&(dt + Duration::minutes(2)));
+        let created = create_event(&storage, &later, window).unwrap();
+        assert_eq!(created.duplicate_of, None);
+        assert_eq!(created.events, vec![event, later]);
+    }
+
+    #[test]
+    fn create_event_finds_the_clock_in_that_the_day_before_ended_with() {
+        let d = tempdir().unwrap();
+        let storage = DayFiles::new(d.path(), Format::Csv);
+        let at = |d, h| Utc.with_ymd_and_hms(2020, 1, d, h, 0, 0).unwrap();
+        let forgotten = Event::clock_in(&at(30, 9));
+        create_event(&storage, &forgotten, Duration::zero()).unwrap();
+
+        let morning = Event::clock_in(&at(31, 9));
+        let created =
+            create_event(&storage, &morning, Duration::zero()).unwrap();
+        assert_eq!(created.dangling, Some(forgotten));
+        let clock_out = Event::clock_out(&at(31, 12));
+        create_event(&storage, &clock_out, Duration::zero()).unwrap();
+        let again = Event::clock_in(&at(31, 13));
+        let created =
+            create_event(&storage, &again, Duration::zero()).unwrap();
+        assert_eq!(created.dangling, None);
+    }
+
+    #[test]
+    fn read_events_fails_for_unreadable_files() {
+        let d = tempdir().unwrap();
+        let storage = DayFiles::new(d.path(), Format::Csv);
+        // a directory where the day file should be can't be read as one
+        fs::create_dir(d.path().join("2020-01-31.csv")).unwrap();
+        let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
+        assert!(read_events(&storage, date).is_err());
+        let other_date = NaiveDate::from_ymd_opt(2020, 2, 1).unwrap();
+        assert!(read_events(&storage, other_date).unwrap().is_empty());
+    }
+
+    #[test]
+    fn jsonl_round_trip() {
+        let d = tempdir().unwrap();
+        let storage = DayFiles::new(d.path(), Format::Jsonl);
+        let event = Event::clock_in(
+            &Utc.with_ymd_and_hms(2020, 1, 31, 8, 15, 0).unwrap(),
+        );
+        create_event(&storage, &event, Duration::zero()).unwrap();
+
+        let file_path = d.path().join("2020-01-31.jsonl");
+        assert_eq!(
+            fs::read_to_string(file_path).unwrap(),
+            "# crc32 c5d51815\n\
+            {\"kind\":\"clock-in\",\"timestamp\":\"2020-01-31T08:15:00+00:00\"}\n"
+        );
+        let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
+        assert_eq!(read_events(&storage, date).unwrap(), vec![event]);
+    }
+
+    #[test]
+    fn migrate_converts_between_formats() {
+        let d = tempdir().unwrap();
+        let csv = DayFiles::new(d.path(), Format::Csv);
+        let jsonl = DayFiles::new(d.path(), Format::Jsonl);
+        let event = Event::clock_out(
+            &Utc.with_ymd_and_hms(2020, 1, 31, 16, 15, 0).unwrap(),
+        );
+        create_event(&csv, &event, Duration::zero()).unwrap();
+
+        assert_eq!(migrate(&csv, &jsonl).unwrap(), 1);
+        assert!(!d.path().join("2020-01-31.csv").exists());
+        assert!(d.path().join("2020-01-31.jsonl").exists());
+        let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
+        // either format can read the other's files
+        assert_eq!(read_events(&csv, date).unwrap(), vec![event]);
+    }
+
+    #[test]
+    fn month_files_keep_days_apart() {
+        let d = tempdir().unwrap();
+        let storage = MonthFiles::new(d.path(), Format::Csv);
+        let day1 = NaiveDate::from_ymd_opt(2020, 1, 30).unwrap();
+        let day2 = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
+        let event1 = Event::clock_in(
+            &Utc.with_ymd_and_hms(2020, 1, 30, 8, 15, 0).unwrap(),
+        );
+        let event2 = Event::clock_in(
+            &Utc.with_ymd_and_hms(2020, 1, 31, 9, 0, 0).unwrap(),
+        );
+        create_event(&storage, &event2, Duration::zero()).unwrap();
+        create_event(&storage, &event1, Duration::zero()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(d.path().join("2020-01.csv")).unwrap(),
+            "# crc32 8f5d4395\n\
+            2020-01-30,clock-in,2020-01-30T08:15:00+00:00\n\
+            2020-01-31,clock-in,2020-01-31T09:00:00+00:00\n"
+        );
+        assert_eq!(read_events(&storage, day1).unwrap(), vec![event1]);
+        assert_eq!(storage.days().unwrap(), vec![day1, day2]);
+
+        delete_event(&storage, day2, 0).unwrap();
+        assert_eq!(storage.days().unwrap(), vec![day1]);
+    }
+
+    #[test]
+    fn deleting_keeps_one_event_per_line() {
+        let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
+        let at = |hour| Utc.with_ymd_and_hms(2020, 1, 31, hour, 0, 0).unwrap();
+        let events = [
+            Event::clock_out(&at(12)),
+            Event::clock_in(&at(8)),
+            Event::clock_out(&at(17)),
+            Event::clock_in(&at(13)),
+        ];
+        for layout in [Layout::Day, Layout::Month] {
+            for format in [Format::Csv, Format::Jsonl] {
+                let d = tempdir().unwrap();
+                let storage = open(d.path(), format, layout);
+                // written out of order, as an edit might
+                storage.write_day(date, &events).unwrap();
+                let remaining =
+                    delete_event(storage.as_ref(), date, 1).unwrap();
+                assert_eq!(
+                    remaining,
+                    [events[1].clone(), events[3].clone(), events[2].clone()]
+                );
+                assert_eq!(
+                    read_events(storage.as_ref(), date).unwrap(),
+                    remaining
+                );
+
+                let file = data_files(d.path()).unwrap().pop().unwrap();
+                let content = fs::read_to_string(file).unwrap();
+                assert!(content.ends_with('\n'), "{content}");
+                assert_eq!(content.lines().count(), 4, "{content}");
+            }
+        }
+    }
+
+    #[test]
+    fn the_latest_day_is_read_from_the_end_of_a_month_file() {
+        let d = tempdir().unwrap();
+        let storage = open(d.path(), Format::Jsonl, Layout::Month);
+        let at = |day, minute| {
+            Utc.with_ymd_and_hms(2020, 1, day, 8, minute, 0).unwrap()
+        };
+        let days: Vec<_> = (1..=31)
+            .map(|day| {
+                let events = (0..20).map(|m| Event::clock_in(&at(day, m)));
+                (at(day, 0).date_naive(), events.collect())
+            })
+            .collect();
+        storage.write_days(&days).unwrap();
+        let file = data_files(d.path()).unwrap().pop().unwrap();
+        assert!(fs::metadata(file).unwrap().len() > TAIL_BYTES);
+
+        for (date, events) in [&days[30], &days[0]] {
+            let latest =
+                read_latest_day(d.path(), Format::Jsonl, Layout::Month, *date)
+                    .unwrap();
+            assert_eq!(&latest, events);
+        }
+    }
+
+    #[test]
+    fn migrate_converts_month_files_between_formats() {
+        let d = tempdir().unwrap();
+        let csv = MonthFiles::new(d.path(), Format::Csv);
+        let jsonl = MonthFiles::new(d.path(), Format::Jsonl);
+        let at = |m, d, h| Utc.with_ymd_and_hms(2020, m, d, h, 0, 0).unwrap();
+        let events = [
+            Event::clock_in(&at(1, 30, 8)),
+            Event::clock_out(&at(1, 30, 16)),
+            Event::clock_in(&at(1, 31, 9)),
+            Event::clock_in(&at(2, 3, 9)),
+        ];
+        create_events(&csv, &events).unwrap();
+        let all = |storage: &dyn Storage| -> Vec<Event> {
+            let days = storage.days().unwrap();
+            days.into_iter()
+                .flat_map(|date| read_events(storage, date).unwrap())
+                .collect()
+        };
+
+        assert_eq!(migrate(&csv, &jsonl).unwrap(), 3);
+        assert!(!d.path().join("2020-01.csv").exists());
+        assert!(!d.path().join("2020-02.csv").exists());
+        assert!(d.path().join("2020-01.jsonl").exists());
+        assert_eq!(all(&jsonl), events);
+
+        assert_eq!(migrate(&jsonl, &csv).unwrap(), 3);
+        assert!(!d.path().join("2020-01.jsonl").exists());
+        assert!(d.path().join("2020-02.csv").exists());
+        assert_eq!(all(&csv), events);
+    }
+
+    #[test]
+    fn migrate_converts_between_layouts() {
+        let d = tempdir().unwrap();
+        let days = DayFiles::new(d.path(), Format::Csv);
+        let months = MonthFiles::new(d.path(), Format::Jsonl);
+        let event = Event::clock_in(
+            &Utc.with_ymd_and_hms(2020, 1, 31, 8, 15, 0).unwrap(),
+        );
+        create_event(&days, &event, Duration::zero()).unwrap();
+
+        assert_eq!(migrate(&days, &months).unwrap(), 1);
+        assert!(!d.path().join("2020-01-31.csv").exists());
+        let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
+        assert_eq!(read_events(&months, date).unwrap(), vec![event.clone()]);
+
+        assert_eq!(migrate(&months, &days).unwrap(), 1);
+        assert!(!d.path().join("2020-01.jsonl").exists());
+        assert_eq!(read_events(&days, date).unwrap(), vec![event]);
+    }
+
+    #[test]
+    fn read_detects_truncated_file() {
+        let d = tempdir().unwrap();
+        let storage = DayFiles::new(d.path(), Format::Csv);
+        let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
+        let events = [
+            Event::clock_in(
+                &Utc.with_ymd_and_hms(2020, 1, 31, 8, 15, 0).unwrap(),
+            ),
+            Event::clock_out(
+                &Utc.with_ymd_and_hms(2020, 1, 31, 16, 15, 0).unwrap(),
+            ),
+        ];
+        storage.write_day(date, &events).unwrap();
+
+        let file_path = d.path().join("2020-01-31.csv");
+        let content = fs::read_to_string(&file_path).unwrap();
+        let truncated = &content[..content.trim_end().rfind('\n').unwrap()];
+        fs::write(&file_path, truncated).unwrap();
+
+        let err = read_events(&storage, date).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(PersistenceError::CorruptedFile { .. })
+        ));
+
+        reseal_file(&file_path).unwrap();
+        verify_file(&file_path).unwrap();
+        assert_eq!(read_events(&storage, date).unwrap(), events[..1]);
+    }
+
+    #[test]
+    fn dry_run_does_not_write() {
+        let d = tempdir().unwrap();
+        let storage = DayFiles::new(d.path(), Format::Csv);
+        let dry_run = DryRun::new(&storage);
+        let event = Event::clock_in(
+            &Utc.with_ymd_and_hms(2020, 1, 31, 8, 15, 0).unwrap(),
+        );
+        create_event(&dry_run, &event, Duration::zero()).unwrap();
+
+        let file_path = d.path().join("2020-01-31.csv");
+        assert!(!file_path.exists());
+        assert_eq!(
+            dry_run.changes(),
+            vec![FileChange {
+                path: file_path,
+                old: None,
+                new: Some(seal("clock-in,2020-01-31T08:15:00+00:00\n")),
+            }]
+        );
+    }
+
+    /// Fails to write the records of one day
+    struct FailingOn<'a> {
+        inner: &'a dyn Storage,
+        date: NaiveDate,
+    }
+
+    impl Storage for FailingOn<'_> {
+        fn read_day(&self, date: NaiveDate) -> Result<Vec<Event>> {
+            self.inner.read_day(date)
+        }
+
+        fn plan_write(
+            &self,
+            date: NaiveDate,
+            events: &[Event],
+        ) -> Result<Vec<FileChange>> {
+            if date == self.date {
+                return Err(anyhow::anyhow!("disk full"));
+            }
+            self.inner.plan_write(date, events)
+        }
+
+        fn plan_remove(&self, date: NaiveDate) -> Result<Vec<FileChange>> {
+            self.inner.plan_remove(date)
+        }
+
+        fn days(&self) -> Result<Vec<NaiveDate>> {
+            self.inner.days()
+        }
+
+        fn archived_days(&self) -> Result<Vec<NaiveDate>> {
+            self.inner.archived_days()
+        }
+
+        fn plan_remove_all(&self) -> Result<Vec<FileChange>> {
+            self.inner.plan_remove_all()
+        }
+    }
+
+    #[test]
+    fn create_events_stores_all_or_nothing() {
+        let d = tempdir().unwrap();
+        let dir = &DayFiles::new(d.path(), Format::Csv);
+        let at = |day, hour| Utc.with_ymd_and_hms(2024, 5, day, hour, 0, 0);
+        let existing = Event::clock_in(&at(1, 7).unwrap());
+        dir.write_day(
+            existing.dt.date_naive(),
+            std::slice::from_ref(&existing),
+        )
+        .unwrap();
+        let new = [
+            Event::clock_out(&at(1, 15).unwrap()),
+            Event::clock_in(&at(2, 7).unwrap()),
+            Event::clock_out(&at(2, 15).unwrap()),
+        ];
+
+        let failing = FailingOn {
+            inner: dir,
+            date: NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(),
+        };
+        assert!(create_events(&failing, &new).is_err());
+        assert_eq!(dir.days().unwrap(), vec![existing.dt.date_naive()]);
+        assert_eq!(
+            dir.read_day(existing.dt.date_naive()).unwrap(),
+            vec![existing.clone()]
+        );
+
+        create_events(dir, &new).unwrap();
+        assert_eq!(dir.days().unwrap().len(), 2);
+        assert_eq!(dir.read_day(existing.dt.date_naive()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn apply_restores_files_when_a_change_fails() {
+        let d = tempdir().unwrap();
+        let existing = d.path().join("existing.csv");
+        let added = d.path().join("added.csv");
+        let blocked = d.path().join("blocked");
+        fs::write(&existing, "old").unwrap();
+        fs::create_dir(&blocked).unwrap();
+        fs::write(blocked.join("inner"), "").unwrap();
+        let change = |path: &Path, new: &str| {
+            FileChange::new(path.to_path_buf(), Some(new.to_string())).unwrap()
+        };
+
+        let changes = [
+            change(&existing, "new"),
+            change(&added, "new"),
+            // renaming a file onto a non-empty directory fails
+            FileChange {
+                path: blocked.clone(),
+                old: None,
+                new: Some("new".to_string()),
+            },
+        ];
+        assert!(apply(&changes).is_err());
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "old");
+        assert!(!added.exists());
+        assert_eq!(fs::read_dir(d.path()).unwrap().count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_to_file_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let d = tempdir().unwrap();
+        let file_path = d.path().join("2024-05-01.csv");
+        fs::write(&file_path, "old").unwrap();
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o640))
+            .unwrap();
+
+        write_to_file(&file_path, "new").unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "new");
+        let mode = fs::metadata(&file_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+
+    #[test]
+    fn events_before_later_records_hint_at_clock_skew() {
+        let d = tempdir().unwrap();
+        let storage = DayFiles::new(d.path(), Format::Csv);
+        let at = |hour| Utc.with_ymd_and_hms(2024, 5, 1, hour, 0, 0).unwrap();
+        create_event(&storage, &Event::clock_in(&at(9)), Duration::zero())
+            .unwrap();
+
+        let now = at(8);
+        assert!(check_clock(&storage, &Event::clock_out(&now), now).is_err());
+        // a time given on purpose
+        assert!(check_clock(&storage, &Event::clock_out(&at(7)), now).is_ok());
+        let now = at(10);
+        assert!(check_clock(&storage, &Event::clock_out(&now), now).is_ok());
+    }
+
+    #[test]
+    fn day_notes_are_kept_next_to_the_records() {
+        let d = tempdir().unwrap();
+        let date = |day| NaiveDate::from_ymd_opt(2024, 5, day).unwrap();
+        let note = |day, text: &str| DayNote {
+            date: date(day),
+            text: text.to_string(),
+        };
+        for added in [note(3, "sick, afternoon"), note(1, "release 3.2")] {
+            apply(&[plan_add_day_note(d.path(), &added).unwrap()]).unwrap();
+        }
+        assert_eq!(
+            read_day_notes(d.path(), date(20)).unwrap(),
+            [note(1, "release 3.2"), note(3, "sick, afternoon")]
+        );
+        assert!(read_day_notes(d.path(), date(1) + Months::new(1))
+            .unwrap()
+            .is_empty());
+        assert_eq!(read_all_day_notes(d.path()).unwrap().len(), 2);
+        assert!(DayFiles::new(d.path(), Format::Csv)
+            .days()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn deleted_events_can_be_restored_from_the_trash() {
+        let d = tempdir().unwrap();
+        let at = |m, d| Utc.with_ymd_and_hms(2024, m, d, 9, 0, 0).unwrap();
+        let (april, may) = (at(4, 30).date_naive(), at(5, 2).date_naive());
+        apply(&[
+            plan_trash(d.path(), may, &[Event::clock_in(&at(5, 2))]).unwrap()
+        ])
+        .unwrap();
+        let ended =
+            [Event::clock_in(&at(4, 30)), Event::clock_out(&at(4, 30))];
+        apply(&[plan_trash(d.path(), april, &ended).unwrap()]).unwrap();
+        let trash = read_trash(d.path()).unwrap();
+        let ids: Vec<_> = trash.iter().map(|t| (t.id, t.date)).collect();
+        assert_eq!(ids, [(1, april), (2, april), (0, may)]);
+
+        // the other IDs stay as they are
+        let (restored, change) = plan_untrash(d.path(), 1).unwrap();
+        assert_eq!(restored.event, ended[0]);
+        apply(&[change]).unwrap();
+        let trash = read_trash(d.path()).unwrap();
+        let ids: Vec<_> = trash.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [2, 0]);
+        let (restored, change) = plan_untrash(d.path(), 2).unwrap();
+        assert_eq!(restored.event, ended[1]);
+        assert_eq!(change.new, None);
+        apply(&[change]).unwrap();
+        assert!(plan_untrash(d.path(), 1).is_err());
+
+        let (restored, change) = plan_untrash(d.path(), 0).unwrap();
+        assert_eq!(restored.date, may);
+        apply(&[change]).unwrap();
+        assert!(read_trash(d.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn writing_creates_the_storage_dir() {
+        let d = tempdir().unwrap();
+        let dir = DayFiles::new(&d.path().join("fresh"), Format::Csv);
+        let event = Event::clock_in(
+            &Utc.with_ymd_and_hms(2024, 5, 1, 7, 0, 0).unwrap(),
+        );
+        create_event(&dir, &event, Duration::zero()).unwrap();
+        assert_eq!(dir.read_day(event.dt.date_naive()).unwrap(), vec![event]);
+    }
+
+    #[test]
+    fn day_files_are_found_whatever_the_case_of_their_name() {
+        let d = tempdir().unwrap();
+        let dir = DayFiles::new(d.path(), Format::Csv);
+        let event = Event::clock_in(
+            &Utc.with_ymd_and_hms(2024, 5, 1, 7, 0, 0).unwrap(),
+        );
+        let date = event.dt.date_naive();
+        fs::write(
+            d.path().join("2024-05-01.CSV"),
+            event_to_str(&event, Format::Csv),
+        )
+        .unwrap();
+        assert_eq!(dir.days().unwrap(), vec![date]);
+        assert_eq!(dir.read_day(date).unwrap(), vec![event.clone()]);
+
+        let events = [event.clone(), event.clone()];
+        dir.write_day(date, &events).unwrap();
+        assert_eq!(dir.read_day(date).unwrap(), events);
+        let names: Vec<_> = fs::read_dir(d.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["2024-05-01.csv"]);
+    }
+
+    #[test]
+    fn ids_stay_what_delete_event_takes() {
+        let d = tempdir().unwrap();
+        let dir = &DayFiles::new(d.path(), Format::Csv);
+        let at = |hour| Utc.with_ymd_and_hms(2024, 5, 1, hour, 0, 0).unwrap();
+        let date = at(0).date_naive();
+        let events = [
+            Event::clock_in(&at(7)),
+            Event::clock_out(&at(12)),
+            Event::clock_in(&at(13)),
+            Event::clock_out(&at(16)),
+        ];
+        // stored out of order, and numbered by time when read back
+        for event in events.iter().rev() {
+            create_event(dir, event, Duration::zero()).unwrap();
+        }
+        let stored = read_stored_events(dir, date).unwrap();
+        assert_eq!(stored, StoredEvent::number(&events));
+
+        let remaining = delete_event(dir, date, stored[1].id).unwrap();
+        let stored = read_stored_events(dir, date).unwrap();
+        assert_eq!(stored, StoredEvent::number(&remaining));
+        assert_eq!(stored[1].event, events[2]);
+
+        delete_event(dir, date, stored[2].id).unwrap();
+        assert_eq!(
+            read_events(dir, date).unwrap(),
+            [events[0].clone(), events[2].clone()]
+        );
+        let err = delete_event(dir, date, 2).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(PersistenceError::EventNotFoundError { id: 2 })
+        ));
+        assert_eq!(read_events(dir, date).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn events_in_a_window_are_deleted_together() {
+        let d = tempdir().unwrap();
+        let dir = &DayFiles::new(d.path(), Format::Csv);
+        let at = |hour| Utc.with_ymd_and_hms(2024, 5, 1, hour, 0, 0).unwrap();
+        let local = |hour| at(hour).with_timezone(&Local).time();
+        let date = at(0).date_naive();
+        let events = [
+            Event::clock_in(&at(9)),
+            Event::clock_out(&at(10)),
+            Event::clock_in(&at(11)),
+            Event::clock_out(&at(12)),
+        ];
+        create_events(dir, &events).unwrap();
+
+        let window =
+            read_events_between(dir, date, local(10), local(11)).unwrap();
+        let ids: Vec<u32> = window.iter().map(|stored| stored.id).collect();
+        assert_eq!(ids, [1, 2]);
+        assert!(read_events_between(dir, date, local(11), local(10)).is_err());
+
+        assert!(delete_events(dir, date, &[1, 7]).is_err());
+        assert_eq!(read_events(dir, date).unwrap(), events);
+        let remaining = delete_events(dir, date, &ids).unwrap();
+        assert_eq!(remaining, [events[0].clone(), events[3].clone()]);
+        assert_eq!(read_events(dir, date).unwrap(), remaining);
+    }
+
+    #[test]
+    fn write_days_writes_several_days_of_a_month_file() {
+        let d = tempdir().unwrap();
+        let month = MonthFiles::new(d.path(), Format::Csv);
+        let at = |day, hour| Utc.with_ymd_and_hms(2024, 5, day, hour, 0, 0);
+        let days: Vec<(NaiveDate, Vec<Event>)> = [1, 2, 3]
+            .into_iter()
+            .map(|day| {
+                let event = Event::clock_in(&at(day, 7).unwrap());
+                (event.dt.date_naive(), vec![event])
+            })
+            .collect();
+        month.write_days(&days).unwrap();
+        for (date, events) in &days {
+            assert_eq!(&month.read_day(*date).unwrap(), events);
+        }
+    }
+
+    #[test]
+    fn batches_are_written_when_committed() {
+        let d = tempdir().unwrap();
+        let storage = open(d.path(), Format::Csv, Layout::Month);
+        let at = |hour| Utc.with_ymd_and_hms(2024, 5, 2, hour, 0, 0).unwrap();
+        let date = at(0).date_naive();
+        let batch = Batch::new(storage.as_ref());
+        create_event(&batch, &Event::clock_in(&at(8)), Duration::zero())
+            .unwrap();
+        let both =
+            create_event(&batch, &Event::clock_out(&at(12)), Duration::zero())
+                .unwrap()
+                .events;
+        assert_eq!(both.len(), 2);
+        assert_eq!(batch.days().unwrap(), [date]);
+        assert!(data_files(d.path()).unwrap().is_empty());
+
+        // the trash gets the deleted events only along with the deletion
+        let kept = delete_event(&batch, date, 0).unwrap();
+        batch.trash(date, &both[..1]);
+        assert!(read_trash(d.path()).unwrap().is_empty());
+
+        batch.commit(d.path()).unwrap();
+        assert_eq!(storage.read_day(date).unwrap(), kept);
+        let trash = read_trash(d.path()).unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].event, both[0]);
+    }
+
+    #[test]
+    fn the_lock_keeps_others_waiting() {
+        let d = tempdir().unwrap();
+        let lock = lock(d.path()).unwrap();
+        let other = fs::File::open(d.path().join(LOCK_FILE)).unwrap();
+        assert!(other.try_lock().is_err());
+        drop(lock);
+        other.try_lock().unwrap();
+    }
 }
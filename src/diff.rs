@@ -0,0 +1,99 @@
+use std::fmt::Write;
+
+use crate::data::FileChange;
+
+/// A line of a diff
+#[derive(Debug, PartialEq, Eq)]
+pub enum Line<'a> {
+    Kept(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Computes a line based diff of two texts using their longest common
+/// subsequence. The files this application deals with are small, so the
+/// quadratic effort doesn't matter.
+pub fn lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old: Vec<_> = old.lines().collect();
+    let new: Vec<_> = new.lines().collect();
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..]
+    // and new[j..]
+    let mut lcs = vec![vec![0_usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            result.push(Line::Kept(old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len()
+            && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1])
+        {
+            result.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            result.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    result
+}
+
+/// Renders a file change in the style of `diff -u`, without hunk headers
+pub fn render(change: &FileChange) -> String {
+    let path = change.path.display();
+    let mut result = String::new();
+    let old_label = if change.old.is_some() {
+        path.to_string()
+    } else {
+        "/dev/null".to_string()
+    };
+    let new_label = if change.new.is_some() {
+        path.to_string()
+    } else {
+        "/dev/null".to_string()
+    };
+    // writing to a String cannot fail
+    let _ = writeln!(result, "--- {old_label}\n+++ {new_label}");
+    let old = change.old.as_deref().unwrap_or_default();
+    let new = change.new.as_deref().unwrap_or_default();
+    for line in lines(old, new) {
+        let _ = match line {
+            Line::Kept(l) => writeln!(result, " {l}"),
+            Line::Removed(l) => writeln!(result, "-{l}"),
+            Line::Added(l) => writeln!(result, "+{l}"),
+        };
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_marks_added_and_removed_lines() {
+        let old = "a\nb\nc";
+        let new = "a\nc\nd";
+        assert_eq!(
+            lines(old, new),
+            vec![
+                Line::Kept("a"),
+                Line::Removed("b"),
+                Line::Kept("c"),
+                Line::Added("d"),
+            ]
+        );
+    }
+}
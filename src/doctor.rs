@@ -0,0 +1,177 @@
+use std::{fmt::Display, path::Path};
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+
+use crate::{
+    constraints::Constraints,
+    data::{data_files, reseal_file, verify_file, PersistenceError, Storage},
+    interval::auto_clock_out,
+};
+
+/// A problem with the stored records
+#[derive(Debug)]
+pub struct Finding {
+    /// The file or day the problem was found in
+    pub location: String,
+    pub problem: String,
+    /// Whether the problem has been repaired
+    pub fixed: bool,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.location, self.problem)?;
+        if self.fixed {
+            write!(f, " (fixed)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks all data files in `storage_dir`, and, if `fix` is set, repairs
+/// what can be repaired
+pub fn check(storage_dir: &Path, fix: bool) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+    for file in data_files(storage_dir)? {
+        match verify_file(&file) {
+            Ok(()) => {}
+            Err(PersistenceError::CorruptedFile { .. }) => {
+                let mut finding = Finding {
+                    location: file.display().to_string(),
+                    problem: "content does not match its checksum".to_string(),
+                    fixed: false,
+                };
+                if fix {
+                    match reseal_file(&file) {
+                        Ok(()) => finding.fixed = true,
+                        Err(err) => {
+                            finding.problem =
+                                format!("{}; {err:#}", finding.problem);
+                        }
+                    }
+                }
+                findings.push(finding);
+            }
+            Err(err) => findings.push(Finding {
+                location: file.display().to_string(),
+                problem: err.to_string(),
+                fixed: false,
+            }),
+        }
+    }
+    Ok(findings)
+}
+
+/// Finds past days that end with a clock-in. If `fix` is set and an
+/// `auto_clockout` time is configured, they're closed at that time.
+pub fn check_days(
+    storage: &dyn Storage,
+    auto_clockout: Option<NaiveTime>,
+    now: DateTime<Utc>,
+    fix: bool,
+) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+    // without a cutoff, days are considered over at midnight
+    let end_of_day = NaiveTime::from_hms_opt(23, 59, 59).unwrap();
+    let cutoff = auto_clockout.unwrap_or(end_of_day);
+    for date in storage.days()? {
+        let mut events = storage.read_day(date)?;
+        let Some(clock_out) = auto_clock_out(date, &events, cutoff, now)
+        else {
+            continue;
+        };
+        let mut finding = Finding {
+            location: date.to_string(),
+            problem: "ends with a clock-in".to_string(),
+            fixed: false,
+        };
+        if fix && auto_clockout.is_some() {
+            events.push(clock_out);
+            storage.write_day(date, &events)?;
+            finding.fixed = true;
+        } else if auto_clockout.is_none() {
+            finding.problem.push_str(
+                "; set auto_clockout in your config file to close such days",
+            );
+        }
+        findings.push(finding);
+    }
+    Ok(findings)
+}
+
+/// Finds past days that violate the configured core hours, allowed hours
+/// or maximum daily hours
+pub fn check_constraints(
+    storage: &dyn Storage,
+    constraints: &Constraints,
+    today: NaiveDate,
+) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+    for date in storage.days()?.into_iter().filter(|date| *date < today) {
+        let events = storage.read_day(date)?;
+        for problem in constraints.violations(date, &events) {
+            findings.push(Finding {
+                location: date.to_string(),
+                problem,
+                fixed: false,
+            });
+        }
+    }
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::{Duration, TimeZone};
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{
+        data::{create_event, DayFiles, Event, Format},
+        interval::AUTO_TAG,
+    };
+
+    #[test]
+    fn check_fixes_corrupted_files() {
+        let d = tempdir().unwrap();
+        let file_path = d.path().join("2020-01-31.csv");
+        fs::write(
+            &file_path,
+            "# crc32 00000000\nclock-in,2020-01-31T08:15:00+00:00",
+        )
+        .unwrap();
+
+        let findings = check(d.path(), false).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert!(!findings[0].fixed);
+
+        let findings = check(d.path(), true).unwrap();
+        assert!(findings[0].fixed);
+        assert!(check(d.path(), false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn check_days_closes_dangling_clock_ins() {
+        let d = tempdir().unwrap();
+        let storage = DayFiles::new(d.path(), Format::Csv);
+        let dt = Utc.with_ymd_and_hms(2020, 1, 31, 8, 15, 0).unwrap();
+        create_event(&storage, &Event::clock_in(&dt), Duration::zero())
+            .unwrap();
+        let now = Utc.with_ymd_and_hms(2020, 2, 3, 8, 0, 0).unwrap();
+        let cutoff = NaiveTime::from_hms_opt(20, 0, 0);
+
+        let findings = check_days(&storage, None, now, true).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert!(!findings[0].fixed);
+
+        let findings = check_days(&storage, cutoff, now, true).unwrap();
+        assert!(findings[0].fixed);
+        let date = dt.date_naive();
+        let events = storage.read_day(date).unwrap();
+        assert_eq!(events[1].tags, [AUTO_TAG]);
+        assert!(check_days(&storage, cutoff, now, true).unwrap().is_empty());
+    }
+}
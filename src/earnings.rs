@@ -0,0 +1,131 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::{Duration, NaiveDate};
+
+use crate::{i18n, interval::Interval};
+
+/// What the time spent on a project is worth
+#[derive(Debug, PartialEq, Clone)]
+pub struct ProjectEarnings {
+    pub project: Option<String>,
+    pub worked: Duration,
+    /// The hourly rate, if one is configured for the project
+    pub rate: Option<f64>,
+}
+
+impl ProjectEarnings {
+    pub fn amount(&self) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        let hours = self.worked.num_seconds() as f64 / 3600.0;
+        self.rate.map_or(0.0, |rate| rate * hours)
+    }
+}
+
+/// The earnings of a month, split by project
+#[derive(Debug, PartialEq, Clone)]
+pub struct Earnings {
+    pub month: NaiveDate,
+    pub projects: Vec<ProjectEarnings>,
+    pub currency: Option<String>,
+}
+
+impl Earnings {
+    /// Sums up the intervals per project and applies the `rates`
+    pub fn compute(
+        month: NaiveDate,
+        intervals: &[Interval],
+        rates: &BTreeMap<String, f64>,
+        currency: Option<String>,
+    ) -> Earnings {
+        let mut worked: BTreeMap<Option<String>, Duration> = BTreeMap::new();
+        for interval in intervals {
+            *worked.entry(interval.project.clone()).or_default() +=
+                interval.duration();
+        }
+        let projects = worked
+            .into_iter()
+            .map(|(project, worked)| ProjectEarnings {
+                rate: project.as_ref().and_then(|p| rates.get(p)).copied(),
+                project,
+                worked,
+            })
+            .collect();
+        Earnings {
+            month,
+            projects,
+            currency,
+        }
+    }
+
+    pub fn total(&self) -> f64 {
+        self.projects.iter().map(ProjectEarnings::amount).sum()
+    }
+}
+
+impl Display for Earnings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let currency = self
+            .currency
+            .as_ref()
+            .map_or_else(String::new, |c| format!(" {c}"));
+        writeln!(f, "Earnings for {}:", self.month.format("%B %Y"))?;
+        let names: Vec<_> = self
+            .projects
+            .iter()
+            .map(|p| p.project.as_deref().unwrap_or("(no project)"))
+            .collect();
+        let width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+        for (name, project) in names.iter().zip(&self.projects) {
+            let worked = i18n::duration(&project.worked);
+            write!(f, "{name:<width$} | {worked} | ")?;
+            match project.rate {
+                Some(rate) => writeln!(
+                    f,
+                    "{rate:.2}/h | {:.2}{currency}",
+                    project.amount()
+                )?,
+                None => writeln!(f, "no rate configured")?,
+            }
+        }
+        writeln!(f, "Total: {:.2}{currency}", self.total())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    #[test]
+    fn earnings_are_split_by_project() {
+        let interval = |project: Option<&str>, hours: u32| Interval {
+            start: Utc.with_ymd_and_hms(2024, 3, 1, 8, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 3, 1, 8 + hours, 30, 0).unwrap(),
+            project: project.map(String::from),
+            tags: Vec::new(),
+        };
+        let intervals = [
+            interval(Some("web"), 1),
+            interval(None, 0),
+            interval(Some("web"), 2),
+        ];
+        let rates = BTreeMap::from([("web".to_string(), 90.0)]);
+        let month = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+
+        let earnings = Earnings::compute(
+            month,
+            &intervals,
+            &rates,
+            Some("EUR".to_string()),
+        );
+        assert!((earnings.total() - 360.0).abs() < 1e-9);
+        assert_eq!(
+            earnings.to_string(),
+            "Earnings for March 2024:\n\
+            (no project) | 00:30 | no rate configured\n\
+            web          | 04:00 | 90.00/h | 360.00 EUR\n\
+            Total: 360.00 EUR\n"
+        );
+    }
+}
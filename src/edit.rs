@@ -0,0 +1,79 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Local, NaiveDate};
+
+use crate::record::{parse_events, Event, EventKind};
+
+/// Parses the content of a hand-edited day, sorts the events and checks
+/// them with [`validate`]
+pub fn normalize(date: NaiveDate, content: &str) -> Result<Vec<Event>> {
+    let mut events = parse_events(content)?;
+    events.sort_by_key(|event| event.dt);
+    validate(date, &events)?;
+    Ok(events)
+}
+
+/// Checks that the `events`, sorted by time, belong to the day and that
+/// clock-ins and clock-outs, as well as the starts and ends of standby,
+/// alternate
+pub fn validate(date: NaiveDate, events: &[Event]) -> Result<()> {
+    if let Some(event) = events.iter().find(|e| e.dt.date_naive() != date) {
+        bail!("{} does not belong to {date}", event.dt.to_rfc3339())
+    }
+    // standby is tracked independently of clock events, and interruptions
+    // need no counterpart
+    for in_category in [EventKind::is_clock, EventKind::is_standby] {
+        let category: Vec<&Event> = events
+            .iter()
+            .filter(|event| in_category(&event.kind))
+            .collect();
+        for pair in category.windows(2) {
+            if pair[0].kind == pair[1].kind {
+                let local_time: DateTime<Local> = DateTime::from(pair[1].dt);
+                let (what, kind) = match pair[1].kind {
+                    EventKind::ClockIn => {
+                        ("Clock-ins and clock-outs", "clock-ins")
+                    }
+                    EventKind::ClockOut => {
+                        ("Clock-ins and clock-outs", "clock-outs")
+                    }
+                    EventKind::StandbyStart => {
+                        ("Standby starts and ends", "starts")
+                    }
+                    EventKind::StandbyEnd => {
+                        ("Standby starts and ends", "ends")
+                    }
+                    EventKind::Interruption { .. } => {
+                        unreachable!("interruptions are not in a category")
+                    }
+                };
+                bail!(
+                    "{what} must alternate, but there are two {kind} in a \
+                    row at {}",
+                    local_time.format("%H:%M")
+                )
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_sorts_and_checks_alternation() {
+        let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
+        let content = "clock-out,2020-01-31T16:00:00+00:00\n\
+            \n\
+            clock-in,2020-01-31T08:00:00+00:00\n";
+        let events = normalize(date, content).unwrap();
+        assert_eq!(events[0].dt.to_rfc3339(), "2020-01-31T08:00:00+00:00");
+
+        let twice = "clock-in,2020-01-31T08:00:00+00:00\n\
+            clock-in,2020-01-31T09:00:00+00:00\n";
+        assert!(normalize(date, twice).is_err());
+        let other_day = "clock-in,2020-02-01T08:00:00+00:00\n";
+        assert!(normalize(date, other_day).is_err());
+    }
+}
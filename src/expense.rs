@@ -0,0 +1,99 @@
+//! Expenses, e.g., for parking or train tickets, which are handed in
+//! together with the hours and stored next to them, one file per month
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::NaiveDate;
+
+use crate::csv;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Expense {
+    pub date: NaiveDate,
+    /// The amount in cents, or whatever the hundredth of the currency is
+    pub cents: i64,
+    pub description: String,
+}
+
+impl Expense {
+    /// Reads a line like `2024-03-01,12.50,parking`
+    pub fn parse(line: &str) -> Result<Expense> {
+        let cols = csv::split(line)?;
+        let [date, amount, description] = cols.as_slice() else {
+            bail!("Misformatted expense: {line}");
+        };
+        Ok(Expense {
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .with_context(|| format!("Invalid date in expense: {line}"))?,
+            cents: parse_amount(amount).map_err(|err| anyhow!(err))?,
+            description: description.clone(),
+        })
+    }
+
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{}",
+            self.date,
+            format_amount(self.cents),
+            csv::escape(&self.description)
+        )
+    }
+}
+
+/// Parses an amount with up to two decimals, e.g., `12.50` or `12,5`
+pub fn parse_amount(s: &str) -> Result<i64, String> {
+    let invalid = || format!("Invalid amount '{s}', try e.g. 12.50");
+    let (units, decimals) =
+        s.trim().split_once(['.', ',']).unwrap_or((s.trim(), "0"));
+    let (sign, units) = match units.strip_prefix('-') {
+        Some(units) => (-1, units),
+        None => (1, units),
+    };
+    let all_digits =
+        |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !all_digits(units) || !all_digits(decimals) || decimals.len() > 2 {
+        return Err(invalid());
+    }
+    let units: i64 = units.parse().map_err(|_| invalid())?;
+    let mut cents: i64 = decimals.parse().map_err(|_| invalid())?;
+    if decimals.len() == 1 {
+        cents *= 10;
+    }
+    units
+        .checked_mul(100)
+        .and_then(|units| units.checked_add(cents))
+        .map(|amount| sign * amount)
+        .ok_or_else(invalid)
+}
+
+/// Writes an amount with two decimals, e.g., `12.50`
+pub fn format_amount(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let cents = cents.unsigned_abs();
+    format!("{sign}{}.{:02}", cents / 100, cents % 100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts_are_read_and_written_with_two_decimals() {
+        assert_eq!(parse_amount("12.50"), Ok(1250));
+        assert_eq!(parse_amount("12,5"), Ok(1250));
+        assert_eq!(parse_amount("7"), Ok(700));
+        assert_eq!(parse_amount("-0.05"), Ok(-5));
+        assert!(parse_amount("1.234").is_err());
+        assert!(parse_amount("twelve").is_err());
+        assert_eq!(format_amount(1250), "12.50");
+        assert_eq!(format_amount(-5), "-0.05");
+
+        let expense = Expense {
+            date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            cents: 1250,
+            description: "parking, airport".to_string(),
+        };
+        let line = expense.to_csv();
+        assert_eq!(line, "2024-03-01,12.50,\"parking, airport\"");
+        assert_eq!(Expense::parse(&line).unwrap(), expense);
+    }
+}
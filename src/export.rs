@@ -0,0 +1,296 @@
+use std::{collections::BTreeMap, fmt::Write, str::FromStr};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use regex::Regex;
+
+use crate::{
+    csv,
+    interval::{Interval, AUTO_TAG},
+    json,
+    record::Event,
+};
+
+/// Which system the `export` command prepares records for
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ExportFormat {
+    /// Worklog entries as expected by Jira's REST API
+    Jira,
+    /// The events as stored, one per line in CSV, which `import` reads
+    Records,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "jira" => Ok(ExportFormat::Jira),
+            "records" => Ok(ExportFormat::Records),
+            _ => {
+                Err(format!("Unknown export format {s}, try jira or records"))
+            }
+        }
+    }
+}
+
+/// Time spent on a Jira issue
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Worklog {
+    pub issue_key: String,
+    pub started: DateTime<Utc>,
+    pub time_spent: Duration,
+}
+
+impl Worklog {
+    /// The request body for Jira's "add worklog" endpoint, plus the issue
+    /// key that goes into the endpoint's URL
+    pub fn to_json(&self) -> json::Value {
+        json::Value::Object(vec![
+            ("issueKey".to_string(), self.issue_key.as_str().into()),
+            (
+                "started".to_string(),
+                self.started
+                    .format("%Y-%m-%dT%H:%M:%S%.3f%z")
+                    .to_string()
+                    .into(),
+            ),
+            (
+                "timeSpentSeconds".to_string(),
+                self.time_spent.num_seconds().into(),
+            ),
+        ])
+    }
+}
+
+/// Turns intervals into worklogs. A project's issue key is looked up in
+/// `issues`; with `issue_from_project`, projects that are named like an
+/// issue key, e.g., `WEB-42`, are used as is. Intervals whose issue can't
+/// be determined are returned separately.
+pub fn jira_worklogs(
+    intervals: &[Interval],
+    issues: &BTreeMap<String, String>,
+    issue_from_project: bool,
+) -> (Vec<Worklog>, Vec<Interval>) {
+    let issue_key = Regex::new(r"^[A-Z][A-Z0-9_]*-\d+$").unwrap();
+    let mut worklogs = Vec::new();
+    let mut unmapped = Vec::new();
+    for interval in intervals {
+        let key = interval.project.as_ref().and_then(|project| {
+            issues.get(project).cloned().or_else(|| {
+                (issue_from_project && issue_key.is_match(project))
+                    .then(|| project.clone())
+            })
+        });
+        match key {
+            Some(issue_key) => worklogs.push(Worklog {
+                issue_key,
+                started: interval.start,
+                time_spent: interval.duration(),
+            }),
+            None => unmapped.push(interval.clone()),
+        }
+    }
+    (worklogs, unmapped)
+}
+
+/// Replaces what `events` tell about whom they're for, e.g., to attach them
+/// to a bug report: projects, tags and locations become `project1`, `tag1`,
+/// `location1` and so on, in the order they first appear, and notes become
+/// `note`. The events are moved back by `weeks`, which keeps their
+/// weekdays.
+pub fn anonymize(events: &[Event], weeks: u32) -> Vec<Event> {
+    let mut names: BTreeMap<(&str, String), String> = BTreeMap::new();
+    let mut pseudonym = |kind: &'static str, name: &String| {
+        let count = names.keys().filter(|(k, _)| *k == kind).count();
+        names
+            .entry((kind, name.clone()))
+            .or_insert_with(|| format!("{kind}{}", count + 1))
+            .clone()
+    };
+    events
+        .iter()
+        .map(|event| {
+            let mut event = event.clone();
+            event.dt -= Duration::weeks(weeks.into());
+            event.project =
+                event.project.map(|name| pseudonym("project", &name));
+            event.tags = event
+                .tags
+                .iter()
+                .map(|tag| match tag.as_str() {
+                    AUTO_TAG => tag.clone(),
+                    _ => pseudonym("tag", tag),
+                })
+                .collect();
+            event.location =
+                event.location.map(|name| pseudonym("location", &name));
+            event.note = event.note.map(|_| "note".to_string());
+            event
+        })
+        .collect()
+}
+
+/// The header of [`per_day_csv`]
+const PER_DAY_HEADER: &str =
+    "date,start,end,breaks,net_hours,overtime,projects";
+
+/// Summarizes each day with records in one CSV row: when work started and
+/// ended, in local time, and how many hours went into breaks, were worked,
+/// were worked beyond `daily_target` and went into each project. Hours are
+/// decimal, and projects are listed like `web=4.50;app=3.00`.
+pub fn per_day_csv(intervals: &[Interval], daily_target: Duration) -> String {
+    let mut days: BTreeMap<NaiveDate, Vec<&Interval>> = BTreeMap::new();
+    for interval in intervals {
+        let date = interval.start.with_timezone(&Local).date_naive();
+        days.entry(date).or_default().push(interval);
+    }
+    let mut csv = format!("{PER_DAY_HEADER}\n");
+    for (date, day) in days {
+        let start = day.iter().map(|interval| interval.start).min();
+        let end = day.iter().map(|interval| interval.end).max();
+        let (Some(start), Some(end)) = (start, end) else {
+            continue;
+        };
+        let mut projects: Vec<(&str, Duration)> = Vec::new();
+        for interval in &day {
+            let project =
+                interval.project.as_deref().unwrap_or("(no project)");
+            match projects.iter_mut().find(|(name, _)| *name == project) {
+                Some((_, worked)) => *worked += interval.duration(),
+                None => projects.push((project, interval.duration())),
+            }
+        }
+        let worked = projects
+            .iter()
+            .fold(Duration::zero(), |sum, (_, worked)| sum + *worked);
+        let breaks = (end - start - worked).max(Duration::zero());
+        let projects: Vec<String> = projects
+            .iter()
+            .map(|(name, worked)| format!("{name}={}", hours(*worked)))
+            .collect();
+        let _ = writeln!(
+            csv,
+            "{date},{},{},{},{},{},{}",
+            start.with_timezone(&Local).format("%H:%M"),
+            end.with_timezone(&Local).format("%H:%M"),
+            hours(breaks),
+            hours(worked),
+            hours(worked - daily_target),
+            csv::escape(&projects.join(";"))
+        );
+    }
+    csv
+}
+
+/// Formats a duration as decimal hours, e.g., `7.50`
+#[allow(clippy::cast_precision_loss)]
+fn hours(duration: Duration) -> String {
+    format!("{:.2}", duration.num_seconds() as f64 / 3600.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn interval(project: Option<&str>) -> Interval {
+        Interval {
+            start: Utc.with_ymd_and_hms(2024, 3, 1, 8, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 3, 1, 9, 30, 0).unwrap(),
+            project: project.map(String::from),
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn jira_worklogs_map_projects_to_issues() {
+        let issues =
+            BTreeMap::from([("web".to_string(), "SHOP-7".to_string())]);
+        let intervals = [
+            interval(Some("web")),
+            interval(Some("WEB-42")),
+            interval(Some("misc")),
+            interval(None),
+        ];
+
+        let (worklogs, unmapped) = jira_worklogs(&intervals, &issues, true);
+        let keys: Vec<_> =
+            worklogs.iter().map(|w| w.issue_key.as_str()).collect();
+        assert_eq!(keys, ["SHOP-7", "WEB-42"]);
+        assert_eq!(unmapped, [interval(Some("misc")), interval(None)]);
+
+        let (worklogs, _) = jira_worklogs(&intervals, &issues, false);
+        assert_eq!(worklogs.len(), 1);
+    }
+
+    #[test]
+    fn anonymized_events_keep_only_their_structure() {
+        let at = |day| Utc.with_ymd_and_hms(2024, 3, day, 8, 0, 0).unwrap();
+        let tags =
+            |tags: &[&str]| tags.iter().map(ToString::to_string).collect();
+        let events = [
+            Event::clock_in(&at(1))
+                .with_project(Some("acme".to_string()))
+                .with_tags(tags(&["billable"]))
+                .with_location(Some("office".to_string())),
+            Event::interruption(&at(1), 20, "acme outage".to_string()),
+            Event::clock_in(&at(4))
+                .with_project(Some("globex".to_string()))
+                .with_tags(tags(&[AUTO_TAG, "billable"])),
+            Event::clock_in(&at(5)).with_project(Some("acme".to_string())),
+        ];
+        let anonymized = anonymize(&events, 2);
+        assert_eq!(anonymized[0].dt, at(1) - Duration::weeks(2));
+        assert_eq!(anonymized[0].project.as_deref(), Some("project1"));
+        assert_eq!(anonymized[0].location.as_deref(), Some("location1"));
+        assert_eq!(anonymized[1].note.as_deref(), Some("note"));
+        assert_eq!(anonymized[2].project.as_deref(), Some("project2"));
+        assert_eq!(anonymized[2].tags, [AUTO_TAG, "tag1"]);
+        assert_eq!(anonymized[3].project.as_deref(), Some("project1"));
+    }
+
+    #[test]
+    fn per_day_csv_summarizes_each_day() {
+        let at = |day, h, m| {
+            Local
+                .with_ymd_and_hms(2024, 3, day, h, m, 0)
+                .unwrap()
+                .to_utc()
+        };
+        let interval =
+            |day, from: (u32, u32), to: (u32, u32), project: Option<&str>| {
+                Interval {
+                    start: at(day, from.0, from.1),
+                    end: at(day, to.0, to.1),
+                    project: project.map(String::from),
+                    tags: Vec::new(),
+                }
+            };
+        let intervals = [
+            interval(1, (8, 0), (12, 0), Some("web")),
+            interval(1, (12, 30), (15, 0), Some("app")),
+            interval(1, (15, 0), (17, 0), Some("web")),
+            interval(4, (9, 0), (15, 0), None),
+        ];
+        assert_eq!(
+            per_day_csv(&intervals, Duration::hours(8)),
+            "date,start,end,breaks,net_hours,overtime,projects\n\
+            2024-03-01,08:00,17:00,0.50,8.50,0.50,web=6.00;app=2.50\n\
+            2024-03-04,09:00,15:00,0.00,6.00,-2.00,(no project)=6.00\n"
+        );
+    }
+
+    #[test]
+    fn worklog_json_matches_jira_api() {
+        let worklog = Worklog {
+            issue_key: "SHOP-7".to_string(),
+            started: Utc.with_ymd_and_hms(2024, 3, 1, 8, 0, 0).unwrap(),
+            time_spent: Duration::minutes(90),
+        };
+        assert_eq!(
+            worklog.to_json().to_string(),
+            r#"{"issueKey":"SHOP-7","started":"2024-03-01T08:00:00.000+0000","timeSpentSeconds":5400}"#
+        );
+    }
+}
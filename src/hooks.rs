@@ -0,0 +1,114 @@
+use std::{fmt::Display, path::Path, process::Command};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local};
+
+use crate::data::{Event, EventKind};
+
+/// The points at which user scripts can be run
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Hook {
+    PostClockIn,
+    PostClockOut,
+    PostStandbyStart,
+    PostStandbyEnd,
+    PostInterruption,
+    PostDelete,
+}
+
+impl Hook {
+    /// The hook that runs after `event` has been recorded
+    pub fn after(event: &Event) -> Hook {
+        match event.kind {
+            EventKind::ClockIn => Hook::PostClockIn,
+            EventKind::ClockOut => Hook::PostClockOut,
+            EventKind::StandbyStart => Hook::PostStandbyStart,
+            EventKind::StandbyEnd => Hook::PostStandbyEnd,
+            EventKind::Interruption { .. } => Hook::PostInterruption,
+        }
+    }
+}
+
+impl Display for Hook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Hook::PostClockIn => write!(f, "post-clock-in"),
+            Hook::PostClockOut => write!(f, "post-clock-out"),
+            Hook::PostStandbyStart => write!(f, "post-standby-start"),
+            Hook::PostStandbyEnd => write!(f, "post-standby-end"),
+            Hook::PostInterruption => write!(f, "post-interruption"),
+            Hook::PostDelete => write!(f, "post-delete"),
+        }
+    }
+}
+
+/// Runs the executable named after `hook` in `hooks_dir`, if there is one,
+/// passing the event's details in `BUSY_BEE_*` environment variables
+pub fn run(
+    hooks_dir: &Path,
+    hook: Hook,
+    event: &Event,
+    storage_dir: &Path,
+) -> Result<()> {
+    let hook_path = hooks_dir.join(hook.to_string());
+    if !hook_path.is_file() {
+        return Ok(());
+    }
+
+    let kind_str = match event.kind {
+        EventKind::ClockIn => "clock-in",
+        EventKind::ClockOut => "clock-out",
+        EventKind::StandbyStart => "standby-start",
+        EventKind::StandbyEnd => "standby-end",
+        EventKind::Interruption { .. } => "interruption",
+    };
+    let local_time: DateTime<Local> = DateTime::from(event.dt);
+    let status = Command::new(&hook_path)
+        .env("BUSY_BEE_HOOK", hook.to_string())
+        .env("BUSY_BEE_EVENT_KIND", kind_str)
+        .env("BUSY_BEE_EVENT_TIME", local_time.to_rfc3339())
+        .env("BUSY_BEE_EVENT_DATE", local_time.date_naive().to_string())
+        .env("BUSY_BEE_STORAGE_DIR", storage_dir)
+        .status()
+        .with_context(|| format!("Could not run {}", hook_path.display()))?;
+    if !status.success() {
+        bail!("{} exited with {status}", hook_path.display())
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    use chrono::{TimeZone, Utc};
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn run_passes_event_details() {
+        let d = tempdir().unwrap();
+        let hook_path = d.path().join("post-clock-in");
+        let out_path = d.path().join("out");
+        fs::write(
+            &hook_path,
+            format!(
+                "#!/bin/sh\necho \"$BUSY_BEE_EVENT_KIND\" > {}\n",
+                out_path.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755))
+            .unwrap();
+
+        let event = Event::clock_in(
+            &Utc.with_ymd_and_hms(2020, 1, 31, 8, 15, 0).unwrap(),
+        );
+        run(d.path(), Hook::PostClockIn, &event, d.path()).unwrap();
+        assert_eq!(fs::read_to_string(out_path).unwrap(), "clock-in\n");
+
+        // missing hooks are fine
+        run(d.path(), Hook::PostClockOut, &event, d.path()).unwrap();
+    }
+}
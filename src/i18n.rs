@@ -0,0 +1,262 @@
+//! Translations of the texts in reports, and how dates and durations are
+//! written in them. Texts are looked up by their English wording, which is
+//! also what's printed if there's no translation.
+
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+/// The language that reports are printed in
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(u8)]
+pub enum Language {
+    English,
+    German,
+}
+
+impl Language {
+    /// Picks the language from a locale like `de_DE.UTF-8`, as found in
+    /// `LANG`, falling back to English
+    pub fn from_locale(locale: &str) -> Language {
+        let code = locale.split(['_', '.', '-']).next().unwrap_or_default();
+        code.parse().unwrap_or(Language::English)
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "en" | "english" => Ok(Language::English),
+            "de" | "german" | "deutsch" => Ok(Language::German),
+            _ => Err(format!("Unknown language {s}, try en or de")),
+        }
+    }
+}
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(Language::English as u8);
+
+/// Sets the language of all reports printed from now on
+pub fn set_language(language: Language) {
+    LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+pub fn language() -> Language {
+    if LANGUAGE.load(Ordering::Relaxed) == Language::German as u8 {
+        Language::German
+    } else {
+        Language::English
+    }
+}
+
+/// How durations are written in reports
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(u8)]
+pub enum DurationFormat {
+    /// Hours and minutes, e.g., `07:45`
+    Hms,
+    /// Hours with a decimal fraction, e.g., `7.75`
+    Decimal,
+}
+
+impl FromStr for DurationFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hms" => Ok(DurationFormat::Hms),
+            "decimal" => Ok(DurationFormat::Decimal),
+            _ => {
+                Err(format!("Unknown duration format {s}, try hms or decimal"))
+            }
+        }
+    }
+}
+
+static DURATION_FORMAT: AtomicU8 = AtomicU8::new(DurationFormat::Hms as u8);
+
+/// Sets how durations are written in all reports printed from now on
+pub fn set_duration_format(format: DurationFormat) {
+    DURATION_FORMAT.store(format as u8, Ordering::Relaxed);
+}
+
+pub fn duration_format() -> DurationFormat {
+    if DURATION_FORMAT.load(Ordering::Relaxed) == DurationFormat::Decimal as u8
+    {
+        DurationFormat::Decimal
+    } else {
+        DurationFormat::Hms
+    }
+}
+
+/// Formats a duration like `07:45` or `7.75`, depending on the current
+/// duration format
+pub fn duration(duration: &Duration) -> String {
+    format_duration(duration_format(), *duration)
+}
+
+fn format_duration(format: DurationFormat, duration: Duration) -> String {
+    let sign = if duration < Duration::zero() { "-" } else { "" };
+    let seconds = duration.num_seconds().abs();
+    match format {
+        DurationFormat::Hms => {
+            let minutes = seconds / 60;
+            format!("{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+        }
+        DurationFormat::Decimal => {
+            #[allow(clippy::cast_precision_loss)]
+            let hours = seconds as f64 / 3600.0;
+            format!("{sign}{hours:.2}")
+        }
+    }
+}
+
+/// Translates `english` into the current language
+pub fn tr(english: &'static str) -> &'static str {
+    translate(language(), english)
+}
+
+fn translate(language: Language, english: &'static str) -> &'static str {
+    if language == Language::English {
+        return english;
+    }
+    match english {
+        "Records for" => "Einträge für",
+        "today" => "heute",
+        "Summary for" => "Übersicht für",
+        "Total working time" => "Gesamtarbeitszeit",
+        "Total working time from" => "Gesamtarbeitszeit vom",
+        "to" => "bis",
+        "hours" => "Stunden",
+        "Incomplete records, please update" => {
+            "Unvollständige Einträge, bitte ergänzen"
+        }
+        "Missing, no records" => "Fehlt, keine Einträge",
+        "Average start" => "Durchschnittlicher Beginn",
+        "Average end" => "Durchschnittliches Ende",
+        "median" => "Median",
+        "Average working time per day" => {
+            "Durchschnittliche Arbeitszeit pro Tag"
+        }
+        "Incomplete days" => "Unvollständige Tage",
+        "Working time per tag" => "Arbeitszeit pro Schlagwort",
+        "Working time per client" => "Arbeitszeit pro Kunde",
+        "Days per location" => "Tage pro Arbeitsort",
+        "Travel time" => "Reisezeit",
+        "counted at" => "angerechnet zu",
+        "Counted working time" => "Angerechnete Arbeitszeit",
+        "Expenses" => "Auslagen",
+        "Note" => "Notiz",
+        "via" => "über",
+        "Summary from" => "Übersicht vom",
+        "break" => "Pause",
+        "missing clock-in or clock-out" => "fehlendes Kommen oder Gehen",
+        "Total expenses" => "Summe der Auslagen",
+        "Date" => "Datum",
+        "Description" => "Beschreibung",
+        "Amount" => "Betrag",
+        "Working time per project in" => "Arbeitszeit pro Projekt im",
+        "Standby time" => "Bereitschaftszeit",
+        "of which" => "davon",
+        "on incidents" => "bei Einsätzen",
+        "(no project)" => "(kein Projekt)",
+        "Per project" => "Pro Projekt",
+        "day" | "Day" => "Tag",
+        "days" | "Days" => "Tage",
+        "Week" => "Woche",
+        "clock in" => "Kommen",
+        "clock out" => "Gehen",
+        "on call" => "Ruf an",
+        "off call" => "Ruf aus",
+        "interrupt" => "Störung",
+        "Time" => "Zeit",
+        "Event" => "Ereignis",
+        "Worked" => "Gearbeitet",
+        "Comment" => "Kommentar",
+        "Tag" => "Schlagwort",
+        "Client" => "Kunde",
+        "Location" => "Arbeitsort",
+        "Project" => "Projekt",
+        "Share" => "Anteil",
+        "worked" => "gearbeitet",
+        "target" | "Target" => "Soll",
+        "both" => "beides",
+        "ahead" => "voraus",
+        "behind" => "im Rückstand",
+        "on" => "am",
+        "overtime" => "Überstunden",
+        "short of the target" => "unter dem Soll",
+        _ => english,
+    }
+}
+
+/// Formats a date like `Oct 06, 2024` or `06.10.2024`
+pub fn date(date: &NaiveDate) -> String {
+    match language() {
+        Language::English => date.format("%b %d, %Y").to_string(),
+        Language::German => date.format("%d.%m.%Y").to_string(),
+    }
+}
+
+/// Formats the month of a date like `October 2024` or `Oktober 2024`
+pub fn month(date: &NaiveDate) -> String {
+    match language() {
+        Language::English => date.format("%B %Y").to_string(),
+        Language::German => {
+            const MONTHS: [&str; 12] = [
+                "Januar",
+                "Februar",
+                "März",
+                "April",
+                "Mai",
+                "Juni",
+                "Juli",
+                "August",
+                "September",
+                "Oktober",
+                "November",
+                "Dezember",
+            ];
+            format!("{} {}", MONTHS[date.month0() as usize], date.year())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_translations_fall_back_to_english() {
+        assert_eq!(
+            translate(Language::German, "Total working time"),
+            "Gesamtarbeitszeit"
+        );
+        assert_eq!(translate(Language::German, "Details"), "Details");
+        assert_eq!(translate(Language::English, "hours"), "hours");
+    }
+
+    #[test]
+    fn language_is_taken_from_locale() {
+        assert_eq!(Language::from_locale("de_DE.UTF-8"), Language::German);
+        assert_eq!(Language::from_locale("en_US.UTF-8"), Language::English);
+        assert_eq!(Language::from_locale("C"), Language::English);
+        assert_eq!(Language::from_locale(""), Language::English);
+    }
+
+    #[test]
+    fn durations_are_formatted_as_configured() {
+        let duration = Duration::minutes(7 * 60 + 45);
+        assert_eq!(format_duration(DurationFormat::Hms, duration), "07:45");
+        assert_eq!(format_duration(DurationFormat::Decimal, duration), "7.75");
+        assert_eq!(
+            format_duration(DurationFormat::Decimal, -Duration::minutes(20)),
+            "-0.33"
+        );
+        assert_eq!("Decimal".parse(), Ok(DurationFormat::Decimal));
+    }
+}
@@ -0,0 +1,163 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+
+/// A timed event read from an iCalendar file
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CalendarEvent {
+    pub summary: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/// Whether the content looks like an iCalendar file, e.g., one exported
+/// from Google Calendar
+pub fn is_calendar(content: &str) -> bool {
+    content.trim_start().starts_with("BEGIN:VCALENDAR")
+}
+
+/// Reads the `VEVENT`s of an iCalendar file. All-day events are left out,
+/// as they don't say when you were busy. Times with a `TZID` are read as
+/// local times, because resolving time zone names is not supported.
+pub fn parse(content: &str) -> Result<Vec<CalendarEvent>> {
+    let mut events = Vec::new();
+    let mut current: Option<Properties> = None;
+    for (i, line) in unfold(content).iter().enumerate() {
+        let line_no = i + 1;
+        match line.as_str() {
+            "BEGIN:VEVENT" => current = Some(Properties::default()),
+            "END:VEVENT" => {
+                let properties = current.take().ok_or(anyhow!(
+                    "Unexpected END:VEVENT in line {line_no}"
+                ))?;
+                if let Some(event) = properties.into_event()? {
+                    events.push(event);
+                }
+            }
+            _ => {
+                let Some(properties) = current.as_mut() else {
+                    continue;
+                };
+                let Some((name, value)) = line.split_once(':') else {
+                    continue;
+                };
+                let (name, params) =
+                    name.split_once(';').unwrap_or((name, ""));
+                match name {
+                    "SUMMARY" => properties.summary = Some(unescape(value)),
+                    "DTSTART" => {
+                        properties.start = Some((params.into(), value.into()));
+                    }
+                    "DTEND" => {
+                        properties.end = Some((params.into(), value.into()));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(events)
+}
+
+#[derive(Default)]
+struct Properties {
+    summary: Option<String>,
+    /// Parameters and value of the property
+    start: Option<(String, String)>,
+    end: Option<(String, String)>,
+}
+
+impl Properties {
+    fn into_event(self) -> Result<Option<CalendarEvent>> {
+        let summary = self.summary.unwrap_or_default();
+        let (Some(start), Some(end)) = (self.start, self.end) else {
+            return Ok(None);
+        };
+        let (Some(start), Some(end)) =
+            (parse_dt(&start.0, &start.1)?, parse_dt(&end.0, &end.1)?)
+        else {
+            return Ok(None);
+        };
+        if end <= start {
+            bail!("Calendar event '{summary}' ends before it starts")
+        }
+        Ok(Some(CalendarEvent {
+            summary,
+            start,
+            end,
+        }))
+    }
+}
+
+/// Returns `None` for dates without a time
+fn parse_dt(params: &str, value: &str) -> Result<Option<DateTime<Utc>>> {
+    if params.contains("VALUE=DATE") && !params.contains("VALUE=DATE-TIME") {
+        return Ok(None);
+    }
+    let (value, is_utc) = match value.strip_suffix('Z') {
+        Some(value) => (value, true),
+        None => (value, false),
+    };
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .with_context(|| format!("Invalid calendar date-time {value}"))?;
+    if is_utc {
+        return Ok(Some(naive.and_utc()));
+    }
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|dt| Some(dt.to_utc()))
+        .ok_or(anyhow!("{naive} does not exist in the local time zone"))
+}
+
+/// Joins lines that were folded by starting the continuation with a space
+/// or tab
+fn unfold(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.trim_end().to_string()),
+        }
+    }
+    lines
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("\\n", " ")
+        .replace("\\N", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_timed_events() {
+        let content = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART:20240301T090000Z\r\n\
+            DTEND:20240301T093000Z\r\n\
+            SUMMARY:Daily\\, stand-\r\n \
+            up\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART;VALUE=DATE:20240302\r\n\
+            DTEND;VALUE=DATE:20240303\r\n\
+            SUMMARY:Holiday\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        assert!(is_calendar(content));
+        assert_eq!(
+            parse(content).unwrap(),
+            vec![CalendarEvent {
+                summary: "Daily, stand-up".to_string(),
+                start: Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap(),
+                end: Utc.with_ymd_and_hms(2024, 3, 1, 9, 30, 0).unwrap(),
+            }]
+        );
+    }
+}
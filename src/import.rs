@@ -0,0 +1,270 @@
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    fmt::Write,
+};
+
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDate, Timelike};
+
+use crate::{
+    data::{create_events, read_events, Event, EventKind, Source, Storage},
+    ics::CalendarEvent,
+    interval::intervals,
+};
+
+/// The tag of events imported from a calendar
+pub const MEETING_TAG: &str = "meeting";
+
+/// What importing an event would do
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Outcome {
+    /// The event is new and will be added
+    Added,
+    /// An event of the same kind exists at the same minute, so the event is
+    /// skipped
+    Duplicate,
+    /// An event of a different kind exists at the same minute, so the event
+    /// is skipped and needs to be resolved by hand
+    Conflict { existing: Event },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Classified {
+    pub event: Event,
+    pub outcome: Outcome,
+}
+
+/// Compares the events to import with the stored ones
+pub fn plan(
+    storage: &dyn Storage,
+    events: &[Event],
+) -> Result<Vec<Classified>> {
+    let mut per_day: BTreeMap<NaiveDate, Vec<Event>> = BTreeMap::new();
+    for event in events {
+        per_day
+            .entry(event.dt.date_naive())
+            .or_default()
+            .push(event.clone());
+    }
+
+    let mut result = Vec::new();
+    for (date, mut events) in per_day {
+        events.sort_by_key(|event| event.dt);
+        let mut known = read_events(storage, date)?;
+        for event in events {
+            let same_minute = known
+                .iter()
+                .find(|existing| truncate(existing) == truncate(&event));
+            let outcome = match same_minute {
+                Some(existing) if existing.kind == event.kind => {
+                    Outcome::Duplicate
+                }
+                Some(existing) => Outcome::Conflict {
+                    existing: existing.clone(),
+                },
+                None => Outcome::Added,
+            };
+            if outcome == Outcome::Added {
+                // so that duplicates within the import file are detected too
+                known.push(event.clone());
+            }
+            result.push(Classified { event, outcome });
+        }
+    }
+    Ok(result)
+}
+
+/// Turns meetings into clock-in/clock-out pairs tagged as meetings. Meetings
+/// that overlap recorded working time, or an earlier meeting, are returned
+/// separately and not turned into events.
+pub fn meetings(
+    storage: &dyn Storage,
+    meetings: &[CalendarEvent],
+) -> Result<(Vec<Event>, Vec<CalendarEvent>)> {
+    let mut sorted = meetings.to_vec();
+    sorted.sort_by_key(|meeting| meeting.start);
+
+    let mut events = Vec::new();
+    let mut overlapping = Vec::new();
+    let mut per_day: BTreeMap<NaiveDate, Vec<Event>> = BTreeMap::new();
+    for meeting in sorted {
+        let mut busy = Vec::new();
+        for date in [meeting.start.date_naive(), meeting.end.date_naive()] {
+            let day = match per_day.entry(date) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    entry.insert(read_events(storage, date)?)
+                }
+            };
+            busy.extend(intervals(day));
+        }
+        busy.extend(intervals(&events));
+        if busy.iter().any(|interval| {
+            interval.start < meeting.end && meeting.start < interval.end
+        }) {
+            overlapping.push(meeting);
+            continue;
+        }
+        let tags = vec![MEETING_TAG.to_string()];
+        events.push(Event::clock_in(&meeting.start).with_tags(tags));
+        events.push(Event::clock_out(&meeting.end));
+    }
+    Ok((events, overlapping))
+}
+
+/// `event` as it's added from elsewhere, keeping a source it already has
+pub fn imported(event: &Event) -> Event {
+    Event {
+        source: event.source.or(Some(Source::Import)),
+        ..event.clone()
+    }
+}
+
+fn truncate(event: &Event) -> DateTime<chrono::Utc> {
+    event
+        .dt
+        .with_second(0)
+        .and_then(|dt| dt.with_nanosecond(0))
+        .unwrap_or(event.dt)
+}
+
+/// Writes the events that were classified as added, and returns how many
+/// there were. Those without a source get [`Source::Import`].
+pub fn apply(storage: &dyn Storage, plan: &[Classified]) -> Result<usize> {
+    let added: Vec<Event> = plan
+        .iter()
+        .filter(|classified| classified.outcome == Outcome::Added)
+        .map(|classified| imported(&classified.event))
+        .collect();
+    create_events(storage, &added)?;
+    Ok(added.len())
+}
+
+/// Renders the plan similar to a unified diff: added events are marked
+/// with `+`, skipped duplicates with a space and conflicts with `!`
+pub fn render_diff(plan: &[Classified]) -> String {
+    let mut result = String::new();
+    let mut current_date = None;
+    for Classified { event, outcome } in plan {
+        let date = event.dt.date_naive();
+        if current_date != Some(date) {
+            // writing to a String cannot fail
+            let _ = writeln!(result, "@@ {date} @@");
+            current_date = Some(date);
+        }
+        let description = describe(event);
+        let _ = match outcome {
+            Outcome::Added => writeln!(result, "+{description}"),
+            Outcome::Duplicate => {
+                writeln!(result, " {description} (duplicate, skipped)")
+            }
+            Outcome::Conflict { existing } => writeln!(
+                result,
+                "!{description} (conflicts with{})",
+                describe(existing)
+            ),
+        };
+    }
+    result
+}
+
+/// The local time and kind of `event`, with a leading space, e.g.,
+/// " 09:00 clock in"
+pub fn describe(event: &Event) -> String {
+    let local_time: DateTime<Local> = DateTime::from(event.dt);
+    let kind_str = match event.kind {
+        EventKind::ClockIn => "clock in",
+        EventKind::ClockOut => "clock out",
+        EventKind::StandbyStart => "standby start",
+        EventKind::StandbyEnd => "standby end",
+        EventKind::Interruption { .. } => "interruption",
+    };
+    format!(" {} {kind_str}", local_time.format("%H:%M"))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::data::{create_event, DayFiles, Format};
+
+    #[test]
+    fn meetings_skip_overlaps() {
+        let d = tempdir().unwrap();
+        let storage = DayFiles::new(d.path(), Format::Csv);
+        let at = |h, m| Utc.with_ymd_and_hms(2020, 1, 31, h, m, 0).unwrap();
+        create_event(&storage, &Event::clock_in(&at(8, 0)), Duration::zero())
+            .unwrap();
+        create_event(
+            &storage,
+            &Event::clock_out(&at(10, 0)),
+            Duration::zero(),
+        )
+        .unwrap();
+        let meeting = |start, end| CalendarEvent {
+            summary: "Sync".to_string(),
+            start,
+            end,
+        };
+        let calendar = [
+            meeting(at(9, 30), at(10, 30)),
+            meeting(at(11, 0), at(12, 0)),
+            meeting(at(11, 30), at(12, 30)),
+            meeting(at(10, 15), at(10, 45)),
+        ];
+
+        let (events, overlapping) = meetings(&storage, &calendar).unwrap();
+        let tags = vec![MEETING_TAG.to_string()];
+        assert_eq!(
+            events,
+            vec![
+                Event::clock_in(&at(10, 15)).with_tags(tags.clone()),
+                Event::clock_out(&at(10, 45)),
+                Event::clock_in(&at(11, 0)).with_tags(tags),
+                Event::clock_out(&at(12, 0)),
+            ]
+        );
+        assert_eq!(overlapping, [calendar[0].clone(), calendar[2].clone()]);
+    }
+
+    #[test]
+    fn plan_classifies_events() {
+        let d = tempdir().unwrap();
+        let storage = DayFiles::new(d.path(), Format::Csv);
+        let clock_in = Event::clock_in(
+            &Utc.with_ymd_and_hms(2020, 1, 31, 8, 15, 0).unwrap(),
+        );
+        create_event(&storage, &clock_in, Duration::zero()).unwrap();
+
+        let duplicate = Event::clock_in(
+            &Utc.with_ymd_and_hms(2020, 1, 31, 8, 15, 30).unwrap(),
+        );
+        let conflict = Event::clock_out(
+            &Utc.with_ymd_and_hms(2020, 1, 31, 8, 15, 0).unwrap(),
+        );
+        let added = Event::clock_out(
+            &Utc.with_ymd_and_hms(2020, 1, 31, 16, 0, 0).unwrap(),
+        );
+        let plan = plan(
+            &storage,
+            &[added.clone(), duplicate.clone(), conflict.clone()],
+        )
+        .unwrap();
+        let outcomes: Vec<_> =
+            plan.iter().map(|c| c.outcome.clone()).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                Outcome::Conflict { existing: clock_in },
+                Outcome::Duplicate,
+                Outcome::Added,
+            ]
+        );
+
+        assert_eq!(apply(&storage, &plan).unwrap(), 1);
+        let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
+        assert_eq!(read_events(&storage, date).unwrap().len(), 2);
+    }
+}
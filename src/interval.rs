@@ -0,0 +1,451 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+
+use crate::{
+    i18n,
+    record::{Event, EventKind},
+};
+
+/// The tag of clock-outs that were added at the configured cutoff time
+pub const AUTO_TAG: &str = "auto";
+
+/// A span of time between a clock-in and the following clock-out
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct Interval {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// The project of the clock-in that started the interval
+    pub project: Option<String>,
+    /// The tags of the clock-in that started the interval
+    pub tags: Vec<String>,
+}
+
+impl Interval {
+    /// How long the interval is, to the second, and never negative even if
+    /// it ends before it starts
+    pub fn duration(&self) -> Duration {
+        (self.end - self.start).max(Duration::zero())
+    }
+}
+
+/// Pairs up clock-ins with the clock-outs following them. Events that have
+/// no counterpart, e.g., the clock-in of a day that is still in progress,
+/// are left out.
+pub fn intervals(events: &[Event]) -> Vec<Interval> {
+    let mut sorted: Vec<&Event> = events.iter().collect();
+    sorted.sort_by_key(|event| event.dt);
+
+    let mut result = Vec::new();
+    let mut open: Option<&Event> = None;
+    for event in sorted {
+        match (&event.kind, open) {
+            (EventKind::ClockIn, _) => open = Some(event),
+            (EventKind::ClockOut, Some(start)) => {
+                result.push(Interval {
+                    start: start.dt,
+                    end: event.dt,
+                    project: start.project.clone(),
+                    tags: start.tags.clone(),
+                });
+                open = None;
+            }
+            (EventKind::ClockOut, None)
+            | (
+                EventKind::StandbyStart
+                | EventKind::StandbyEnd
+                | EventKind::Interruption { .. },
+                _,
+            ) => {}
+        }
+    }
+    result
+}
+
+/// Pairs up the starts of on-call standby with the ends following them,
+/// like [`intervals`] does for clock events
+pub fn standby(events: &[Event]) -> Vec<Interval> {
+    let mut sorted: Vec<&Event> = events.iter().collect();
+    sorted.sort_by_key(|event| event.dt);
+
+    let mut result = Vec::new();
+    let mut open: Option<&Event> = None;
+    for event in sorted {
+        match (&event.kind, open) {
+            (EventKind::StandbyStart, _) => open = Some(event),
+            (EventKind::StandbyEnd, Some(start)) => {
+                result.push(Interval {
+                    start: start.dt,
+                    end: event.dt,
+                    project: None,
+                    tags: Vec::new(),
+                });
+                open = None;
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+/// How much of the time in `a` is also covered by `b`, e.g., the incidents
+/// worked on during standby
+pub fn overlap(a: &[Interval], b: &[Interval]) -> Duration {
+    let mut total = Duration::zero();
+    for x in a {
+        for y in b {
+            let start = x.start.max(y.start);
+            let end = x.end.min(y.end);
+            if start < end {
+                total += end - start;
+            }
+        }
+    }
+    total
+}
+
+/// Keeps the clock-ins that have all of the `tags`, and the clock-outs that
+/// end the intervals they start
+pub fn with_tags(events: &[Event], tags: &[String]) -> Vec<Event> {
+    let mut sorted: Vec<&Event> = events.iter().collect();
+    sorted.sort_by_key(|event| event.dt);
+
+    let mut result = Vec::new();
+    let mut open = false;
+    for event in sorted {
+        match event.kind {
+            EventKind::ClockIn => {
+                open = tags.iter().all(|tag| event.tags.contains(tag));
+                if open {
+                    result.push(event.clone());
+                }
+            }
+            EventKind::ClockOut if open => {
+                result.push(event.clone());
+                open = false;
+            }
+            EventKind::ClockOut
+            | EventKind::StandbyStart
+            | EventKind::StandbyEnd
+            | EventKind::Interruption { .. } => {}
+        }
+    }
+    result
+}
+
+/// Leaves out each clock-out that is followed within `max_gap` on the same
+/// local day by a clock-in of the same project, tags and location, along
+/// with that clock-in, so that the intervals around the gap count as one. A
+/// clock-in with a note is kept, and a zero `max_gap` keeps everything.
+pub fn merge_gaps(events: &[Event], max_gap: Duration) -> Vec<Event> {
+    merge_gaps_in(&Local, events, max_gap)
+}
+
+/// Like [`merge_gaps`], with the days of the time zone `tz`
+fn merge_gaps_in<Tz: TimeZone>(
+    tz: &Tz,
+    events: &[Event],
+    max_gap: Duration,
+) -> Vec<Event> {
+    let local_date = |dt: DateTime<Utc>| dt.with_timezone(tz).date_naive();
+    let mut sorted: Vec<&Event> = events.iter().collect();
+    sorted.sort_by_key(|event| event.dt);
+    if max_gap <= Duration::zero() {
+        return sorted.into_iter().cloned().collect();
+    }
+
+    let mut merged = vec![false; sorted.len()];
+    let mut open: Option<&Event> = None;
+    let mut last_clock_out: Option<usize> = None;
+    for (i, event) in sorted.iter().enumerate() {
+        match event.kind {
+            EventKind::ClockIn => {
+                if let (Some(start), Some(out)) = (open, last_clock_out) {
+                    let end = sorted[out];
+                    if event.dt - end.dt <= max_gap
+                        && local_date(event.dt) == local_date(end.dt)
+                        && event.project == start.project
+                        && event.tags == start.tags
+                        && event.location == start.location
+                        && event.note.is_none()
+                    {
+                        merged[out] = true;
+                        merged[i] = true;
+                        last_clock_out = None;
+                        continue;
+                    }
+                }
+                open = Some(event);
+                last_clock_out = None;
+            }
+            EventKind::ClockOut => last_clock_out = Some(i),
+            EventKind::StandbyStart
+            | EventKind::StandbyEnd
+            | EventKind::Interruption { .. } => {}
+        }
+    }
+    sorted
+        .into_iter()
+        .zip(merged)
+        .filter(|(_, merged)| !merged)
+        .map(|(event, _)| event.clone())
+        .collect()
+}
+
+/// The clock-out that closes a day ending with a clock-in, at the `cutoff`
+/// local time of `date`. There is none if the day is complete, or if the
+/// cutoff hasn't passed yet, as the day may still be in progress.
+pub fn auto_clock_out(
+    date: NaiveDate,
+    events: &[Event],
+    cutoff: NaiveTime,
+    now: DateTime<Utc>,
+) -> Option<Event> {
+    let last = events
+        .iter()
+        .filter(|event| event.kind.is_clock())
+        .max_by_key(|event| event.dt)?;
+    if last.kind != EventKind::ClockIn {
+        return None;
+    }
+    let end = Local
+        .from_local_datetime(&date.and_time(cutoff))
+        .earliest()?
+        .to_utc();
+    (last.dt < end && end <= now)
+        .then(|| Event::clock_out(&end).with_tags(vec![AUTO_TAG.to_string()]))
+}
+
+/// A day that adds up to more hours than seem plausible, which usually
+/// means a clock-out was forgotten
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Implausible {
+    pub date: NaiveDate,
+    pub worked: Duration,
+    /// The longest interval of the day, likely the one that lacks its
+    /// clock-out
+    pub longest: Interval,
+}
+
+impl Display for Implausible {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let end = self.longest.end.with_timezone(&Local);
+        // the end is on another day if the clock-out came the next morning
+        let end_format = if end.date_naive() == self.date {
+            "%H:%M"
+        } else {
+            "%Y-%m-%d %H:%M"
+        };
+        write!(
+            f,
+            "{} adds up to {} hours, is a clock-out missing from {} to {}?",
+            self.date,
+            i18n::duration(&self.worked),
+            self.longest.start.with_timezone(&Local).format("%H:%M"),
+            end.format(end_format)
+        )
+    }
+}
+
+/// The days, by the local date that intervals start on, whose `intervals`
+/// add up to more than `max`
+pub fn implausible_days(
+    intervals: &[Interval],
+    max: Duration,
+) -> Vec<Implausible> {
+    let mut days: BTreeMap<NaiveDate, Vec<&Interval>> = BTreeMap::new();
+    for interval in intervals {
+        let date = interval.start.with_timezone(&Local).date_naive();
+        days.entry(date).or_default().push(interval);
+    }
+    days.into_iter()
+        .filter_map(|(date, day)| {
+            let worked = day.iter().map(|interval| interval.duration()).sum();
+            let longest = day.into_iter().max_by_key(|i| i.duration())?;
+            (worked > max).then(|| Implausible {
+                date,
+                worked,
+                longest: longest.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_with_too_many_hours_are_implausible() {
+        let at = |d, h| Local.with_ymd_and_hms(2024, 3, d, h, 0, 0).unwrap();
+        let events = [
+            Event::clock_in(&at(1, 9)),
+            Event::clock_out(&at(1, 17)),
+            // forgot to clock out on the 4th
+            Event::clock_in(&at(4, 8)),
+            Event::clock_out(&at(4, 10)),
+            Event::clock_in(&at(4, 11)),
+            Event::clock_out(&at(5, 5)),
+        ];
+        let implausible =
+            implausible_days(&intervals(&events), Duration::hours(16));
+        assert_eq!(implausible.len(), 1);
+        assert_eq!(implausible[0].date, at(4, 0).date_naive());
+        assert_eq!(implausible[0].worked, Duration::hours(20));
+        assert_eq!(implausible[0].longest.start, at(4, 11));
+        assert_eq!(
+            implausible[0].to_string(),
+            "2024-03-04 adds up to 20:00 hours, is a clock-out missing from \
+            11:00 to 2024-03-05 05:00?"
+        );
+    }
+
+    #[test]
+    fn intervals_pair_clock_ins_with_clock_outs() {
+        let at = |h, m| Utc.with_ymd_and_hms(2024, 3, 1, h, m, 0).unwrap();
+        let events = [
+            Event::clock_out(&at(7, 0)),
+            Event::clock_in(&at(8, 0)).with_project(Some("web".to_string())),
+            Event::clock_out(&at(12, 0)),
+            Event::clock_in(&at(13, 0)),
+            Event::clock_out(&at(17, 30)),
+            Event::clock_in(&at(20, 0)),
+        ];
+        let result = intervals(&events);
+        assert_eq!(
+            result,
+            vec![
+                Interval {
+                    start: at(8, 0),
+                    end: at(12, 0),
+                    project: Some("web".to_string()),
+                    tags: Vec::new(),
+                },
+                Interval {
+                    start: at(13, 0),
+                    end: at(17, 30),
+                    project: None,
+                    tags: Vec::new(),
+                },
+            ]
+        );
+        assert_eq!(result[1].duration(), Duration::minutes(270));
+    }
+
+    #[test]
+    fn with_tags_keeps_tagged_intervals() {
+        let at = |h| Utc.with_ymd_and_hms(2024, 3, 1, h, 0, 0).unwrap();
+        let tags =
+            |tags: &[&str]| tags.iter().copied().map(String::from).collect();
+        let events = [
+            Event::clock_in(&at(8)).with_tags(tags(&["onsite", "billable"])),
+            Event::clock_out(&at(9)),
+            Event::clock_in(&at(10)).with_tags(tags(&["onsite"])),
+            Event::clock_out(&at(11)),
+        ];
+        assert_eq!(with_tags(&events, &tags(&["billable"])), events[..2]);
+        assert_eq!(with_tags(&events, &tags(&["onsite"])), events);
+        assert!(with_tags(&events, &tags(&["meeting"])).is_empty());
+    }
+
+    #[test]
+    fn tiny_gaps_between_intervals_are_merged() {
+        let at = |h, m| Utc.with_ymd_and_hms(2024, 3, 1, h, m, 0).unwrap();
+        let events = [
+            Event::clock_in(&at(9, 0)),
+            Event::clock_out(&at(15, 0)),
+            Event::clock_in(&at(15, 2)),
+            Event::clock_out(&at(16, 0)),
+            Event::clock_in(&at(16, 4)).with_project(Some("web".to_string())),
+            Event::clock_out(&at(17, 0)),
+        ];
+        let merged = merge_gaps(&events, Duration::minutes(5));
+        assert_eq!(
+            merged,
+            [
+                events[0].clone(),
+                events[3].clone(),
+                events[4].clone(),
+                events[5].clone()
+            ]
+        );
+        assert_eq!(intervals(&merged)[0].duration(), Duration::hours(7));
+        assert_eq!(merge_gaps(&events, Duration::minutes(1)), events);
+        assert_eq!(merge_gaps(&events, Duration::zero()), events);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn gaps_are_merged_on_the_same_local_day() {
+        use crate::testing::CentralEurope;
+
+        let at = |d, h, m| {
+            CentralEurope.with_ymd_and_hms(2024, 1, d, h, m, 0).unwrap()
+        };
+        // 00:58 to 01:02 is around midnight in UTC, 23:58 to 00:02 isn't
+        let events = [
+            Event::clock_in(&at(1, 0, 30)),
+            Event::clock_out(&at(1, 0, 58)),
+            Event::clock_in(&at(1, 1, 2)),
+            Event::clock_out(&at(1, 23, 58)),
+            Event::clock_in(&at(2, 0, 2)),
+            Event::clock_out(&at(2, 1, 0)),
+        ];
+        let merged =
+            merge_gaps_in(&CentralEurope, &events, Duration::minutes(5));
+        assert_eq!(
+            merged,
+            [
+                events[0].clone(),
+                events[3].clone(),
+                events[4].clone(),
+                events[5].clone()
+            ]
+        );
+    }
+
+    #[test]
+    fn auto_clock_out_closes_dangling_clock_ins() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let at = |h, m| {
+            Local
+                .from_local_datetime(&date.and_hms_opt(h, m, 0).unwrap())
+                .unwrap()
+                .to_utc()
+        };
+        let cutoff = NaiveTime::from_hms_opt(23, 59, 0).unwrap();
+        let next_day = at(23, 59) + Duration::hours(1);
+        let events = [Event::clock_in(&at(8, 0))];
+
+        assert_eq!(
+            auto_clock_out(date, &events, cutoff, next_day),
+            Some(
+                Event::clock_out(&at(23, 59))
+                    .with_tags(vec![AUTO_TAG.to_string()])
+            )
+        );
+        assert_eq!(auto_clock_out(date, &events, cutoff, at(20, 0)), None);
+        let complete = [events[0].clone(), Event::clock_out(&at(17, 0))];
+        assert_eq!(auto_clock_out(date, &complete, cutoff, next_day), None);
+    }
+
+    #[test]
+    fn standby_is_paired_apart_from_clock_events() {
+        let at = |h, m| Utc.with_ymd_and_hms(2024, 3, 1, h, m, 0).unwrap();
+        let events = [
+            Event::standby_start(&at(18, 0)),
+            Event::clock_in(&at(20, 0)),
+            Event::clock_out(&at(20, 30)),
+            Event::standby_end(&at(23, 0)),
+            Event::clock_in(&at(23, 30)),
+            Event::clock_out(&at(23, 45)),
+        ];
+        let standby = standby(&events);
+        assert_eq!(standby.len(), 1);
+        assert_eq!(standby[0].duration(), Duration::hours(5));
+        let worked = intervals(&events);
+        assert_eq!(worked.len(), 2);
+        assert_eq!(overlap(&standby, &worked), Duration::minutes(30));
+    }
+}
@@ -1,5 +1,103 @@
-#![allow(clippy::missing_errors_doc)]
+#![allow(
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc,
+    clippy::must_use_candidate
+)]
 
+//! Records when you clock in and out, and reports on the time worked.
+//!
+//! Applications that build on this crate should import from [`prelude`],
+//! which is kept stable across releases. Structs and enums in it are
+//! `#[non_exhaustive]`, so build events with [`Event::clock_in`] and
+//! [`Event::clock_out`] rather than struct literals.
+//!
+//! Only [`prelude`] is covered by semantic versioning. The other modules
+//! are public so that the `busy-bee` binary can use them, and may change
+//! in any release; those that only the binary needs are hidden from the
+//! documentation.
+//!
+//! Everything that touches the filesystem or runs programs is behind the
+//! default `fs` feature. Without it, the parsing and report calculations
+//! build for targets such as `wasm32-unknown-unknown`.
+//!
+//! [`Event::clock_in`]: record::Event::clock_in
+//! [`Event::clock_out`]: record::Event::clock_out
+
+#[cfg(feature = "fs")]
+#[doc(hidden)]
+pub mod archive;
+#[cfg(feature = "async")]
+pub mod async_storage;
+pub mod burndown;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
+pub mod cache;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
 pub mod cli;
+pub mod compliance;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
+pub mod config;
+pub mod constraints;
+pub mod csv;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
 pub mod data;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
+pub mod diff;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
+pub mod doctor;
+pub mod earnings;
+pub mod edit;
+pub mod expense;
+pub mod export;
+#[cfg(feature = "fs")]
+pub(crate) mod gzip;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
+pub mod hooks;
+pub mod i18n;
+pub mod ics;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
+pub mod import;
+pub mod interval;
+pub mod json;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
+pub mod man;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
+pub mod merge;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
+pub mod output;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
+pub mod platform;
+pub mod prelude;
+pub mod query;
+pub mod record;
+pub mod report;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
+pub mod rpc;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
+pub mod sections;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
+pub mod signals;
+pub mod status;
+#[cfg(feature = "fs")]
+#[doc(hidden)]
+pub mod suggest;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "self-update")]
+#[doc(hidden)]
+pub mod update;
 pub mod view;
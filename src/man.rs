@@ -0,0 +1,201 @@
+//! Man pages in roff, generated from the definition of the command line
+//! interface so that they can't get out of date, e.g., for packages
+
+use std::fmt::Write;
+
+use clap::{Arg, Command};
+
+/// The man pages of `command` and of each of its subcommands, named like
+/// `busy-bee.1` and `busy-bee-clock-in.1`, with their content
+pub fn pages(command: &Command) -> Vec<(String, String)> {
+    let name = command.get_name();
+    let mut pages = vec![(format!("{name}.1"), page(command, &[name]))];
+    for subcommand in command.get_subcommands() {
+        let words = [name, subcommand.get_name()];
+        pages.push((
+            format!("{}.1", words.join("-")),
+            page(subcommand, &words),
+        ));
+    }
+    pages
+}
+
+/// The man page of `command`, which is invoked as `words`, e.g.,
+/// `["busy-bee", "clock-in"]`
+pub fn page(command: &Command, words: &[&str]) -> String {
+    let title = words.join("-");
+    let mut roff = String::new();
+    let _ = writeln!(
+        roff,
+        ".TH {} 1 \"\" \"busy-bee {}\"",
+        escape(&title.to_uppercase()),
+        env!("CARGO_PKG_VERSION")
+    );
+    let _ = writeln!(roff, ".SH NAME");
+    match command.get_about() {
+        Some(about) => {
+            let about = escape(&about.to_string());
+            let _ = writeln!(roff, "{} \\- {about}", escape(&title));
+        }
+        None => {
+            let _ = writeln!(roff, "{}", escape(&title));
+        }
+    }
+
+    let _ = writeln!(roff, ".SH SYNOPSIS");
+    let mut synopsis =
+        format!("\\fB{}\\fR [\\fIOPTIONS\\fR]", escape(&words.join(" ")));
+    for arg in command.get_positionals() {
+        let name = value_name(arg);
+        if arg.is_required_set() {
+            let _ = write!(synopsis, " \\fI{name}\\fR");
+        } else {
+            let _ = write!(synopsis, " [\\fI{name}\\fR]");
+        }
+    }
+    if command.has_subcommands() {
+        synopsis.push_str(" \\fICOMMAND\\fR");
+    }
+    let _ = writeln!(roff, "{synopsis}");
+
+    if let Some(description) = command.get_long_about() {
+        let description = escape(&description.to_string());
+        let _ = writeln!(roff, ".SH DESCRIPTION\n{description}");
+    }
+
+    let args: Vec<&Arg> = command
+        .get_arguments()
+        .filter(|arg| !arg.is_hide_set())
+        .collect();
+    if !args.is_empty() {
+        let _ = writeln!(roff, ".SH OPTIONS");
+    }
+    for arg in args {
+        let _ = writeln!(roff, ".TP\n{}", arg_heading(arg));
+        let help = arg.get_long_help().or(arg.get_help());
+        if let Some(help) = help {
+            let _ = writeln!(roff, "{}", escape(&help.to_string()));
+        }
+        let defaults: Vec<_> = arg
+            .get_default_values()
+            .iter()
+            .map(|value| value.to_string_lossy())
+            .collect();
+        if !defaults.is_empty() {
+            let _ = writeln!(
+                roff,
+                ".br\nDefault: {}",
+                escape(&defaults.join(", "))
+            );
+        }
+    }
+
+    let subcommands: Vec<&Command> = command.get_subcommands().collect();
+    if !subcommands.is_empty() {
+        let _ = writeln!(roff, ".SH COMMANDS");
+    }
+    for subcommand in subcommands {
+        let _ =
+            writeln!(roff, ".TP\n\\fB{}\\fR", escape(subcommand.get_name()));
+        if let Some(about) = subcommand.get_about() {
+            let _ = writeln!(roff, "{}", escape(&about.to_string()));
+        }
+    }
+
+    if let Some(after_help) = command.get_after_help() {
+        let after_help = after_help.to_string();
+        let examples = after_help
+            .strip_prefix("Examples:\n")
+            .unwrap_or(&after_help);
+        let _ = writeln!(roff, ".SH EXAMPLES\n.nf");
+        for line in examples.lines() {
+            let _ = writeln!(roff, "{}", escape(line.trim()));
+        }
+        let _ = writeln!(roff, ".fi");
+    }
+
+    if words.len() > 1 {
+        let parent = words[..words.len() - 1].join("-");
+        let _ = writeln!(roff, ".SH SEE ALSO\n\\fB{}\\fR(1)", escape(&parent));
+    }
+    roff
+}
+
+/// How an option is listed, e.g., `-d, --date <DATE>`
+fn arg_heading(arg: &Arg) -> String {
+    if arg.is_positional() {
+        return format!("\\fI{}\\fR", value_name(arg));
+    }
+    let mut names = Vec::new();
+    if let Some(short) = arg.get_short() {
+        names.push(format!("\\fB\\-{}\\fR", escape(&short.to_string())));
+    }
+    if let Some(long) = arg.get_long() {
+        names.push(format!("\\fB\\-\\-{}\\fR", escape(long)));
+    }
+    let mut heading = names.join(", ");
+    if arg.get_action().takes_values() {
+        let _ = write!(heading, " \\fI{}\\fR", value_name(arg));
+    }
+    heading
+}
+
+fn value_name(arg: &Arg) -> String {
+    let name = arg
+        .get_value_names()
+        .and_then(|names| names.first())
+        .map_or_else(
+            || arg.get_id().to_string().to_uppercase(),
+            ToString::to_string,
+        );
+    format!("<{}>", escape(&name))
+}
+
+/// Keeps roff from reading text as requests or escapes
+fn escape(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let line = line.replace('\\', "\\e").replace('-', "\\-");
+            if line.starts_with(['.', '\'']) {
+                format!("\\&{line}")
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+    use crate::cli::Cli;
+
+    #[test]
+    fn there_is_a_page_per_command() {
+        let pages = pages(&Cli::command());
+        let (name, main_page) = &pages[0];
+        assert_eq!(name, "busy-bee.1");
+        assert!(main_page.starts_with(".TH BUSY\\-BEE 1 "));
+        assert!(main_page.contains("\\fB\\-\\-dry\\-run\\fR"));
+        assert!(main_page.contains(".TP\n\\fBclock\\-in\\fR\n"));
+
+        let (_, clock_in) = pages
+            .iter()
+            .find(|(name, _)| name == "busy-bee-clock-in.1")
+            .unwrap();
+        assert!(
+            clock_in.contains(".SH EXAMPLES\n.nf\nbusy\\-bee clock\\-in\n")
+        );
+        assert!(clock_in
+            .contains("\\fB\\-d\\fR, \\fB\\-\\-date\\fR \\fI<DATE>\\fR"));
+        assert!(clock_in.contains(".SH SEE ALSO\n\\fBbusy\\-bee\\fR(1)"));
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(escape(".hidden\nC:\\x"), "\\&.hidden\nC:\\ex");
+    }
+}
@@ -0,0 +1,147 @@
+//! Combining the records of another storage directory with these, e.g.,
+//! when two machines recorded time at once. Each day gets the events of
+//! both, without duplicates. Days whose combined events don't make sense,
+//! e.g., two clock-ins in a row, are left as they are and reported, so
+//! that they can be resolved by hand.
+
+use std::fmt::Write;
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDate};
+
+use crate::{
+    data::{find_duplicates, Event, Storage},
+    edit::validate,
+    import::{describe, imported},
+};
+
+/// A day that can't be merged cleanly
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Conflict {
+    pub date: NaiveDate,
+    pub problem: String,
+    pub mine: Vec<Event>,
+    pub theirs: Vec<Event>,
+}
+
+/// What merging would change
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Plan {
+    /// The days that get new events, with all their events
+    pub days: Vec<(NaiveDate, Vec<Event>)>,
+    /// How many events are new
+    pub added: usize,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Combines the events of `theirs` with those of `mine` day by day,
+/// treating events less than `window` apart as the same
+pub fn plan(
+    mine: &dyn Storage,
+    theirs: &dyn Storage,
+    window: Duration,
+) -> Result<Plan> {
+    let mut dates = mine.days()?;
+    dates.extend(theirs.days()?);
+    dates.sort();
+    dates.dedup();
+
+    let mut plan = Plan::default();
+    for date in dates {
+        let my_events = mine.read_day(date)?;
+        let their_events = theirs.read_day(date)?;
+        let mut union = my_events.clone();
+        union.extend(their_events.iter().map(imported));
+        let (merged, _) = find_duplicates(&union, window);
+        if merged.len() == my_events.len() {
+            continue;
+        }
+        match validate(date, &merged) {
+            Ok(()) => {
+                plan.added += merged.len() - my_events.len();
+                plan.days.push((date, merged));
+            }
+            Err(err) => plan.conflicts.push(Conflict {
+                date,
+                problem: format!("{err:#}"),
+                mine: my_events,
+                theirs: their_events,
+            }),
+        }
+    }
+    Ok(plan)
+}
+
+/// Lists each conflicting day with the events on either side
+pub fn render_conflicts(conflicts: &[Conflict]) -> String {
+    let mut result = String::new();
+    for conflict in conflicts {
+        writeln!(result, "{}: {}", conflict.date, conflict.problem).unwrap();
+        for (side, events) in
+            [("mine", &conflict.mine), ("theirs", &conflict.theirs)]
+        {
+            writeln!(result, "  {side}:").unwrap();
+            for event in events {
+                writeln!(result, "   {}", describe(event)).unwrap();
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::data::{self, create_events, Format, Layout};
+
+    #[test]
+    fn days_are_merged_unless_they_conflict() {
+        let (a, b) = (tempdir().unwrap(), tempdir().unwrap());
+        let mine = data::open(a.path(), Format::Csv, Layout::Day);
+        let theirs = data::open(b.path(), Format::Jsonl, Layout::Month);
+        let at = |d, h, m| Utc.with_ymd_and_hms(2024, 3, d, h, m, 0).unwrap();
+        create_events(
+            mine.as_ref(),
+            &[
+                Event::clock_in(&at(1, 9, 0)),
+                Event::clock_out(&at(1, 12, 0)),
+                Event::clock_in(&at(4, 9, 0)),
+                Event::clock_out(&at(4, 17, 0)),
+            ],
+        )
+        .unwrap();
+        create_events(
+            theirs.as_ref(),
+            &[
+                // the same morning, and an afternoon that only they have
+                Event::clock_in(&at(1, 9, 0)),
+                Event::clock_out(&at(1, 12, 0)),
+                Event::clock_in(&at(1, 13, 0)),
+                Event::clock_out(&at(1, 17, 0)),
+                // overlaps with mine
+                Event::clock_in(&at(4, 10, 0)),
+                Event::clock_out(&at(4, 18, 0)),
+                Event::clock_in(&at(5, 9, 0)),
+            ],
+        )
+        .unwrap();
+
+        let plan = plan(mine.as_ref(), theirs.as_ref(), Duration::minutes(1))
+            .unwrap();
+        let dates: Vec<_> = plan.days.iter().map(|(date, _)| *date).collect();
+        assert_eq!(
+            dates,
+            [at(1, 0, 0).date_naive(), at(5, 0, 0).date_naive()]
+        );
+        assert_eq!(plan.days[0].1.len(), 4);
+        assert_eq!(plan.added, 3);
+        assert_eq!(plan.conflicts.len(), 1);
+        assert_eq!(plan.conflicts[0].date, at(4, 0, 0).date_naive());
+        assert!(render_conflicts(&plan.conflicts).starts_with(
+            "2024-03-04: Clock-ins and clock-outs must alternate"
+        ));
+    }
+}
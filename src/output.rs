@@ -0,0 +1,173 @@
+//! Where commands send what they'd otherwise print
+
+use std::{
+    env, fs,
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Context, Result};
+
+/// Where the output of `view`, `report` and `export` goes
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Destination {
+    Stdout,
+    /// Stdout, through the user's pager if stdout is a terminal and the
+    /// output is longer than it's high
+    Pager,
+    /// A file that is written as UTF-8 whatever the console's encoding
+    File(PathBuf),
+    Clipboard,
+}
+
+impl Destination {
+    pub fn send(&self, content: &str) -> Result<()> {
+        match self {
+            Destination::Stdout => {
+                io::stdout().write_all(content.as_bytes())?;
+            }
+            Destination::Pager => page(content)?,
+            Destination::File(path) => {
+                fs::write(path, content).with_context(|| {
+                    format!("Could not write to {}", path.display())
+                })?;
+            }
+            Destination::Clipboard => copy_to_clipboard(content)?,
+        }
+        Ok(())
+    }
+}
+
+/// Shows `content` in `$PAGER`, default is `less`, unless it fits on the
+/// terminal or there is none
+fn page(content: &str) -> Result<()> {
+    let print = || Ok(io::stdout().lock().write_all(content.as_bytes())?);
+    if !io::stdout().is_terminal()
+        || terminal_height()
+            .is_some_and(|height| content.lines().count() < height)
+    {
+        return print();
+    }
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    let mut words = pager.split_whitespace();
+    let Some(program) = words.next().filter(|program| *program != "cat")
+    else {
+        return print();
+    };
+    let mut command = Command::new(program);
+    command.args(words).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        // Like git: quit if it fits after all, keep colors, don't clear
+        command.env("LESS", "FRX");
+    }
+    let Ok(mut child) = command.spawn() else {
+        return print();
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        match stdin.write_all(content.as_bytes()) {
+            // The user quit the pager before reading everything
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+            result => result?,
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// The number of lines of the terminal, from `LINES` or `stty`
+fn terminal_height() -> Option<usize> {
+    if let Some(lines) = env::var("LINES").ok().and_then(|l| l.parse().ok()) {
+        return Some(lines);
+    }
+    stty_size().map(|(lines, _)| lines)
+}
+
+/// The number of columns of the terminal that stdout is, from `COLUMNS` or
+/// `stty`, if it is one
+pub fn terminal_width() -> Option<usize> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+    let columns = env::var("COLUMNS").ok().and_then(|c| c.parse().ok());
+    columns.or_else(|| stty_size().map(|(_, columns)| columns))
+}
+
+/// The lines and columns of the terminal, as `stty size` prints them
+fn stty_size() -> Option<(usize, usize)> {
+    let output = Command::new("stty")
+        .arg("size")
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let size = String::from_utf8(output.stdout).ok()?;
+    let mut numbers = size.split_whitespace().map(str::parse);
+    Some((numbers.next()?.ok()?, numbers.next()?.ok()?))
+}
+
+/// The programs that put what they read from stdin on the clipboard, in
+/// the order they're tried
+fn clipboard_programs() -> &'static [&'static [&'static str]] {
+    if cfg!(target_os = "macos") {
+        &[&["pbcopy"]]
+    } else if cfg!(windows) {
+        &[&[
+            "powershell",
+            "-NoProfile",
+            "-Command",
+            "[Console]::InputEncoding = [Text.Encoding]::UTF8; \
+            $input | Set-Clipboard",
+        ]]
+    } else {
+        &[
+            &["wl-copy"],
+            &["xclip", "-selection", "clipboard"],
+            &["xsel", "--clipboard", "--input"],
+        ]
+    }
+}
+
+fn copy_to_clipboard(content: &str) -> Result<()> {
+    for program in clipboard_programs() {
+        let Ok(mut child) = Command::new(program[0])
+            .args(&program[1..])
+            .stdin(Stdio::piped())
+            .spawn()
+        else {
+            continue;
+        };
+        // Dropping stdin closes it, so that the program stops reading
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(content.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            bail!("{} exited with {status}", program[0]);
+        }
+        return Ok(());
+    }
+    let names: Vec<_> = clipboard_programs()
+        .iter()
+        .map(|program| program[0])
+        .collect();
+    bail!(
+        "Could not copy to the clipboard, please install {}",
+        names.join(" or ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn files_are_written_as_utf8() {
+        let d = tempdir().unwrap();
+        let path = d.path().join("report.md");
+        Destination::File(path.clone()).send("Übersicht\n").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), "Übersicht\n".as_bytes());
+    }
+}
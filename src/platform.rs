@@ -0,0 +1,168 @@
+//! Differences between the file systems of the operating systems that the
+//! storage works around, so that records can be moved between them: Windows
+//! reserves names like `nul` and has `\\?\` paths, and Windows and macOS
+//! ignore the case of file names while Linux doesn't.
+
+use std::{
+    cell::OnceCell,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The names of devices on Windows, which no file may have, whatever its
+/// extension
+const RESERVED: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5",
+    "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5",
+    "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Whether Windows refuses `name` as the name of a file or directory, e.g.,
+/// `nul` or `com1.csv`
+pub fn is_reserved(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    RESERVED
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+/// Removes the `\\?\` prefix that, e.g., `fs::canonicalize` gives paths on
+/// Windows and that many programs can't handle, so `\\?\C:\records` becomes
+/// `C:\records` and `\\?\UNC\server\share` becomes `\\server\share`
+pub fn normalize(path: &Path) -> PathBuf {
+    let Some(path_str) = path.to_str() else {
+        return path.to_path_buf();
+    };
+    if let Some(share) = path_str.strip_prefix(r"\\?\UNC\") {
+        return PathBuf::from(format!(r"\\{share}"));
+    }
+    match path_str.strip_prefix(r"\\?\") {
+        Some(rest) if has_drive(rest) => PathBuf::from(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn has_drive(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// Finds the files in a directory whose names only differ in case from
+/// the ones asked for, e.g., `2024-01-02.CSV` for `2024-01-02.csv`, as
+/// found after copying records from Windows to Linux. The directory is
+/// listed once, when first asked, as listing it for each file that doesn't
+/// exist is slow on network shares.
+#[derive(Debug)]
+pub struct CaseVariants {
+    dir: PathBuf,
+    names: OnceCell<Vec<String>>,
+}
+
+impl CaseVariants {
+    pub fn new(dir: &Path) -> CaseVariants {
+        CaseVariants {
+            dir: dir.to_path_buf(),
+            names: OnceCell::new(),
+        }
+    }
+
+    /// The file whose name only differs in case from that of `file_path`,
+    /// if `file_path` is in the directory and there is one
+    pub fn find(&self, file_path: &Path) -> io::Result<Option<PathBuf>> {
+        let Some(name) = file_path.file_name() else {
+            return Ok(None);
+        };
+        if parent_dir(file_path) != self.dir {
+            return Ok(None);
+        }
+        let name = name.to_string_lossy();
+        let names = if let Some(names) = self.names.get() {
+            names
+        } else {
+            let names = list_names(&self.dir)?;
+            self.names.get_or_init(|| names)
+        };
+        Ok(names
+            .iter()
+            .find(|other| **other != name && other.eq_ignore_ascii_case(&name))
+            .map(|other| self.dir.join(other)))
+    }
+}
+
+fn list_names(dir: &Path) -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Ok(Vec::new())
+        }
+        Err(err) => return Err(err),
+    };
+    entries
+        .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+        .collect()
+}
+
+/// The directory that `file_path` is in, which is the current one for bare
+/// file names
+pub fn parent_dir(file_path: &Path) -> &Path {
+    match file_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_names_are_reserved() {
+        assert!(is_reserved("nul"));
+        assert!(is_reserved("COM1.csv"));
+        assert!(is_reserved("Aux .jsonl"));
+        assert!(!is_reserved("console"));
+        assert!(!is_reserved("2024-01-02.csv"));
+    }
+
+    #[test]
+    fn case_variants_are_found_from_one_listing() {
+        let d = tempfile::tempdir().unwrap();
+        fs::write(d.path().join("2024-01-02.CSV"), "").unwrap();
+        let variants = CaseVariants::new(d.path());
+        let find = |name| variants.find(&d.path().join(name)).unwrap();
+        assert_eq!(
+            find("2024-01-02.csv"),
+            Some(d.path().join("2024-01-02.CSV"))
+        );
+        assert_eq!(find("2024-01-02.CSV"), None);
+        assert_eq!(find("2024-01-03.csv"), None);
+        // not listed again
+        fs::write(d.path().join("2024-01-03.CSV"), "").unwrap();
+        assert_eq!(find("2024-01-03.csv"), None);
+        let other = tempfile::tempdir().unwrap();
+        assert_eq!(
+            variants.find(&other.path().join("2024-01-02.csv")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn normalize_removes_verbatim_prefixes() {
+        assert_eq!(
+            normalize(Path::new(r"\\?\C:\records")),
+            PathBuf::from(r"C:\records")
+        );
+        assert_eq!(
+            normalize(Path::new(r"\\?\UNC\server\share\records")),
+            PathBuf::from(r"\\server\share\records")
+        );
+        assert_eq!(
+            normalize(Path::new(r"\\?\Volume{1234}\records")),
+            PathBuf::from(r"\\?\Volume{1234}\records")
+        );
+        assert_eq!(
+            normalize(Path::new("/home/me/records")),
+            PathBuf::from("/home/me/records")
+        );
+    }
+}
@@ -0,0 +1,32 @@
+//! The types and functions that other applications need to record events
+//! and produce reports, e.g., `use busy_bee::prelude::*;`
+
+pub use crate::{
+    earnings::Earnings,
+    expense::Expense,
+    interval::{intervals, Interval},
+    record::{
+        events_to_string, parse_events, DayNote, Event, EventKind, Format,
+        PersistenceError, Source, StoredEvent,
+    },
+    report::{DayTotal, MonthTotals, Report, ReportOptions, YearReport},
+    status::Status,
+    view::{
+        client_report, daily_report, daily_report_with_ids, expense_report,
+        location_report, monthly_report, range_report, tag_report,
+        travel_report, week_timeline, DailyReport, Grouping, MonthlyReport,
+        ReportFormat, ReportRow, RowFilter, RowOptions, ViewError, TRAVEL_TAG,
+    },
+};
+
+#[cfg(feature = "fs")]
+pub use crate::{
+    config::Config,
+    data::{
+        create_event, delete_event, open, read_events, read_range,
+        read_stored_events, Created, Layout, Storage,
+    },
+};
+
+#[cfg(feature = "async")]
+pub use crate::async_storage::{AsyncStorage, Blocking};
@@ -0,0 +1,517 @@
+//! A small language to add up records from scripts, e.g.,
+//! `sum(hours) where project = 'acme' and month = 2024-03 by tag`.
+//!
+//! A query adds up rows, one per interval worked (kind `work`), on standby
+//! (`standby`) or interruption (`interruption`). It starts with `sum`,
+//! `avg` or `count` of `hours`, `minutes` or `*`, may filter the rows with
+//! `where` and conditions joined by `and`, and may group them with `by`,
+//! which gives one line per group instead of a single value. Conditions
+//! compare `project`, `tag` or `kind` with `=` or `!=`, and `date`, `month`
+//! or `year` with any of `=`, `!=`, `<`, `<=`, `>` and `>=`. Without a
+//! condition on `kind`, only `work` rows count, as interruptions fall within
+//! the time worked and standby isn't working time.
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Utc};
+
+use crate::{
+    csv,
+    interval::{intervals, standby},
+    record::{Event, EventKind},
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Aggregate {
+    Sum,
+    Avg,
+    Count,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Field {
+    Hours,
+    Minutes,
+    /// Only counted
+    Rows,
+}
+
+/// What rows are compared or grouped by
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Key {
+    Project,
+    Tag,
+    Kind,
+    Date,
+    Month,
+    Year,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Condition {
+    key: Key,
+    op: Op,
+    value: String,
+}
+
+/// A parsed query, see the module documentation
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Query {
+    aggregate: Aggregate,
+    field: Field,
+    conditions: Vec<Condition>,
+    by: Option<Key>,
+}
+
+/// What a query adds up: a span of time of some kind
+struct Row {
+    kind: &'static str,
+    date: NaiveDate,
+    duration: Duration,
+    project: Option<String>,
+    tags: Vec<String>,
+}
+
+/// The result of a [`Query`], a value or one per group, which is written
+/// as a single value or as CSV
+#[derive(Debug, PartialEq, Clone)]
+pub enum Answer {
+    Value(String),
+    Groups {
+        header: [String; 2],
+        rows: Vec<(String, String)>,
+    },
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Query> {
+        let tokens = tokenize(query)?;
+        let mut tokens = tokens.iter().map(String::as_str).peekable();
+        let aggregate = match tokens.next() {
+            Some("sum") => Aggregate::Sum,
+            Some("avg") => Aggregate::Avg,
+            Some("count") => Aggregate::Count,
+            other => bail!(
+                "Expected sum, avg or count at the start, found {}",
+                other.unwrap_or("nothing")
+            ),
+        };
+        expect(tokens.next(), "(")?;
+        let field = match tokens.next() {
+            Some("hours") => Field::Hours,
+            Some("minutes") => Field::Minutes,
+            Some("*") if aggregate == Aggregate::Count => Field::Rows,
+            other => bail!(
+                "Expected hours or minutes, or * to count, found {}",
+                other.unwrap_or("nothing")
+            ),
+        };
+        expect(tokens.next(), ")")?;
+        let mut conditions = Vec::new();
+        if tokens.next_if_eq(&"where").is_some() {
+            loop {
+                conditions.push(condition(&mut tokens)?);
+                if tokens.next_if_eq(&"and").is_none() {
+                    break;
+                }
+            }
+        }
+        let by = match tokens.next() {
+            Some("by") => Some(key(tokens.next())?),
+            Some(other) => bail!("Expected where, and or by, found {other}"),
+            None => None,
+        };
+        if let Some(extra) = tokens.next() {
+            bail!("Unexpected {extra} at the end");
+        }
+        Ok(Query {
+            aggregate,
+            field,
+            conditions,
+            by,
+        })
+    }
+
+    /// Whether rows on `date` may match, so that other days needn't be read
+    pub fn covers(&self, date: NaiveDate) -> bool {
+        self.conditions
+            .iter()
+            .filter(|condition| {
+                matches!(condition.key, Key::Date | Key::Month | Key::Year)
+            })
+            .all(|condition| condition.matches_date(date))
+    }
+
+    /// Adds up the rows of `events` that match the conditions
+    pub fn run(&self, events: &[Event]) -> Answer {
+        let any_kind = self.conditions.iter().any(|c| c.key == Key::Kind);
+        let rows: Vec<Row> = rows(events)
+            .into_iter()
+            .filter(|row| any_kind || row.kind == "work")
+            .filter(|row| self.conditions.iter().all(|c| c.matches(row)))
+            .collect();
+        let Some(by) = self.by else {
+            let rows: Vec<&Row> = rows.iter().collect();
+            return Answer::Value(self.aggregate(&rows));
+        };
+        let mut groups: BTreeMap<String, Vec<&Row>> = BTreeMap::new();
+        for row in &rows {
+            for group in by.values(row) {
+                groups.entry(group).or_default().push(row);
+            }
+        }
+        Answer::Groups {
+            header: [by.name().to_string(), self.heading()],
+            rows: groups
+                .into_iter()
+                .map(|(group, rows)| (group, self.aggregate(&rows)))
+                .collect(),
+        }
+    }
+
+    fn aggregate(&self, rows: &[&Row]) -> String {
+        if self.aggregate == Aggregate::Count {
+            return rows.len().to_string();
+        }
+        let total: Duration = rows.iter().map(|row| row.duration).sum();
+        #[allow(clippy::cast_precision_loss)]
+        let minutes = total.num_minutes() as f64;
+        let value = match self.aggregate {
+            Aggregate::Avg if rows.is_empty() => 0.0,
+            #[allow(clippy::cast_precision_loss)]
+            Aggregate::Avg => minutes / rows.len() as f64,
+            _ => minutes,
+        };
+        match self.field {
+            Field::Hours => format!("{:.2}", value / 60.0),
+            Field::Minutes if self.aggregate == Aggregate::Sum => {
+                format!("{value}")
+            }
+            _ => format!("{value:.2}"),
+        }
+    }
+
+    /// Names the value as it was asked for, e.g., `sum(hours)`
+    fn heading(&self) -> String {
+        let aggregate = match self.aggregate {
+            Aggregate::Sum => "sum",
+            Aggregate::Avg => "avg",
+            Aggregate::Count => "count",
+        };
+        let field = match self.field {
+            Field::Hours => "hours",
+            Field::Minutes => "minutes",
+            Field::Rows => "*",
+        };
+        format!("{aggregate}({field})")
+    }
+}
+
+impl Display for Answer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Answer::Value(value) => writeln!(f, "{value}"),
+            Answer::Groups { header, rows } => {
+                writeln!(f, "{},{}", header[0], header[1])?;
+                for (group, value) in rows {
+                    writeln!(f, "{},{value}", csv::escape(group))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Key {
+    fn name(self) -> &'static str {
+        match self {
+            Key::Project => "project",
+            Key::Tag => "tag",
+            Key::Kind => "kind",
+            Key::Date => "date",
+            Key::Month => "month",
+            Key::Year => "year",
+        }
+    }
+
+    /// The values of `row` for the key, several for tags and none for a
+    /// row without tags
+    fn values(self, row: &Row) -> Vec<String> {
+        match self {
+            Key::Project => vec![row.project.clone().unwrap_or_default()],
+            Key::Tag => row.tags.clone(),
+            Key::Kind => vec![row.kind.to_string()],
+            Key::Date => vec![row.date.to_string()],
+            Key::Month => vec![row.date.format("%Y-%m").to_string()],
+            Key::Year => vec![row.date.year().to_string()],
+        }
+    }
+
+    /// The day, month or year of `date`, zero-padded so that they sort
+    /// like text
+    fn values_of_date(self, date: NaiveDate) -> String {
+        match self {
+            Key::Month => date.format("%Y-%m").to_string(),
+            Key::Year => date.year().to_string(),
+            _ => date.to_string(),
+        }
+    }
+}
+
+impl Condition {
+    fn matches(&self, row: &Row) -> bool {
+        match self.key {
+            Key::Date | Key::Month | Key::Year => self.matches_date(row.date),
+            Key::Tag => row.tags.contains(&self.value) == (self.op == Op::Eq),
+            Key::Project | Key::Kind => {
+                self.key.values(row).contains(&self.value)
+                    == (self.op == Op::Eq)
+            }
+        }
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        let value = self.key.values_of_date(date);
+        let ordering = value.as_str().cmp(self.value.as_str());
+        match self.op {
+            Op::Eq => ordering.is_eq(),
+            Op::Ne => ordering.is_ne(),
+            Op::Lt => ordering.is_lt(),
+            Op::Le => ordering.is_le(),
+            Op::Gt => ordering.is_gt(),
+            Op::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+fn expect(token: Option<&str>, expected: &str) -> Result<()> {
+    match token {
+        Some(token) if token == expected => Ok(()),
+        other => {
+            bail!("Expected {expected}, found {}", other.unwrap_or("nothing"))
+        }
+    }
+}
+
+fn key(token: Option<&str>) -> Result<Key> {
+    match token {
+        Some("project") => Ok(Key::Project),
+        Some("tag") => Ok(Key::Tag),
+        Some("kind") => Ok(Key::Kind),
+        Some("date") => Ok(Key::Date),
+        Some("month") => Ok(Key::Month),
+        Some("year") => Ok(Key::Year),
+        other => bail!(
+            "Expected project, tag, kind, date, month or year, found {}",
+            other.unwrap_or("nothing")
+        ),
+    }
+}
+
+fn condition<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+) -> Result<Condition> {
+    let key = key(tokens.next())?;
+    let op = match tokens.next() {
+        Some("=") => Op::Eq,
+        Some("!=") => Op::Ne,
+        Some("<") => Op::Lt,
+        Some("<=") => Op::Le,
+        Some(">") => Op::Gt,
+        Some(">=") => Op::Ge,
+        other => bail!(
+            "Expected a comparison after {}, found {}",
+            key.name(),
+            other.unwrap_or("nothing")
+        ),
+    };
+    let value = tokens
+        .next()
+        .ok_or(anyhow!("Expected a value after {}", key.name()))?;
+    let value = value.strip_prefix('\'').unwrap_or(value).to_string();
+    let is_date = |value: &str| {
+        value.len() == 10
+            && NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok()
+    };
+    let (valid, example) = match key {
+        Key::Project | Key::Tag | Key::Kind => {
+            if !matches!(op, Op::Eq | Op::Ne) {
+                bail!("{} can only be compared with = or !=", key.name());
+            }
+            (true, "")
+        }
+        Key::Date => (is_date(&value), "2024-03-01"),
+        Key::Month => (is_date(&format!("{value}-01")), "2024-03"),
+        Key::Year => (is_date(&format!("{value}-01-01")), "2024"),
+    };
+    if !valid {
+        bail!("Invalid {} {value}, try e.g. {example}", key.name());
+    }
+    Ok(Condition { key, op, value })
+}
+
+/// Splits a query into words, operators, parentheses and quoted text, which
+/// keeps its opening quote to tell it from a word
+fn tokenize(query: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' | ')' | '*' => tokens.push(c.to_string()),
+            '=' => tokens.push("=".to_string()),
+            '!' | '<' | '>' => {
+                let mut op = c.to_string();
+                if chars.next_if_eq(&'=').is_some() {
+                    op.push('=');
+                } else if c == '!' {
+                    bail!("Expected != in the query");
+                }
+                tokens.push(op);
+            }
+            '\'' | '"' => {
+                let mut text = String::from('\'');
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        Some(other) => text.push(other),
+                        None => bail!("Unclosed quote in the query"),
+                    }
+                }
+                tokens.push(text);
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| {
+                    !c.is_whitespace() && !"()*=!<>'\"".contains(*c)
+                }) {
+                    word.push(c);
+                }
+                tokens.push(word);
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// The rows of `events`, dated by the local day they start on
+fn rows(events: &[Event]) -> Vec<Row> {
+    let date = |dt: DateTime<Utc>| DateTime::<Local>::from(dt).date_naive();
+    let worked = intervals(events).into_iter().map(|interval| Row {
+        kind: "work",
+        date: date(interval.start),
+        duration: interval.duration(),
+        project: interval.project,
+        tags: interval.tags,
+    });
+    let on_standby = standby(events).into_iter().map(|interval| Row {
+        kind: "standby",
+        date: date(interval.start),
+        duration: interval.duration(),
+        project: None,
+        tags: Vec::new(),
+    });
+    let interruptions = events.iter().filter_map(|event| match event.kind {
+        EventKind::Interruption { minutes } => Some(Row {
+            kind: "interruption",
+            date: date(event.dt),
+            duration: Duration::minutes(minutes.into()),
+            project: event.project.clone(),
+            tags: event.tags.clone(),
+        }),
+        _ => None,
+    });
+    worked.chain(on_standby).chain(interruptions).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn events() -> Vec<Event> {
+        let at = |m, d, h| {
+            Local
+                .with_ymd_and_hms(2024, m, d, h, 0, 0)
+                .unwrap()
+                .to_utc()
+        };
+        let tags = vec!["billable".to_string()];
+        vec![
+            Event::clock_in(&at(3, 1, 9))
+                .with_project(Some("acme".to_string()))
+                .with_tags(tags.clone()),
+            Event::clock_out(&at(3, 1, 12)),
+            Event::clock_in(&at(3, 1, 13)).with_project(Some("web".into())),
+            Event::clock_out(&at(3, 1, 17)),
+            Event::clock_in(&at(4, 2, 9))
+                .with_project(Some("acme".to_string()))
+                .with_tags(tags),
+            Event::clock_out(&at(4, 2, 11)),
+            Event::interruption(&at(4, 2, 10), 30, "alert".to_string())
+                .with_project(Some("acme".to_string())),
+        ]
+    }
+
+    fn run(query: &str) -> String {
+        Query::parse(query).unwrap().run(&events()).to_string()
+    }
+
+    #[test]
+    fn queries_add_up_matching_rows() {
+        assert_eq!(
+            run("sum(hours) where project = 'acme' and month = 2024-03"),
+            "3.00\n"
+        );
+        assert_eq!(run("sum(minutes) where kind=work"), "540\n");
+        assert_eq!(run("avg(hours) where kind = work"), "3.00\n");
+        assert_eq!(run("count(*) where date >= 2024-03-02"), "1\n");
+        assert_eq!(run("count(*) where tag != billable"), "1\n");
+        // interruptions fall within the time worked
+        assert_eq!(run("sum(hours) where project = acme"), "5.00\n");
+        assert_eq!(run("sum(minutes) where kind = interruption"), "30\n");
+        assert_eq!(run("count(*) where kind != standby"), "4\n");
+        assert_eq!(
+            run("sum(hours) where kind = work by project"),
+            "project,sum(hours)\nacme,5.00\nweb,4.00\n"
+        );
+        assert_eq!(
+            run("count(*) by month"),
+            "month,count(*)\n2024-03,2\n2024-04,1\n"
+        );
+
+        let query = Query::parse("count(*) where year < 2024").unwrap();
+        assert!(!query.covers(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()));
+        assert!(query.covers(NaiveDate::from_ymd_opt(2023, 12, 31).unwrap()));
+    }
+
+    #[test]
+    fn mistakes_in_queries_are_pointed_out() {
+        for query in [
+            "",
+            "total(hours)",
+            "sum(*)",
+            "sum(hours) where",
+            "sum(hours) where project < acme",
+            "sum(hours) where month = 2024-3",
+            "sum(hours) where date = march",
+            "sum(hours) where project = 'acme",
+            "sum(hours) by week",
+            "sum(hours) project = acme",
+        ] {
+            assert!(Query::parse(query).is_err(), "{query}");
+        }
+    }
+}
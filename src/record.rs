@@ -0,0 +1,773 @@
+//! How events are represented and serialized, independent of where they
+//! are stored
+
+use std::{
+    borrow::Cow, error::Error, fmt::Display, io, path::PathBuf, str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+
+use crate::{csv, json};
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PersistenceError {
+    EventNotFoundError { id: u32 },
+    InvalidFormatError,
+    InvalidDataError { detail: String },
+    IoError { err: io::Error },
+    CorruptedFile { path: PathBuf },
+    PermissionDenied { path: PathBuf },
+}
+
+impl Display for PersistenceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EventNotFoundError { id } => {
+                write!(f, "There is no event with ID {id}")
+            }
+            Self::InvalidFormatError => write!(f, "Invalid file format"),
+            Self::InvalidDataError { detail } => {
+                write!(f, "Invalid data: {detail}")
+            }
+            Self::IoError { err } => write!(f, "IO error: {err}"),
+            Self::CorruptedFile { path } => write!(
+                f,
+                "{} does not match its checksum; it might have been \
+                truncated or edited by hand. Check its content, then run \
+                'busy-bee doctor --fix' to accept it as it is.",
+                path.display()
+            ),
+            Self::PermissionDenied { path } => write!(
+                f,
+                "Permission denied to read {}; check its owner and mode",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl Error for PersistenceError {}
+
+impl From<io::Error> for PersistenceError {
+    fn from(err: std::io::Error) -> Self {
+        Self::IoError { err }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum EventKind {
+    ClockIn,
+    ClockOut,
+    /// On-call standby, which is tracked separately from working time
+    StandbyStart,
+    StandbyEnd,
+    /// A context switch of `minutes` within a working block, starting at
+    /// the time of the event
+    Interruption {
+        minutes: u32,
+    },
+}
+
+impl EventKind {
+    /// Whether the event starts or ends a working block
+    pub fn is_clock(&self) -> bool {
+        matches!(self, EventKind::ClockIn | EventKind::ClockOut)
+    }
+
+    pub fn is_standby(&self) -> bool {
+        matches!(self, EventKind::StandbyStart | EventKind::StandbyEnd)
+    }
+}
+
+/// How an event came to be recorded
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum Source {
+    /// Typed on the command line
+    Manual,
+    /// Recorded by a program running in the background, e.g., one that
+    /// clocks out when the computer is idle
+    Daemon,
+    /// Added from a file or another storage directory
+    Import,
+    /// Requested through the JSON interface, e.g., by an editor plugin
+    Api,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Source::Manual => "manual",
+            Source::Daemon => "daemon",
+            Source::Import => "import",
+            Source::Api => "api",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Source {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "manual" => Ok(Source::Manual),
+            "daemon" => Ok(Source::Daemon),
+            "import" => Ok(Source::Import),
+            "api" => Ok(Source::Api),
+            _ => Err(format!(
+                "Unknown source {s}, try manual, daemon, import or api"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct Event {
+    pub kind: EventKind,
+    pub dt: DateTime<Utc>,
+    /// What was worked on, only set on clock-ins
+    pub project: Option<String>,
+    /// Labels for the time following a clock-in, e.g., `meeting`
+    pub tags: Vec<String>,
+    /// What an interruption was about
+    pub note: Option<String>,
+    /// Where the time following a clock-in was worked, e.g., `office` or
+    /// `home`
+    pub location: Option<String>,
+    /// How the event was recorded, unknown for events recorded before this
+    /// was
+    pub source: Option<Source>,
+}
+
+impl Event {
+    pub fn clock_in<Tz: TimeZone>(dt: &DateTime<Tz>) -> Event {
+        Self {
+            kind: EventKind::ClockIn,
+            dt: dt.to_utc(),
+            project: None,
+            tags: Vec::new(),
+            note: None,
+            location: None,
+            source: None,
+        }
+    }
+
+    pub fn clock_out<Tz: TimeZone>(dt: &DateTime<Tz>) -> Event {
+        Self {
+            kind: EventKind::ClockOut,
+            dt: dt.to_utc(),
+            project: None,
+            tags: Vec::new(),
+            note: None,
+            location: None,
+            source: None,
+        }
+    }
+
+    pub fn standby_start<Tz: TimeZone>(dt: &DateTime<Tz>) -> Event {
+        Self {
+            kind: EventKind::StandbyStart,
+            ..Event::clock_in(dt)
+        }
+    }
+
+    pub fn standby_end<Tz: TimeZone>(dt: &DateTime<Tz>) -> Event {
+        Self {
+            kind: EventKind::StandbyEnd,
+            ..Event::clock_in(dt)
+        }
+    }
+
+    pub fn interruption<Tz: TimeZone>(
+        dt: &DateTime<Tz>,
+        minutes: u32,
+        note: String,
+    ) -> Event {
+        Self {
+            kind: EventKind::Interruption { minutes },
+            note: Some(note),
+            ..Event::clock_in(dt)
+        }
+    }
+
+    #[must_use]
+    pub fn with_project(mut self, project: Option<String>) -> Event {
+        self.project = project;
+        self
+    }
+
+    #[must_use]
+    pub fn with_tags(mut self, tags: Vec<String>) -> Event {
+        self.tags = tags;
+        self
+    }
+
+    #[must_use]
+    pub fn with_location(mut self, location: Option<String>) -> Event {
+        self.location = location;
+        self
+    }
+
+    #[must_use]
+    pub fn with_source(mut self, source: Source) -> Event {
+        self.source = Some(source);
+        self
+    }
+}
+
+/// A free-form note on a day as a whole, e.g., what was deployed
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DayNote {
+    pub date: NaiveDate,
+    pub text: String,
+}
+
+impl DayNote {
+    /// Reads a line like `2024-03-01,deployed release 3.2`
+    pub fn parse(line: &str) -> Result<DayNote> {
+        let cols = csv::split(line)?;
+        let [date, text] = cols.as_slice() else {
+            bail!("Misformatted note: {line}");
+        };
+        Ok(DayNote {
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .with_context(|| format!("Invalid date in note: {line}"))?,
+            text: check_note(text)?,
+        })
+    }
+
+    pub fn to_csv(&self) -> String {
+        format!("{},{}", self.date, csv::escape(&self.text))
+    }
+}
+
+/// An event together with the ID that deleting or editing it takes
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct StoredEvent {
+    pub id: u32,
+    pub event: Event,
+}
+
+impl StoredEvent {
+    /// Numbers all events of a day in the order they're stored, i.e.,
+    /// sorted by time, which gives them their IDs
+    pub fn number(events: &[Event]) -> Vec<StoredEvent> {
+        (0..)
+            .zip(events)
+            .map(|(id, event)| StoredEvent {
+                id,
+                event: event.clone(),
+            })
+            .collect()
+    }
+}
+
+/// The serialization used for stored events
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum Format {
+    /// Positional, comma separated values
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+}
+
+impl Format {
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Jsonl => "jsonl",
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    pub(crate) fn other(self) -> Format {
+        match self {
+            Format::Csv => Format::Jsonl,
+            Format::Jsonl => Format::Csv,
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "jsonl" => Ok(Format::Jsonl),
+            _ => Err(format!("Unknown storage format {s}, try csv or jsonl")),
+        }
+    }
+}
+
+/// Parses events from the content of any data file, e.g., for importing
+/// them. Comment lines, such as checksums, are ignored.
+pub fn parse_events(content: &str) -> Result<Vec<Event>> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            parse_event(line).or_else(|_| parse_row(line).map(|(_, e)| e))
+        })
+        .collect()
+}
+
+/// Serializes events one per line, the way day files store them, but
+/// without a checksum
+pub fn events_to_string(events: &[Event], format: Format) -> String {
+    events
+        .iter()
+        .map(|event| event_to_str(event, format) + "\n")
+        .collect()
+}
+
+/// Parses a row of a month file, which is an event prefixed with its date
+pub(crate) fn parse_row(line: &str) -> Result<(NaiveDate, Event)> {
+    let date_str = if line.trim_start().starts_with('{') {
+        json::parse(line)
+            .ok()
+            .and_then(|value| value.get("date")?.as_str().map(String::from))
+    } else {
+        line.split_once(',')
+            .map(|(date, _)| date.trim().to_string())
+    }
+    .ok_or(anyhow!("Missing date in line: {line}"))?;
+    let date = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
+        .with_context(|| format!("Invalid date in line: {line}"))?;
+
+    let event = if line.trim_start().starts_with('{') {
+        parse_json_event(line)?
+    } else {
+        // the date column was just parsed, so there is a comma
+        parse_event(line.split_once(',').unwrap().1)?
+    };
+    Ok((date, event))
+}
+
+pub(crate) fn parse_event(line: &str) -> Result<Event> {
+    if line.trim_start().starts_with('{') {
+        return parse_json_event(line);
+    }
+
+    let cols = csv::split(line)?;
+    if !(2..=8).contains(&cols.len()) {
+        bail!("Misformatted line: {line}")
+    }
+
+    let mut kind = parse_kind(&cols[0])?;
+    if let EventKind::Interruption { minutes } = &mut kind {
+        *minutes = cols
+            .get(4)
+            .and_then(|minutes| minutes.parse().ok())
+            .ok_or(anyhow!("Missing minutes in line: {line}"))?;
+    }
+    let dt = parse_dt(&cols[1])?;
+    let project = cols.get(2).filter(|p| !p.is_empty()).cloned();
+    let tags = cols.get(3).map_or_else(Vec::new, |tags| {
+        tags.split(';')
+            .filter(|tag| !tag.is_empty())
+            .map(String::from)
+            .collect()
+    });
+    let note = cols.get(5).filter(|note| !note.is_empty()).cloned();
+    let location = cols.get(6).filter(|l| !l.is_empty()).cloned();
+    let source = cols
+        .get(7)
+        .filter(|source| !source.is_empty())
+        .map(|source| parse_source(source))
+        .transpose()?;
+    Ok(Event {
+        kind,
+        dt,
+        project,
+        tags,
+        note,
+        location,
+        source,
+    })
+}
+
+fn parse_json_event(line: &str) -> Result<Event> {
+    let value = json::parse(line)
+        .with_context(|| format!("Misformatted line: {line}"))?;
+    let field = |name: &str| {
+        value
+            .get(name)
+            .and_then(json::Value::as_str)
+            .ok_or(anyhow!("Missing field '{name}' in line: {line}"))
+    };
+    let mut kind = parse_kind(field("kind")?)?;
+    if let EventKind::Interruption { minutes } = &mut kind {
+        *minutes = value
+            .get("minutes")
+            .map(ToString::to_string)
+            .and_then(|minutes| minutes.parse().ok())
+            .ok_or(anyhow!("Missing minutes in line: {line}"))?;
+    }
+    let dt = parse_dt(field("timestamp")?)?;
+    let project = value
+        .get("project")
+        .and_then(json::Value::as_str)
+        .map(|project| check_label(project, Label::Project))
+        .transpose()?;
+    let tags = value
+        .get("tags")
+        .and_then(json::Value::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(json::Value::as_str)
+        .map(|tag| check_label(tag, Label::Tag))
+        .collect::<Result<_, _>>()?;
+    let note = value
+        .get("note")
+        .and_then(json::Value::as_str)
+        .map(check_note)
+        .transpose()?;
+    let location = value
+        .get("location")
+        .and_then(json::Value::as_str)
+        .map(|location| check_label(location, Label::Location))
+        .transpose()?;
+    let source = value
+        .get("source")
+        .and_then(json::Value::as_str)
+        .map(parse_source)
+        .transpose()?;
+    Ok(Event {
+        kind,
+        dt,
+        project,
+        tags,
+        note,
+        location,
+        source,
+    })
+}
+
+/// What a label names, which decides what it may contain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Label {
+    Project,
+    /// Can't contain semicolons, which separate tags
+    Tag,
+    Location,
+}
+
+impl Display for Label {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Label::Project => "project",
+            Label::Tag => "tag",
+            Label::Location => "location",
+        })
+    }
+}
+
+/// Rejects projects, tags and locations that couldn't be stored as CSV, so
+/// that records can always be migrated between formats
+fn check_label(label: &str, kind: Label) -> Result<String, PersistenceError> {
+    let is_valid = !label.is_empty()
+        && label.trim() == label
+        && !label.contains(['\n', '\r'])
+        && (kind != Label::Tag || !label.contains(';'));
+    if !is_valid {
+        return Err(PersistenceError::InvalidDataError {
+            detail: format!("Invalid {kind} '{label}'"),
+        });
+    }
+    Ok(label.to_string())
+}
+
+/// Rejects notes that couldn't be stored as CSV
+pub fn check_note(note: &str) -> Result<String, PersistenceError> {
+    if note.is_empty() || note.trim() != note || note.contains(['\n', '\r']) {
+        return Err(PersistenceError::InvalidDataError {
+            detail: format!("Invalid note '{note}'"),
+        });
+    }
+    Ok(note.to_string())
+}
+
+fn parse_kind(s: &str) -> Result<EventKind, PersistenceError> {
+    match s {
+        "clock-in" => Ok(EventKind::ClockIn),
+        "clock-out" => Ok(EventKind::ClockOut),
+        "standby-start" => Ok(EventKind::StandbyStart),
+        "standby-end" => Ok(EventKind::StandbyEnd),
+        // the minutes are in a separate field
+        "interruption" => Ok(EventKind::Interruption { minutes: 0 }),
+        other => Err(PersistenceError::InvalidDataError {
+            detail: format!("Unknown event kind {other}"),
+        }),
+    }
+}
+
+fn parse_source(s: &str) -> Result<Source, PersistenceError> {
+    s.parse()
+        .map_err(|detail| PersistenceError::InvalidDataError { detail })
+}
+
+fn parse_dt(date_str: &str) -> Result<DateTime<Utc>, PersistenceError> {
+    DateTime::parse_from_rfc3339(date_str)
+        .map_err(|err| PersistenceError::InvalidDataError {
+            detail: format!("Could not parse {date_str} as datetime: {err}"),
+        })
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+pub(crate) fn event_to_str(event: &Event, format: Format) -> String {
+    match format {
+        Format::Csv => event_to_csv(event),
+        Format::Jsonl => json::Value::Object(event_to_json(event)).to_string(),
+    }
+}
+
+#[cfg(feature = "fs")]
+pub(crate) fn row_to_str(
+    date: NaiveDate,
+    event: &Event,
+    format: Format,
+) -> String {
+    match format {
+        Format::Csv => format!("{date},{}", event_to_csv(event)),
+        Format::Jsonl => {
+            let mut entries =
+                vec![("date".to_string(), date.to_string().into())];
+            entries.extend(event_to_json(event));
+            json::Value::Object(entries).to_string()
+        }
+    }
+}
+
+fn kind_to_str(kind: &EventKind) -> &'static str {
+    match kind {
+        EventKind::ClockIn => "clock-in",
+        EventKind::ClockOut => "clock-out",
+        EventKind::StandbyStart => "standby-start",
+        EventKind::StandbyEnd => "standby-end",
+        EventKind::Interruption { .. } => "interruption",
+    }
+}
+
+fn event_to_csv(event: &Event) -> String {
+    let line =
+        format!("{},{}", kind_to_str(&event.kind), event.dt.to_rfc3339());
+    let project = csv::escape(event.project.as_deref().unwrap_or_default());
+    let tags = event.tags.join(";");
+    let tags = csv::escape(&tags);
+    let (minutes, note) = match event.kind {
+        EventKind::Interruption { minutes } => (
+            minutes.to_string(),
+            csv::escape(event.note.as_deref().unwrap_or_default()),
+        ),
+        _ => (String::new(), Cow::Borrowed("")),
+    };
+    if event.location.is_some() || event.source.is_some() {
+        let location =
+            csv::escape(event.location.as_deref().unwrap_or_default());
+        let line =
+            format!("{line},{project},{tags},{minutes},{note},{location}");
+        return match event.source {
+            Some(source) => format!("{line},{source}"),
+            None => line,
+        };
+    }
+    if let EventKind::Interruption { .. } = event.kind {
+        return format!("{line},{project},{tags},{minutes},{note}");
+    }
+    match (event.project.is_some(), event.tags.is_empty()) {
+        (_, false) => format!("{line},{project},{tags}"),
+        (true, true) => format!("{line},{project}"),
+        (false, true) => line,
+    }
+}
+
+pub(crate) fn event_to_json(event: &Event) -> Vec<(String, json::Value)> {
+    let mut entries = vec![
+        ("kind".to_string(), kind_to_str(&event.kind).into()),
+        ("timestamp".to_string(), event.dt.to_rfc3339().into()),
+    ];
+    if let Some(project) = &event.project {
+        entries.push(("project".to_string(), project.as_str().into()));
+    }
+    if !event.tags.is_empty() {
+        let tags = event.tags.iter().map(|tag| tag.as_str().into()).collect();
+        entries.push(("tags".to_string(), json::Value::Array(tags)));
+    }
+    if let EventKind::Interruption { minutes } = event.kind {
+        entries.push(("minutes".to_string(), minutes.into()));
+    }
+    if let Some(note) = &event.note {
+        entries.push(("note".to_string(), note.as_str().into()));
+    }
+    if let Some(location) = &event.location {
+        entries.push(("location".to_string(), location.as_str().into()));
+    }
+    if let Some(source) = event.source {
+        entries.push(("source".to_string(), source.to_string().into()));
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Rng;
+
+    #[test]
+    fn projects_and_tags_round_trip() {
+        let dt = Utc.with_ymd_and_hms(2020, 1, 31, 8, 15, 0).unwrap();
+        let events = [
+            Event::clock_in(&dt).with_project(Some("web".to_string())),
+            Event::clock_in(&dt)
+                .with_tags(vec!["meeting".to_string(), "onsite".to_string()]),
+            Event::clock_in(&dt)
+                .with_project(Some("web".to_string()))
+                .with_tags(vec!["meeting".to_string()]),
+        ];
+        for format in [Format::Csv, Format::Jsonl] {
+            for event in &events {
+                let line = event_to_str(event, format);
+                assert_eq!(&parse_event(&line).unwrap(), event, "{line}");
+            }
+        }
+        assert_eq!(
+            event_to_str(&events[1], Format::Csv),
+            "clock-in,2020-01-31T08:15:00+00:00,,meeting;onsite"
+        );
+    }
+
+    #[test]
+    fn locations_round_trip() {
+        let dt = Utc.with_ymd_and_hms(2020, 1, 31, 8, 15, 0).unwrap();
+        let event = Event::clock_in(&dt)
+            .with_project(Some("web".to_string()))
+            .with_location(Some("home".to_string()));
+        for format in [Format::Csv, Format::Jsonl] {
+            let line = event_to_str(&event, format);
+            assert_eq!(parse_event(&line).unwrap(), event, "{line}");
+        }
+        assert_eq!(
+            event_to_str(&event, Format::Csv),
+            "clock-in,2020-01-31T08:15:00+00:00,web,,,,home"
+        );
+    }
+
+    #[test]
+    fn sources_round_trip() {
+        let dt = Utc.with_ymd_and_hms(2020, 1, 31, 8, 15, 0).unwrap();
+        let events = [
+            Event::clock_out(&dt).with_source(Source::Daemon),
+            Event::interruption(&dt, 5, "call".to_string())
+                .with_source(Source::Api),
+        ];
+        for format in [Format::Csv, Format::Jsonl] {
+            for event in &events {
+                let line = event_to_str(event, format);
+                assert_eq!(&parse_event(&line).unwrap(), event, "{line}");
+            }
+        }
+        assert_eq!(
+            event_to_str(&events[0], Format::Csv),
+            "clock-out,2020-01-31T08:15:00+00:00,,,,,,daemon"
+        );
+        assert!(parse_event("clock-out,2020-01-31T08:15:00+00:00,,,,,,cron")
+            .is_err());
+    }
+
+    #[test]
+    fn interruptions_round_trip() {
+        let dt = Utc.with_ymd_and_hms(2020, 1, 31, 10, 0, 0).unwrap();
+        let event = Event::interruption(&dt, 20, "prod alert".to_string());
+        for format in [Format::Csv, Format::Jsonl] {
+            let line = event_to_str(&event, format);
+            assert_eq!(parse_event(&line).unwrap(), event, "{line}");
+        }
+        assert_eq!(
+            event_to_str(&event, Format::Csv),
+            "interruption,2020-01-31T10:00:00+00:00,,,20,prod alert"
+        );
+        assert!(parse_event("interruption,2020-01-31T10:00:00+00:00").is_err());
+    }
+
+    #[test]
+    fn commas_and_formulas_are_quoted_in_csv() {
+        let dt = Utc.with_ymd_and_hms(2020, 1, 31, 10, 0, 0).unwrap();
+        let event = Event::interruption(&dt, 5, "ops, again".to_string())
+            .with_project(Some("=HYPERLINK(\"x\")".to_string()));
+        let line = event_to_str(&event, Format::Csv);
+        assert_eq!(
+            line,
+            "interruption,2020-01-31T10:00:00+00:00,\
+            \"'=HYPERLINK(\"\"x\"\")\",,5,\"ops, again\""
+        );
+        assert_eq!(parse_event(&line).unwrap(), event);
+    }
+
+    #[test]
+    fn generated_events_round_trip() {
+        let mut rng = Rng::new(850);
+        let label = |rng: &mut Rng, kind| loop {
+            if let Ok(label) = check_label(&rng.string(8), kind) {
+                return label;
+            }
+        };
+        for _ in 0..500 {
+            let seconds = i64::try_from(rng.below(4_000_000_000)).unwrap();
+            let nanos = u32::try_from(rng.below(1_000_000_000)).unwrap();
+            let dt = DateTime::from_timestamp(seconds, nanos).unwrap();
+            let event = if rng.below(2) == 0 {
+                Event::clock_in(&dt)
+            } else {
+                Event::clock_out(&dt)
+            };
+            let project =
+                (rng.below(2) == 0).then(|| label(&mut rng, Label::Project));
+            let tags = (0..rng.below(3))
+                .map(|_| label(&mut rng, Label::Tag))
+                .collect();
+            let event = event.with_project(project).with_tags(tags);
+            for format in [Format::Csv, Format::Jsonl] {
+                let line = event_to_str(&event, format);
+                assert_eq!(parse_event(&line).unwrap(), event, "{line}");
+            }
+        }
+
+        // semicolons separate tags, but may be part of a project
+        let dt = DateTime::from_timestamp(0, 0).unwrap();
+        let event = Event::clock_in(&dt).with_project(Some("a;b".into()));
+        let line = event_to_str(&event, Format::Jsonl);
+        assert_eq!(parse_event(&line).unwrap(), event);
+        let line =
+            event_to_str(&event.with_tags(vec!["c;d".into()]), Format::Jsonl);
+        let err = parse_event(&line).unwrap_err();
+        assert!(format!("{err:#}").contains("Invalid tag 'c;d'"), "{err:#}");
+    }
+
+    #[test]
+    fn parse_event_does_not_panic_on_arbitrary_input() {
+        let mut rng = Rng::new(850);
+        let prefixes =
+            ["", "clock-in,", "{\"kind\":\"clock-in\",", "2020-01-31,"];
+        for _ in 0..1000 {
+            #[allow(clippy::cast_possible_truncation)]
+            let prefix = prefixes[rng.below(prefixes.len() as u64) as usize];
+            let line = format!("{prefix}{}", rng.string(40));
+            let _ = parse_event(&line);
+            let _ = parse_events(&line);
+        }
+    }
+}
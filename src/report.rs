@@ -0,0 +1,381 @@
+//! Monthly reports as numbers rather than text, for applications that show
+//! them their own way, e.g., on a dashboard:
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! use busy_bee::{data, prelude::*, report};
+//! use chrono::NaiveDate;
+//!
+//! let storage = data::open(Path::new("data"), Format::Csv, Layout::Day);
+//! let month = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+//! let report =
+//!     report::monthly(storage.as_ref(), month, &ReportOptions::default())?;
+//! println!("{} minutes", report.worked.num_minutes());
+//! println!("{report}");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveTime};
+
+use crate::{
+    burndown,
+    i18n::{self, tr},
+    interval::{intervals, merge_gaps, with_tags},
+    record::Event,
+    view::{total_line, Grouping, MonthlyReport, ReportRow, RowOptions},
+};
+#[cfg(feature = "fs")]
+use crate::{
+    cache,
+    config::Config,
+    data::{read_events, Storage},
+    interval::auto_clock_out,
+};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// What a [`Report`] counts
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[non_exhaustive]
+pub struct ReportOptions {
+    /// Only count time that has all of these tags
+    pub tags: Vec<String>,
+    /// Count a clock-out and a clock-in of the same project that follows
+    /// within this time as one stretch of work
+    pub merge_gap: Duration,
+    /// When to close a day that ends with a clock-in, as read from storage
+    pub auto_clockout: Option<NaiveTime>,
+    /// The hours due on each weekday that isn't one of the `holidays`
+    pub daily_target: Duration,
+    pub holidays: Vec<NaiveDate>,
+}
+
+#[cfg(feature = "fs")]
+impl ReportOptions {
+    /// The options that `busy-bee report` uses with the `config`
+    pub fn from_config(config: &Config) -> ReportOptions {
+        ReportOptions {
+            tags: Vec::new(),
+            merge_gap: config.merge_gap,
+            auto_clockout: config.auto_clockout,
+            daily_target: config.daily_target,
+            holidays: config.holidays.clone(),
+        }
+    }
+}
+
+/// The time worked on one day of a [`Report`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct DayTotal {
+    pub date: NaiveDate,
+    pub worked: Duration,
+    /// Whether the records pair up, which a clock-out without a clock-in
+    /// or two clock-ins in a row break; a day in progress is complete
+    pub complete: bool,
+}
+
+/// The time worked in a month, day by day and per project
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct Report {
+    /// The first day of the month
+    pub month: NaiveDate,
+    /// The days that have records, in order
+    pub days: Vec<DayTotal>,
+    /// The time of complete intervals per project, `None` for time without
+    /// one
+    pub per_project: BTreeMap<Option<String>, Duration>,
+    pub worked: Duration,
+    /// The hours due in the whole month
+    pub target: Duration,
+}
+
+impl Report {
+    /// Adds up the `events` of the month of `month`, leaving out the
+    /// others
+    pub fn compute(
+        month: NaiveDate,
+        events: &[Event],
+        options: &ReportOptions,
+    ) -> Report {
+        let first_day = month.with_day(1).unwrap();
+        let last_day = (first_day + Months::new(1)).pred_opt().unwrap();
+        let mut events: Vec<Event> = events
+            .iter()
+            .filter(|event| {
+                let date = event.dt.date_naive();
+                first_day <= date && date <= last_day
+            })
+            .cloned()
+            .collect();
+        events = merge_gaps(&events, options.merge_gap);
+        if !options.tags.is_empty() {
+            events = with_tags(&events, &options.tags);
+        }
+
+        let rows = RowOptions {
+            daily_target: options.daily_target,
+            holidays: options.holidays.clone(),
+            ..RowOptions::default()
+        };
+        // the same days as `busy-bee report` lists
+        let monthly =
+            MonthlyReport::month(&first_day, &events, Grouping::Day, &rows);
+        let days = monthly
+            .rows
+            .iter()
+            .filter_map(|row| match row {
+                ReportRow::Day {
+                    date,
+                    worked,
+                    complete,
+                    ..
+                } => Some(DayTotal {
+                    date: *date,
+                    worked: *worked,
+                    complete: *complete,
+                }),
+                _ => None,
+            })
+            .collect();
+        let mut per_project = BTreeMap::new();
+        for interval in intervals(&events) {
+            *per_project.entry(interval.project.clone()).or_default() +=
+                interval.duration();
+        }
+        Report {
+            month: first_day,
+            days,
+            per_project,
+            worked: monthly.worked,
+            target: burndown::due(
+                first_day,
+                last_day,
+                options.daily_target,
+                &options.holidays,
+            ),
+        }
+    }
+
+    /// How much more was worked than was due in the month, which is
+    /// negative when behind
+    pub fn balance(&self) -> Duration {
+        self.worked - self.target
+    }
+}
+
+/// Reads the month of `month` from `storage` and adds it up, like
+/// `busy-bee report` does
+#[cfg(feature = "fs")]
+pub fn monthly(
+    storage: &dyn Storage,
+    month: NaiveDate,
+    options: &ReportOptions,
+) -> anyhow::Result<Report> {
+    let first_day = month.with_day(1).unwrap();
+    let next_month = first_day + Months::new(1);
+    let mut events = Vec::new();
+    for date in first_day.iter_days().take_while(|d| *d < next_month) {
+        let mut day = read_events(storage, date)?;
+        if let Some(cutoff) = options.auto_clockout {
+            day.extend(auto_clock_out(date, &day, cutoff, chrono::Utc::now()));
+        }
+        events.extend(day);
+    }
+    Ok(Report::compute(first_day, &events, options))
+}
+
+/// The time worked in a month in total and per project, which is what
+/// reports over a year take from each month
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct MonthTotals {
+    /// The first day of the month
+    pub month: NaiveDate,
+    pub worked: Duration,
+    pub per_project: BTreeMap<Option<String>, Duration>,
+}
+
+impl From<&Report> for MonthTotals {
+    fn from(report: &Report) -> MonthTotals {
+        MonthTotals {
+            month: report.month,
+            worked: report.worked,
+            per_project: report.per_project.clone(),
+        }
+    }
+}
+
+/// The time worked in each month of a year, up to the current month
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct YearReport {
+    pub year: i32,
+    pub months: Vec<MonthTotals>,
+}
+
+impl YearReport {
+    pub fn worked(&self) -> Duration {
+        self.months.iter().map(|month| month.worked).sum()
+    }
+
+    /// The time per project over all months, `None` for time without one
+    pub fn per_project(&self) -> BTreeMap<Option<String>, Duration> {
+        let mut per_project = BTreeMap::new();
+        for (project, worked) in
+            self.months.iter().flat_map(|month| &month.per_project)
+        {
+            *per_project.entry(project.clone()).or_default() += *worked;
+        }
+        per_project
+    }
+}
+
+/// Adds up each month of `year` up to the one of `today`. Past months come
+/// from the cache in `cache_dir` where their data files haven't changed
+/// since, and are cached otherwise; without a `cache_dir` every month is
+/// read again.
+#[cfg(feature = "fs")]
+pub fn yearly(
+    storage: &dyn Storage,
+    cache_dir: Option<&Path>,
+    year: i32,
+    options: &ReportOptions,
+    today: NaiveDate,
+) -> anyhow::Result<YearReport> {
+    let this_month = today.with_day(1).unwrap();
+    let mut months = Vec::new();
+    for month in (1..=12).filter_map(|m| NaiveDate::from_ymd_opt(year, m, 1)) {
+        if month > this_month {
+            break;
+        }
+        let totals = match cache_dir {
+            // the current month still changes, and so does the auto
+            // clock-out of its open days
+            Some(dir) if month < this_month => {
+                cache::month_totals(storage, dir, month, options)?
+            }
+            _ => MonthTotals::from(&monthly(storage, month, options)?),
+        };
+        months.push(totals);
+    }
+    Ok(YearReport { year, months })
+}
+
+impl Display for YearReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} {}:", tr("Summary for"), self.year)?;
+        let months: Vec<_> = self
+            .months
+            .iter()
+            .map(|month| (i18n::month(&month.month), month.worked))
+            .collect();
+        let mut per_project: Vec<_> = self.per_project().into_iter().collect();
+        // projects sort before time without project
+        per_project.sort_by_key(|(project, _)| project.is_none());
+        let per_project: Vec<_> = per_project
+            .into_iter()
+            .map(|(project, worked)| {
+                (project.unwrap_or(tr("(no project)").to_string()), worked)
+            })
+            .collect();
+        let width = months
+            .iter()
+            .chain(&per_project)
+            .map(|(name, _)| name.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (name, worked) in &months {
+            writeln!(f, "{name:<width$} | {}", i18n::duration(worked))?;
+        }
+        if !per_project.is_empty() {
+            writeln!(f, "{}:", tr("Per project"))?;
+        }
+        for (name, worked) in &per_project {
+            writeln!(f, "{name:<width$} | {}", i18n::duration(worked))?;
+        }
+        writeln!(f, "{}", total_line(self.worked()))
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} {}:", tr("Summary for"), i18n::month(&self.month))?;
+        for day in &self.days {
+            let worked = if day.complete {
+                i18n::duration(&day.worked)
+            } else {
+                "?".to_string()
+            };
+            let comment = if day.complete {
+                ""
+            } else {
+                tr("Incomplete records, please update")
+            };
+            writeln!(f, "{:<2} | {worked:<5} | {comment}", day.date.day())?;
+        }
+        writeln!(f, "{}", total_line(self.worked))?;
+        let balance = self.balance();
+        writeln!(
+            f,
+            "{}: {} {}, {} {}",
+            tr("Target"),
+            i18n::duration(&self.target),
+            tr("hours"),
+            i18n::duration(&balance.abs()),
+            if balance < Duration::zero() {
+                tr("behind")
+            } else {
+                tr("ahead")
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    #[test]
+    fn reports_add_up_the_month() {
+        let at = |d, h| Utc.with_ymd_and_hms(2024, 6, d, h, 0, 0).unwrap();
+        let events = [
+            Event::clock_in(&at(3, 9)).with_project(Some("web".to_string())),
+            Event::clock_out(&at(3, 17)),
+            Event::clock_in(&at(4, 9)),
+            Event::clock_out(&at(4, 13)),
+            Event::clock_out(&at(5, 10)),
+            // another month
+            Event::clock_in(
+                &Utc.with_ymd_and_hms(2024, 7, 1, 9, 0, 0).unwrap(),
+            ),
+        ];
+        let options = ReportOptions {
+            daily_target: Duration::hours(8),
+            ..ReportOptions::default()
+        };
+        let report = Report::compute(at(1, 0).date_naive(), &events, &options);
+        let days: Vec<_> = report
+            .days
+            .iter()
+            .map(|day| (day.date.day(), day.complete))
+            .collect();
+        assert_eq!(days, [(3, true), (4, true), (5, false)]);
+        assert_eq!(report.worked, Duration::hours(12));
+        assert_eq!(
+            report.per_project[&Some("web".to_string())],
+            Duration::hours(8)
+        );
+        // 20 workdays in June 2024
+        assert_eq!(report.target, Duration::hours(160));
+        assert!(report
+            .to_string()
+            .starts_with("Summary for June 2024:\n3  | 08:00 | \n"));
+    }
+}
@@ -0,0 +1,313 @@
+//! Driving this application with JSON instead of command-line arguments,
+//! e.g., from editor plugins or assistants, which then don't have to parse
+//! text meant for people. Each line read is a request like
+//! `{"id": 1, "method": "clock_in", "params": {"project": "web"}}`, and
+//! each line written is its response, `{"id": 1, "result": ...}` or
+//! `{"id": 1, "error": "..."}`.
+//!
+//! The methods are `clock_in`, `clock_out`, `status`, `day` and `month`.
+//! Hooks don't run, as what they print would mix with the responses.
+//! Writes fail while another command is changing the records, instead of
+//! waiting for it.
+
+use std::{
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Datelike, Local, Months, NaiveDate, Utc};
+
+use crate::{
+    burndown,
+    config::Config,
+    data::{self, create_event, read_stored_events, Storage},
+    interval::{auto_clock_out, merge_gaps},
+    json,
+    record::{Event, Source},
+    status::Status,
+    view::{DailyReport, Grouping, MonthlyReport, RowOptions},
+};
+
+/// Answers requests against `storage`, with the settings of `config`
+pub struct Server<'a> {
+    pub storage: &'a dyn Storage,
+    /// Where `storage` keeps its files, which is locked for each write
+    pub storage_dir: &'a Path,
+    pub config: &'a Config,
+    /// Whether to record events even if they seem too far off
+    pub force: bool,
+}
+
+impl Server<'_> {
+    /// Answers each line of `input` with a line of `output` until `input`
+    /// ends
+    pub fn serve(
+        &self,
+        input: impl BufRead,
+        mut output: impl Write,
+    ) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            writeln!(output, "{}", self.respond(&line))?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// The response to the request on `line`
+    pub fn respond(&self, line: &str) -> json::Value {
+        let request = match json::parse(line) {
+            Ok(request) => request,
+            Err(err) => return response(json::Value::Null, Err(err)),
+        };
+        let id = request.get("id").cloned().unwrap_or(json::Value::Null);
+        let params = request
+            .get("params")
+            .cloned()
+            .unwrap_or(json::Value::Object(Vec::new()));
+        let result = match request.get("method").and_then(json::Value::as_str)
+        {
+            Some(method) => self.call(method, &params),
+            None => Err(anyhow!("The request has no method")),
+        };
+        response(id, result)
+    }
+
+    fn call(&self, method: &str, params: &json::Value) -> Result<json::Value> {
+        let now = Utc::now();
+        match method {
+            "clock_in" => {
+                let event = Event::clock_in(&time(params, now)?)
+                    .with_project(string(params, "project")?)
+                    .with_tags(strings(params, "tags")?)
+                    .with_location(string(params, "location")?)
+                    .with_source(Source::Api);
+                self.record(&event, now)
+            }
+            "clock_out" => {
+                let event = Event::clock_out(&time(params, now)?)
+                    .with_source(Source::Api);
+                self.record(&event, now)
+            }
+            "status" => {
+                let today = Local::now().date_naive();
+                let events = data::read_events(self.storage, today)?;
+                Ok(Status::compute(&events, now, self.config.daily_target)
+                    .to_json())
+            }
+            "day" => {
+                let date = date(params, "date")?
+                    .unwrap_or_else(|| Local::now().date_naive());
+                self.day(date)
+            }
+            "month" => self.month(date(params, "month")?),
+            _ => bail!(
+                "Unknown method {method}, try clock_in, clock_out, \
+                status, day or month"
+            ),
+        }
+    }
+
+    /// Records `event` like the commands do, and returns its day
+    fn record(
+        &self,
+        event: &Event,
+        now: DateTime<Utc>,
+    ) -> Result<json::Value> {
+        if self.config.storage_read_only {
+            bail!("The storage directory is read-only");
+        }
+        // only while writing, so that an open editor doesn't keep the
+        // commands waiting
+        data::create_dir(self.storage_dir)?;
+        let _lock = data::try_lock(self.storage_dir)?;
+        if !self.force {
+            self.config.check_plausible(event.dt, now)?;
+            data::check_clock(self.storage, event, now)?;
+        }
+        let created =
+            create_event(self.storage, event, self.config.dedupe_window)?;
+        let day = self.day(event.dt.date_naive())?;
+        Ok(json::Value::Object(vec![
+            (
+                "duplicate".to_string(),
+                created.duplicate_of.is_some().into(),
+            ),
+            ("day".to_string(), day),
+        ]))
+    }
+
+    fn day(&self, date: NaiveDate) -> Result<json::Value> {
+        let stored = read_stored_events(self.storage, date)?;
+        Ok(DailyReport::compute(date, &stored, &[], &[]).to_json())
+    }
+
+    /// The report of the month of `month`, default is the current one
+    fn month(&self, month: Option<NaiveDate>) -> Result<json::Value> {
+        let today = Local::now().date_naive();
+        let first_day = month.unwrap_or(today).with_day(1).unwrap();
+        let last_day = (first_day + Months::new(1)).pred_opt().unwrap();
+        let mut events = Vec::new();
+        for date in first_day.iter_days().take_while(|d| *d <= last_day) {
+            let mut day = data::read_events(self.storage, date)?;
+            if let Some(cutoff) = self.config.auto_clockout {
+                day.extend(auto_clock_out(date, &day, cutoff, Utc::now()));
+            }
+            events.extend(day);
+        }
+        events = merge_gaps(&events, self.config.merge_gap);
+        let options = RowOptions {
+            missing: burndown::missing_days(
+                first_day,
+                last_day,
+                &events,
+                &self.config.holidays,
+                today,
+            ),
+            daily_target: self.config.daily_target,
+            holidays: self.config.holidays.clone(),
+            only: None,
+        };
+        let report =
+            MonthlyReport::month(&first_day, &events, Grouping::Day, &options);
+        Ok(report.to_json())
+    }
+}
+
+fn response(id: json::Value, result: Result<json::Value>) -> json::Value {
+    let outcome = match result {
+        Ok(result) => ("result".to_string(), result),
+        Err(err) => ("error".to_string(), format!("{err:#}").into()),
+    };
+    json::Value::Object(vec![("id".to_string(), id), outcome])
+}
+
+/// The `time` parameter in RFC 3339, e.g., `2024-03-01T09:00:00+01:00`,
+/// default is `now`
+fn time(params: &json::Value, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    match string(params, "time")? {
+        Some(time) => Ok(DateTime::parse_from_rfc3339(&time)
+            .map_err(|_| anyhow!("Invalid time {time}, try RFC 3339"))?
+            .to_utc()),
+        None => Ok(now),
+    }
+}
+
+/// A date parameter like `2024-03-01`, or a month like `2024-03`
+fn date(params: &json::Value, key: &str) -> Result<Option<NaiveDate>> {
+    let Some(date) = string(params, key)? else {
+        return Ok(None);
+    };
+    let full = if date.len() == 7 {
+        format!("{date}-01")
+    } else {
+        date.clone()
+    };
+    NaiveDate::parse_from_str(&full, "%Y-%m-%d")
+        .map(Some)
+        .map_err(|_| anyhow!("Invalid {key} {date}, try e.g. 2024-03-01"))
+}
+
+fn string(params: &json::Value, key: &str) -> Result<Option<String>> {
+    match params.get(key) {
+        None | Some(json::Value::Null) => Ok(None),
+        Some(json::Value::String(s)) => Ok(Some(s.clone())),
+        Some(_) => bail!("Expected a string for '{key}'"),
+    }
+}
+
+fn strings(params: &json::Value, key: &str) -> Result<Vec<String>> {
+    match params.get(key) {
+        None | Some(json::Value::Null) => Ok(Vec::new()),
+        Some(json::Value::Array(values)) => values
+            .iter()
+            .map(|value| {
+                value
+                    .as_str()
+                    .map(String::from)
+                    .ok_or(anyhow!("Expected strings in '{key}'"))
+            })
+            .collect(),
+        Some(_) => bail!("Expected an array of strings for '{key}'"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{data::Layout, record::Format};
+
+    #[test]
+    fn requests_are_answered_line_by_line() {
+        let d = tempdir().unwrap();
+        let storage = data::open(d.path(), Format::Csv, Layout::Day);
+        let config = Config::default();
+        let server = Server {
+            storage: storage.as_ref(),
+            storage_dir: d.path(),
+            config: &config,
+            force: true,
+        };
+        let input = "\
+            {\"id\": 1, \"method\": \"clock_in\", \"params\": \
+                {\"time\": \"2024-06-03T09:00:00Z\", \"tags\": [\"x\"]}}\n\
+            \n\
+            {\"id\": \"b\", \"method\": \"day\", \"params\": \
+                {\"date\": \"2024-06-03\"}}\n\
+            {\"id\": 3, \"method\": \"sleep\"}\n\
+            not json\n";
+        let mut output = Vec::new();
+        server.serve(input.as_bytes(), &mut output).unwrap();
+        let responses: Vec<json::Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| json::parse(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 4);
+        assert_eq!(
+            responses[0].get("result").unwrap().get("duplicate"),
+            Some(&json::Value::Bool(false))
+        );
+        let day = responses[1].get("result").unwrap();
+        assert_eq!(responses[1].get("id"), Some(&"b".into()));
+        assert_eq!(day.get("events").unwrap().as_array().unwrap().len(), 1);
+        assert!(responses[2]
+            .get("error")
+            .and_then(json::Value::as_str)
+            .unwrap()
+            .starts_with("Unknown method sleep"));
+        assert_eq!(responses[3].get("id"), Some(&json::Value::Null));
+        assert!(responses[3].get("error").is_some());
+    }
+
+    #[test]
+    fn writes_fail_while_the_storage_is_locked() {
+        let d = tempdir().unwrap();
+        let storage = data::open(d.path(), Format::Csv, Layout::Day);
+        let config = Config::default();
+        let server = Server {
+            storage: storage.as_ref(),
+            storage_dir: d.path(),
+            config: &config,
+            force: true,
+        };
+        let clock_in = "{\"id\": 1, \"method\": \"clock_in\"}";
+        let lock = data::lock(d.path()).unwrap();
+        let response = server.respond(clock_in);
+        assert!(response
+            .get("error")
+            .and_then(json::Value::as_str)
+            .unwrap()
+            .starts_with("Another command is changing the records"));
+        assert!(storage.days().unwrap().is_empty());
+
+        drop(lock);
+        assert!(server.respond(clock_in).get("result").is_some());
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    fmt::{Display, Write as _},
+    io::{self, Write},
+    process::{Command, Stdio},
+    thread,
+};
+
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+
+use crate::data::{events_to_string, Event, Format};
+
+/// What a report covers
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Scope {
+    Day(NaiveDate),
+    /// The month of the date
+    Month(NaiveDate),
+    /// The days from the first date to the second, both inclusive
+    Range(NaiveDate, NaiveDate),
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scope::Day(_) => write!(f, "day"),
+            Scope::Month(_) => write!(f, "month"),
+            Scope::Range(..) => write!(f, "range"),
+        }
+    }
+}
+
+impl Scope {
+    fn date(self) -> NaiveDate {
+        match self {
+            Scope::Day(date) | Scope::Month(date) | Scope::Range(date, _) => {
+                date
+            }
+        }
+    }
+}
+
+/// Additional content at the end of daily and monthly reports
+pub trait ReportSection {
+    fn title(&self) -> &str;
+
+    /// Renders the section for the events the report is made of
+    fn render(&self, scope: Scope, events: &[Event]) -> Result<String>;
+}
+
+/// The sections appended to reports, in order of registration
+#[derive(Default)]
+pub struct Registry {
+    sections: Vec<Box<dyn ReportSection>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, section: Box<dyn ReportSection>) {
+        self.sections.push(section);
+    }
+
+    /// Renders all sections, each below its title. A failing section is
+    /// reported in its place, so that the rest of the report is still shown.
+    pub fn render(&self, scope: Scope, events: &[Event]) -> String {
+        let mut result = String::new();
+        for section in &self.sections {
+            let content = section
+                .render(scope, events)
+                .unwrap_or_else(|err| format!("Could not render: {err:#}\n"));
+            // writing to a String cannot fail
+            let _ = write!(result, "\n{}:\n{content}", section.title());
+            if !result.ends_with('\n') {
+                result.push('\n');
+            }
+        }
+        result
+    }
+}
+
+/// A section rendered by an external program. The program receives the
+/// report's events on stdin, one JSON object per line, learns the scope
+/// from the `BUSY_BEE_REPORT` (`day`, `month` or `range`) and
+/// `BUSY_BEE_DATE` environment variables, plus `BUSY_BEE_TO` with the last
+/// day of a range, and prints the section's content to stdout.
+pub struct ScriptSection {
+    pub title: String,
+    /// The program followed by its arguments
+    pub command: Vec<String>,
+}
+
+impl ReportSection for ScriptSection {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn render(&self, scope: Scope, events: &[Event]) -> Result<String> {
+        let Some((program, args)) = self.command.split_first() else {
+            bail!("No command configured")
+        };
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .env("BUSY_BEE_REPORT", scope.to_string())
+            .env("BUSY_BEE_DATE", scope.date().to_string());
+        if let Scope::Range(_, to) = scope {
+            command.env("BUSY_BEE_TO", to.to_string());
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Could not run {program}"))?;
+        // take() so that stdin is closed once the events are written, which
+        // happens alongside reading the output, as a script that prints
+        // while it reads would wait for it to be read
+        let stdin = child.stdin.take();
+        let input = events_to_string(events, Format::Jsonl);
+        let writer = thread::spawn(move || -> io::Result<()> {
+            if let Some(mut stdin) = stdin {
+                match stdin.write_all(input.as_bytes()) {
+                    // The script doesn't read all events
+                    Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+                    result => result?,
+                }
+            }
+            Ok(())
+        });
+        let output = child.wait_with_output()?;
+        writer.join().expect("writing the events doesn't panic")?;
+        if !output.status.success() {
+            bail!("{program} exited with {}", output.status)
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    struct Count;
+
+    impl ReportSection for Count {
+        fn title(&self) -> &'static str {
+            "Events"
+        }
+
+        fn render(&self, _: Scope, events: &[Event]) -> Result<String> {
+            Ok(format!("{}\n", events.len()))
+        }
+    }
+
+    #[test]
+    fn registry_renders_sections_in_order() {
+        let mut registry = Registry::new();
+        registry.register(Box::new(Count));
+        registry.register(Box::new(ScriptSection {
+            title: "Broken".to_string(),
+            command: Vec::new(),
+        }));
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let events = [Event::clock_in(
+            &Utc.with_ymd_and_hms(2024, 3, 1, 8, 0, 0).unwrap(),
+        )];
+        assert_eq!(
+            registry.render(Scope::Day(date), &events),
+            "\nEvents:\n1\n\nBroken:\nCould not render: No command configured\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn script_sections_receive_events_on_stdin() {
+        let section = ScriptSection {
+            title: "Lines".to_string(),
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo $BUSY_BEE_REPORT; wc -l".to_string(),
+            ],
+        };
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let events = [Event::clock_in(
+            &Utc.with_ymd_and_hms(2024, 3, 1, 8, 0, 0).unwrap(),
+        )];
+        let content = section.render(Scope::Month(date), &events).unwrap();
+        let words: Vec<_> = content.split_whitespace().collect();
+        assert_eq!(words, ["month", "1"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn script_sections_may_print_while_reading_or_not_read_at_all() {
+        let script = |script: &str| ScriptSection {
+            title: "Script".to_string(),
+            command: vec!["sh".to_string(), "-c".to_string(), script.into()],
+        };
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 8, 0, 0).unwrap();
+        // more than fits into a pipe's buffer
+        let events: Vec<Event> = (0..5000)
+            .map(|i| Event::clock_in(&(start + chrono::Duration::seconds(i))))
+            .collect();
+        let content = script("cat").render(Scope::Month(date), &events);
+        assert_eq!(content.unwrap().lines().count(), events.len());
+        let content = script("echo done").render(Scope::Month(date), &events);
+        assert_eq!(content.unwrap(), "done\n");
+    }
+}
@@ -0,0 +1,50 @@
+//! Stopping long-running commands cleanly when they're interrupted, e.g.,
+//! with Ctrl-C, rather than ending the process wherever it happens to be
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Makes SIGINT and SIGTERM set the flag that [`interrupted`] reads
+/// instead of ending the process. Elsewhere than on Unix, they still end
+/// it.
+pub fn catch_interrupts() {
+    #[cfg(unix)]
+    {
+        extern "C" fn handle(_: libc::c_int) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        }
+        let handler = handle as extern "C" fn(libc::c_int);
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            // SAFETY: the handler only stores to an atomic, which is safe
+            // to do in a signal handler
+            unsafe {
+                libc::signal(signal, handler as libc::sighandler_t);
+            }
+        }
+    }
+}
+
+/// Whether SIGINT or SIGTERM arrived since [`catch_interrupts`]
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interrupts_are_caught() {
+        catch_interrupts();
+        assert!(!interrupted());
+        // SAFETY: raising a signal that has a handler is safe, and so is
+        // restoring the default handlers for the other tests
+        unsafe {
+            libc::raise(libc::SIGTERM);
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+            libc::signal(libc::SIGTERM, libc::SIG_DFL);
+        }
+        assert!(interrupted());
+    }
+}
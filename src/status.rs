@@ -0,0 +1,383 @@
+use std::{
+    fmt::{Display, Write},
+    str::FromStr,
+};
+
+use chrono::{DateTime, Duration, Local, Utc};
+
+use crate::{
+    i18n, json,
+    record::{Event, EventKind},
+};
+
+/// How the `status` command prints its result
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    /// The JSON structure that waybar's custom modules expect
+    Waybar,
+    /// Gauges in Prometheus' text format, e.g., for the textfile collector
+    /// of its node exporter
+    Prometheus,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "waybar" => Ok(OutputFormat::Waybar),
+            "prometheus" => Ok(OutputFormat::Prometheus),
+            _ => Err(format!(
+                "Unknown output format {s}, try text, json, waybar or \
+                prometheus"
+            )),
+        }
+    }
+}
+
+/// The current state of today's records
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Status {
+    pub clocked_in: bool,
+    /// When the currently open interval started
+    pub since: Option<DateTime<Utc>>,
+    /// Working time so far, including the currently open interval
+    pub worked: Duration,
+    pub target: Duration,
+    /// When the target will be reached if work continues without a break
+    pub eta: Option<DateTime<Utc>>,
+    /// Whether clock-ins and clock-outs alternate properly
+    pub complete: bool,
+}
+
+impl Status {
+    pub fn compute(
+        events: &[Event],
+        now: DateTime<Utc>,
+        target: Duration,
+    ) -> Status {
+        let mut worked = Duration::zero();
+        let mut since = None;
+        let mut complete = true;
+        for event in events {
+            match (event.kind.clone(), since) {
+                (EventKind::ClockIn, None) => since = Some(event.dt),
+                // For incomplete records, the later of two clock-ins wins
+                (EventKind::ClockIn, Some(_)) => {
+                    since = Some(event.dt);
+                    complete = false;
+                }
+                // out of order, which would take time off the day
+                (EventKind::ClockOut, Some(start)) if event.dt < start => {
+                    since = None;
+                    complete = false;
+                }
+                (EventKind::ClockOut, Some(start)) => {
+                    worked += event.dt - start;
+                    since = None;
+                }
+                // a dangling clock-out is ignored
+                (EventKind::ClockOut, None) => complete = false,
+                (
+                    EventKind::StandbyStart
+                    | EventKind::StandbyEnd
+                    | EventKind::Interruption { .. },
+                    _,
+                ) => {}
+            }
+        }
+        if let Some(start) = since {
+            worked += (now - start).max(Duration::zero());
+        }
+        let eta = since
+            .filter(|_| worked < target)
+            .map(|_| now + (target - worked));
+        Status {
+            clocked_in: since.is_some(),
+            since,
+            worked,
+            target,
+            eta,
+            complete,
+        }
+    }
+
+    pub fn to_json(&self) -> json::Value {
+        let local = |dt: &DateTime<Utc>| {
+            DateTime::<Local>::from(*dt).to_rfc3339().into()
+        };
+        json::Value::Object(vec![
+            ("clocked_in".to_string(), self.clocked_in.into()),
+            (
+                "since".to_string(),
+                self.since.as_ref().map_or(json::Value::Null, local),
+            ),
+            (
+                "worked_today_minutes".to_string(),
+                self.worked.num_minutes().into(),
+            ),
+            (
+                "target_minutes".to_string(),
+                self.target.num_minutes().into(),
+            ),
+            (
+                "eta".to_string(),
+                self.eta.as_ref().map_or(json::Value::Null, local),
+            ),
+        ])
+    }
+}
+
+impl Status {
+    /// Returns the structure described in waybar's `waybar-custom(5)`, with
+    /// CSS classes `clocked-in` or `clocked-out`, plus `overtime` and
+    /// `incomplete` where they apply
+    pub fn to_waybar(&self) -> json::Value {
+        let mut classes = vec![if self.clocked_in {
+            "clocked-in"
+        } else {
+            "clocked-out"
+        }];
+        if self.worked > self.target {
+            classes.push("overtime");
+        }
+        if !self.complete {
+            classes.push("incomplete");
+        }
+        let percentage = if self.target > Duration::zero() {
+            self.worked.num_minutes() * 100 / self.target.num_minutes().max(1)
+        } else {
+            0
+        };
+        json::Value::Object(vec![
+            ("text".to_string(), i18n::duration(&self.worked).into()),
+            ("tooltip".to_string(), self.to_string().into()),
+            (
+                "class".to_string(),
+                json::Value::Array(
+                    classes.into_iter().map(json::Value::from).collect(),
+                ),
+            ),
+            ("percentage".to_string(), percentage.min(100).into()),
+        ])
+    }
+}
+
+impl Status {
+    /// Returns the status as Prometheus gauges named `busybee_*`, with
+    /// their help and type lines
+    pub fn to_prometheus(&self) -> String {
+        let gauges = [
+            (
+                "clocked_in",
+                "Whether you're clocked in, 1 or 0",
+                i64::from(self.clocked_in),
+            ),
+            (
+                "worked_seconds_today",
+                "Working time today, including the open interval",
+                self.worked.num_seconds(),
+            ),
+            (
+                "target_seconds_today",
+                "The working time you intend per day",
+                self.target.num_seconds(),
+            ),
+            (
+                "records_complete",
+                "Whether today's clock-ins and clock-outs pair up, 1 or 0",
+                i64::from(self.complete),
+            ),
+        ];
+        let mut result = String::new();
+        for (name, help, value) in gauges {
+            let _ = write!(
+                result,
+                "# HELP busybee_{name} {help}\n\
+                # TYPE busybee_{name} gauge\n\
+                busybee_{name} {value}\n"
+            );
+        }
+        result
+    }
+}
+
+impl Status {
+    /// Returns the status as a single line of tab-separated fields for
+    /// scripts, e.g., launchers and shell prompts: `in` or `out`, the
+    /// minutes worked today and due, since when you're clocked in and when
+    /// the target is reached, in RFC 3339 or empty, and `complete` or
+    /// `incomplete`. These fields keep their order and meaning across
+    /// versions; new ones are only ever added at the end.
+    pub fn to_porcelain(&self) -> String {
+        let local = |dt: Option<DateTime<Utc>>| {
+            dt.map(|dt| DateTime::<Local>::from(dt).to_rfc3339())
+                .unwrap_or_default()
+        };
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            if self.clocked_in { "in" } else { "out" },
+            self.worked.num_minutes(),
+            self.target.num_minutes(),
+            local(self.since),
+            local(self.eta),
+            if self.complete {
+                "complete"
+            } else {
+                "incomplete"
+            }
+        )
+    }
+}
+
+impl Status {
+    /// Returns the time worked today with a sign whether you're clocked
+    /// in, e.g., `⏱ 3:42 ▶`, short enough for a shell prompt
+    pub fn to_prompt(&self) -> String {
+        let minutes = self.worked.num_minutes();
+        let worked = format!("{}:{:02}", minutes / 60, minutes % 60);
+        if self.clocked_in {
+            format!("⏱ {worked} ▶")
+        } else {
+            format!("⏱ {worked}")
+        }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let local_time = |dt: DateTime<Utc>| {
+            DateTime::<Local>::from(dt).format("%H:%M").to_string()
+        };
+        match self.since {
+            Some(since) => write!(f, "Clocked in since {}", local_time(since)),
+            None => write!(f, "Clocked out"),
+        }?;
+        write!(
+            f,
+            ", worked {} of {} today",
+            i18n::duration(&self.worked),
+            i18n::duration(&self.target)
+        )?;
+        if let Some(eta) = self.eta {
+            write!(f, ", target reached at {}", local_time(eta))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn compute_includes_open_interval() {
+        let at = |h, m| Utc.with_ymd_and_hms(2020, 1, 31, h, m, 0).unwrap();
+        let events = [
+            Event::clock_in(&at(8, 0)),
+            Event::clock_out(&at(12, 0)),
+            Event::clock_in(&at(13, 0)),
+        ];
+        let status = Status::compute(&events, at(14, 30), Duration::hours(8));
+        assert!(status.clocked_in);
+        assert_eq!(status.since, Some(at(13, 0)));
+        assert_eq!(status.worked, Duration::minutes(330));
+        assert_eq!(status.eta, Some(at(17, 0)));
+        assert_eq!(
+            status.to_json().get("worked_today_minutes"),
+            Some(&json::Value::Number(330.0))
+        );
+    }
+
+    #[test]
+    fn compute_keeps_seconds_and_skips_out_of_order_events() {
+        let at = |h, m, s| Utc.with_ymd_and_hms(2020, 1, 31, h, m, s).unwrap();
+        let events = [
+            Event::clock_in(&at(8, 0, 20)),
+            Event::clock_out(&at(8, 0, 50)),
+            Event::clock_in(&at(9, 0, 0)),
+            Event::clock_out(&at(9, 0, 40)),
+            // recorded out of order, e.g., by hand
+            Event::clock_in(&at(12, 0, 0)),
+            Event::clock_out(&at(11, 0, 0)),
+        ];
+        let status =
+            Status::compute(&events, at(14, 0, 0), Duration::hours(8));
+        assert_eq!(status.worked, Duration::seconds(70));
+        assert!(!status.complete);
+        assert_eq!(crate::i18n::duration(&status.worked), "00:01");
+    }
+
+    #[test]
+    fn compute_has_no_eta_when_clocked_out() {
+        let at = |h, m| Utc.with_ymd_and_hms(2020, 1, 31, h, m, 0).unwrap();
+        let events = [Event::clock_in(&at(8, 0)), Event::clock_out(&at(9, 0))];
+        let status = Status::compute(&events, at(14, 30), Duration::hours(8));
+        assert!(!status.clocked_in);
+        assert_eq!(status.worked, Duration::hours(1));
+        assert_eq!(status.eta, None);
+    }
+
+    #[test]
+    fn to_waybar_sets_classes() {
+        let at = |h, m| Utc.with_ymd_and_hms(2020, 1, 31, h, m, 0).unwrap();
+        let events = [Event::clock_out(&at(7, 0)), Event::clock_in(&at(8, 0))];
+        let status = Status::compute(&events, at(17, 0), Duration::hours(8));
+        let waybar = status.to_waybar();
+        assert_eq!(
+            waybar.get("class").map(ToString::to_string).as_deref(),
+            Some(r#"["clocked-in","overtime","incomplete"]"#)
+        );
+        assert_eq!(
+            waybar.get("text"),
+            Some(&json::Value::String("09:00".to_string()))
+        );
+    }
+
+    #[test]
+    fn to_prometheus_writes_gauges() {
+        let at = |h, m| Utc.with_ymd_and_hms(2020, 1, 31, h, m, 0).unwrap();
+        let events = [Event::clock_in(&at(8, 0))];
+        let status = Status::compute(&events, at(9, 30), Duration::hours(8));
+        let metrics = status.to_prometheus();
+        assert!(metrics.starts_with(
+            "# HELP busybee_clocked_in Whether you're clocked in, 1 or 0\n\
+            # TYPE busybee_clocked_in gauge\n\
+            busybee_clocked_in 1\n"
+        ));
+        assert!(metrics.contains("\nbusybee_worked_seconds_today 5400\n"));
+    }
+
+    #[test]
+    fn to_prompt_is_short() {
+        let at = |h, m| Utc.with_ymd_and_hms(2020, 1, 31, h, m, 0).unwrap();
+        let events = [Event::clock_in(&at(8, 0)), Event::clock_out(&at(9, 5))];
+        let status = Status::compute(&events, at(9, 30), Duration::hours(8));
+        assert_eq!(status.to_prompt(), "⏱ 1:05");
+        let status =
+            Status::compute(&events[..1], at(11, 42), Duration::hours(8));
+        assert_eq!(status.to_prompt(), "⏱ 3:42 ▶");
+    }
+
+    #[test]
+    fn to_porcelain_writes_one_line() {
+        let at = |h, m| Utc.with_ymd_and_hms(2020, 1, 31, h, m, 0).unwrap();
+        let events = [Event::clock_in(&at(8, 0)), Event::clock_out(&at(9, 0))];
+        let status = Status::compute(&events, at(9, 30), Duration::hours(8));
+        assert_eq!(status.to_porcelain(), "out\t60\t480\t\t\tcomplete");
+
+        let status =
+            Status::compute(&events[..1], at(9, 30), Duration::hours(8));
+        let line = status.to_porcelain();
+        let fields: Vec<_> = line.split('\t').collect();
+        assert_eq!(fields.len(), 6);
+        assert_eq!(fields[..3], ["in", "90", "480"]);
+        assert_eq!(DateTime::parse_from_rfc3339(fields[3]).unwrap(), at(8, 0));
+    }
+}
@@ -0,0 +1,130 @@
+use std::{path::Path, process::Command};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+
+use crate::data::{Event, EventKind};
+
+/// When the commits of the current git user in `repo` were authored on
+/// the given local date
+pub fn commit_times(
+    repo: &Path,
+    date: NaiveDate,
+) -> Result<Vec<DateTime<Utc>>> {
+    let email = git(repo, &["config", "user.email"])?;
+    let since = local_midnight(date)?;
+    let until = local_midnight(date + Duration::days(1))?;
+    let log = git(
+        repo,
+        &[
+            "log",
+            "--all",
+            &format!("--author={}", email.trim()),
+            &format!("--since={}", since.to_rfc3339()),
+            "--format=%aI",
+        ],
+    )?;
+    let mut times = Vec::new();
+    for line in log.lines().filter(|line| !line.is_empty()) {
+        let dt = DateTime::parse_from_rfc3339(line)
+            .with_context(|| format!("Unexpected output from git: {line}"))?
+            .to_utc();
+        // --since filters by commit date, which may differ from the date
+        // the commit was authored
+        if since <= dt && dt < until {
+            times.push(dt);
+        }
+    }
+    Ok(times)
+}
+
+fn git(repo: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .context("Could not run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed in {}: {}",
+            args[0],
+            repo.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn local_midnight(date: NaiveDate) -> Result<DateTime<Utc>> {
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .earliest()
+        .map(|dt| dt.to_utc())
+        .context("Midnight does not exist in the local time zone")
+}
+
+/// Suggests the clock events of a day that were likely forgotten, judging
+/// by when commits were made: a clock-in at the first commit if the day
+/// doesn't start with one, and a clock-out at the last commit if the day
+/// doesn't end with one
+pub fn suggest(events: &[Event], commits: &[DateTime<Utc>]) -> Vec<Event> {
+    let (Some(first), Some(last)) =
+        (commits.iter().min(), commits.iter().max())
+    else {
+        return Vec::new();
+    };
+    let mut sorted: Vec<&Event> = events.iter().collect();
+    sorted.sort_by_key(|event| event.dt);
+
+    let mut suggestions = Vec::new();
+    match sorted.first() {
+        None => {
+            suggestions.push(Event::clock_in(first));
+            if last > first {
+                suggestions.push(Event::clock_out(last));
+            }
+            return suggestions;
+        }
+        Some(event)
+            if event.kind == EventKind::ClockOut && first < &event.dt =>
+        {
+            suggestions.push(Event::clock_in(first));
+        }
+        Some(_) => {}
+    }
+    if let Some(event) = sorted.last() {
+        if event.kind == EventKind::ClockIn && last > &event.dt {
+            suggestions.push(Event::clock_out(last));
+        }
+    }
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggest_fills_in_missing_ends_of_the_day() {
+        let at = |h, m| Utc.with_ymd_and_hms(2024, 3, 1, h, m, 0).unwrap();
+        let commits = [at(11, 0), at(9, 15), at(17, 40)];
+
+        assert_eq!(
+            suggest(&[], &commits),
+            vec![Event::clock_in(&at(9, 15)), Event::clock_out(&at(17, 40))]
+        );
+        assert_eq!(
+            suggest(&[Event::clock_in(&at(8, 0))], &commits),
+            vec![Event::clock_out(&at(17, 40))]
+        );
+        assert_eq!(
+            suggest(&[Event::clock_out(&at(18, 0))], &commits),
+            vec![Event::clock_in(&at(9, 15))]
+        );
+        let complete =
+            [Event::clock_in(&at(8, 0)), Event::clock_out(&at(18, 0))];
+        assert!(suggest(&complete, &commits).is_empty());
+        assert!(suggest(&[], &[]).is_empty());
+    }
+}
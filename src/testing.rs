@@ -0,0 +1,123 @@
+#[cfg(feature = "fs")]
+use chrono::{
+    FixedOffset, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+};
+
+/// A small, seeded pseudo random number generator (xorshift64*) for tests
+/// that check properties on many generated inputs
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift must not start at zero
+        Self(seed | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A number in `0..bound`
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    /// A string of up to `max_len` characters, mixing ASCII, separators
+    /// used by the data formats, and other unicode
+    pub fn string(&mut self, max_len: u64) -> String {
+        const SPECIAL: [char; 12] = [
+            ',', ';', ':', '"', '\\', '{', '}', '\n', ' ', 'ä', '€', '🐝',
+        ];
+        let len = self.below(max_len + 1);
+        (0..len)
+            .map(|_| match self.below(3) {
+                #[allow(clippy::cast_possible_truncation)]
+                0 => char::from(b' ' + self.below(95) as u8),
+                #[allow(clippy::cast_possible_truncation)]
+                1 => SPECIAL[self.below(SPECIAL.len() as u64) as usize],
+                _ => char::from_u32(
+                    u32::try_from(self.below(0x11_0000)).unwrap(),
+                )
+                .unwrap_or('x'),
+            })
+            .collect()
+    }
+}
+
+/// Central European Time with the daylight saving time of 2024, from
+/// 02:00 on March 31 to 03:00 on October 27, for tests that mustn't depend
+/// on the time zone of the machine they run on
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy)]
+pub struct CentralEurope;
+
+#[cfg(feature = "fs")]
+impl CentralEurope {
+    fn offsets() -> [FixedOffset; 2] {
+        // summer time first, as it's the earlier instant of an ambiguous
+        // local time
+        [
+            FixedOffset::east_opt(2 * 3600).unwrap(),
+            FixedOffset::east_opt(3600).unwrap(),
+        ]
+    }
+}
+
+#[cfg(feature = "fs")]
+impl TimeZone for CentralEurope {
+    type Offset = FixedOffset;
+
+    fn from_offset(_: &FixedOffset) -> Self {
+        CentralEurope
+    }
+
+    fn offset_from_local_date(
+        &self,
+        local: &NaiveDate,
+    ) -> LocalResult<FixedOffset> {
+        self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+    }
+
+    fn offset_from_local_datetime(
+        &self,
+        local: &NaiveDateTime,
+    ) -> LocalResult<FixedOffset> {
+        let matching: Vec<FixedOffset> = Self::offsets()
+            .into_iter()
+            .filter(|offset| {
+                let utc = *local
+                    - chrono::Duration::seconds(
+                        offset.local_minus_utc().into(),
+                    );
+                self.offset_from_utc_datetime(&utc) == *offset
+            })
+            .collect();
+        match matching[..] {
+            [offset] => LocalResult::Single(offset),
+            [earliest, latest] => LocalResult::Ambiguous(earliest, latest),
+            _ => LocalResult::None,
+        }
+    }
+
+    fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+        self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+    }
+
+    fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+        let at = |month, day| {
+            NaiveDate::from_ymd_opt(2024, month, day)
+                .unwrap()
+                .and_hms_opt(1, 0, 0)
+                .unwrap()
+        };
+        let [summer, winter] = Self::offsets();
+        if at(3, 31) <= *utc && *utc < at(10, 27) {
+            summer
+        } else {
+            winter
+        }
+    }
+}
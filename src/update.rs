@@ -0,0 +1,279 @@
+//! Replacing the running executable with the latest release from GitHub,
+//! for those who didn't install with cargo. Downloads go through `curl`,
+//! and binaries are checked against the SHA-256 checksum published next to
+//! them before they're installed.
+
+use std::{
+    env,
+    fmt::Write as _,
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use tempfile::NamedTempFile;
+
+use crate::{json, platform::parent_dir};
+
+const LATEST_RELEASE: &str =
+    "https://api.github.com/repos/hbibel/busy-bee/releases/latest";
+
+/// The latest release, with the downloads for this platform
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Release {
+    /// The version without the `v` of its tag, e.g., `0.2.0`
+    pub version: String,
+    pub binary_url: String,
+    /// Where to find the checksum of the binary, as `sha256sum` prints it
+    pub checksum_url: String,
+}
+
+impl Release {
+    pub fn latest() -> Result<Release> {
+        let response = download(LATEST_RELEASE)?;
+        let response = String::from_utf8(response)
+            .context("GitHub sent a release that isn't UTF-8")?;
+        Release::parse(&response, &asset_name())
+    }
+
+    /// Reads a release as described by GitHub's REST API, picking the
+    /// assets named `asset` and `asset` + `.sha256`
+    pub fn parse(response: &str, asset: &str) -> Result<Release> {
+        let value = json::parse(response)?;
+        let tag = value
+            .get("tag_name")
+            .and_then(json::Value::as_str)
+            .ok_or(anyhow!("The release has no tag"))?;
+        let assets = value
+            .get("assets")
+            .and_then(json::Value::as_array)
+            .unwrap_or_default();
+        let url = |name: &str| {
+            assets
+                .iter()
+                .find(|a| {
+                    a.get("name").and_then(json::Value::as_str) == Some(name)
+                })
+                .and_then(|a| a.get("browser_download_url"))
+                .and_then(json::Value::as_str)
+                .map(String::from)
+                .ok_or(anyhow!("Release {tag} has no download {name}"))
+        };
+        Ok(Release {
+            version: tag.trim_start_matches('v').to_string(),
+            binary_url: url(asset)?,
+            checksum_url: url(&format!("{asset}.sha256"))?,
+        })
+    }
+
+    /// Whether this release is newer than the running version
+    pub fn is_newer(&self) -> bool {
+        is_newer(&self.version, env!("CARGO_PKG_VERSION"))
+    }
+
+    /// Downloads the binary, checks it and puts it in place of the running
+    /// executable, whose path is returned
+    pub fn install(&self) -> Result<PathBuf> {
+        let binary = download(&self.binary_url)?;
+        let checksum = String::from_utf8(download(&self.checksum_url)?)?;
+        let expected = checksum
+            .split_whitespace()
+            .next()
+            .ok_or(anyhow!("The checksum of the download is empty"))?;
+        if !hex(&sha256(&binary)).eq_ignore_ascii_case(expected) {
+            bail!("The download doesn't match its checksum, nothing changed");
+        }
+        replace_executable(&binary)
+    }
+}
+
+/// The name of the download for this platform, e.g.,
+/// `busy-bee-x86_64-linux` or `busy-bee-x86_64-windows.exe`
+pub fn asset_name() -> String {
+    format!(
+        "busy-bee-{}-{}{}",
+        env::consts::ARCH,
+        env::consts::OS,
+        env::consts::EXE_SUFFIX
+    )
+}
+
+/// Compares versions like `0.10.1` number by number
+fn is_newer(version: &str, current: &str) -> bool {
+    let numbers = |version: &str| -> Vec<u64> {
+        version
+            .split(['.', '-', '+'])
+            .map_while(|number| number.parse().ok())
+            .collect()
+    };
+    numbers(version) > numbers(current)
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location", url])
+        .stdin(Stdio::null())
+        .output()
+        .context("Could not run curl, please install it")?;
+    if !output.status.success() {
+        bail!(
+            "Could not download {url}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// Writes `binary` next to the running executable and renames it to take
+/// its place. Windows doesn't let running executables be replaced, but
+/// renamed, so there the old one is kept as `.old` until the next update.
+fn replace_executable(binary: &[u8]) -> Result<PathBuf> {
+    let exe = env::current_exe()?.canonicalize()?;
+    let dir = parent_dir(&exe);
+    let mut file = NamedTempFile::new_in(dir)
+        .with_context(|| format!("Could not write to {}", dir.display()))?;
+    file.write_all(binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(file.path(), fs::Permissions::from_mode(0o755))?;
+    }
+    if cfg!(windows) {
+        let old = exe.with_extension("old");
+        let _ = fs::remove_file(&old);
+        fs::rename(&exe, &old)?;
+    }
+    file.persist(&exe)?;
+    Ok(exe)
+}
+
+/// The round constants of SHA-256
+#[allow(clippy::unreadable_literal)]
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1,
+    0x923f82a4, 0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3,
+    0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786,
+    0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147,
+    0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13,
+    0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+    0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a,
+    0x5b9cca4f, 0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208,
+    0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// The SHA-256 digest of `data`, with the names of the specification
+#[allow(clippy::many_single_char_names, clippy::unreadable_literal)]
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f,
+        0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    let bits = u64::try_from(data.len()).unwrap().wrapping_mul(8);
+    message.extend_from_slice(&bits.to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7)
+                ^ w[i - 15].rotate_right(18)
+                ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17)
+                ^ w[i - 2].rotate_right(19)
+                ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 =
+                e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 =
+                a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+    let mut digest = [0; 32];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha256_matches_known_digests() {
+        assert_eq!(
+            hex(&sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex(&sha256(&[b'a'; 1000])),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
+    }
+
+    #[test]
+    fn releases_are_read_from_the_api_response() {
+        let response = r#"{"tag_name": "v0.10.0", "assets": [
+            {"name": "busy-bee-x86_64-linux",
+             "browser_download_url": "https://example.com/bin"},
+            {"name": "busy-bee-x86_64-linux.sha256",
+             "browser_download_url": "https://example.com/bin.sha256"}
+        ]}"#;
+        let release =
+            Release::parse(response, "busy-bee-x86_64-linux").unwrap();
+        assert_eq!(release.version, "0.10.0");
+        assert_eq!(release.binary_url, "https://example.com/bin");
+        assert_eq!(release.checksum_url, "https://example.com/bin.sha256");
+        assert!(Release::parse(response, "busy-bee-aarch64-macos").is_err());
+
+        assert!(is_newer("0.10.0", "0.9.3"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+    }
+}
@@ -1,13 +1,24 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     error::Error,
     fmt::{Display, Write},
     ops::Sub,
+    str::FromStr,
 };
 
-use chrono::{DateTime, Datelike, Duration, Local, NaiveDate};
+use chrono::{
+    DateTime, Datelike, Duration, Local, Months, NaiveDate, NaiveTime,
+    Timelike, Utc,
+};
 
-use crate::data::{Event, EventKind};
+use crate::{
+    burndown,
+    expense::{format_amount, Expense},
+    i18n::{self, tr},
+    interval::{intervals, overlap, standby, Interval},
+    json,
+    record::{event_to_json, DayNote, Event, EventKind, StoredEvent},
+};
 
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug)]
@@ -29,125 +40,1567 @@ impl<T: Error> From<T> for ViewError {
     }
 }
 
+/// How reports are printed
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum ReportFormat {
+    Text,
+    /// Headings and tables to paste into wikis and issue comments
+    Markdown,
+    /// The numbers of the report for scripts, durations in minutes
+    Json,
+    /// Headings and tables to put on a web page
+    Html,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(ReportFormat::Text),
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            "json" => Ok(ReportFormat::Json),
+            "html" => Ok(ReportFormat::Html),
+            _ => Err(format!(
+                "Unknown report format {s}, try text, markdown, json or html"
+            )),
+        }
+    }
+}
+
+/// How the days of a monthly report are grouped
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Grouping {
+    Day,
+    /// Days grouped by ISO week, with the time worked in each week
+    Week,
+}
+
+impl FromStr for Grouping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "day" => Ok(Grouping::Day),
+            "week" => Ok(Grouping::Week),
+            _ => Err(format!("Unknown grouping {s}, try day or week")),
+        }
+    }
+}
+
+/// Which days of a [`MonthlyReport`] are due, and which of them it lists
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct RowOptions {
+    /// Days that should have records but have none, e.g., workdays
+    pub missing: Vec<NaiveDate>,
+    /// The hours due on each weekday that isn't one of the `holidays`
+    pub daily_target: Duration,
+    pub holidays: Vec<NaiveDate>,
+    /// Only list the days that match, so that long reports can be scanned
+    pub only: Option<RowFilter>,
+}
+
+impl RowOptions {
+    fn target(&self, date: NaiveDate) -> Duration {
+        burndown::due(date, date, self.daily_target, &self.holidays)
+    }
+}
+
+/// The days that a [`MonthlyReport`] can be limited to
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum RowFilter {
+    /// Days whose records are incomplete or missing
+    Incomplete,
+    /// Complete days with more time worked than due
+    Overtime,
+}
+
+/// Lists the events of a day with their IDs, which are their positions in
+/// `events`. These only match the IDs that deleting takes if `events` are
+/// all stored events of the day, in order.
 pub fn daily_report(
     date: &NaiveDate,
     events: &[Event],
+    format: ReportFormat,
 ) -> Result<String, ViewError> {
-    let mut result = String::new();
+    daily_report_with_ids(date, &StoredEvent::number(events), &[], &[], format)
+}
+
+/// Lists the `stored` events of a day with their IDs, along with events
+/// that count towards the day without being stored, e.g., an automatic
+/// clock-out, which get no ID, and the `notes` on the day
+pub fn daily_report_with_ids(
+    date: &NaiveDate,
+    stored: &[StoredEvent],
+    unstored: &[Event],
+    notes: &[DayNote],
+    format: ReportFormat,
+) -> Result<String, ViewError> {
+    DailyReport::compute(*date, stored, unstored, notes).render(format)
+}
 
-    write!(result, "Records for ")?;
-    let today = Local::now().date_naive();
-    if same_date(date, &today) {
-        write!(result, "today, ")?;
+/// The records of a day and what they add up to, for each of the ways
+/// that reports are rendered
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct DailyReport {
+    pub date: NaiveDate,
+    /// The events in order, with the IDs of those that are stored
+    pub events: Vec<(Option<u32>, Event)>,
+    pub intervals: Vec<Interval>,
+    pub worked: Duration,
+    /// Whether the clock-ins and clock-outs pair up
+    pub complete: bool,
+    pub notes: Vec<String>,
+    /// Whether the details tell how each event was recorded
+    pub sources: bool,
+    /// How many columns the text fits into, if it's limited
+    pub width: Option<usize>,
+}
+
+impl DailyReport {
+    /// Sorts the `stored` events in with the `unstored` ones, which get no
+    /// ID, and adds them up
+    pub fn compute(
+        date: NaiveDate,
+        stored: &[StoredEvent],
+        unstored: &[Event],
+        notes: &[DayNote],
+    ) -> DailyReport {
+        let mut listed: Vec<(Option<u32>, Event)> = stored
+            .iter()
+            .map(|stored| (Some(stored.id), stored.event.clone()))
+            .chain(unstored.iter().map(|event| (None, event.clone())))
+            .collect();
+        listed.sort_by_key(|(_, event)| event.dt);
+        let events: Vec<Event> =
+            listed.iter().map(|(_, event)| event.clone()).collect();
+        let WorkingTime { worked, complete } = working_time(&events);
+        DailyReport {
+            date,
+            events: listed,
+            intervals: intervals(&events),
+            worked,
+            complete,
+            notes: notes.iter().map(|note| note.text.clone()).collect(),
+            sources: false,
+            width: None,
+        }
+    }
+
+    /// Tells how each event was recorded, e.g., to tell the events of an
+    /// idle daemon from those typed in
+    #[must_use]
+    pub fn with_sources(mut self, sources: bool) -> DailyReport {
+        self.sources = sources;
+        self
     }
-    writeln!(result, "{}:", date.format("%b %d, %Y"))?;
 
-    for (i, event) in events.iter().enumerate() {
-        let local_time: DateTime<Local> = DateTime::from(event.dt);
-        let time_str = local_time.format("%H:%M");
-        let kind_str = match event.kind {
-            EventKind::ClockIn => "clock in ",
-            EventKind::ClockOut => "clock out",
+    /// Fits the text into `width` columns, e.g., those of the terminal.
+    /// Details that don't fit next to their event go on a line of their
+    /// own, shortened if they still don't fit.
+    #[must_use]
+    pub fn with_width(mut self, width: Option<usize>) -> DailyReport {
+        self.width = width;
+        self
+    }
+
+    /// Renders the report in `format`
+    pub fn render(&self, format: ReportFormat) -> Result<String, ViewError> {
+        let mut result = String::new();
+        let title = self.title();
+        let rows = self.rows();
+        if format == ReportFormat::Json {
+            writeln!(result, "{}", self.to_json())?;
+            return Ok(result);
+        } else if format == ReportFormat::Html {
+            return Ok(self.to_html());
+        } else if format == ReportFormat::Markdown {
+            markdown_table(
+                &mut result,
+                &title,
+                ["#", tr("Time"), tr("Event"), tr("Details")],
+                &rows,
+            )?;
+        } else {
+            writeln!(result, "{title}:")?;
+            let width = self.width.unwrap_or(usize::MAX);
+            for [i, time_str, kind_str, details] in &rows {
+                let event = format!("{i} | {time_str} | {kind_str} |");
+                if details.is_empty() {
+                    writeln!(result, "{event}")?;
+                } else if event.chars().count() + details.chars().count()
+                    < width
+                {
+                    writeln!(result, "{event} {details}")?;
+                } else {
+                    let details = ellipsize(
+                        details,
+                        width.saturating_sub(DETAILS_INDENT),
+                    );
+                    writeln!(result, "{event}")?;
+                    writeln!(result, "{:DETAILS_INDENT$}{details}", "")?;
+                }
+            }
+        }
+        for line in self.lines() {
+            paragraph(&mut result, format, &line)?;
+        }
+        Ok(result)
+    }
+
+    /// Tells how long was worked on the day and how that compares to the
+    /// `target`, e.g., "Worked 08:12 hours today, 00:12 overtime"
+    pub fn summary(&self, target: Duration) -> String {
+        let today = Local::now().date_naive();
+        let when = if same_date(&self.date, &today) {
+            tr("today").to_string()
+        } else {
+            format!("{} {}", tr("on"), i18n::date(&self.date))
         };
-        writeln!(result, "{i} | {time_str} | {kind_str} |")?;
+        let difference = self.worked - target;
+        format!(
+            "{} {} {} {when}, {} {}",
+            tr("Worked"),
+            i18n::duration(&self.worked),
+            tr("hours"),
+            i18n::duration(&difference.abs()),
+            if difference < Duration::zero() {
+                tr("short of the target")
+            } else {
+                tr("overtime")
+            }
+        )
+    }
+
+    /// The report as a JSON object, with durations in minutes
+    pub fn to_json(&self) -> json::Value {
+        let events = self
+            .events
+            .iter()
+            .map(|(id, event)| {
+                let mut entries = vec![("id".to_string(), (*id).into())];
+                entries.extend(event_to_json(event));
+                json::Value::Object(entries)
+            })
+            .collect();
+        json::Value::Object(vec![
+            ("date".to_string(), self.date.to_string().into()),
+            ("events".to_string(), json::Value::Array(events)),
+            ("intervals".to_string(), intervals_to_json(&self.intervals)),
+            (
+                "worked_minutes".to_string(),
+                self.worked.num_minutes().into(),
+            ),
+            ("complete".to_string(), self.complete.into()),
+            (
+                "notes".to_string(),
+                json::Value::Array(
+                    self.notes.iter().map(|n| n.as_str().into()).collect(),
+                ),
+            ),
+        ])
     }
 
-    let WorkingTime {
-        hours,
-        minutes,
-        complete,
-    } = working_time(events);
-    writeln!(result, "Total working time: {hours:02}:{minutes:02} hours")?;
-    if !complete {
-        writeln!(result, "Incomplete records, please update")?;
+    /// The report as a single line of tab-separated fields for scripts:
+    /// the date, the minutes worked, `complete` or `incomplete`, and how
+    /// many events there are. These fields keep their order and meaning
+    /// across versions; new ones are only ever added at the end.
+    pub fn to_porcelain(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}",
+            self.date,
+            self.worked.num_minutes(),
+            if self.complete {
+                "complete"
+            } else {
+                "incomplete"
+            },
+            self.events.len()
+        )
+    }
+
+    /// The report as an HTML fragment, a heading and a table followed by
+    /// paragraphs
+    pub fn to_html(&self) -> String {
+        let mut result = html_table(
+            &self.title(),
+            ["#", tr("Time"), tr("Event"), tr("Details")],
+            &self.rows(),
+        );
+        for line in self.lines() {
+            result.push_str(&html_paragraph(&line));
+        }
+        result
     }
+
+    fn title(&self) -> String {
+        let mut title = format!("{} ", tr("Records for"));
+        let today = Local::now().date_naive();
+        if same_date(&self.date, &today) {
+            let _ = write!(title, "{}, ", tr("today"));
+        }
+        title.push_str(&i18n::date(&self.date));
+        title
+    }
+
+    fn rows(&self) -> Vec<[String; 4]> {
+        self.events
+            .iter()
+            .map(|(id, event)| {
+                let local_time: DateTime<Local> = DateTime::from(event.dt);
+                let time_str = local_time.format("%H:%M");
+                let kind_str = match event.kind {
+                    EventKind::ClockIn => "clock in",
+                    EventKind::ClockOut => "clock out",
+                    EventKind::StandbyStart => "on call",
+                    EventKind::StandbyEnd => "off call",
+                    EventKind::Interruption { .. } => "interrupt",
+                };
+                let kind_str = format!("{:<9}", tr(kind_str));
+                let mut details = match &event.kind {
+                    EventKind::Interruption { minutes } => {
+                        let note = event.note.as_deref().unwrap_or_default();
+                        format!("{minutes} min, {note}")
+                    }
+                    _ => event.tags.join(", "),
+                };
+                if let Some(source) = event.source.filter(|_| self.sources) {
+                    if !details.is_empty() {
+                        details.push_str(", ");
+                    }
+                    let _ = write!(details, "{} {source}", tr("via"));
+                }
+                let id = id.map_or("-".to_string(), |id| id.to_string());
+                [id, time_str.to_string(), kind_str, details]
+            })
+            .collect()
+    }
+
+    /// The total, whether the records are incomplete, and the notes
+    fn lines(&self) -> Vec<String> {
+        let mut lines = vec![total_line(self.worked)];
+        if !self.complete {
+            lines.push(tr("Incomplete records, please update").to_string());
+        }
+        for note in &self.notes {
+            lines.push(format!("{}: {note}", tr("Note")));
+        }
+        lines
+    }
+}
+
+/// The number of characters that a day takes in [`week_timeline`], one per
+/// 15 minutes
+const SLOTS_PER_DAY: usize = 24 * 4;
+
+/// Draws each of the `days` as a bar with one character per 15 minutes:
+/// `#` for working time, `.` for breaks and `?` where a clock-in or
+/// clock-out seems to be missing. Blocks still open at `now` count as work
+/// in progress on the current day only.
+pub fn week_timeline(
+    days: &[(NaiveDate, Vec<Event>)],
+    now: DateTime<Utc>,
+) -> Result<String, ViewError> {
+    let bars: Vec<[char; SLOTS_PER_DAY]> = days
+        .iter()
+        .map(|(date, events)| day_bar(*date, events, now))
+        .collect();
+    // only the hours that anything happened in, but at least 8 to 18
+    let used = |bar: &[char; SLOTS_PER_DAY]| {
+        let first = bar.iter().position(|c| *c != ' ');
+        first.zip(bar.iter().rposition(|c| *c != ' '))
+    };
+    let (first, last) = bars
+        .iter()
+        .filter_map(used)
+        .fold((8 * 4, 18 * 4 - 1), |(first, last), (start, end)| {
+            (first.min(start), last.max(end))
+        });
+    let (first, last) = (first / 4 * 4, last / 4 * 4 + 3);
+
+    let mut result = String::new();
+    let labels: Vec<String> =
+        days.iter().map(|(d, _)| i18n::date(d)).collect();
+    let width = labels.iter().map(String::len).max().unwrap_or(0);
+    let mut hours = String::new();
+    for slot in (first..=last).step_by(8) {
+        write!(hours, "{:<8}", format!("{:02}", slot / 4))?;
+    }
+    writeln!(result, "{:width$}  {}", "", hours.trim_end())?;
+    for ((label, bar), (_, events)) in labels.iter().zip(&bars).zip(days) {
+        let bar: String = bar[first..=last].iter().collect();
+        let worked = working_time(events).worked;
+        writeln!(
+            result,
+            "{label:<width$} |{bar}| {}",
+            i18n::duration(&worked)
+        )?;
+    }
+    writeln!(
+        result,
+        "# {}, . {}, ? {}",
+        tr("worked"),
+        tr("break"),
+        tr("missing clock-in or clock-out")
+    )?;
     Ok(result)
 }
 
+fn day_bar(
+    date: NaiveDate,
+    events: &[Event],
+    now: DateTime<Utc>,
+) -> [char; SLOTS_PER_DAY] {
+    let mut bar = [' '; SLOTS_PER_DAY];
+    let slot = |dt: DateTime<Utc>| {
+        let local = dt.with_timezone(&Local);
+        if local.date_naive() < date {
+            return 0;
+        }
+        if local.date_naive() > date {
+            return SLOTS_PER_DAY;
+        }
+        let minutes = local.time().num_seconds_from_midnight() / 60;
+        usize::try_from(minutes / 15).unwrap_or(0)
+    };
+    let mut fill = |from: DateTime<Utc>, to: DateTime<Utc>, c: char| {
+        let from = slot(from).min(SLOTS_PER_DAY - 1);
+        let to = slot(to).clamp(from + 1, SLOTS_PER_DAY);
+        bar[from..to].fill(c);
+    };
+
+    let mut clock_events: Vec<&Event> = events
+        .iter()
+        .filter(|event| event.kind.is_clock())
+        .collect();
+    clock_events.sort_by_key(|event| event.dt);
+    let mut open: Option<DateTime<Utc>> = None;
+    let mut last_out: Option<DateTime<Utc>> = None;
+    for event in clock_events {
+        match (&event.kind, open) {
+            (EventKind::ClockIn, Some(start)) => fill(start, event.dt, '?'),
+            (EventKind::ClockIn, None) => {
+                if let Some(end) = last_out {
+                    fill(end, event.dt, '.');
+                }
+            }
+            (EventKind::ClockOut, Some(start)) => fill(start, event.dt, '#'),
+            _ => fill(event.dt, event.dt, '?'),
+        }
+        if event.kind == EventKind::ClockIn {
+            open = Some(event.dt);
+        } else {
+            open = None;
+            last_out = Some(event.dt);
+        }
+    }
+    if let Some(start) = open {
+        if now.with_timezone(&Local).date_naive() == date {
+            fill(start, now, '#');
+        } else {
+            let end_of_day = start + Duration::days(1);
+            fill(start, end_of_day, '?');
+        }
+    }
+    bar
+}
+
+/// Lists the time worked on each day of the month of `date` that has
+/// `events` against the time due, and flags the missing days of `options`
 pub fn monthly_report(
     date: &NaiveDate,
     events: &[Event],
+    grouping: Grouping,
+    options: &RowOptions,
+    format: ReportFormat,
 ) -> Result<String, ViewError> {
-    let mut result = String::new();
+    MonthlyReport::month(date, events, grouping, options).render(format)
+}
+
+/// Summarizes the days from `from` to `to` like [`monthly_report`], along
+/// with how much was worked compared to the hours `due` in that time
+pub fn range_report(
+    from: &NaiveDate,
+    to: &NaiveDate,
+    events: &[Event],
+    grouping: Grouping,
+    due: Duration,
+    options: &RowOptions,
+    format: ReportFormat,
+) -> Result<String, ViewError> {
+    MonthlyReport::range(from, to, events, grouping, due, options)
+        .render(format)
+}
+
+/// A line of a [`MonthlyReport`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub enum ReportRow {
+    Day {
+        date: NaiveDate,
+        worked: Duration,
+        /// Whether the clock-ins and clock-outs of the day pair up
+        complete: bool,
+        target: Duration,
+    },
+    /// A day that should have records but has none, e.g., a workday
+    Missing { date: NaiveDate, target: Duration },
+    /// The time worked in an ISO week, following its days
+    Week {
+        week: u32,
+        worked: Duration,
+        /// The time due on the days of the week within the report
+        target: Duration,
+    },
+}
+
+/// The time worked on each day of a month, or of any range of days, for
+/// each of the ways that reports are rendered
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct MonthlyReport {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// The days that have events and the missing days, in order
+    pub rows: Vec<ReportRow>,
+    pub worked: Duration,
+    /// The hours due in a range; a month has none, and is titled by its
+    /// name instead
+    pub due: Option<Duration>,
+    pub stats: DayStats,
+}
+
+/// When the days of a report typically start and end, from the first
+/// clock-in and the last clock-out of each day that has intervals
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct DayStats {
+    pub average_start: Option<NaiveTime>,
+    pub median_start: Option<NaiveTime>,
+    pub average_end: Option<NaiveTime>,
+    pub median_end: Option<NaiveTime>,
+    /// The time worked per day that has intervals
+    pub average_worked: Duration,
+    /// The days whose clock-ins and clock-outs don't pair up
+    pub incomplete: usize,
+}
+
+impl DayStats {
+    /// Works out the local start and end times of the days of `intervals`,
+    /// along with the number of `incomplete` days
+    pub fn compute(intervals: &[Interval], incomplete: usize) -> DayStats {
+        let mut per_day: BTreeMap<NaiveDate, (Interval, Duration)> =
+            BTreeMap::new();
+        for interval in intervals {
+            let (day, worked) = per_day
+                .entry(interval.start.date_naive())
+                .or_insert_with(|| (interval.clone(), Duration::zero()));
+            day.start = day.start.min(interval.start);
+            day.end = day.end.max(interval.end);
+            *worked += interval.duration();
+        }
+        let local = |dt: &DateTime<Utc>| {
+            dt.with_timezone(&Local).time().num_seconds_from_midnight()
+        };
+        let starts: Vec<u32> =
+            per_day.values().map(|(day, _)| local(&day.start)).collect();
+        let ends: Vec<u32> =
+            per_day.values().map(|(day, _)| local(&day.end)).collect();
+        let total: Duration =
+            per_day.values().map(|(_, worked)| *worked).sum();
+        DayStats {
+            average_start: average_time(&starts),
+            median_start: median_time(starts),
+            average_end: average_time(&ends),
+            median_end: median_time(ends),
+            average_worked: i32::try_from(per_day.len())
+                .ok()
+                .filter(|days| *days > 0)
+                .map_or_else(Duration::zero, |days| total / days),
+            incomplete,
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let hm = |time: Option<NaiveTime>| {
+            time.map_or("-".to_string(), |t| t.format("%H:%M").to_string())
+        };
+        if self.average_start.is_some() {
+            lines.push(format!(
+                "{}: {}, {} {}",
+                tr("Average start"),
+                hm(self.average_start),
+                tr("median"),
+                hm(self.median_start)
+            ));
+            lines.push(format!(
+                "{}: {}, {} {}",
+                tr("Average end"),
+                hm(self.average_end),
+                tr("median"),
+                hm(self.median_end)
+            ));
+            lines.push(format!(
+                "{}: {} {}",
+                tr("Average working time per day"),
+                i18n::duration(&self.average_worked),
+                tr("hours")
+            ));
+        }
+        if self.incomplete > 0 {
+            lines.push(format!(
+                "{}: {}",
+                tr("Incomplete days"),
+                self.incomplete
+            ));
+        }
+        lines
+    }
 
-    writeln!(result, "Summary for {}:", date.format("%B %Y"))?;
+    fn to_json(&self) -> json::Value {
+        let hm = |time: Option<NaiveTime>| {
+            time.map(|t| t.format("%H:%M").to_string()).into()
+        };
+        json::Value::Object(vec![
+            ("average_start".to_string(), hm(self.average_start)),
+            ("median_start".to_string(), hm(self.median_start)),
+            ("average_end".to_string(), hm(self.average_end)),
+            ("median_end".to_string(), hm(self.median_end)),
+            (
+                "average_worked_minutes".to_string(),
+                self.average_worked.num_minutes().into(),
+            ),
+            (
+                "incomplete_days".to_string(),
+                u32::try_from(self.incomplete).unwrap_or(u32::MAX).into(),
+            ),
+        ])
+    }
+}
 
-    // using BTreeMap for its sorted keys
-    let mut events_per_day = BTreeMap::new();
+/// The mean of times given in seconds since midnight
+fn average_time(seconds: &[u32]) -> Option<NaiveTime> {
+    let count = u64::try_from(seconds.len()).ok().filter(|n| *n > 0)?;
+    let sum: u64 = seconds.iter().map(|s| u64::from(*s)).sum();
+    let mean = u32::try_from(sum / count).ok()?;
+    NaiveTime::from_num_seconds_from_midnight_opt(mean, 0)
+}
+
+fn median_time(mut seconds: Vec<u32>) -> Option<NaiveTime> {
+    seconds.sort_unstable();
+    let middle = seconds.len() / 2;
+    let median = match seconds.len() {
+        0 => return None,
+        n if n % 2 == 0 => u32::midpoint(seconds[middle - 1], seconds[middle]),
+        _ => seconds[middle],
+    };
+    NaiveTime::from_num_seconds_from_midnight_opt(median, 0)
+}
+
+impl MonthlyReport {
+    /// Adds up the `events` of the month of `date` day by day, with rows
+    /// for the missing days of `options` in between
+    pub fn month(
+        date: &NaiveDate,
+        events: &[Event],
+        grouping: Grouping,
+        options: &RowOptions,
+    ) -> MonthlyReport {
+        let from = date.with_day(1).unwrap();
+        let to = (from + Months::new(1)).pred_opt().unwrap();
+        MonthlyReport::compute(from, to, events, grouping, None, options)
+    }
+
+    /// Adds up the `events` from `from` to `to` like [`MonthlyReport::month`],
+    /// to compare with the hours `due` in that time
+    pub fn range(
+        from: &NaiveDate,
+        to: &NaiveDate,
+        events: &[Event],
+        grouping: Grouping,
+        due: Duration,
+        options: &RowOptions,
+    ) -> MonthlyReport {
+        MonthlyReport::compute(
+            *from,
+            *to,
+            events,
+            grouping,
+            Some(due),
+            options,
+        )
+    }
+
+    fn compute(
+        from: NaiveDate,
+        to: NaiveDate,
+        events: &[Event],
+        grouping: Grouping,
+        due: Option<Duration>,
+        options: &RowOptions,
+    ) -> MonthlyReport {
+        // using BTreeMap for its sorted keys
+        let mut events_per_day: BTreeMap<NaiveDate, Vec<Event>> = options
+            .missing
+            .iter()
+            .map(|date| (*date, Vec::new()))
+            .collect();
+        for event in events {
+            let days_events =
+                events_per_day.entry(event.dt.date_naive()).or_default();
+            days_events.push(event.clone());
+        }
+
+        let mut rows = Vec::new();
+        let mut week = None;
+        let mut weeks_events = Vec::new();
+        let week_row = |week: Option<u32>, events: &[Event]| {
+            let week = week.unwrap_or_default();
+            ReportRow::Week {
+                week,
+                worked: working_time(events).worked,
+                target: from
+                    .iter_days()
+                    .take_while(|date| *date <= to)
+                    .filter(|date| date.iso_week().week() == week)
+                    .map(|date| options.target(date))
+                    .sum(),
+            }
+        };
+        for (day, days_events) in events_per_day {
+            if grouping == Grouping::Week {
+                let day_week = Some(day.iso_week().week());
+                if week.is_some() && week != day_week {
+                    rows.push(week_row(week, &weeks_events));
+                    weeks_events.clear();
+                }
+                week = day_week;
+                weeks_events.extend_from_slice(&days_events);
+            }
+            let target = options.target(day);
+            if days_events.is_empty() {
+                rows.push(ReportRow::Missing { date: day, target });
+                continue;
+            }
+            let WorkingTime { worked, complete } = working_time(&days_events);
+            rows.push(ReportRow::Day {
+                date: day,
+                worked,
+                complete,
+                target,
+            });
+        }
+        if week.is_some() {
+            rows.push(week_row(week, &weeks_events));
+        }
+        let incomplete = rows
+            .iter()
+            .filter(|row| {
+                matches!(
+                    row,
+                    ReportRow::Day {
+                        complete: false,
+                        ..
+                    }
+                )
+            })
+            .count();
+        if let Some(only) = options.only {
+            rows.retain(|row| match (only, row) {
+                (_, ReportRow::Week { .. })
+                | (RowFilter::Incomplete, ReportRow::Missing { .. }) => true,
+                (RowFilter::Incomplete, ReportRow::Day { complete, .. }) => {
+                    !complete
+                }
+                (
+                    RowFilter::Overtime,
+                    ReportRow::Day {
+                        worked,
+                        complete,
+                        target,
+                        ..
+                    },
+                ) => *complete && worked > target,
+                (RowFilter::Overtime, ReportRow::Missing { .. }) => false,
+            });
+        }
+        MonthlyReport {
+            from,
+            to,
+            rows,
+            worked: working_time(events).worked,
+            due,
+            stats: DayStats::compute(&intervals(events), incomplete),
+        }
+    }
+
+    /// Renders the report in `format`
+    pub fn render(&self, format: ReportFormat) -> Result<String, ViewError> {
+        let mut result = String::new();
+        let title = self.title();
+        let rows = self.cells();
+        if format == ReportFormat::Json {
+            writeln!(result, "{}", self.to_json())?;
+            return Ok(result);
+        } else if format == ReportFormat::Html {
+            return Ok(self.to_html());
+        } else if format == ReportFormat::Markdown {
+            markdown_table(
+                &mut result,
+                &title,
+                MonthlyReport::header(),
+                &rows,
+            )?;
+        } else {
+            writeln!(result, "{title}:")?;
+            let width =
+                rows.iter().map(|[day, ..]| day.len()).max().unwrap_or(0);
+            let width = width.max(2);
+            for [day, recorded_time, target, delta, comment] in &rows {
+                writeln!(
+                    result,
+                    "{day:<width$} | {recorded_time:<5} | {target:<5} \
+                    | {delta:<6} | {comment}"
+                )?;
+            }
+        }
+        for line in self.lines() {
+            paragraph(&mut result, format, &line)?;
+        }
+        Ok(result)
+    }
+
+    /// The report as a JSON object, with durations in minutes
+    pub fn to_json(&self) -> json::Value {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| match row {
+                ReportRow::Day {
+                    date,
+                    worked,
+                    complete,
+                    target,
+                } => json::Value::Object(vec![
+                    ("date".to_string(), date.to_string().into()),
+                    (
+                        "worked_minutes".to_string(),
+                        worked.num_minutes().into(),
+                    ),
+                    ("complete".to_string(), (*complete).into()),
+                    (
+                        "target_minutes".to_string(),
+                        target.num_minutes().into(),
+                    ),
+                ]),
+                ReportRow::Missing { date, target } => {
+                    json::Value::Object(vec![
+                        ("date".to_string(), date.to_string().into()),
+                        ("missing".to_string(), true.into()),
+                        (
+                            "target_minutes".to_string(),
+                            target.num_minutes().into(),
+                        ),
+                    ])
+                }
+                ReportRow::Week {
+                    week,
+                    worked,
+                    target,
+                } => json::Value::Object(vec![
+                    ("week".to_string(), (*week).into()),
+                    (
+                        "worked_minutes".to_string(),
+                        worked.num_minutes().into(),
+                    ),
+                    (
+                        "target_minutes".to_string(),
+                        target.num_minutes().into(),
+                    ),
+                ]),
+            })
+            .collect();
+        json::Value::Object(vec![
+            ("from".to_string(), self.from.to_string().into()),
+            ("to".to_string(), self.to.to_string().into()),
+            ("rows".to_string(), json::Value::Array(rows)),
+            (
+                "worked_minutes".to_string(),
+                self.worked.num_minutes().into(),
+            ),
+            (
+                "due_minutes".to_string(),
+                self.due.map(|due| due.num_minutes()).into(),
+            ),
+            ("stats".to_string(), self.stats.to_json()),
+        ])
+    }
+
+    /// The report as an HTML fragment, a heading and a table followed by
+    /// paragraphs
+    pub fn to_html(&self) -> String {
+        let mut result =
+            html_table(&self.title(), MonthlyReport::header(), &self.cells());
+        for line in self.lines() {
+            result.push_str(&html_paragraph(&line));
+        }
+        result
+    }
+
+    fn title(&self) -> String {
+        match self.due {
+            None => {
+                format!("{} {}", tr("Summary for"), i18n::month(&self.from))
+            }
+            Some(_) => format!(
+                "{} {} {} {}",
+                tr("Summary from"),
+                i18n::date(&self.from),
+                tr("to"),
+                i18n::date(&self.to)
+            ),
+        }
+    }
+
+    fn header() -> [&'static str; 5] {
+        [tr("Day"), tr("Worked"), tr("Target"), "+/-", tr("Comment")]
+    }
+
+    fn cells(&self) -> Vec<[String; 5]> {
+        let label = |date: &NaiveDate| {
+            if self.due.is_some() {
+                date.format("%Y-%m-%d").to_string()
+            } else {
+                date.day().to_string()
+            }
+        };
+        self.rows
+            .iter()
+            .map(|row| match row {
+                ReportRow::Day {
+                    date,
+                    worked,
+                    complete,
+                    target,
+                } => {
+                    let label = label(date);
+                    if *complete {
+                        [
+                            label,
+                            i18n::duration(worked),
+                            i18n::duration(target),
+                            delta(*worked - *target),
+                            String::new(),
+                        ]
+                    } else {
+                        [
+                            label,
+                            "?".to_string(),
+                            i18n::duration(target),
+                            "?".to_string(),
+                            tr("Incomplete records, please update")
+                                .to_string(),
+                        ]
+                    }
+                }
+                ReportRow::Missing { date, target } => [
+                    label(date),
+                    "-".to_string(),
+                    i18n::duration(target),
+                    delta(-*target),
+                    tr("Missing, no records").to_string(),
+                ],
+                ReportRow::Week {
+                    week,
+                    worked,
+                    target,
+                } => [
+                    format!("{} {week}", tr("Week")),
+                    i18n::duration(worked),
+                    i18n::duration(target),
+                    delta(*worked - *target),
+                    String::new(),
+                ],
+            })
+            .collect()
+    }
+
+    /// The total, for a range the hours due, and when days start and end
+    fn lines(&self) -> Vec<String> {
+        let mut lines = vec![total_line(self.worked)];
+        if let Some(due) = self.due {
+            let balance = self.worked - due;
+            lines.push(format!(
+                "{}: {} {}, {} {}",
+                tr("Target"),
+                i18n::duration(&due),
+                tr("hours"),
+                i18n::duration(&balance.abs()),
+                if balance < Duration::zero() {
+                    tr("behind")
+                } else {
+                    tr("ahead")
+                }
+            ));
+        }
+        lines.extend(self.stats.lines());
+        lines
+    }
+}
+
+/// Lists the time recorded per tag, or nothing if no events are tagged
+pub fn tag_report(
+    events: &[Event],
+    format: ReportFormat,
+) -> Result<String, ViewError> {
+    let mut per_tag: BTreeMap<&str, Duration> = BTreeMap::new();
+    let intervals = intervals(events);
+    for interval in &intervals {
+        for tag in &interval.tags {
+            *per_tag.entry(tag).or_insert_with(Duration::zero) +=
+                interval.duration();
+        }
+    }
+    totals_report(tr("Working time per tag"), tr("Tag"), &per_tag, format)
+}
+
+/// Lists the time recorded per client, given which projects belong to
+/// which client, or nothing if no time was spent on their projects
+pub fn client_report(
+    events: &[Event],
+    clients: &BTreeMap<String, Vec<String>>,
+    format: ReportFormat,
+) -> Result<String, ViewError> {
+    let mut per_client: BTreeMap<&str, Duration> = BTreeMap::new();
+    for interval in intervals(events) {
+        let Some(project) = &interval.project else {
+            continue;
+        };
+        let client = clients
+            .iter()
+            .find(|(_, projects)| projects.contains(project));
+        if let Some((client, _)) = client {
+            *per_client.entry(client).or_insert_with(Duration::zero) +=
+                interval.duration();
+        }
+    }
+    totals_report(
+        tr("Working time per client"),
+        tr("Client"),
+        &per_client,
+        format,
+    )
+}
+
+/// The tag of time spent commuting or travelling for work, which contracts
+/// often count only in part
+pub const TRAVEL_TAG: &str = "travel";
+
+/// Sums up the time tagged [`TRAVEL_TAG`] and, if only `percent` of it
+/// counts as working time, how much time that leaves in total; nothing if
+/// there was no travel
+pub fn travel_report(
+    events: &[Event],
+    percent: u32,
+    format: ReportFormat,
+) -> Result<String, ViewError> {
+    let mut result = String::new();
+    let intervals = intervals(events);
+    let (travel, total) = intervals.iter().fold(
+        (Duration::zero(), Duration::zero()),
+        |(travel, total), interval| {
+            let duration = interval.duration();
+            if interval.tags.iter().any(|tag| tag == TRAVEL_TAG) {
+                (travel + duration, total + duration)
+            } else {
+                (travel, total + duration)
+            }
+        },
+    );
+    if travel.is_zero() {
+        return Ok(result);
+    }
+    let travel_line = format!(
+        "{}: {} {}",
+        tr("Travel time"),
+        i18n::duration(&travel),
+        tr("hours")
+    );
+    if percent >= 100 {
+        paragraph(&mut result, format, &travel_line)?;
+        return Ok(result);
+    }
+    let counted = travel * i32::try_from(percent).unwrap_or(0) / 100;
+    let line = format!(
+        "{travel_line}, {} {percent}% = {}",
+        tr("counted at"),
+        i18n::duration(&counted)
+    );
+    paragraph(&mut result, format, &line)?;
+    let line = format!(
+        "{}: {} {}",
+        tr("Counted working time"),
+        i18n::duration(&(total - travel + counted)),
+        tr("hours")
+    );
+    paragraph(&mut result, format, &line)?;
+    Ok(result)
+}
+
+/// Counts the days worked at each location that clock-ins were given,
+/// e.g., to tell days in the office from days at home; nothing if none were
+pub fn location_report(
+    events: &[Event],
+    format: ReportFormat,
+) -> Result<String, ViewError> {
+    let mut result = String::new();
+    let mut per_location: BTreeMap<&str, BTreeSet<NaiveDate>> =
+        BTreeMap::new();
     for event in events {
-        let days_events = events_per_day
-            .entry(event.dt.day())
-            .or_insert_with(Vec::new);
-        days_events.push(event.clone());
+        if let (EventKind::ClockIn, Some(location)) =
+            (&event.kind, &event.location)
+        {
+            let date = event.dt.with_timezone(&Local).date_naive();
+            per_location.entry(location).or_default().insert(date);
+        }
+    }
+    if per_location.is_empty() {
+        return Ok(result);
+    }
+    let rows: Vec<[String; 2]> = per_location
+        .iter()
+        .map(|(location, days)| {
+            let unit = tr(if days.len() == 1 { "day" } else { "days" });
+            [location.to_string(), format!("{} {unit}", days.len())]
+        })
+        .collect();
+    let title = tr("Days per location");
+    if format == ReportFormat::Markdown {
+        markdown_table(
+            &mut result,
+            title,
+            [tr("Location"), tr("Days")],
+            &rows,
+        )?;
+        return Ok(result);
     }
+    writeln!(result, "{title}:")?;
+    let width = per_location.keys().map(|l| l.len()).max().unwrap_or(0);
+    for [location, days] in &rows {
+        writeln!(result, "{location:<width$} | {days}")?;
+    }
+    Ok(result)
+}
 
-    for (day, days_events) in events_per_day {
-        let WorkingTime {
-            hours,
-            minutes,
-            complete,
-        } = working_time(&days_events);
-        let mut comment = "";
-        if !complete {
-            comment = "Incomplete records, please update";
+/// Lists the expenses of a month with their total, each amount followed by
+/// the `currency`; nothing if there were none
+pub fn expense_report(
+    expenses: &[Expense],
+    currency: Option<&str>,
+    format: ReportFormat,
+) -> Result<String, ViewError> {
+    let mut result = String::new();
+    if expenses.is_empty() {
+        return Ok(result);
+    }
+    let amount = |cents| match currency {
+        Some(currency) => format!("{} {currency}", format_amount(cents)),
+        None => format_amount(cents),
+    };
+    let rows: Vec<[String; 3]> = expenses
+        .iter()
+        .map(|expense| {
+            [
+                i18n::date(&expense.date),
+                expense.description.clone(),
+                amount(expense.cents),
+            ]
+        })
+        .collect();
+    let total = format!(
+        "{}: {}",
+        tr("Total expenses"),
+        amount(expenses.iter().map(|expense| expense.cents).sum())
+    );
+    if format == ReportFormat::Markdown {
+        markdown_table(
+            &mut result,
+            tr("Expenses"),
+            [tr("Date"), tr("Description"), tr("Amount")],
+            &rows,
+        )?;
+    } else {
+        writeln!(result, "{}:", tr("Expenses"))?;
+        let width = rows.iter().map(|[_, d, _]| d.len()).max().unwrap_or(0);
+        for [date, description, amount] in &rows {
+            writeln!(result, "{date} | {description:<width$} | {amount:>10}")?;
         }
+    }
+    paragraph(&mut result, format, &total)?;
+    Ok(result)
+}
 
-        let recorded_time = if complete {
-            format!("{hours:02}:{minutes:02}")
+/// Sums up on-call standby, which doesn't count as working time, and how
+/// much of it was spent working on incidents; nothing if there was none
+pub fn standby_report(events: &[Event]) -> Result<String, ViewError> {
+    let mut result = String::new();
+    let standby = standby(events);
+    if standby.is_empty() {
+        return Ok(result);
+    }
+    let total = standby.iter().fold(Duration::zero(), |total, interval| {
+        total + interval.duration()
+    });
+    let incidents = overlap(&standby, &intervals(events));
+    writeln!(
+        result,
+        "{}: {} {}, {} {} {}",
+        tr("Standby time"),
+        i18n::duration(&total),
+        tr("hours"),
+        tr("of which"),
+        i18n::duration(&incidents),
+        tr("on incidents"),
+    )?;
+    Ok(result)
+}
+
+/// Lists the time recorded per project, and per tag within each project,
+/// with its share of the total time and the number of days it was worked on.
+/// Names that don't fit into `columns` are shortened.
+pub fn project_report(
+    date: &NaiveDate,
+    events: &[Event],
+    format: ReportFormat,
+    columns: Option<usize>,
+) -> Result<String, ViewError> {
+    let mut result = String::new();
+    let title = format!(
+        "{} {}",
+        tr("Working time per project in"),
+        i18n::month(date)
+    );
+
+    // projects sort before time without project
+    let mut per_project: BTreeMap<(bool, &str), (Share, PerTag)> =
+        BTreeMap::new();
+    let intervals = intervals(events);
+    for interval in &intervals {
+        let project = interval.project.as_deref();
+        let (share, per_tag) = per_project
+            .entry((project.is_none(), project.unwrap_or(tr("(no project)"))))
+            .or_default();
+        share.add(interval);
+        for tag in &interval.tags {
+            per_tag.entry(tag).or_default().add(interval);
+        }
+    }
+
+    let total = intervals.iter().fold(Duration::zero(), |total, interval| {
+        total + interval.duration()
+    });
+    let width = per_project
+        .iter()
+        .flat_map(|((_, project), (_, per_tag))| {
+            let tag_widths = per_tag.keys().map(|tag| tag.len() + 2);
+            tag_widths.chain([project.len()])
+        })
+        .max()
+        .unwrap_or(0);
+    if format == ReportFormat::Markdown {
+        let mut rows = Vec::new();
+        for ((_, project), (share, per_tag)) in &per_project {
+            rows.push(share.cells(project.to_string(), total));
+            for (tag, share) in per_tag {
+                rows.push(share.cells(format!("{project} / {tag}"), total));
+            }
+        }
+        markdown_table(
+            &mut result,
+            &title,
+            [tr("Project"), tr("Worked"), tr("Share"), tr("Days")],
+            &rows,
+        )?;
+    } else {
+        writeln!(result, "{title}:")?;
+        // names give way where the columns after them don't fit
+        let described = per_project
+            .values()
+            .flat_map(|(share, per_tag)| {
+                per_tag.values().chain([share]).map(|s| s.describe(total))
+            })
+            .map(|described| described.chars().count())
+            .max()
+            .unwrap_or(0);
+        let width = match columns {
+            Some(columns) => {
+                width.min(columns.saturating_sub(described + 3).max(MIN_NAME))
+            }
+            None => width,
+        };
+        for ((_, project), (share, per_tag)) in &per_project {
+            let project = ellipsize(project, width);
+            writeln!(result, "{project:<width$} | {}", share.describe(total))?;
+            for (tag, share) in per_tag {
+                let name = ellipsize(&format!("  {tag}"), width);
+                writeln!(
+                    result,
+                    "{name:<width$} | {}",
+                    share.describe(total)
+                )?;
+            }
+        }
+    }
+
+    paragraph(&mut result, format, &total_line(total))?;
+    Ok(result)
+}
+
+type PerTag<'a> = BTreeMap<&'a str, Share>;
+
+#[derive(Default)]
+struct Share {
+    worked: Duration,
+    days: BTreeSet<NaiveDate>,
+}
+
+impl Share {
+    fn add(&mut self, interval: &Interval) {
+        self.worked += interval.duration();
+        self.days
+            .insert(interval.start.with_timezone(&Local).date_naive());
+    }
+
+    fn describe(&self, total: Duration) -> String {
+        let [_, worked, percent, days] = self.cells(String::new(), total);
+        format!("{worked} | {percent:>4} | {days}")
+    }
+
+    fn cells(&self, name: String, total: Duration) -> [String; 4] {
+        let percent = if total.num_minutes() > 0 {
+            self.worked.num_minutes() * 100 / total.num_minutes()
         } else {
-            "?".to_string()
+            0
         };
-        writeln!(result, "{day:<2} | {recorded_time:<5} | {comment}")?;
+        let days = self.days.len();
+        let unit = tr(if days == 1 { "day" } else { "days" });
+        [
+            name,
+            i18n::duration(&self.worked),
+            format!("{percent}%"),
+            format!("{days} {unit}"),
+        ]
     }
+}
 
-    let WorkingTime {
-        hours,
-        minutes,
-        complete: _,
-    } = working_time(events);
-    writeln!(result, "Total working time: {hours:02}:{minutes:02} hours")?;
-    // TODO compute overtime
+fn totals_report(
+    title: &str,
+    name_header: &str,
+    totals: &BTreeMap<&str, Duration>,
+    format: ReportFormat,
+) -> Result<String, ViewError> {
+    let mut result = String::new();
+    if totals.is_empty() {
+        return Ok(result);
+    }
+    let rows: Vec<[String; 2]> = totals
+        .iter()
+        .map(|(name, duration)| [name.to_string(), i18n::duration(duration)])
+        .collect();
+    if format == ReportFormat::Markdown {
+        markdown_table(
+            &mut result,
+            title,
+            [name_header, tr("Worked")],
+            &rows,
+        )?;
+        return Ok(result);
+    }
+    writeln!(result, "{title}:")?;
+    let width = totals.keys().map(|name| name.len()).max().unwrap_or(0);
+    for [name, worked] in &rows {
+        writeln!(result, "{name:<width$} | {worked}")?;
+    }
     Ok(result)
 }
 
+pub(crate) fn total_line(worked: Duration) -> String {
+    format!(
+        "{}: {} {}",
+        tr("Total working time"),
+        i18n::duration(&worked),
+        tr("hours")
+    )
+}
+
+/// A difference in time with its sign, e.g., `+01:30` or `-00:15`
+fn delta(duration: Duration) -> String {
+    let sign = if duration < Duration::zero() {
+        '-'
+    } else {
+        '+'
+    };
+    format!("{sign}{}", i18n::duration(&duration.abs()))
+}
+
+/// Writes a heading and a table, followed by an empty line so that
+/// whatever comes next starts a new block
+fn markdown_table<const N: usize>(
+    result: &mut String,
+    title: &str,
+    header: [&str; N],
+    rows: &[[String; N]],
+) -> std::fmt::Result {
+    writeln!(result, "### {title}\n")?;
+    writeln!(result, "| {} |", header.join(" | "))?;
+    writeln!(result, "|{}", "---|".repeat(N))?;
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|cell| cell.trim().replace('|', "\\|"))
+            .collect();
+        writeln!(result, "| {} |", cells.join(" | "))?;
+    }
+    writeln!(result)
+}
+
+/// A heading and a table in HTML, with the cells escaped
+fn html_table<const N: usize>(
+    title: &str,
+    header: [&str; N],
+    rows: &[[String; N]],
+) -> String {
+    let mut result = format!("<h3>{}</h3>\n<table>\n", html_escape(title));
+    result.push_str("<thead><tr>");
+    for cell in header {
+        let _ = write!(result, "<th>{}</th>", html_escape(cell));
+    }
+    result.push_str("</tr></thead>\n<tbody>\n");
+    for row in rows {
+        result.push_str("<tr>");
+        for cell in row {
+            let _ = write!(result, "<td>{}</td>", html_escape(cell.trim()));
+        }
+        result.push_str("</tr>\n");
+    }
+    result.push_str("</tbody>\n</table>\n");
+    result
+}
+
+fn html_paragraph(text: &str) -> String {
+    format!("<p>{}</p>\n", html_escape(text))
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn intervals_to_json(intervals: &[Interval]) -> json::Value {
+    json::Value::Array(
+        intervals
+            .iter()
+            .map(|interval| {
+                json::Value::Object(vec![
+                    ("start".to_string(), interval.start.to_rfc3339().into()),
+                    ("end".to_string(), interval.end.to_rfc3339().into()),
+                    (
+                        "project".to_string(),
+                        interval.project.as_deref().into(),
+                    ),
+                ])
+            })
+            .collect(),
+    )
+}
+
+/// Writes a line of text, which Markdown needs separated by an empty line
+/// to not run into the next one
+fn paragraph(
+    result: &mut String,
+    format: ReportFormat,
+    text: &str,
+) -> std::fmt::Result {
+    writeln!(result, "{text}")?;
+    if format == ReportFormat::Markdown {
+        writeln!(result)?;
+    }
+    Ok(())
+}
+
 fn same_date<T: Datelike, U: Datelike>(date1: &T, date2: &U) -> bool {
     date1.day() == date2.day()
         && date1.month() == date2.month()
         && date1.year() == date2.year()
 }
 
-struct WorkingTime {
-    hours: u32,
-    minutes: u32,
-    complete: bool,
+/// How far details are indented on a line of their own
+const DETAILS_INDENT: usize = 4;
+
+/// How short names get at least when they're shortened to fit
+const MIN_NAME: usize = 8;
+
+/// Shortens `text` to `width` characters, with an ellipsis for the rest
+pub fn ellipsize(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut result: String =
+        text.chars().take(width.saturating_sub(1)).collect();
+    if width > 0 {
+        result.push('…');
+    }
+    result
 }
 
-fn working_time(events: &[Event]) -> WorkingTime {
+pub(crate) struct WorkingTime {
+    pub(crate) worked: Duration,
+    pub(crate) complete: bool,
+}
+
+pub(crate) fn working_time(events: &[Event]) -> WorkingTime {
     let (worked, complete, _) = events.iter().fold(
         (Duration::new(0, 0).unwrap(), true, None),
         |(duration, complete, maybe_previous), event| match (
             maybe_previous,
             event,
         ) {
+            (
+                _,
+                Event {
+                    kind:
+                        EventKind::StandbyStart
+                        | EventKind::StandbyEnd
+                        | EventKind::Interruption { .. },
+                    ..
+                },
+            ) => (duration, complete, maybe_previous),
             (
                 None,
                 Event {
                     kind: EventKind::ClockIn,
-                    dt: _,
+                    ..
                 },
             ) => (duration, complete, Some(event)),
             (
                 None,
                 Event {
                     kind: EventKind::ClockOut,
-                    dt: _,
+                    ..
                 },
             ) => (duration, false, None),
             (
                 Some(_),
                 Event {
                     kind: EventKind::ClockIn,
-                    dt: _,
+                    ..
                 },
             ) => (duration, false, Some(event)),
             (
@@ -155,16 +1608,127 @@ fn working_time(events: &[Event]) -> WorkingTime {
                 Event {
                     kind: EventKind::ClockOut,
                     dt,
+                    ..
+                },
+                // a clock-out before its clock-in is a data error, which
+                // mustn't take time off the day
+            ) if *dt < prev.dt => (duration, false, None),
+            (
+                Some(prev),
+                Event {
+                    kind: EventKind::ClockOut,
+                    dt,
+                    ..
                 },
             ) => (duration + dt.sub(prev.dt), complete, None),
         },
     );
 
-    let hours: u32 = worked.num_hours().try_into().unwrap();
-    let minutes: u32 = (worked.num_minutes() % 60).try_into().unwrap();
-    WorkingTime {
-        hours,
-        minutes,
-        complete,
+    WorkingTime { worked, complete }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn events() -> [Event; 2] {
+        let dt = Local.with_ymd_and_hms(2024, 5, 6, 9, 0, 0).unwrap();
+        [
+            Event::clock_in(&dt)
+                .with_project(Some("international".to_string()))
+                .with_tags(vec!["meetings".to_string()]),
+            Event::clock_out(&(dt + Duration::hours(2))),
+        ]
+    }
+
+    fn render(width: Option<usize>) -> String {
+        let events = events();
+        let date = events[0].dt.with_timezone(&Local).date_naive();
+        DailyReport::compute(date, &StoredEvent::number(&events), &[], &[])
+            .with_width(width)
+            .render(ReportFormat::Text)
+            .unwrap()
+    }
+
+    #[test]
+    fn details_fit_next_to_their_event_or_below() {
+        let line = "0 | 09:00 | clock in  | meetings";
+        assert!(render(None).contains(line));
+        // exactly as wide as the report may be
+        assert!(render(Some(line.len())).contains(line));
+
+        let below = "0 | 09:00 | clock in  |\n    meetings\n";
+        assert!(render(Some(line.len() - 1)).contains(below));
+        assert!(render(Some(10)).contains("clock in  |\n    meeti…\n"));
+    }
+
+    #[test]
+    fn project_names_give_way_to_the_columns_after_them() {
+        let events = events();
+        let date = events[0].dt.with_timezone(&Local).date_naive();
+        let report = |columns| {
+            project_report(&date, &events, ReportFormat::Text, columns)
+                .unwrap()
+        };
+        let full = report(None);
+        assert!(full.contains("international | 02:00 | 100% | 1 day\n"));
+        assert!(full.contains("  meetings    | 02:00 | 100% | 1 day\n"));
+        assert_eq!(report(Some(36)), full);
+
+        let short = report(Some(20));
+        assert!(short.contains("interna… | 02:00 | 100% | 1 day\n"));
+        assert!(short.contains("  meeti… | 02:00 | 100% | 1 day\n"));
+    }
+
+    #[test]
+    fn reports_render_as_json_and_html() {
+        let events = events();
+        let date = events[0].dt.with_timezone(&Local).date_naive();
+        let daily = DailyReport::compute(
+            date,
+            &StoredEvent::number(&events),
+            &[],
+            &[],
+        );
+        let json = daily.render(ReportFormat::Json).unwrap();
+        assert_eq!(json, format!("{}\n", daily.to_json()));
+        let value = json::parse(&json).unwrap();
+        assert_eq!(value.get("worked_minutes").unwrap().as_f64(), Some(120.0));
+        let html = daily.render(ReportFormat::Html).unwrap();
+        assert!(html.starts_with("<h3>"));
+        assert!(html.contains("<td>meetings</td>"));
+
+        let monthly = MonthlyReport::month(
+            &date,
+            &events,
+            Grouping::Day,
+            &RowOptions::default(),
+        );
+        let value =
+            json::parse(&monthly.render(ReportFormat::Json).unwrap()).unwrap();
+        assert_eq!(value.get("rows").unwrap().as_array().unwrap().len(), 1);
+        assert_eq!(
+            monthly.render(ReportFormat::Html).unwrap(),
+            monthly.to_html()
+        );
+    }
+
+    #[test]
+    fn report_formats_are_parsed_by_name() {
+        assert_eq!("JSON".parse(), Ok(ReportFormat::Json));
+        assert_eq!("html".parse(), Ok(ReportFormat::Html));
+        assert_eq!("md".parse(), Ok(ReportFormat::Markdown));
+        assert!("pdf".parse::<ReportFormat>().unwrap_err().contains("html"));
+    }
+
+    #[test]
+    fn ellipsize_keeps_text_that_fits() {
+        assert_eq!(ellipsize("meetings", 8), "meetings");
+        assert_eq!(ellipsize("meetings", 7), "meetin…");
+        assert_eq!(ellipsize("äöü", 2), "ä…");
+        assert_eq!(ellipsize("meetings", 1), "…");
+        assert_eq!(ellipsize("meetings", 0), "");
     }
 }